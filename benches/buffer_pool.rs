@@ -0,0 +1,58 @@
+use std::{fs, path::PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use innodb::innodb::{
+    buffer_manager::{lru::LRUBufferManager, BufferManager},
+    page::{writer::PageWriter, ChecksumKind, PageSize, PageType},
+};
+
+const SPACE_ID: u32 = 1;
+const PAGE_SIZE: PageSize = PageSize::Size4K;
+/// Pages past the largest pool, so cycling through the file always misses
+const EXTRA_PAGES: u32 = 1000;
+
+/// A page file of `pages` empty index pages in the page directory layout
+fn page_directory(pages: u32) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("buffer_pool_bench_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let empty = vec![0u8; PAGE_SIZE.bytes()];
+    let mut data = Vec::with_capacity(pages as usize * PAGE_SIZE.bytes());
+    for offset in 0..pages {
+        let mut writer = PageWriter::try_from_bytes_with_size(&empty, PAGE_SIZE).unwrap();
+        writer.set_offset(offset);
+        writer.set_space_id(SPACE_ID);
+        writer.set_page_type(PageType::Index);
+        writer.set_lsn(1);
+        data.extend_from_slice(&writer.finalize(ChecksumKind::Crc32));
+    }
+    fs::write(dir.join(format!("{:08}.pages", SPACE_ID)), data).unwrap();
+    dir
+}
+
+/// Pins on a full pool that each evict a frame
+fn eviction(c: &mut Criterion) {
+    let pools = [16, 10_000];
+    let pages = pools[1] + EXTRA_PAGES;
+    let dir = page_directory(pages);
+    let mut group = c.benchmark_group("lru");
+    for frames in pools {
+        let buffer_mgr =
+            LRUBufferManager::with_page_size_and_capacity(&dir, PAGE_SIZE, frames as usize);
+        for offset in 0..frames {
+            drop(buffer_mgr.pin(SPACE_ID, offset).unwrap());
+        }
+        let mut next = frames;
+        group.bench_function(format!("miss_{}_frames", frames), |b| {
+            b.iter(|| {
+                drop(buffer_mgr.pin(SPACE_ID, next % pages).unwrap());
+                next += 1;
+            })
+        });
+    }
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, eviction);
+criterion_main!(benches);
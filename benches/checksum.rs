@@ -0,0 +1,66 @@
+use crc::{Crc, CRC_32_ISCSI};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use innodb::innodb::{
+    page::{
+        crc32_checksum, innochecksum::crc32_legacy_big_endian_checksum, innodb_checksum, Page,
+        FIL_PAGE_SIZE,
+    },
+    test_data,
+};
+
+/// The table driven CRC-32C used before, kept as the baseline
+const TABLE_CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+fn sample_page() -> Vec<u8> {
+    let data = test_data("float_sample.ibd");
+    data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec()
+}
+
+fn checksums(c: &mut Criterion) {
+    let page = sample_page();
+    let mut group = c.benchmark_group("checksum");
+    group.throughput(Throughput::Bytes(page.len() as u64));
+
+    group.bench_function("crc32_table", |b| {
+        b.iter(|| {
+            TABLE_CRC32C.checksum(std::hint::black_box(&page[4..26]))
+                ^ TABLE_CRC32C.checksum(std::hint::black_box(&page[38..FIL_PAGE_SIZE - 8]))
+        })
+    });
+    group.bench_function("crc32", |b| {
+        b.iter(|| crc32_checksum(std::hint::black_box(&page)))
+    });
+    group.bench_function("crc32_legacy", |b| {
+        b.iter(|| crc32_legacy_big_endian_checksum(std::hint::black_box(&page)))
+    });
+    group.bench_function("innodb", |b| {
+        b.iter(|| innodb_checksum(std::hint::black_box(&page)))
+    });
+    group.finish();
+}
+
+/// Page::validate on pages matching each algorithm in the order they are
+/// tried, and on one matching none of them
+fn validation(c: &mut Criterion) {
+    let crc32 = sample_page();
+    let mut innodb = crc32.clone();
+    innodb[0..4].copy_from_slice(&innodb_checksum(&crc32).to_be_bytes());
+    let mut corrupted = crc32.clone();
+    corrupted[200] ^= 0xFF;
+
+    let mut group = c.benchmark_group("validate");
+    group.throughput(Throughput::Bytes(FIL_PAGE_SIZE as u64));
+    for (name, buf) in [
+        ("crc32", &crc32),
+        ("innodb", &innodb),
+        ("corrupted", &corrupted),
+    ] {
+        let page = Page::from_bytes(buf).unwrap();
+        group.bench_function(name, |b| b.iter(|| std::hint::black_box(&page).validate()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, checksums, validation);
+criterion_main!(benches);
@@ -0,0 +1,299 @@
+use clap::Parser;
+use innodb::innodb::page::{ChecksumKind, ChecksumPolicy, Page, PageSize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+};
+use tracing::{debug, info, Level};
+
+#[derive(Parser, Debug)]
+struct Arguments {
+    #[arg(long="no-color", action = clap::ArgAction::SetFalse)]
+    color: bool,
+
+    #[arg(short='v', action = clap::ArgAction::Count, help="verbose level")]
+    verbose: u8,
+
+    #[arg(
+        long = "page-size",
+        value_name = "SIZE",
+        help = "innodb_page_size of both tablespaces, read from the header of OLD by default"
+    )]
+    page_size: Option<PageSize>,
+
+    #[arg(help = "Tablespace before the change", value_name = "OLD")]
+    old: PathBuf,
+
+    #[arg(help = "Tablespace after the change", value_name = "NEW")]
+    new: PathBuf,
+}
+
+// Bytes rewritten on every flush: checksum, LSN, flush LSN and the trailer
+fn metadata_ranges(page_size: usize) -> [(usize, usize); 4] {
+    [(0, 4), (16, 24), (26, 34), (page_size - 8, page_size)]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageChange {
+    Identical,
+    /// Only the checksum and LSN fields differ
+    Metadata,
+    Content,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct DiffSummary {
+    pages: usize,
+    identical: usize,
+    metadata: usize,
+    content: usize,
+    added: usize,
+    removed: usize,
+}
+
+impl DiffSummary {
+    fn record(&mut self, change: PageChange) {
+        self.pages += 1;
+        match change {
+            PageChange::Identical => self.identical += 1,
+            PageChange::Metadata => self.metadata += 1,
+            PageChange::Content => self.content += 1,
+            PageChange::Added => self.added += 1,
+            PageChange::Removed => self.removed += 1,
+        }
+    }
+}
+
+fn is_empty(page: Option<&[u8]>) -> bool {
+    page.is_none_or(|buf| buf.iter().all(|b| *b == 0))
+}
+
+fn classify(old: Option<&[u8]>, new: Option<&[u8]>) -> PageChange {
+    match (is_empty(old), is_empty(new)) {
+        (true, true) => return PageChange::Identical,
+        (true, false) => return PageChange::Added,
+        (false, true) => return PageChange::Removed,
+        (false, false) => {}
+    }
+    let (old, new) = (old.unwrap(), new.unwrap());
+    if old == new {
+        return PageChange::Identical;
+    }
+
+    let mut start = 0;
+    for (from, to) in metadata_ranges(old.len()) {
+        if old[start..from] != new[start..from] {
+            return PageChange::Content;
+        }
+        start = to;
+    }
+    PageChange::Metadata
+}
+
+fn checksum_state(buf: &[u8], page_size: PageSize) -> &'static str {
+    match Page::from_bytes_with_size(buf, page_size) {
+        // Only describes the page, so pages without a checksum are named too
+        Ok(page) => match page.validate_checksum(ChecksumPolicy { allow_none: true }) {
+            Some(ChecksumKind::Crc32) => "crc32",
+            Some(ChecksumKind::InnoDb) => "innodb",
+            Some(ChecksumKind::FullCrc32) => "full_crc32",
+            Some(ChecksumKind::None) => "none",
+            None => "invalid",
+        },
+        Err(_) => "unreadable",
+    }
+}
+
+fn describe(buf: Option<&[u8]>, page_size: PageSize) -> String {
+    match buf.filter(|buf| !is_empty(Some(buf))) {
+        Some(buf) => match Page::from_bytes_with_size(buf, page_size) {
+            Ok(page) => format!(
+                "{:?} lsn {} checksum {}",
+                page.header.page_type,
+                page.header.lsn,
+                checksum_state(buf, page_size)
+            ),
+            Err(e) => format!("unreadable: {:?}", e),
+        },
+        None => String::from("empty"),
+    }
+}
+
+/// Reads a full page, false once the reader is exhausted
+fn read_page<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    // A partial trailing page is padded with zeros
+    buf[filled..].fill(0);
+    Ok(filled > 0)
+}
+
+fn diff<R: Read, F: FnMut(usize, PageChange, Option<&[u8]>, Option<&[u8]>)>(
+    mut old: R,
+    mut new: R,
+    page_size: PageSize,
+    mut report: F,
+) -> std::io::Result<DiffSummary> {
+    let mut summary = DiffSummary::default();
+    let mut old_buf = vec![0u8; page_size.bytes()];
+    let mut new_buf = vec![0u8; page_size.bytes()];
+    for page_number in 0.. {
+        let old_page = read_page(&mut old, &mut old_buf)?.then_some(old_buf.as_slice());
+        let new_page = read_page(&mut new, &mut new_buf)?.then_some(new_buf.as_slice());
+        if old_page.is_none() && new_page.is_none() {
+            break;
+        }
+        let change = classify(old_page, new_page);
+        summary.record(change);
+        report(page_number, change, old_page, new_page);
+    }
+    Ok(summary)
+}
+
+fn main() {
+    let args = Arguments::parse();
+
+    let subscriber = tracing_subscriber::FmtSubscriber::builder()
+        .with_max_level(match args.verbose {
+            0 => Level::INFO,
+            1 => Level::DEBUG,
+            _ => Level::TRACE,
+        })
+        .with_ansi(args.color)
+        .finish();
+    _ = tracing::subscriber::set_global_default(subscriber);
+
+    let mut old = BufReader::new(File::open(&args.old).expect("Failed to open old tablespace"));
+    let new = BufReader::new(File::open(&args.new).expect("Failed to open new tablespace"));
+    let page_size = args.page_size.unwrap_or_else(|| {
+        let head = old.fill_buf().expect("Failed to read old tablespace");
+        PageSize::detect(head).unwrap_or_default()
+    });
+
+    let summary = diff(
+        old,
+        new,
+        page_size,
+        |page_number, change, old, new| match change {
+            PageChange::Identical => debug!("Page {} identical", page_number),
+            _ => info!(
+                "Page {}: {:?}, old: {}, new: {}",
+                page_number,
+                change,
+                describe(old, page_size),
+                describe(new, page_size)
+            ),
+        },
+    )
+    .expect("Failed to read tablespaces");
+
+    info!(
+        "Compared {} pages: {} identical, {} metadata only, {} content changed, {} added, {} removed",
+        summary.pages,
+        summary.identical,
+        summary.metadata,
+        summary.content,
+        summary.added,
+        summary.removed
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use innodb::innodb::{
+        page::{PageSize, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{classify, diff, DiffSummary, PageChange};
+
+    #[test]
+    fn test_classify() {
+        let old = test_data("float_sample.ibd")[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+
+        let mut flushed = old.clone();
+        flushed[16..24].copy_from_slice(&u64::MAX.to_be_bytes());
+        flushed[0] ^= 0xFF;
+        flushed[FIL_PAGE_SIZE - 1] ^= 0xFF;
+        assert_eq!(classify(Some(&old), Some(&flushed)), PageChange::Metadata);
+
+        let mut modified = flushed.clone();
+        modified[130] ^= 0xFF;
+        assert_eq!(classify(Some(&old), Some(&modified)), PageChange::Content);
+
+        let zeros = vec![0u8; FIL_PAGE_SIZE];
+        assert_eq!(classify(Some(&old), Some(&old)), PageChange::Identical);
+        assert_eq!(classify(Some(&zeros), Some(&old)), PageChange::Added);
+        assert_eq!(classify(Some(&old), None), PageChange::Removed);
+        assert_eq!(classify(None, Some(&zeros)), PageChange::Identical);
+    }
+
+    #[test]
+    fn test_diff_one_page() {
+        let old = test_data("float_sample.ibd");
+        let mut new = old.clone();
+        new[4 * FIL_PAGE_SIZE + 130] ^= 0xFF;
+
+        let mut changed = Vec::new();
+        let summary = diff(
+            Cursor::new(&old),
+            Cursor::new(&new),
+            PageSize::default(),
+            |page, change, _, _| {
+                if change != PageChange::Identical {
+                    changed.push((page, change));
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(changed, vec![(4, PageChange::Content)]);
+        assert_eq!(
+            summary,
+            DiffSummary {
+                pages: 7,
+                identical: 6,
+                content: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_8k_pages() {
+        let old = test_data("float_sample_8k.ibd");
+        let mut new = old.clone();
+        // LSN of page 3 and a record byte of page 4
+        new[3 * 8192 + 20] ^= 0xFF;
+        new[4 * 8192 + 130] ^= 0xFF;
+
+        let mut changed = Vec::new();
+        let summary = diff(
+            Cursor::new(&old),
+            Cursor::new(&new),
+            PageSize::Size8K,
+            |page, change, _, _| {
+                if change != PageChange::Identical {
+                    changed.push((page, change));
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            changed,
+            vec![(3, PageChange::Metadata), (4, PageChange::Content)]
+        );
+        assert_eq!(summary.pages, 7);
+    }
+}
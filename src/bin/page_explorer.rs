@@ -1,21 +1,49 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::{
+    cmp::Ordering,
+    collections::HashMap,
     fs::{read_to_string, File},
-    io::{BufReader, Read, Write},
+    io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
+    num::NonZeroUsize,
+    ops::ControlFlow,
     path::PathBuf,
+    rc::Rc,
     sync::Arc,
 };
 
 use clap::Parser;
 use innodb::innodb::{
+    btree::BTreeIndex,
     buffer_manager::{
-        lru::LRUBufferManager, BufferManager, DummyBufferMangaer,
+        lru::{LRUBufferManager, LRU_PAGE_COUNT},
+        memory::MemoryBufferManager,
+        BufferManager, DummyBufferMangaer,
     },
+    doublewrite::DoublewriteBuffer,
+    encryption::{keyring::Keyring, MasterKey, PageDecryptor},
+    input::{is_stdin, open_input},
     page::{
-        index::{record::RecordType, IndexPage},
-        Page, PageType, FIL_PAGE_SIZE,
+        fsp::FspHeaderPage,
+        ibuf::IbufBitmap,
+        index::{
+            record::{Record, RecordType},
+            IndexHeader, IndexPage, PageInconsistency,
+        },
+        innochecksum::InnochecksumAlgorithm,
+        inode::InodePage,
+        lob::verify_chain,
+        rtree::RtreePage,
+        xdes::XdesPage,
+        ChecksumPolicy, FILHeader, Page, PageSize, PageType, PageValidation,
     },
-    table::{field::FieldValue, row::Row, TableDefinition},
+    scan::{dump_index, parse_row, IndexDump},
+    table::{
+        field::{key_prefix_cmp, FieldType, FieldValue},
+        row::Row,
+        TableDefinition,
+    },
+    tablespace::Tablespace,
+    undo::{RowVersion, VersionChain},
 };
 use struson::writer::{JsonStreamWriter, JsonWriter};
 use tracing::{debug, info, trace, warn, Level};
@@ -31,15 +59,78 @@ struct Arguments {
     #[arg(long)]
     limit: Option<usize>,
 
+    #[arg(
+        long = "max-rows",
+        help = "Stop after this many data rows, --limit counts index pages"
+    )]
+    max_rows: Option<usize>,
+
     #[arg(long = "tablespace-dir")]
     tablespce_dir: Option<PathBuf>,
 
-    #[arg(long = "index-id")]
-    index_id: Option<u64>,
+    #[arg(
+        long = "read-ahead",
+        default_value_t = 0,
+        help = "Pages to read ahead on a buffer manager miss"
+    )]
+    read_ahead: usize,
+
+    #[arg(
+        long = "buffer-pages",
+        default_value_t = NonZeroUsize::new(LRU_PAGE_COUNT).unwrap(),
+        help = "Pages the buffer manager keeps in memory"
+    )]
+    buffer_pages: NonZeroUsize,
+
+    #[arg(
+        long = "keyring",
+        value_name = "KEYRING FILE",
+        help = "keyring_file plugin file with the master keys of encrypted tablespaces"
+    )]
+    keyring: Option<PathBuf>,
+
+    #[arg(
+        long = "master-key",
+        value_name = "HEX",
+        conflicts_with = "keyring",
+        help = "Master key of encrypted tablespaces as 64 hex digits"
+    )]
+    master_key: Option<String>,
+
+    #[arg(
+        long = "encrypted-count",
+        help = "Report the encrypted pages left undecrypted for each space, --keyring or --master-key decrypts them"
+    )]
+    encrypted_count: bool,
+
+    #[arg(
+        long = "doublewrite",
+        value_name = "IBDATA1",
+        help = "System tablespace whose doublewrite buffer holds copies to restore torn pages from"
+    )]
+    doublewrite: Option<PathBuf>,
+
+    #[arg(
+        long = "index-id",
+        help = "Only explore pages of this index, can be given several times"
+    )]
+    index_ids: Vec<u64>,
+
+    #[arg(
+        long = "list-indexes",
+        help = "List the indexes in the page file with their page count, levels and a sample record length instead of dumping rows"
+    )]
+    list_indexes: bool,
 
     #[arg(long = "page-id")]
     page_id: Option<u32>,
 
+    #[arg(
+        long = "sorted",
+        help = "The page file holds every page at the position of its page number, --page-id seeks to the page instead of scanning. Assumed when the file starts with a tablespace header"
+    )]
+    sorted: bool,
+
     #[arg(
         short = 't',
         long = "table",
@@ -47,33 +138,440 @@ struct Arguments {
     )]
     table_def: Option<PathBuf>,
 
+    #[arg(
+        long = "table-json",
+        conflicts_with = "table_def",
+        help = "Path to a table definition saved as JSON, instead of --table"
+    )]
+    table_json: Option<PathBuf>,
+
     #[arg(short = 'o', long = "output", help = "JSON file to write output to")]
     output: Option<PathBuf>,
 
     #[arg(
-        help = "Page(s) file, should contain one or multiple raw 16K page, ideally sorted",
+        long = "system-columns",
+        help = "Include the hidden _trx_id and _roll_ptr columns in the output"
+    )]
+    system_columns: bool,
+
+    #[arg(
+        long = "versions",
+        help = "Follow the roll pointer of every row and add its older versions as _versions, needs the undo pages"
+    )]
+    versions: bool,
+
+    #[arg(
+        long = "undo-space",
+        value_name = "SPACE ID",
+        value_delimiter = ',',
+        help = "Ids of the undo tablespaces for --versions in the order the server opened them, comma separated. Without it undo pages are looked up in the system tablespace"
+    )]
+    undo_spaces: Vec<u32>,
+
+    #[arg(
+        long = "scan-garbage",
+        help = "Also dump deleted records from the page garbage list, tagged with _garbage"
+    )]
+    scan_garbage: bool,
+
+    #[arg(
+        long = "root",
+        value_name = "ROOT PAGE",
+        help = "Root page of the clustered index for --walk-btree and --get-pk"
+    )]
+    root: Option<u32>,
+
+    #[arg(
+        long = "walk-btree",
+        requires = "root",
+        help = "Dump the rows of the index at --root in key order instead of file order, needs --table"
+    )]
+    walk_btree: bool,
+
+    #[arg(
+        long = "get-pk",
+        value_name = "KEY",
+        requires = "root",
+        help = "Print the row with this primary key as a JSON object, comma separated for composite keys"
+    )]
+    get_pk: Option<String>,
+
+    #[arg(
+        long = "pk-min",
+        value_name = "KEY",
+        help = "Skip rows with a primary key below this, comma separated for composite keys. \
+                A prefix of the key works, strings compare as binary so pass them as stored"
+    )]
+    pk_min: Option<String>,
+
+    #[arg(
+        long = "pk-max",
+        value_name = "KEY",
+        help = "Skip rows with a primary key above this, same format as --pk-min"
+    )]
+    pk_max: Option<String>,
+
+    #[arg(
+        long = "check-siblings",
+        help = "Check every index page links to a next sibling of the same index and level"
+    )]
+    check_siblings: bool,
+
+    #[arg(
+        long = "verify-lobs",
+        help = "Check the LOB chain of every externally stored column"
+    )]
+    verify_lobs: bool,
+
+    #[arg(
+        long = "page-size",
+        value_name = "SIZE",
+        help = "innodb_page_size of the pages (4K to 64K), read from the tablespace header when the page file starts with one, 16K otherwise"
+    )]
+    page_size: Option<PageSize>,
+
+    #[arg(
+        long = "allow-no-checksum",
+        help = "Accept pages written with innodb_checksum_algorithm=none"
+    )]
+    allow_no_checksum: bool,
+
+    #[arg(
+        long = "innochecksum",
+        value_name = "ALGORITHM",
+        num_args = 0..=1,
+        default_missing_value = "crc32",
+        help = "Print the checksums of every page as innochecksum computes them, and whether it passes them with this --strict-check algorithm (crc32 by default)"
+    )]
+    innochecksum: Option<InnochecksumAlgorithm>,
+
+    #[arg(
+        help = "Page(s) file, should contain one or multiple raw pages, ideally sorted. - reads stdin",
         value_name = "PAGE FILE"
     )]
     file: PathBuf,
 }
 
+/// What --list-indexes shows about an index
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct IndexSummary {
+    pages: usize,
+    /// Pages on the leaf level
+    leaf_pages: usize,
+    /// Level of the root, 0 when the index is a single page
+    max_level: u16,
+    /// Space taken by the first record found on a leaf page
+    sample_record_len: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct IndexStats {
+    pages: usize,
+    records: usize,
+    checksum_failures: usize,
+}
+
+/// Writes a single field, returns true when the value could not be recovered
+fn write_value<W: JsonWriter>(writer: &mut W, value: &FieldValue) -> Result<bool> {
+    match value {
+        FieldValue::SignedInt(v) => writer.number_value(*v)?,
+        FieldValue::UnsignedInt(v) => writer.number_value(*v)?,
+        // JSON has no NaN or infinity, those are written as strings
+        FieldValue::Float(v) if v.is_finite() => writer.fp_number_value(*v)?,
+        FieldValue::Float(v) => writer.string_value(&v.to_string())?,
+        FieldValue::Double(v) if v.is_finite() => writer.fp_number_value(*v)?,
+        FieldValue::Double(v) => writer.string_value(&v.to_string())?,
+        FieldValue::String(s) => writer.string_value(s)?,
+        FieldValue::PartialString { partial, total_len } => {
+            writer.begin_object()?;
+            writer.name("partial")?;
+            writer.string_value(partial)?;
+            writer.name("total_len")?;
+            writer.number_value(*total_len)?;
+            writer.end_object()?;
+        }
+        FieldValue::Bytes(_) => writer.string_value(&value.to_string())?,
+        FieldValue::Null => writer.null_value()?,
+        FieldValue::Skipped => {
+            writer.null_value()?;
+            return Ok(true);
+        }
+    };
+    Ok(false)
+}
+
+/// Carved data has pages that claim to be index pages without being one,
+/// those don't have the system records in place
+fn read_index_page<'a>(page: &Page<'a>) -> Result<IndexPage<'a>> {
+    let index_page = IndexPage::try_from_page_ref(page)?;
+    index_page.infimum()?;
+    index_page.supremum()?;
+    Ok(index_page)
+}
+
+/// Table definition from --table or --table-json
+fn load_table_def(args: &Arguments) -> Result<Option<TableDefinition>> {
+    match (&args.table_def, &args.table_json) {
+        (Some(path), _) => Ok(Some(TableDefinition::try_from_sql_statement(
+            &read_to_string(path)?,
+        )?)),
+        (None, Some(path)) => Ok(Some(TableDefinition::from_json(&read_to_string(path)?)?)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Decryptor for the master key given by --keyring or --master-key
+fn open_decryptor(args: &Arguments) -> Result<Option<Arc<PageDecryptor>>> {
+    let master_key = match (&args.keyring, &args.master_key) {
+        (Some(path), _) => MasterKey::Keyring(Keyring::from_bytes(&std::fs::read(path)?)?),
+        (None, Some(hex)) => MasterKey::from_hex(hex)?,
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(Arc::new(PageDecryptor::new(master_key))))
+}
+
+/// Doublewrite buffer of the system tablespace given by --doublewrite
+fn open_doublewrite(args: &Arguments) -> Result<Option<Arc<DoublewriteBuffer>>> {
+    let Some(path) = &args.doublewrite else {
+        return Ok(None);
+    };
+    let mut file = BufReader::new(File::open(path)?);
+    let page_size = match args.page_size {
+        Some(size) => size,
+        None => {
+            let mut head = vec![0u8; PageSize::Size4K.bytes()];
+            file.read_exact(&mut head)?;
+            PageSize::detect(&head)?
+        }
+    };
+    Ok(Some(Arc::new(DoublewriteBuffer::read_from(
+        &mut file, page_size,
+    )?)))
+}
+
+/// Parses a comma separated primary key into values of the cluster columns
+fn parse_key(table: &TableDefinition, key: &str) -> Result<Vec<FieldValue>> {
+    let values = parse_key_prefix(table, key)?;
+    if values.len() != table.cluster_columns.len() {
+        return Err(anyhow!(
+            "Key has {} parts, the primary key has {} columns",
+            values.len(),
+            table.cluster_columns.len()
+        ));
+    }
+    Ok(values)
+}
+
+/// Like [`parse_key`] but accepts the leading columns of the key only
+fn parse_key_prefix(table: &TableDefinition, key: &str) -> Result<Vec<FieldValue>> {
+    let parts: Vec<&str> = key.split(',').collect();
+    if parts.len() > table.cluster_columns.len() {
+        return Err(anyhow!(
+            "Key has {} parts, the primary key has {} columns",
+            parts.len(),
+            table.cluster_columns.len()
+        ));
+    }
+    parts
+        .iter()
+        .zip(table.cluster_columns.iter())
+        .map(|(part, field)| match field.field_type {
+            FieldType::TinyInt(true)
+            | FieldType::SmallInt(true)
+            | FieldType::MediumInt(true)
+            | FieldType::Int(true)
+            | FieldType::Int6(true)
+            | FieldType::BigInt(true) => Ok(FieldValue::SignedInt(part.trim().parse()?)),
+            FieldType::TinyInt(false)
+            | FieldType::SmallInt(false)
+            | FieldType::MediumInt(false)
+            | FieldType::Int(false)
+            | FieldType::Int6(false)
+            | FieldType::BigInt(false)
+            | FieldType::RowId => Ok(FieldValue::UnsignedInt(part.trim().parse()?)),
+            FieldType::Float | FieldType::Double => Err(anyhow!(
+                "Floating point key {} is not supported",
+                field.name
+            )),
+            _ => Ok(FieldValue::String(part.to_string())),
+        })
+        .collect()
+}
+
 struct PageExplorer {
     arguments: Arguments,
     table_def: Option<Arc<TableDefinition>>,
     output_writer: Option<JsonStreamWriter<Box<dyn Write>>>,
-    buffer_mgr: Box<dyn BufferManager>,
+    buffer_mgr: Arc<dyn BufferManager>,
+    decryptor: Option<Arc<PageDecryptor>>,
+    doublewrite: Option<Arc<DoublewriteBuffer>>,
     total_records: usize,
+    // Data rows written out, or counted without a table definition, which
+    // is what --max-rows limits
+    dumped_records: usize,
     missing_records: usize,
     incomplete_records: usize,
+    failed_records: usize,
+    garbage_records: usize,
+    corrupt_pages: usize,
+    // Claim to be index pages but can't be read as one
+    failed_pages: usize,
+    inconsistent_pages: usize,
+    // FSP header, XDES, INODE and change buffer bitmap pages
+    space_pages: usize,
+    // In use according to the extent descriptors
+    allocated_pages: usize,
+    // Still encrypted when explored, by space
+    encrypted_pages: HashMap<u32, usize>,
+    broken_sibling_links: usize,
+    lobs_verified: usize,
+    lobs_inconsistent: usize,
+    pk_min: Option<Vec<FieldValue>>,
+    pk_max: Option<Vec<FieldValue>>,
+    filtered_records: usize,
+    // Stdin is read once, kept when the pages are needed again
+    stdin_data: Option<Rc<[u8]>>,
+    // (space_id, index_id)
+    index_stats: HashMap<(u32, u64), IndexStats>,
+    index_summaries: HashMap<(u32, u64), IndexSummary>,
 }
 
 impl PageExplorer {
-    fn write_row(&mut self, deleted: bool, values: &[FieldValue]) -> Result<()> {
+    fn new(arguments: Arguments, table_def: Option<Arc<TableDefinition>>) -> Self {
+        PageExplorer {
+            arguments,
+            table_def,
+            buffer_mgr: Arc::new(DummyBufferMangaer),
+            decryptor: None,
+            doublewrite: None,
+            output_writer: None,
+            total_records: 0,
+            dumped_records: 0,
+            missing_records: 0,
+            incomplete_records: 0,
+            failed_records: 0,
+            garbage_records: 0,
+            corrupt_pages: 0,
+            failed_pages: 0,
+            inconsistent_pages: 0,
+            space_pages: 0,
+            allocated_pages: 0,
+            encrypted_pages: HashMap::new(),
+            broken_sibling_links: 0,
+            lobs_verified: 0,
+            lobs_inconsistent: 0,
+            pk_min: None,
+            pk_max: None,
+            filtered_records: 0,
+            stdin_data: None,
+            index_stats: HashMap::new(),
+            index_summaries: HashMap::new(),
+        }
+    }
+
+    fn is_index_selected(&self, index_id: u64) -> bool {
+        self.arguments.index_ids.is_empty() || self.arguments.index_ids.contains(&index_id)
+    }
+
+    fn summarize_index(&mut self, index: &IndexPage) {
+        let header = &index.index_header;
+        let summary = self
+            .index_summaries
+            .entry((index.page.header.space_id, header.index_id))
+            .or_default();
+        summary.pages += 1;
+        summary.max_level = summary.max_level.max(header.page_level);
+        if header.page_level == 0 {
+            summary.leaf_pages += 1;
+            if summary.sample_record_len.is_none() {
+                summary.sample_record_len = index
+                    .records()
+                    .next()
+                    .and_then(|record| record.ok()?.payload_len(header).ok());
+            }
+        }
+    }
+
+    fn print_index_summaries(&self) {
+        let mut keys: Vec<&(u32, u64)> = self.index_summaries.keys().collect();
+        keys.sort();
+        info!(
+            "{:>10} {:>20} {:>10} {:>10} {:>10} {:>10}",
+            "space_id", "index_id", "pages", "leaves", "levels", "rec_len"
+        );
+        for key in keys {
+            let summary = &self.index_summaries[key];
+            info!(
+                "{:>10} {:>20} {:>10} {:>10} {:>10} {:>10}",
+                key.0,
+                key.1,
+                summary.pages,
+                summary.leaf_pages,
+                summary.max_level + 1,
+                summary
+                    .sample_record_len
+                    .map_or("-".to_string(), |len| len.to_string())
+            );
+        }
+    }
+
+    fn index_stats_mut(&mut self, page: &Page, index_id: u64) -> &mut IndexStats {
+        self.index_stats
+            .entry((page.header.space_id, index_id))
+            .or_default()
+    }
+
+    /// Older versions of the row, as far as the undo log goes
+    fn row_versions(&self, row: &Row, values: &[FieldValue]) -> Vec<RowVersion> {
+        let (Some(trx_id), Some(roll_ptr)) = (row.trx_id(), row.roll_ptr()) else {
+            return Vec::new();
+        };
+        let td = self.table_def.as_ref().unwrap();
+        let mut versions = Vec::new();
+        let chain = VersionChain::new(
+            values.to_vec(),
+            trx_id,
+            roll_ptr,
+            &self.arguments.undo_spaces,
+            td,
+            self.buffer_mgr.as_ref(),
+        );
+        for version in chain {
+            match version {
+                Ok(version) => versions.push(version),
+                Err(e) => warn!("Stopped following the versions of a row: {:?}", e),
+            }
+        }
+        versions
+    }
+
+    fn write_row(&mut self, row: &Row, values: &[FieldValue], garbage: bool) -> Result<()> {
         let mut has_missing = false;
+        let versions = if self.arguments.versions && self.output_writer.is_some() {
+            self.row_versions(row, values)
+        } else {
+            Vec::new()
+        };
         if let Some(writer) = &mut self.output_writer {
             writer.begin_object()?;
             writer.name("_deleted")?;
-            writer.bool_value(deleted)?;
+            writer.bool_value(row.record.header.info_flags.deleted)?;
+            if self.arguments.scan_garbage {
+                writer.name("_garbage")?;
+                writer.bool_value(garbage)?;
+            }
+            if self.arguments.system_columns {
+                writer.name("_trx_id")?;
+                match row.trx_id() {
+                    Some(trx_id) => writer.number_value(trx_id)?,
+                    None => writer.null_value()?,
+                }
+                writer.name("_roll_ptr")?;
+                match row.roll_ptr() {
+                    Some(roll_ptr) => writer.number_value(roll_ptr)?,
+                    None => writer.null_value()?,
+                }
+            }
 
             let td = self.table_def.as_ref().unwrap();
             for (idx, col) in td
@@ -83,17 +581,31 @@ impl PageExplorer {
                 .enumerate()
             {
                 writer.name(&col.name)?;
-                match &values[idx] {
-                    FieldValue::SignedInt(v) => writer.number_value(*v)?,
-                    FieldValue::UnsignedInt(v) => writer.number_value(*v)?,
-                    FieldValue::String(s) => writer.string_value(s)?,
-                    FieldValue::Null => writer.null_value()?,
-                    FieldValue::Skipped => {
-                        has_missing = true;
-                        writer.null_value()?;
+                has_missing |= write_value(writer, &values[idx])?;
+            }
+            if self.arguments.versions {
+                writer.name("_versions")?;
+                writer.begin_array()?;
+                for version in versions.iter() {
+                    writer.begin_object()?;
+                    writer.name("_deleted")?;
+                    writer.bool_value(version.deleted)?;
+                    writer.name("_trx_id")?;
+                    writer.number_value(version.trx_id)?;
+                    writer.name("_roll_ptr")?;
+                    writer.number_value(version.roll_ptr)?;
+                    for (col, value) in td
+                        .cluster_columns
+                        .iter()
+                        .chain(td.data_columns.iter())
+                        .zip(version.values.iter())
+                    {
+                        writer.name(&col.name)?;
+                        write_value(writer, value)?;
                     }
-                    _ => panic!("Unsupported Field Value for writing JSON"),
-                };
+                    writer.end_object()?;
+                }
+                writer.end_array()?;
             }
             writer.end_object()?;
         }
@@ -104,51 +616,235 @@ impl PageExplorer {
         Ok(())
     }
 
-    pub fn explore_index(&mut self, index: &IndexPage) {
-        let index_header = &index.index_header;
-        debug!("Inspecting Index Page {}", index.page.header.offset);
-        trace!("Index Header:\n{:#?}", &index_header);
-        let mut record = index.infimum().unwrap();
-        let mut data_counter = 0;
-        let mut other_record_counter = 0;
-        loop {
-            match record.header.record_type {
-                RecordType::Infimum => {}
-                RecordType::Supremum => {
+    fn verify_lobs(&mut self, row: &Row) {
+        let references = match row.extern_references() {
+            Ok(references) => references,
+            Err(e) => {
+                warn!("Failed to locate extern columns: {:?}", e);
+                return;
+            }
+        };
+
+        for extern_ref in references.iter() {
+            self.lobs_verified += 1;
+            match verify_chain(extern_ref, self.buffer_mgr.as_ref()) {
+                Ok(report) if report.is_consistent() => {
+                    trace!("LOB {:?} is consistent", extern_ref);
+                }
+                Ok(report) => {
+                    self.lobs_inconsistent += 1;
+                    warn!("Inconsistent LOB {:?}: {:#?}", extern_ref, report);
+                }
+                Err(e) => {
+                    self.lobs_inconsistent += 1;
+                    warn!("Failed to verify LOB {:?}: {:?}", extern_ref, e);
+                }
+            }
+        }
+    }
+
+    /// Parses --pk-min and --pk-max against the table definition
+    fn parse_pk_range(&mut self) -> Result<()> {
+        let Some(table) = self.table_def.clone() else {
+            return Ok(());
+        };
+        let parse = |key: &Option<String>| {
+            key.as_deref()
+                .map(|key| parse_key_prefix(&table, key))
+                .transpose()
+        };
+        self.pk_min = parse(&self.arguments.pk_min)?;
+        self.pk_max = parse(&self.arguments.pk_max)?;
+        Ok(())
+    }
+
+    fn in_pk_range(&self, row: &Row) -> Result<bool> {
+        if self.pk_min.is_none() && self.pk_max.is_none() {
+            return Ok(true);
+        }
+        let key = row.cluster_key()?;
+        if let Some(min) = &self.pk_min {
+            if key_prefix_cmp(&key, min)? == Ordering::Less {
+                return Ok(false);
+            }
+        }
+        if let Some(max) = &self.pk_max {
+            if key_prefix_cmp(&key, max)? == Ordering::Greater {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Writes out a parsed row of `index`, returns false if it failed to be
+    /// written
+    fn dump_row(
+        &mut self,
+        row: &Row,
+        values: &[FieldValue],
+        index: &IndexPage,
+        garbage: bool,
+    ) -> bool {
+        debug!("{:?}", values);
+        match self.in_pk_range(row).and_then(|in_range| {
+            if !in_range {
+                self.filtered_records += 1;
+                return Ok(false);
+            }
+            self.write_row(row, values, garbage)?;
+            Ok(true)
+        }) {
+            Ok(true) => {
+                if !garbage {
+                    self.dumped_records += 1;
+                }
+                // Deleted records may point at LOBs that were already freed
+                if self.arguments.verify_lobs && !garbage {
+                    self.verify_lobs(row);
+                }
+                true
+            }
+            Ok(false) => true,
+            Err(e) => {
+                warn!(
+                    "Failed to write record at {} on page {}: {:?}",
+                    row.record.offset, index.page.header.offset, e
+                );
+                false
+            }
+        }
+    }
+
+    /// Parses a record and writes it out, returns false if it failed to parse
+    fn dump_record(&mut self, record: &Record, index: &IndexPage, garbage: bool) -> bool {
+        let Some(table) = self.table_def.clone() else {
+            return true;
+        };
+        match parse_row(record, &table, self.buffer_mgr.as_ref()) {
+            Ok((row, values)) => self.dump_row(&row, &values, index, garbage),
+            Err(e) => {
+                warn!(
+                    "Failed to parse record at {} on page {}: {:?}",
+                    record.offset, index.page.header.offset, e
+                );
+                false
+            }
+        }
+    }
+
+    fn scan_garbage(&mut self, index: &IndexPage) {
+        for record in index.garbage_records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!(
+                        "Stopped walking garbage list of page {}: {:?}",
+                        index.page.header.offset, e
+                    );
                     break;
                 }
+            };
+            if record.header.record_type != RecordType::Conventional {
+                continue;
+            }
+            if self.dump_record(&record, index, true) {
+                self.garbage_records += 1;
+            }
+        }
+    }
+
+    /// Whether --max-rows data rows were dumped, counting `pending` rows of
+    /// the page being explored
+    fn max_rows_reached(&self, pending: usize) -> bool {
+        self.arguments
+            .max_rows
+            .is_some_and(|max_rows| self.dumped_records + pending >= max_rows)
+    }
+
+    /// Counts the records of an index page there's no table definition for
+    fn count_records(&self, index: &IndexPage) -> IndexDump {
+        let mut dump = IndexDump::default();
+        for record in index.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!(
+                        "Page {} is corrupt, stopped walking records: {:?}",
+                        index.page.header.offset, e
+                    );
+                    dump.corrupt = true;
+                    break;
+                }
+            };
+            match record.header.record_type {
                 RecordType::Conventional => {
-                    data_counter += 1;
-                    if let Some(table) = &self.table_def {
-                        let row = Row::try_from_record_and_table(&record, table)
-                            .expect("Failed to parse row");
-                        let values = row.parse_values(self.buffer_mgr.as_mut());
-                        assert_eq!(values.len(), table.field_count());
-                        debug!("{:?}", values);
-                        self.write_row(row.record.header.info_flags.deleted, &values)
-                            .expect("Failed to write row");
+                    if self.max_rows_reached(dump.rows) {
+                        break;
                     }
+                    dump.rows += 1;
                 }
-                RecordType::NodePointer => {
-                    other_record_counter += 1;
-                }
-                #[allow(unreachable_patterns)]
-                _ => {
-                    info!("Unknown Record Type: {:?}", record);
-                }
+                RecordType::NodePointer => dump.node_pointers += 1,
+                _ => info!("Unknown Record Type: {:?}", record),
+            }
+        }
+        dump
+    }
+
+    pub fn explore_index(&mut self, index: &IndexPage) -> Result<()> {
+        let index_header = &index.index_header;
+        debug!("Inspecting Index Page {}", index.page.header.offset);
+        trace!("Index Header:\n{:#?}", &index_header);
+        let dump = match self.table_def.clone() {
+            Some(table) => {
+                let buffer_mgr = self.buffer_mgr.clone();
+                let dump = dump_index(index, &table, buffer_mgr.as_ref(), |row, values| {
+                    if !self.dump_row(row, values, index, false) {
+                        self.failed_records += 1;
+                    }
+                    if self.max_rows_reached(0) {
+                        return ControlFlow::Break(());
+                    }
+                    ControlFlow::Continue(())
+                });
+                self.failed_records += dump.failed;
+                dump
             }
-            let new_rec = record.next().unwrap();
-            record = new_rec;
+            None => {
+                let dump = self.count_records(index);
+                self.dumped_records += dump.rows;
+                dump
+            }
+        };
+        if dump.corrupt {
+            self.corrupt_pages += 1;
+        }
+        let data_counter = dump.rows + dump.failed;
+        let other_record_counter = dump.node_pointers;
+        if self.arguments.scan_garbage && !self.max_rows_reached(0) {
+            self.scan_garbage(index);
         }
         self.total_records += data_counter;
-        let missing =
-            index.index_header.number_of_records as usize - data_counter - other_record_counter;
-        if missing > 0 {
-            self.missing_records += missing;
-            warn!(
-                "Missing {} records on page {}",
-                missing, index.page.header.offset
-            );
+        let stats = self.index_stats_mut(&index.page, index_header.index_id);
+        stats.pages += 1;
+        stats.records += data_counter;
+        if self.arguments.check_siblings {
+            if let Err(e) = index.next_sibling(self.buffer_mgr.as_ref()) {
+                warn!(
+                    "Broken sibling link after page {}: {:?}",
+                    index.page.header.offset, e
+                );
+                self.broken_sibling_links += 1;
+            }
+        }
+        let report = index.verify();
+        if !report.is_empty() {
+            self.inconsistent_pages += 1;
+        }
+        for inconsistency in report {
+            warn!("Page {}: {}", index.page.header.offset, inconsistency);
+            if let PageInconsistency::RecordCount { header, found } = inconsistency {
+                self.missing_records += (header as usize).saturating_sub(found);
+            }
         }
         info!(
             "Found ({} data + {} node pointer)/{} records on index page {}",
@@ -157,25 +853,132 @@ impl PageExplorer {
             index.index_header.number_of_records,
             index.page.header.offset
         );
+        Ok(())
+    }
+
+    fn explore_rtree(&mut self, rtree: &RtreePage) -> Result<()> {
+        let mut entries = 0;
+        for entry in rtree.entries() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!(
+                        "Spatial index page {} is corrupt, stopped walking entries: {:?}",
+                        rtree.index.page.header.offset, e
+                    );
+                    self.corrupt_pages += 1;
+                    break;
+                }
+            };
+            debug!("{:?} child: {:?}", entry.mbr, entry.child_page);
+            entries += 1;
+        }
+        info!(
+            "Found {}/{} {} entries on spatial index page {}",
+            entries,
+            rtree.index.index_header.number_of_records,
+            if rtree.is_leaf() {
+                "leaf"
+            } else {
+                "node pointer"
+            },
+            rtree.index.page.header.offset
+        );
+        Ok(())
+    }
+
+    /// Counts the pages the extent descriptors mark as in use
+    fn explore_xdes(&mut self, page: Page) -> Result<()> {
+        let xdes = XdesPage::try_from_page(page)?;
+        let mut allocated = 0;
+        for entry in xdes.entries() {
+            allocated += entry?.allocated_pages().count();
+        }
+        debug!(
+            "Descriptor page {} marks {} pages in use",
+            xdes.page.header.offset, allocated
+        );
+        self.space_pages += 1;
+        self.allocated_pages += allocated;
+        Ok(())
     }
 
-    fn explore_page(&mut self, file_offset: usize, page: Page) {
+    fn explore_page(&mut self, file_offset: usize, page: Page) -> Result<()> {
         if page.header.page_type == PageType::Allocated {
-            return;
+            return Ok(());
         }
-        if page.crc32_checksum() == page.header.new_checksum {
-            trace!("Page @ {:#x} byte has valid CRC32c checksum", file_offset);
-        } else if page.innodb_checksum() == page.header.new_checksum {
-            trace!("Page @ {:#x} byte has valid InnoDB checksum", file_offset);
-        } else {
-            warn!(
-                "Page @ {:#x} has invalid checksum: {:#08x} vs crc32: {:#08x} InnoDB: {:#08x}",
+        let policy = ChecksumPolicy {
+            allow_none: self.arguments.allow_no_checksum,
+        };
+        if let Some(algorithm) = self.arguments.innochecksum {
+            let report = page.innochecksum();
+            info!(
+                "Page {} @ {:#x}: {}; {} {}, library {:?}",
+                page.header.offset,
                 file_offset,
-                page.header.new_checksum,
-                page.crc32_checksum(),
-                page.innodb_checksum()
+                report,
+                algorithm,
+                if report.is_valid(algorithm) {
+                    "passes"
+                } else {
+                    "fails"
+                },
+                page.validate_checksum(policy)
             );
-            return;
+        }
+        let validation = page.validate_with(policy);
+        if validation == PageValidation::Encrypted {
+            // Nothing on the page can be read without the key
+            debug!(
+                "Page {} of space {} is encrypted, skipped",
+                page.header.offset, page.header.space_id
+            );
+            *self
+                .encrypted_pages
+                .entry(page.header.space_id)
+                .or_default() += 1;
+            return Ok(());
+        }
+        if let Some(kind) = validation.checksum_kind() {
+            trace!(
+                "Page @ {:#x} byte has valid {:?} checksum",
+                file_offset,
+                kind
+            );
+        } else {
+            match validation {
+                PageValidation::TornPage {
+                    header_lsn,
+                    trailer_lsn,
+                } => warn!(
+                    "Page @ {:#x} looks torn, header LSN {:#x} vs trailer {:#010x}",
+                    file_offset, header_lsn, trailer_lsn
+                ),
+                PageValidation::BadChecksum {
+                    stored,
+                    crc32,
+                    innodb,
+                } => warn!(
+                    "Page @ {:#x} has invalid checksum: {:#08x} vs crc32: {:#08x} InnoDB: {:#08x}, full_crc32 {:#08x} vs {:#08x}",
+                    file_offset,
+                    stored,
+                    crc32,
+                    innodb,
+                    page.trailer.lsn_low_32,
+                    page.full_crc32_checksum()
+                ),
+                _ => warn!(
+                    "Page @ {:#x} is not a page: {:?}, page type {}",
+                    file_offset, validation, page.header.page_type_raw
+                ),
+            }
+            if page.header.page_type == PageType::Index {
+                if let Ok(index_header) = IndexHeader::from_bytes(page.body()) {
+                    self.index_stats_mut(&page, index_header.index_id)
+                        .checksum_failures += 1;
+                }
+            }
+            return Ok(());
         }
 
         trace!("{:x?}", page);
@@ -183,41 +986,245 @@ impl PageExplorer {
         match page.header.page_type {
             PageType::Allocated => {}
             PageType::Index => {
-                let index_page = IndexPage::try_from_page(page).expect("Failed to construct index");
-                if let Some(filtered_index_id) = self.arguments.index_id {
-                    if index_page.index_header.index_id != filtered_index_id {
-                        return;
+                let index_page = match read_index_page(&page) {
+                    Ok(index_page) => index_page,
+                    Err(e) => {
+                        warn!(
+                            "Page {} @ {:#x} is not a readable index page: {:?}",
+                            page.header.offset, file_offset, e
+                        );
+                        self.failed_pages += 1;
+                        return Ok(());
                     }
+                };
+                if !self.is_index_selected(index_page.index_header.index_id) {
+                    return Ok(());
+                }
+                if self.arguments.list_indexes {
+                    self.summarize_index(&index_page);
+                    return Ok(());
+                }
+                self.explore_index(&index_page)?;
+            }
+            PageType::RTree => {
+                let rtree_page = RtreePage::try_from_page(page)?;
+                if !self.is_index_selected(rtree_page.index.index_header.index_id)
+                    || self.arguments.list_indexes
+                {
+                    return Ok(());
                 }
-                self.explore_index(&index_page);
+                self.explore_rtree(&rtree_page)?;
             }
             PageType::Blob | PageType::LobFirst | PageType::LobData => {}
-            _ => warn!("Unknown page type: {:?}", page.header.page_type),
+            PageType::FspHdr => {
+                let fsp = FspHeaderPage::try_from_page(&page)?;
+                info!(
+                    "Space {} header: {} pages, free limit {}, {} page size, flags {:#x}{}{}",
+                    fsp.space_id,
+                    fsp.size,
+                    fsp.free_limit,
+                    fsp.logical_page_size()?,
+                    fsp.flags.0,
+                    if fsp.has_sdi() { ", has SDI" } else { "" },
+                    if fsp.is_encrypted() {
+                        ", encrypted"
+                    } else {
+                        ""
+                    },
+                );
+                debug!("{:#?}", fsp);
+                self.explore_xdes(page)?;
+            }
+            PageType::Xdes => self.explore_xdes(page)?,
+            PageType::Inode => {
+                let inode_page = InodePage::try_from_page(page)?;
+                for inode in inode_page.inodes() {
+                    let inode = inode?;
+                    debug!(
+                        "Segment {} at offset {}, fragment pages {:?}, {} full and {} partly used extents",
+                        inode.fseg_id,
+                        inode.offset,
+                        inode.frag_pages().collect::<Vec<_>>(),
+                        inode.full.list_len,
+                        inode.not_full.list_len,
+                    );
+                }
+                self.space_pages += 1;
+            }
+            PageType::IbufBitmap => {
+                let bitmap = IbufBitmap::try_from_page(page)?;
+                let buffered = bitmap.buffered_pages().collect::<Vec<_>>();
+                if buffered.is_empty() {
+                    debug!(
+                        "Change buffer bitmap page {} of space {}, nothing buffered",
+                        bitmap.page.header.offset, bitmap.page.header.space_id
+                    );
+                } else {
+                    // Expected after a crash, the merge never happened
+                    info!(
+                        "Space {} has changes for pages {:?} in the change buffer, their rows may be outdated",
+                        bitmap.page.header.space_id, buffered
+                    );
+                }
+                self.space_pages += 1;
+            }
+            _ => warn!(
+                "Unknown page type: {:?} ({})",
+                page.header.page_type, page.header.page_type_raw
+            ),
         }
+        Ok(())
     }
 
-    fn run(&mut self) {
-        let mut reader =
-            BufReader::new(File::open(&self.arguments.file).expect("Can't open page file"));
-        let mut buffer = Box::<[u8]>::from([0u8; FIL_PAGE_SIZE]);
-        let mut counter = 0usize;
-        let mut index_counter = 0usize;
+    /// Restores a torn page read straight from the page file from the
+    /// doublewrite buffer, pages are known by their header only
+    fn restore_page(&self, buf: &mut [u8]) {
+        if let (Some(doublewrite), Ok(header)) = (&self.doublewrite, FILHeader::from_bytes(buf)) {
+            doublewrite.restore(header.space_id, header.offset, buf);
+        }
+    }
 
-        if let Some(output) = &self.arguments.output {
-            let file = File::create(output).expect("Can't open output file for write");
-            let mut writer = JsonStreamWriter::new(Box::new(file) as Box<dyn Write>);
-            writer.begin_array().expect("Can't begin array");
-            self.output_writer.replace(writer);
+    /// Decrypts a page read straight from the page file, the key of a
+    /// tablespace is picked up from its page 0 on the way
+    fn decrypt_page(&self, buf: &mut [u8]) -> Result<()> {
+        let Some(decryptor) = &self.decryptor else {
+            return Ok(());
+        };
+        if FILHeader::from_bytes(buf)?.page_type == PageType::FspHdr {
+            decryptor.add_space(buf)?;
+            return Ok(());
+        }
+        decryptor.decrypt_page(buf, |space_id| {
+            let first_page = self.buffer_mgr.pin(space_id, 0)?;
+            Ok(first_page.raw_data().to_vec())
+        })
+    }
+
+    fn input(&self) -> Result<Box<dyn Read>> {
+        match &self.stdin_data {
+            Some(data) => Ok(Box::new(Cursor::new(data.clone()))),
+            None => Ok(open_input(&self.arguments.file)?),
+        }
+    }
+
+    /// --page-size, or the size in the tablespace header if `head` starts
+    /// with one
+    fn page_size(&self, head: &[u8]) -> PageSize {
+        if let Some(size) = self.arguments.page_size {
+            return size;
+        }
+        match PageSize::detect(head) {
+            Ok(size) => {
+                debug!("Detected {} pages from the tablespace header", size);
+                size
+            }
+            Err(_) => PageSize::default(),
+        }
+    }
+
+    /// Reads only the page at the position of `page_id`. A file starting with
+    /// a tablespace header is read through [`Tablespace`], other page files
+    /// only with --sorted. Fails before exploring anything when the file
+    /// doesn't look sorted, the caller then scans the whole file instead.
+    fn read_page_by_id<R: Read + Seek>(&mut self, mut reader: R, page_id: u32) -> Result<usize> {
+        let read_at = |reader: &mut R, n: u32, size: PageSize| -> Result<Box<[u8]>> {
+            let mut buffer = vec![0u8; size.bytes()].into_boxed_slice();
+            reader.seek(SeekFrom::Start(n as u64 * size.bytes() as u64))?;
+            reader.read_exact(&mut buffer)?;
+            Ok(buffer)
+        };
+        let needs_key = self.decryptor.is_some();
+        let sorted_size = self.arguments.page_size.filter(|_| self.arguments.sorted);
+        let (first_page, mut buffer, page_size) = match sorted_size {
+            Some(size) => (
+                needs_key.then(|| read_at(&mut reader, 0, size)).transpose()?,
+                read_at(&mut reader, page_id, size)?,
+                size,
+            ),
+            None => match Tablespace::from_reader(&mut reader) {
+                Ok(tablespace) => (
+                    needs_key
+                        .then(|| tablespace.page(0).map(|page| page.raw_data().into()))
+                        .transpose()?,
+                    tablespace.page(page_id)?.raw_data().into(),
+                    tablespace.page_size(),
+                ),
+                Err(_) if self.arguments.sorted => (
+                    needs_key
+                        .then(|| read_at(&mut reader, 0, PageSize::default()))
+                        .transpose()?,
+                    read_at(&mut reader, page_id, PageSize::default())?,
+                    PageSize::default(),
+                ),
+                Err(e) => {
+                    return Err(e.context(
+                        "The page file doesn't start with a tablespace header, pass --sorted to seek anyway",
+                    ))
+                }
+            },
+        };
+        if let Some(mut first_page) = first_page {
+            // The tablespace key is on page 0
+            self.decrypt_page(&mut first_page)?;
+        }
+
+        let file_offset = page_id as usize * page_size.bytes();
+        self.restore_page(&mut buffer);
+        let decrypted = self.decrypt_page(&mut buffer);
+        let page = Page::from_bytes_with_size(&buffer, page_size)?;
+        if page.header.offset != page_id {
+            return Err(anyhow!(
+                "Found page {} at the position of page {}, the page file is not sorted",
+                page.header.offset,
+                page_id
+            ));
+        }
+        if let Err(e) = decrypted {
+            warn!("Failed to decrypt page @ {:#x}: {:?}", file_offset, e);
+            self.corrupt_pages += 1;
+        } else if let Err(e) = self.explore_page(file_offset, page) {
+            warn!("Failed to explore page @ {:#x}: {:?}", file_offset, e);
+            self.corrupt_pages += 1;
+        }
+        Ok(1)
+    }
+
+    fn scan_file(&mut self) -> usize {
+        if let Some(page_id) = self.arguments.page_id {
+            if !is_stdin(&self.arguments.file) {
+                let result = File::open(&self.arguments.file)
+                    .map_err(Into::into)
+                    .and_then(|file| self.read_page_by_id(BufReader::new(file), page_id));
+                match result {
+                    Ok(counter) => return counter,
+                    Err(e) => debug!("Scanning the page file for page {}: {:?}", page_id, e),
+                }
+            }
+        }
+
+        let mut reader = BufReader::new(self.input().expect("Can't open page file"));
+        // The smallest page is enough to find the page size, put it back in
+        // front of the rest of the input afterwards
+        let mut head = vec![0u8; PageSize::Size4K.bytes()];
+        match reader.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return 0,
+            Err(e) => panic!("Read error: {:?}", e),
         }
+        let page_size = self.page_size(&head);
+        let mut reader = Cursor::new(head).chain(reader);
+        let mut buffer = vec![0u8; page_size.bytes()].into_boxed_slice();
+        let mut counter = 0usize;
+        let mut index_counter = 0usize;
 
         loop {
-            let cur_offset = counter * FIL_PAGE_SIZE;
-            match reader.read(&mut buffer) {
-                Ok(num_bytes) => {
-                    if num_bytes < buffer.len() {
-                        break;
-                    }
-                    let page = Page::from_bytes(&buffer).unwrap();
+            let cur_offset = counter * page_size.bytes();
+            // Pipes hand out partial reads, only a short last page ends the scan
+            match reader.read_exact(&mut buffer) {
+                Ok(()) => {
+                    self.restore_page(&mut buffer);
+                    let decrypted = self.decrypt_page(&mut buffer);
+                    let page = Page::from_bytes_with_size(&buffer, page_size).unwrap();
                     if page.header.page_type == PageType::Index {
                         index_counter += 1;
                     }
@@ -227,8 +1234,15 @@ impl PageExplorer {
                         }
                     }
                     counter += 1;
-                    self.explore_page(cur_offset, page);
+                    if let Err(e) = decrypted {
+                        warn!("Failed to decrypt page @ {:#x}: {:?}", cur_offset, e);
+                        self.corrupt_pages += 1;
+                    } else if let Err(e) = self.explore_page(cur_offset, page) {
+                        warn!("Failed to explore page @ {:#x}: {:?}", cur_offset, e);
+                        self.corrupt_pages += 1;
+                    }
                 }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
                 Err(e) => panic!("Read error: {:?}", e),
             }
 
@@ -238,17 +1252,238 @@ impl PageExplorer {
                     break;
                 }
             }
+            if self.max_rows_reached(0) {
+                info!("Exiting early due to --max-rows argument");
+                break;
+            }
+        }
+        counter
+    }
+
+    /// Root, space id and table of the index given by --root. Without a
+    /// tablespace directory the pages are served from the page file.
+    fn open_btree(&mut self) -> Result<(u32, u32, Arc<TableDefinition>)> {
+        let root = self
+            .arguments
+            .root
+            .ok_or_else(|| anyhow!("The index root page is not set"))?;
+        let table = self
+            .table_def
+            .clone()
+            .ok_or_else(|| anyhow!("Walking the B+tree needs a table definition"))?;
+        let data = self.serve_page_file()?;
+        let page_size = self.page_size(&data);
+        let space_id = Page::from_bytes_with_size(
+            data.get(..page_size.bytes()).unwrap_or_default(),
+            page_size,
+        )?
+        .header
+        .space_id;
+        Ok((root, space_id, table))
+    }
+
+    /// Serves pages from the page file unless a tablespace directory is set
+    fn serve_page_file(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.input()?.read_to_end(&mut data)?;
+        if is_stdin(&self.arguments.file) {
+            self.stdin_data = Some(Rc::from(data.as_slice()));
+        }
+        if self.arguments.tablespce_dir.is_none() {
+            let page_size = self.page_size(&data);
+            let mut buffer_mgr = MemoryBufferManager::with_page_size(page_size);
+            if let Some(decryptor) = &self.decryptor {
+                buffer_mgr.set_decryptor(decryptor.clone());
+            }
+            if let Some(doublewrite) = &self.doublewrite {
+                buffer_mgr.set_doublewrite(doublewrite.clone());
+            }
+            for buf in data.chunks_exact(page_size.bytes()) {
+                buffer_mgr.add_page(buf)?;
+            }
+            self.buffer_mgr = Arc::new(buffer_mgr);
+        }
+        Ok(data)
+    }
+
+    /// Explores the leaf pages of the index in key order
+    fn walk_btree(&mut self) -> Result<usize> {
+        let (root, space_id, table) = self.open_btree()?;
+        let buffer_mgr = self.buffer_mgr.clone();
+        let btree = BTreeIndex::new(root, space_id, buffer_mgr.as_ref());
+        let mut counter = 0;
+        for leaf in btree.leaves(&table) {
+            let leaf = match leaf {
+                Ok(leaf) => leaf,
+                Err(e) => {
+                    warn!("Stopped walking the B+tree at {}: {:?}", root, e);
+                    self.corrupt_pages += 1;
+                    break;
+                }
+            };
+            counter += 1;
+            let file_offset = leaf.header.offset as usize * leaf.size().bytes();
+            if let Err(e) = self.explore_page(file_offset, leaf.page()) {
+                warn!("Failed to explore page {}: {:?}", leaf.header.offset, e);
+                self.corrupt_pages += 1;
+            }
+            if self.max_rows_reached(0) {
+                info!("Exiting early due to --max-rows argument");
+                break;
+            }
+        }
+        Ok(counter)
+    }
+
+    /// Writes the row matching `key` as a single JSON object, or `null`
+    fn get_pk(&mut self, key: &str) -> Result<()> {
+        let (root, space_id, table) = self.open_btree()?;
+        let key = parse_key(&table, key)?;
+        let output: Box<dyn Write> = match &self.arguments.output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let mut writer = JsonStreamWriter::new(output);
+
+        let buffer_mgr = self.buffer_mgr.clone();
+        let btree = BTreeIndex::new(root, space_id, buffer_mgr.as_ref());
+        match btree.lookup(&key, &table)? {
+            Some(row) => {
+                let (row, values) = parse_row(&row.record(), &table, buffer_mgr.as_ref())?;
+                self.output_writer = Some(writer);
+                self.write_row(&row, &values, false)?;
+                writer = self.output_writer.take().unwrap();
+            }
+            None => {
+                info!("No row with key {:?}", key);
+                writer.null_value()?;
+            }
+        }
+        writer.finish_document()?;
+        Ok(())
+    }
+
+    fn run(&mut self) {
+        if let Err(e) = self.parse_pk_range() {
+            warn!("Invalid primary key range: {:?}", e);
+            return;
+        }
+        if let Some(key) = self.arguments.get_pk.clone() {
+            if let Err(e) = self.get_pk(&key) {
+                warn!("Failed to look up {}: {:?}", key, e);
+            }
+            return;
+        }
+
+        if let Some(output) = &self.arguments.output {
+            let file = File::create(output).expect("Can't open output file for write");
+            let mut writer = JsonStreamWriter::new(Box::new(file) as Box<dyn Write>);
+            writer.begin_array().expect("Can't begin array");
+            self.output_writer.replace(writer);
+        }
+
+        if (self.arguments.check_siblings || self.arguments.versions) && !self.arguments.walk_btree
+        {
+            if let Err(e) = self.serve_page_file() {
+                warn!("Failed to load the page file: {:?}", e);
+            }
         }
 
+        let counter = if self.arguments.walk_btree {
+            self.walk_btree().unwrap_or_else(|e| {
+                warn!("Failed to walk the B+tree: {:?}", e);
+                0
+            })
+        } else {
+            self.scan_file()
+        };
+
         if let Some(mut writer) = self.output_writer.take() {
             writer.end_array().expect("Can't end array");
             writer.finish_document().expect("Can't finish document");
         }
 
         info!(
-            "Processed {} pages, total records: {}, potentially missing: {}, Incomplete: {}",
-            counter, self.total_records, self.missing_records, self.incomplete_records
+            "Processed {} pages, total records: {}, potentially missing: {}, Incomplete: {}, Failed: {}, Corrupt pages: {}, Failed pages: {}, Inconsistent pages: {}, Space management pages: {}",
+            counter,
+            self.total_records,
+            self.missing_records,
+            self.incomplete_records,
+            self.failed_records,
+            self.corrupt_pages,
+            self.failed_pages,
+            self.inconsistent_pages,
+            self.space_pages
+        );
+        if self.allocated_pages > 0 {
+            info!(
+                "Extent descriptors mark {} pages in use",
+                self.allocated_pages
+            );
+        }
+        if self.arguments.encrypted_count {
+            self.print_encrypted_pages();
+        }
+        if self.arguments.scan_garbage {
+            info!(
+                "Recovered {} records from garbage lists",
+                self.garbage_records
+            );
+        }
+        if self.pk_min.is_some() || self.pk_max.is_some() {
+            info!(
+                "Skipped {} records outside the primary key range",
+                self.filtered_records
+            );
+        }
+        if self.arguments.check_siblings {
+            info!(
+                "Checked sibling links, {} broken",
+                self.broken_sibling_links
+            );
+        }
+        if self.arguments.verify_lobs {
+            info!(
+                "Verified {} LOBs, {} inconsistent",
+                self.lobs_verified, self.lobs_inconsistent
+            );
+        }
+        if self.arguments.list_indexes {
+            self.print_index_summaries();
+        } else {
+            self.print_index_stats();
+        }
+    }
+
+    fn print_encrypted_pages(&self) {
+        if self.encrypted_pages.is_empty() {
+            info!("No encrypted pages left undecrypted");
+            return;
+        }
+        let mut spaces: Vec<_> = self.encrypted_pages.iter().collect();
+        spaces.sort();
+        for (space_id, pages) in spaces {
+            info!(
+                "Space {} has {} encrypted pages that were skipped",
+                space_id, pages
+            );
+        }
+    }
+
+    fn print_index_stats(&self) {
+        let mut keys: Vec<&(u32, u64)> = self.index_stats.keys().collect();
+        keys.sort();
+        info!(
+            "{:>10} {:>20} {:>10} {:>10} {:>10}",
+            "space_id", "index_id", "pages", "records", "bad_csum"
         );
+        for key in keys {
+            let stats = &self.index_stats[key];
+            info!(
+                "{:>10} {:>20} {:>10} {:>10} {:>10}",
+                key.0, key.1, stats.pages, stats.records, stats.checksum_failures
+            );
+        }
     }
 }
 
@@ -267,27 +1502,813 @@ fn main() {
         .finish();
     tracing::subscriber::set_global_default(subscriber).expect("Failed to setup Logger");
 
-    let table_def: Option<Arc<TableDefinition>> = args.table_def.as_ref().map(|table_def_sql| {
-        let sql = read_to_string(table_def_sql).expect("Can't load SQL file");
-        let tbl = TableDefinition::try_from_sql_statement(&sql).expect("Failed parsing table");
-        info!("Loaded Table:\n{:#?}", &tbl);
-        Arc::new(tbl)
-    });
-
-    let mut explorer = PageExplorer {
-        arguments: args.clone(),
-        table_def,
-        buffer_mgr: Box::new(DummyBufferMangaer),
-        output_writer: None,
-        total_records: 0,
-        missing_records: 0,
-        incomplete_records: 0,
-    };
+    let table_def = load_table_def(&args)
+        .expect("Can't load the table definition")
+        .map(|tbl| {
+            info!("Loaded Table:\n{:#?}", &tbl);
+            Arc::new(tbl)
+        });
+
+    let mut explorer = PageExplorer::new(args.clone(), table_def);
+    explorer.decryptor = open_decryptor(&args).expect("Can't load the master key");
+    explorer.doublewrite = open_doublewrite(&args).expect("Can't read the doublewrite buffer");
 
     if let Some(tablespace) = &args.tablespce_dir {
         // explorer.buffer_mgr = Box::new(SimpleBufferManager::new(tablespace));
-        explorer.buffer_mgr = Box::new(LRUBufferManager::new(tablespace));
+        let mut buffer_mgr = LRUBufferManager::with_page_size_and_capacity(
+            tablespace,
+            args.page_size.unwrap_or_default(),
+            args.buffer_pages.get(),
+        );
+        buffer_mgr.set_read_ahead(args.read_ahead);
+        buffer_mgr.set_checksum_policy(ChecksumPolicy {
+            allow_none: args.allow_no_checksum,
+        });
+        if let Some(decryptor) = &explorer.decryptor {
+            buffer_mgr.set_decryptor(decryptor.clone());
+        }
+        if let Some(doublewrite) = &explorer.doublewrite {
+            buffer_mgr.set_doublewrite(doublewrite.clone());
+        }
+        explorer.buffer_mgr = Arc::new(buffer_mgr);
     }
 
     explorer.run();
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{Read, Seek, SeekFrom},
+    };
+
+    use std::sync::Arc;
+
+    use clap::Parser;
+    use innodb::innodb::{
+        page::{index::IndexPage, Page, PageType, BUF_NO_CHECKSUM_MAGIC, FIL_PAGE_SIZE},
+        table::{field::FieldValue, row::Row, TableDefinition},
+        test_data, test_data_path,
+        testing::{blank_page, set_crc32_checksum, with_records},
+    };
+    use struson::{
+        reader::{JsonReader, JsonStreamReader},
+        writer::{JsonStreamWriter, JsonWriter},
+    };
+
+    use super::{
+        load_table_def, open_decryptor, open_doublewrite, parse_key, parse_key_prefix, Arguments,
+        IndexStats, PageExplorer,
+    };
+
+    fn float_sample_index_page() -> Vec<u8> {
+        let data = test_data("float_sample.ibd");
+        data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec()
+    }
+
+    fn set_index_id(page: &mut [u8], index_id: u64) {
+        page[38 + 28..38 + 36].copy_from_slice(&index_id.to_be_bytes());
+        set_crc32_checksum(page);
+    }
+
+    fn explore(name: &str, pages: &[Vec<u8>]) -> PageExplorer {
+        explore_with_args(name, pages, &[])
+    }
+
+    fn explore_with_args(name: &str, pages: &[Vec<u8>], extra_args: &[&str]) -> PageExplorer {
+        let path = std::env::temp_dir().join(format!("{}_{}.pages", name, std::process::id()));
+        fs::write(&path, pages.concat()).unwrap();
+
+        let mut args = vec!["page_explorer"];
+        args.extend_from_slice(extra_args);
+        args.push(path.to_str().unwrap());
+        let args = Arguments::parse_from(args);
+        let mut explorer = PageExplorer::new(args.clone(), None);
+        explorer.decryptor = open_decryptor(&args).unwrap();
+        explorer.doublewrite = open_doublewrite(&args).unwrap();
+        explorer.run();
+        fs::remove_file(&path).unwrap();
+        explorer
+    }
+
+    const ROW_SIZE: usize = 1 + 5 + 4 + 13 + 10;
+
+    /// Index page of `(id INT, name VARCHAR(10))` rows, names are written
+    /// with the given length prefix
+    fn build_name_page(names: &[(&str, u8)]) -> Vec<u8> {
+        let mut page = float_sample_index_page();
+        page[120..1024].fill(0);
+        let records: Vec<(Vec<u8>, Vec<u8>)> = names
+            .iter()
+            .enumerate()
+            .map(|(i, (name, len))| {
+                let mut data = ((i as u32) ^ 0x8000_0000).to_be_bytes().to_vec();
+                data.extend_from_slice(&(1000 + i as u64).to_be_bytes()[2..]);
+                data.extend_from_slice(&[0u8; 7]);
+                let mut name = name.as_bytes().to_vec();
+                name.resize(10, 0);
+                data.extend_from_slice(&name);
+                (vec![*len], data)
+            })
+            .collect();
+        let mut page = with_records(page, &records);
+        set_crc32_checksum(&mut page);
+        page
+    }
+
+    #[test]
+    fn test_index_stats_per_index() {
+        let first = float_sample_index_page();
+        let mut second = first.clone();
+        set_index_id(&mut second, 961);
+        let mut corrupt = second.clone();
+        corrupt[0] ^= 0xFF;
+
+        let explorer = explore("index_stats", &[first, second, corrupt]);
+
+        assert_eq!(explorer.index_stats.len(), 2);
+        assert_eq!(
+            explorer.index_stats[&(351, 960)],
+            IndexStats {
+                pages: 1,
+                records: 2,
+                checksum_failures: 0
+            }
+        );
+        assert_eq!(
+            explorer.index_stats[&(351, 961)],
+            IndexStats {
+                pages: 1,
+                records: 2,
+                checksum_failures: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_restore_from_doublewrite() {
+        let sample = test_data("float_sample.ibd");
+        // TRX_SYS page with the buffer at pages 64 and 128, the first holding
+        // an intact copy of the index page
+        let mut ibdata = vec![0u8; 192 * FIL_PAGE_SIZE];
+        let mut trx_sys = sample[2 * FIL_PAGE_SIZE..3 * FIL_PAGE_SIZE].to_vec();
+        trx_sys[4..8].copy_from_slice(&5u32.to_be_bytes());
+        trx_sys[24..26].copy_from_slice(&7u16.to_be_bytes());
+        trx_sys[34..38].copy_from_slice(&0u32.to_be_bytes());
+        let dblwr = FIL_PAGE_SIZE - 200;
+        for (i, word) in [536853855u32, 64, 128].iter().enumerate() {
+            trx_sys[dblwr + 10 + i * 4..dblwr + 14 + i * 4].copy_from_slice(&word.to_be_bytes());
+        }
+        set_crc32_checksum(&mut trx_sys);
+        ibdata[5 * FIL_PAGE_SIZE..6 * FIL_PAGE_SIZE].copy_from_slice(&trx_sys);
+        ibdata[64 * FIL_PAGE_SIZE..65 * FIL_PAGE_SIZE].copy_from_slice(&float_sample_index_page());
+        let ibdata_path =
+            std::env::temp_dir().join(format!("doublewrite_{}.ibd", std::process::id()));
+        fs::write(&ibdata_path, &ibdata).unwrap();
+
+        let mut torn = float_sample_index_page();
+        torn[FIL_PAGE_SIZE / 2..].fill(0);
+        let explorer = explore("torn", &[torn.clone()]);
+        assert_eq!(explorer.index_stats[&(351, 960)].checksum_failures, 1);
+        let explorer = explore_with_args(
+            "torn_doublewrite",
+            &[torn],
+            &[
+                "--page-size",
+                "16K",
+                "--doublewrite",
+                ibdata_path.to_str().unwrap(),
+            ],
+        );
+        fs::remove_file(&ibdata_path).unwrap();
+        assert_eq!(
+            explorer.index_stats[&(351, 960)],
+            IndexStats {
+                pages: 1,
+                records: 2,
+                checksum_failures: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_list_indexes() {
+        let first = float_sample_index_page();
+        let mut second = first.clone();
+        set_index_id(&mut second, 961);
+        let mut node = second.clone();
+        node[38 + 26..38 + 28].copy_from_slice(&1u16.to_be_bytes());
+        set_crc32_checksum(&mut node);
+
+        let explorer = explore_with_args(
+            "list_indexes",
+            &[first.clone(), second.clone(), node],
+            &["--list-indexes"],
+        );
+        assert!(explorer.index_stats.is_empty());
+        assert_eq!(explorer.index_summaries.len(), 2);
+        let summary = &explorer.index_summaries[&(351, 960)];
+        assert_eq!(
+            (summary.pages, summary.leaf_pages, summary.max_level),
+            (1, 1, 0)
+        );
+        assert!(summary.sample_record_len.is_some_and(|len| len > 13));
+        let summary = &explorer.index_summaries[&(351, 961)];
+        assert_eq!(
+            (summary.pages, summary.leaf_pages, summary.max_level),
+            (2, 1, 1)
+        );
+
+        // Several indexes picked at once
+        let mut third = first.clone();
+        set_index_id(&mut third, 962);
+        let explorer = explore_with_args(
+            "several_indexes",
+            &[first, second, third],
+            &["--index-id", "960", "--index-id", "962"],
+        );
+        let mut ids = explorer
+            .index_stats
+            .keys()
+            .map(|key| key.1)
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, [960, 962]);
+    }
+
+    #[test]
+    fn test_looping_page_is_corrupt() {
+        let good = float_sample_index_page();
+        let mut looping = good.clone();
+        // Second user record (at 179) points back to the first (at 127)
+        looping[177..179].copy_from_slice(&(127i16 - 179).to_be_bytes());
+        set_crc32_checksum(&mut looping);
+
+        let explorer = explore("looping", &[looping, good]);
+        assert_eq!(explorer.corrupt_pages, 1);
+        assert_eq!(explorer.total_records, 4);
+    }
+
+    #[test]
+    fn test_junk_index_page() {
+        let good = float_sample_index_page();
+        let mut junk = good.clone();
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for byte in junk[38..FIL_PAGE_SIZE - 8].iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+        set_crc32_checksum(&mut junk);
+
+        let explorer = explore("junk_index", &[junk, good]);
+        assert_eq!(explorer.failed_pages, 1);
+        assert_eq!(explorer.corrupt_pages, 0);
+        assert_eq!(explorer.total_records, 2);
+    }
+
+    fn name_table() -> Arc<TableDefinition> {
+        Arc::new(
+            TableDefinition::try_from_sql_statement(
+                "CREATE TABLE t (id INT NOT NULL, name VARCHAR(10) NOT NULL, PRIMARY KEY (id))",
+            )
+            .unwrap(),
+        )
+    }
+
+    fn dump(name: &str, page: Vec<u8>, extra_args: &[&str]) -> (PageExplorer, String) {
+        let name = format!("{}_{}", name, std::process::id());
+        let input = std::env::temp_dir().join(format!("{}.pages", name));
+        let output = std::env::temp_dir().join(format!("{}.json", name));
+        fs::write(&input, page).unwrap();
+
+        let mut args = vec!["page_explorer", "-o", output.to_str().unwrap()];
+        args.extend_from_slice(extra_args);
+        args.push(input.to_str().unwrap());
+        let mut explorer = PageExplorer::new(Arguments::parse_from(args), Some(name_table()));
+        explorer.run();
+        let json = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+        (explorer, json)
+    }
+
+    #[test]
+    fn test_max_rows() {
+        let names = [("a", 1), ("bb", 2), ("ccc", 3), ("dddd", 4)];
+        let page = build_name_page(&names);
+        let pages = [page.clone(), page].concat();
+        let (explorer, json) = dump("max_rows", pages.clone(), &["--max-rows", "6"]);
+        assert_eq!(explorer.total_records, 6);
+        assert_eq!(json.matches("\"_deleted\"").count(), 6);
+        assert!(json.ends_with(']'), "{}", json);
+
+        // Stops at the end of a page too
+        let (explorer, json) = dump("max_rows_page", pages, &["--max-rows", "4"]);
+        assert_eq!(explorer.total_records, 4);
+        assert_eq!(json.matches("\"_deleted\"").count(), 4);
+    }
+
+    /// Counts the bytes read through it
+    struct CountingReader<R> {
+        inner: R,
+        read: usize,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_page_id_sorted() {
+        let path = test_data_path("float_sample.ibd");
+        let args = Arguments::parse_from([
+            "page_explorer",
+            "--page-id",
+            "4",
+            "--sorted",
+            "--page-size",
+            "16K",
+            path.to_str().unwrap(),
+        ]);
+        let mut explorer = PageExplorer::new(args, None);
+        let mut reader = CountingReader {
+            inner: fs::File::open(&path).unwrap(),
+            read: 0,
+        };
+        assert_eq!(explorer.read_page_by_id(&mut reader, 4).unwrap(), 1);
+        assert_eq!(reader.read, FIL_PAGE_SIZE);
+        assert_eq!(explorer.total_records, 2);
+
+        // Without --sorted the tablespace header gives it away, the page is
+        // read through Tablespace which only reads page 0 besides it
+        let args =
+            Arguments::parse_from(["page_explorer", "--page-id", "4", path.to_str().unwrap()]);
+        let mut explorer = PageExplorer::new(args, None);
+        let mut reader = CountingReader {
+            inner: fs::File::open(&path).unwrap(),
+            read: 0,
+        };
+        assert_eq!(explorer.read_page_by_id(&mut reader, 4).unwrap(), 1);
+        assert!(reader.read < 3 * FIL_PAGE_SIZE, "{}", reader.read);
+        assert_eq!(explorer.total_records, 2);
+
+        let data = fs::read(&path).unwrap();
+        let pages: Vec<Vec<u8>> = data.chunks(FIL_PAGE_SIZE).map(<[u8]>::to_vec).collect();
+        let explorer = explore_with_args("page_id_sorted", &pages, &["--page-id", "4"]);
+        assert_eq!(explorer.total_records, 2);
+
+        // Out of place pages fall back to scanning
+        let mut swapped = pages.clone();
+        swapped.swap(3, 4);
+        let explorer = explore_with_args("page_id_unsorted", &swapped, &["--page-id", "4"]);
+        assert_eq!(explorer.total_records, 2);
+    }
+
+    #[test]
+    fn test_corrupt_row_is_skipped() {
+        let names = [
+            ("a", 1),
+            ("bb", 2),
+            ("broken", 11),
+            ("dddd", 4),
+            ("eeeee", 5),
+        ];
+        let (explorer, json) = dump("corrupt_row", build_name_page(&names), &[]);
+
+        assert_eq!(explorer.failed_records, 1);
+        assert_eq!(explorer.total_records, 5);
+        for name in ["a", "bb", "dddd", "eeeee"] {
+            assert!(json.contains(&format!("\"name\":\"{}\"", name)), "{}", json);
+        }
+        assert!(!json.contains("broken"));
+        assert_eq!(json.matches("\"_deleted\"").count(), 4);
+        assert!(!json.contains("_trx_id"));
+    }
+
+    #[test]
+    fn test_system_columns_output() {
+        let (_, json) = dump(
+            "system_columns",
+            build_name_page(&[("a", 1), ("bb", 2)]),
+            &["--system-columns"],
+        );
+        assert!(
+            json.contains("\"_trx_id\":1000,\"_roll_ptr\":0"),
+            "{}",
+            json
+        );
+        assert!(
+            json.contains("\"_trx_id\":1001,\"_roll_ptr\":0"),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn test_versions_output() {
+        let mut page = build_name_page(&[("a", 1), ("bb", 2)]);
+        // The first row was updated, its undo record is at 56 on page 9 of
+        // the system tablespace
+        let rec = 120 + 6;
+        page[rec + 10..rec + 17].copy_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x38]);
+        set_crc32_checksum(&mut page);
+
+        let mut undo = blank_page(0, 9, PageType::UndoLog);
+        let record: &[u8] = &[
+            0x00, 0x00, 12, 0x01, 0x84, 0x2a, 0x00, // UPD_EXIST
+            0x00, 0x00, 0x00, 0x00, 0x10, // DB_TRX_ID
+            0xE0, 0x80, 0x00, 0x00, 0x00, 0x90, 0x01, 0x20, // DB_ROLL_PTR, an insert
+            4, 0x80, 0x00, 0x00, 0x00, // id
+            1, 3, 3, b'o', b'l', b'd', // name
+        ];
+        undo[56..56 + record.len()].copy_from_slice(record);
+        undo[38..40].copy_from_slice(&2u16.to_be_bytes());
+        undo[40..42].copy_from_slice(&56u16.to_be_bytes());
+        undo[42..44].copy_from_slice(&(56 + record.len() as u16).to_be_bytes());
+        set_crc32_checksum(&mut undo);
+
+        let (_, json) = dump("versions", [page, undo].concat(), &["--versions"]);
+        assert!(
+            json.contains(
+                "\"name\":\"a\",\"_versions\":[{\"_deleted\":false,\"_trx_id\":16,\"_roll_ptr\":36028797028401440,\"id\":0,\"name\":\"old\"}]"
+            ),
+            "{}",
+            json
+        );
+        // The roll pointer of the second row points nowhere
+        assert!(
+            json.contains("\"name\":\"bb\",\"_versions\":[]"),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn test_scan_garbage() {
+        let mut page = build_name_page(&[("a", 1), ("bb", 2), ("ccc", 3)]);
+        // Move the last record to the garbage list
+        let (second, third) = (120 + ROW_SIZE + 6, 120 + 2 * ROW_SIZE + 6);
+        page[second - 2..second].copy_from_slice(&(112 - second as i16).to_be_bytes());
+        page[third - 2..third].copy_from_slice(&0i16.to_be_bytes());
+        page[third - 5] |= 0x20;
+        page[44..46].copy_from_slice(&(third as u16).to_be_bytes());
+        set_crc32_checksum(&mut page);
+
+        let (explorer, json) = dump("no_garbage", page.clone(), &[]);
+        assert_eq!(explorer.garbage_records, 0);
+        assert!(!json.contains("ccc"), "{}", json);
+
+        let (explorer, json) = dump("garbage", page, &["--scan-garbage"]);
+        assert_eq!(explorer.garbage_records, 1);
+        assert!(
+            json.contains("\"_deleted\":false,\"_garbage\":false,\"id\":1,\"name\":\"bb\""),
+            "{}",
+            json
+        );
+        assert!(
+            json.contains("\"_deleted\":true,\"_garbage\":true,\"id\":2,\"name\":\"ccc\""),
+            "{}",
+            json
+        );
+    }
+
+    /// Leaf of the name table at page `offset` whose ids start at `first_id`
+    fn build_name_leaf(offset: u32, next: u32, first_id: u32, names: &[(&str, u8)]) -> Vec<u8> {
+        let mut page = build_name_page(names);
+        for i in 0..names.len() {
+            let rec = 120 + i * ROW_SIZE + 6;
+            page[rec..rec + 4]
+                .copy_from_slice(&((first_id + i as u32) ^ 0x8000_0000).to_be_bytes());
+        }
+        page[4..8].copy_from_slice(&offset.to_be_bytes());
+        page[12..16].copy_from_slice(&next.to_be_bytes());
+        set_crc32_checksum(&mut page);
+        page
+    }
+
+    /// Two leaves of the name table under a root on page 3, stored in the
+    /// file as root, second leaf, first leaf
+    fn build_name_tree() -> Vec<u8> {
+        let mut root = float_sample_index_page();
+        root[120..1024].fill(0);
+        root[4..8].copy_from_slice(&3u32.to_be_bytes());
+        root[38 + 26..38 + 28].copy_from_slice(&1u16.to_be_bytes());
+        let records: Vec<(Vec<u8>, Vec<u8>)> = [(0u32, 5u32), (10, 4)]
+            .iter()
+            .map(|(key, child)| {
+                (
+                    Vec::new(),
+                    [(key ^ 0x8000_0000).to_be_bytes(), child.to_be_bytes()].concat(),
+                )
+            })
+            .collect();
+        let mut root = with_records(root, &records);
+        set_crc32_checksum(&mut root);
+
+        let second = build_name_leaf(4, u32::MAX, 10, &[("ccc", 3), ("dddd", 4)]);
+        let first = build_name_leaf(5, 4, 0, &[("a", 1), ("bb", 2)]);
+        [root, second, first].concat()
+    }
+
+    #[test]
+    fn test_walk_btree() {
+        let pages = build_name_tree();
+
+        let (explorer, json) = dump(
+            "walk_btree",
+            pages.clone(),
+            &["--root", "3", "--walk-btree"],
+        );
+        assert_eq!(explorer.total_records, 4);
+        assert_eq!(explorer.corrupt_pages, 0);
+        let positions: Vec<usize> = ["\"a\"", "\"bb\"", "\"ccc\"", "\"dddd\""]
+            .iter()
+            .map(|name| json.find(name).unwrap())
+            .collect();
+        assert!(positions.is_sorted(), "{}", json);
+        assert!(json.contains("\"id\":11,\"name\":\"dddd\""), "{}", json);
+
+        let (_, json) = dump("file_order", pages, &[]);
+        assert!(json.find("\"ccc\"").unwrap() < json.find("\"a\"").unwrap());
+    }
+
+    #[test]
+    fn test_full_crc32_page() {
+        let page = test_data("full_crc32.page");
+        let explorer = explore("full_crc32", &[page]);
+        assert_eq!(
+            explorer.index_stats[&(351, 960)],
+            IndexStats {
+                pages: 1,
+                records: 2,
+                checksum_failures: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_allow_no_checksum() {
+        let mut page = float_sample_index_page();
+        page[0..4].copy_from_slice(&BUF_NO_CHECKSUM_MAGIC.to_be_bytes());
+        page[FIL_PAGE_SIZE - 8..FIL_PAGE_SIZE - 4]
+            .copy_from_slice(&BUF_NO_CHECKSUM_MAGIC.to_be_bytes());
+
+        let explorer = explore("no_checksum", &[page.clone()]);
+        assert_eq!(explorer.index_stats[&(351, 960)].checksum_failures, 1);
+
+        let explorer = explore_with_args("no_checksum_allowed", &[page], &["--allow-no-checksum"]);
+        assert_eq!(explorer.index_stats[&(351, 960)].checksum_failures, 0);
+    }
+
+    #[test]
+    fn test_encrypted_tablespace() {
+        let data = test_data("encrypted_sample.ibd");
+        let pages: Vec<Vec<u8>> = data.chunks(FIL_PAGE_SIZE).map(|p| p.to_vec()).collect();
+
+        // Without a key the encrypted pages are recognized and not parsed
+        let explorer = explore_with_args("encrypted_no_key", &pages, &["--encrypted-count"]);
+        assert!(!explorer.index_stats.contains_key(&(351, 960)));
+        assert_eq!(explorer.encrypted_pages[&351], 4);
+        assert_eq!(explorer.total_records, 0);
+        assert_eq!(explorer.corrupt_pages, 0);
+
+        let keyring = test_data_path("encrypted_keyring");
+        let explorer = explore_with_args(
+            "encrypted_keyring",
+            &pages,
+            &["--keyring", keyring.to_str().unwrap()],
+        );
+        assert_eq!(explorer.index_stats[&(351, 960)].records, 2);
+        assert_eq!(explorer.corrupt_pages, 0);
+        assert!(explorer.encrypted_pages.is_empty());
+
+        let wrong_key = "00".repeat(32);
+        let explorer =
+            explore_with_args("encrypted_wrong_key", &pages, &["--master-key", &wrong_key]);
+        assert!(!explorer.index_stats.contains_key(&(351, 960)));
+        // Page 0 and the four encrypted pages
+        assert_eq!(explorer.corrupt_pages, 5);
+    }
+
+    #[test]
+    fn test_space_pages() {
+        let data = test_data("float_sample.ibd");
+        let pages: Vec<Vec<u8>> = data.chunks(FIL_PAGE_SIZE).map(|p| p.to_vec()).collect();
+        // FSP header, change buffer bitmap and INODE page
+        let explorer = explore("space_pages", &pages);
+        assert_eq!(explorer.space_pages, 3);
+        assert_eq!(explorer.corrupt_pages, 0);
+        assert_eq!(explorer.index_stats[&(351, 960)].records, 2);
+
+        let explorer = explore("fsp_page", &pages[..1]);
+        assert_eq!(explorer.space_pages, 1);
+        assert_eq!(explorer.allocated_pages, 5);
+    }
+
+    #[test]
+    fn test_table_json() {
+        let sql = test_data_path("double_test_table.sql");
+        let args = Arguments::parse_from(["page_explorer", "-t", sql.to_str().unwrap(), "-"]);
+        let table = load_table_def(&args).unwrap().unwrap();
+
+        let path = std::env::temp_dir().join(format!("table_{}.json", std::process::id()));
+        fs::write(&path, table.to_json().unwrap()).unwrap();
+        let args =
+            Arguments::parse_from(["page_explorer", "--table-json", path.to_str().unwrap(), "-"]);
+        assert_eq!(load_table_def(&args).unwrap(), Some(table));
+        fs::remove_file(&path).unwrap();
+
+        let args = Arguments::parse_from(["page_explorer", "-"]);
+        assert_eq!(load_table_def(&args).unwrap(), None);
+    }
+
+    #[test]
+    fn test_page_size() {
+        let data = test_data("float_sample_8k.ibd");
+        let stats = IndexStats {
+            pages: 1,
+            records: 2,
+            checksum_failures: 0,
+        };
+
+        // Detected from the FSP header on page 0
+        let explorer = explore("page_size_detect", std::slice::from_ref(&data));
+        assert_eq!(explorer.index_stats[&(351, 960)], stats);
+
+        let page = data[4 * 8192..5 * 8192].to_vec();
+        let explorer = explore_with_args(
+            "page_size_8k",
+            std::slice::from_ref(&page),
+            &["--page-size", "8K"],
+        );
+        assert_eq!(explorer.index_stats[&(351, 960)], stats);
+
+        // Without a header this is half of a 16K page
+        let explorer = explore("page_size_default", &[page]);
+        assert!(explorer.index_stats.is_empty());
+    }
+
+    #[test]
+    fn test_check_siblings() {
+        let mut pages = build_name_tree();
+        let (explorer, _) = dump("siblings_broken", pages.clone(), &["--check-siblings"]);
+        // The second leaf doesn't link back to the first
+        assert_eq!(explorer.broken_sibling_links, 1);
+
+        let second = &mut pages[FIL_PAGE_SIZE..2 * FIL_PAGE_SIZE];
+        second[8..12].copy_from_slice(&5u32.to_be_bytes());
+        set_crc32_checksum(second);
+        let (explorer, _) = dump("siblings", pages, &["--check-siblings"]);
+        assert_eq!(explorer.broken_sibling_links, 0);
+        assert_eq!(explorer.total_records, 4);
+    }
+
+    #[test]
+    fn test_pk_range() {
+        let (explorer, json) = dump(
+            "pk_range",
+            build_name_tree(),
+            &["--pk-min", "1", "--pk-max", "10"],
+        );
+        assert_eq!(explorer.filtered_records, 2);
+        assert!(json.contains("\"name\":\"bb\""), "{}", json);
+        assert!(json.contains("\"name\":\"ccc\""), "{}", json);
+        assert!(
+            !json.contains("\"a\"") && !json.contains("dddd"),
+            "{}",
+            json
+        );
+
+        let (explorer, json) = dump("pk_min", build_name_tree(), &["--pk-min", "11"]);
+        assert_eq!(explorer.filtered_records, 3);
+        assert!(json.contains("\"id\":11,\"name\":\"dddd\""), "{}", json);
+    }
+
+    #[test]
+    fn test_max_rows_pk_range() {
+        // Rows outside the range don't count towards --max-rows, the leaf
+        // with dddd comes first in the file
+        let (explorer, json) = dump(
+            "max_rows_pk_range",
+            build_name_tree(),
+            &["--pk-max", "10", "--max-rows", "2"],
+        );
+        assert_eq!(explorer.dumped_records, 2);
+        assert_eq!(json.matches("\"_deleted\"").count(), 2);
+        assert!(json.contains("\"name\":\"ccc\""), "{}", json);
+        assert!(json.contains("\"name\":\"a\""), "{}", json);
+        assert!(!json.contains("dddd") && !json.contains("bb"), "{}", json);
+    }
+
+    #[test]
+    fn test_get_pk() {
+        let (_, json) = dump(
+            "get_pk",
+            build_name_tree(),
+            &["--root", "3", "--get-pk", "11"],
+        );
+        assert_eq!(json, "{\"_deleted\":false,\"id\":11,\"name\":\"dddd\"}");
+        let (_, json) = dump(
+            "get_pk_first",
+            build_name_tree(),
+            &["--root", "3", "--get-pk", "0"],
+        );
+        assert_eq!(json, "{\"_deleted\":false,\"id\":0,\"name\":\"a\"}");
+
+        let (_, json) = dump(
+            "get_pk_missing",
+            build_name_tree(),
+            &["--root", "3", "--get-pk", "7"],
+        );
+        assert_eq!(json, "null");
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let table = TableDefinition::try_from_sql_statement(
+            "CREATE TABLE t (id INT UNSIGNED NOT NULL, name VARCHAR(10) NOT NULL, \
+             PRIMARY KEY (id, name))",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_key(&table, "12345,abc").unwrap(),
+            vec![
+                FieldValue::UnsignedInt(12345),
+                FieldValue::String(String::from("abc"))
+            ]
+        );
+        assert!(parse_key(&table, "12345").is_err());
+        assert_eq!(
+            parse_key_prefix(&table, "12345").unwrap(),
+            vec![FieldValue::UnsignedInt(12345)]
+        );
+        assert!(parse_key_prefix(&table, "1,a,b").is_err());
+        assert!(parse_key(&table, "-1,abc").is_err());
+    }
+
+    #[test]
+    fn test_partial_string_output() {
+        let page = build_name_page(&[("a", 1)]);
+        let page = Page::from_bytes(&page).unwrap();
+        let index = IndexPage::try_from_page(page).unwrap();
+        let record = index.records().next().unwrap().unwrap();
+        let table = name_table();
+        let row = Row::try_from_record_and_table(&record, &table).unwrap();
+
+        let path = std::env::temp_dir().join(format!("partial_string_{}.json", std::process::id()));
+        let file = fs::File::create(&path).unwrap();
+        let mut explorer = PageExplorer::new(
+            Arguments::parse_from(["page_explorer", "page_file"]),
+            Some(table),
+        );
+        explorer.output_writer.replace(JsonStreamWriter::new(
+            Box::new(file) as Box<dyn std::io::Write>
+        ));
+        let values = [
+            FieldValue::SignedInt(0),
+            FieldValue::PartialString {
+                partial: String::from("abc"),
+                total_len: 70000,
+            },
+        ];
+        explorer.write_row(&row, &values, false).unwrap();
+        explorer
+            .output_writer
+            .take()
+            .unwrap()
+            .finish_document()
+            .unwrap();
+        assert_eq!(explorer.incomplete_records, 0);
+
+        let json = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut reader = JsonStreamReader::new(json.as_bytes());
+        reader.begin_object().unwrap();
+        assert_eq!(reader.next_name().unwrap(), "_deleted");
+        assert!(!reader.next_bool().unwrap());
+        assert_eq!(reader.next_name().unwrap(), "id");
+        assert_eq!(reader.next_number::<i64>().unwrap().unwrap(), 0);
+        assert_eq!(reader.next_name().unwrap(), "name");
+        reader.begin_object().unwrap();
+        assert_eq!(reader.next_name().unwrap(), "partial");
+        assert_eq!(reader.next_string().unwrap(), "abc");
+        assert_eq!(reader.next_name().unwrap(), "total_len");
+        assert_eq!(reader.next_number::<usize>().unwrap().unwrap(), 70000);
+        reader.end_object().unwrap();
+        reader.end_object().unwrap();
+        reader.consume_trailing_whitespace().unwrap();
+    }
+}
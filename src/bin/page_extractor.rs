@@ -1,12 +1,25 @@
 use std::{
+    ffi::OsString,
     fs::File,
-    io::{BufReader, Read, Write},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    ops::Range,
     path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, FromArgMatches, Parser};
 use indicatif::{ProgressBar, ProgressStyle};
-use innodb::innodb::page::{index::IndexHeader, Page, PageType};
+use innodb::innodb::{
+    doublewrite,
+    input::{is_stdin, open_input},
+    page::{
+        index::IndexHeader, trx_sys::TrxSysHeader, ChecksumPolicy, Page, PageSize, PageType,
+        PageValidation,
+    },
+};
 use tracing::{debug, info, trace, Level};
 
 #[derive(Parser, Debug)]
@@ -41,9 +54,68 @@ struct Arguments {
     )]
     output: PathBuf,
 
+    #[arg(
+        long = "start-offset",
+        default_value_t = 0,
+        help = "byte offset to start scanning from, aligned down to 4KB (to resume a previous run)"
+    )]
+    start_offset: usize,
+
+    #[arg(
+        long = "page-size",
+        default_value_t = PageSize::default(),
+        help = "innodb_page_size of the pages to look for"
+    )]
+    page_size: PageSize,
+
+    #[arg(
+        long = "allow-no-checksum",
+        help = "Accept pages written with innodb_checksum_algorithm=none"
+    )]
+    allow_no_checksum: bool,
+
+    #[arg(
+        long = "skip-doublewrite",
+        help = "Leave out LEGACY_DBLWR pages and the copies in the doublewrite extents named by the system tablespace TRX_SYS page"
+    )]
+    skip_doublewrite: bool,
+
+    #[arg(
+        long = "doublewrite-only",
+        conflicts_with_all = ["skip_doublewrite", "start_offset"],
+        help = "Only scan the doublewrite buffer extents named by the TRX_SYS page, FILE must be ibdata1"
+    )]
+    doublewrite_only: bool,
+
+    #[arg(help = "Disk image or file to scan, - reads stdin")]
     file: PathBuf,
 }
 
+impl Arguments {
+    /// Parses the command line, also rejecting what clap can't tell from
+    /// the flags alone
+    fn parse_checked<I, T>(args: I) -> Result<Arguments, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut command = Arguments::command();
+        let args = Arguments::from_arg_matches(&command.try_get_matches_from_mut(args)?)?;
+        if args.doublewrite_only && is_stdin(&args.file) {
+            return Err(command.error(
+                ErrorKind::ArgumentConflict,
+                "--doublewrite-only needs a file to seek in, not stdin",
+            ));
+        }
+        Ok(args)
+    }
+}
+
+#[allow(clippy::identity_op)]
+const CACHE_BUFFER_MAX_SIZE: usize = 1 * 1024 * 1024;
+/// Every page size is a multiple of the smallest one
+const STEP_SIZE: usize = 4096;
+
 #[derive(Debug)]
 enum PageValidationResult<'a> {
     Valid(Page<'a>),
@@ -52,34 +124,203 @@ enum PageValidationResult<'a> {
     EmptyPage,
 }
 
-fn validate_page(page: &[u8]) -> PageValidationResult {
-    let page = Page::from_bytes(page).expect("Can't construct page?");
-    match page.header.page_type {
-        PageType::Unknown => {
-            return PageValidationResult::NotAPage;
+fn validate_page(
+    page: &[u8],
+    page_size: PageSize,
+    policy: ChecksumPolicy,
+) -> PageValidationResult<'_> {
+    let page = Page::from_bytes_with_size(page, page_size).expect("Can't construct page?");
+    match page.validate_with(policy) {
+        validation if validation.is_valid() => PageValidationResult::Valid(page),
+        PageValidation::Empty => PageValidationResult::EmptyPage,
+        PageValidation::BadChecksum { .. } => PageValidationResult::InvalidChecksum,
+        // Random data rarely has matching LSNs, a torn page is no different
+        validation => {
+            trace!("Bad page ({:?}): {:#?}", validation, page);
+            PageValidationResult::NotAPage
         }
-        PageType::Allocated => {
-            if page.header.new_checksum == 0 {
-                return PageValidationResult::EmptyPage;
+    }
+}
+
+/// Recognizes the copies in the doublewrite buffer, which would otherwise
+/// come out next to the pages they duplicate, often as an older version:
+/// - Pages of type LEGACY_DBLWR, which MySQL 8.0.20 and later leaves in the
+///   old buffer, are always doublewrite pages.
+/// - A TRX_SYS page (page 5 of space 0) names the two extents of the buffer
+///   in the system tablespace. Taking it as page 5 of an ibdata file at its
+///   offset, pages found in those extents are copies unless their header
+///   says they are the system tablespace page in that very place.
+///
+/// Only buffers whose TRX_SYS page comes before them in the scan are found,
+/// which holds for an ibdata file that is laid out in order. The separate
+/// `#ib_*.dblwr` files of MySQL 8.0.20 and later hold plain page copies and
+/// are not recognized.
+#[derive(Debug, Default)]
+struct DoublewriteFilter {
+    /// Offsets in the scanned file of the buffer extents, with the offset of
+    /// their system tablespace page 0
+    extents: Vec<(Range<usize>, usize)>,
+}
+
+impl DoublewriteFilter {
+    fn is_copy(&mut self, offset: usize, page: &Page) -> bool {
+        let page_size = page.size().bytes();
+        match page.header.page_type {
+            PageType::LegacyDblwr => return true,
+            PageType::TrxSys if page.header.space_id == 0 && page.header.offset == 5 => {
+                if let Some(space_start) = offset.checked_sub(5 * page_size) {
+                    let doublewrite = TrxSysHeader::try_from_page(page)
+                        .ok()
+                        .and_then(|header| header.doublewrite);
+                    if let Some(doublewrite) = doublewrite {
+                        debug!(
+                            "Doublewrite buffer at pages {} and {} of the system tablespace at {:#x}",
+                            doublewrite.block1, doublewrite.block2, space_start
+                        );
+                        for pages in doublewrite.pages(page) {
+                            let start = space_start + pages.start as usize * page_size;
+                            let end = space_start + pages.end as usize * page_size;
+                            self.extents.push((start..end, space_start));
+                        }
+                    }
+                }
             }
+            _ => {}
         }
-        _ => {
-            if page.crc32_checksum() == page.header.new_checksum
-                || page.innodb_checksum() == page.header.new_checksum
-            {
-                return PageValidationResult::Valid(page);
-            } else if (page.header.lsn as u32) == page.trailer.lsn_low_32 {
-                return PageValidationResult::InvalidChecksum;
+        self.extents.iter().any(|(extent, space_start)| {
+            extent.contains(&offset)
+                && !(page.header.space_id == 0
+                    && (offset - space_start) / page_size == page.header.offset as usize)
+        })
+    }
+}
+
+/// Byte ranges of the two doublewrite buffer extents in a system tablespace
+fn doublewrite_regions<R: Read + Seek>(
+    reader: &mut R,
+    page_size: PageSize,
+) -> anyhow::Result<Vec<Range<usize>>> {
+    Ok(doublewrite::locate(reader, page_size)?
+        .into_iter()
+        .map(|pages| {
+            pages.start as usize * page_size.bytes()..pages.end as usize * page_size.bytes()
+        })
+        .collect())
+}
+
+/// Scans `reader`, which must be positioned at `start_offset`, for pages and
+/// hands every candidate to `handle` along with its offset in the file. Stops
+/// at the end of input or once `stop` is set, returning the offset reached.
+fn scan_pages<R, F>(
+    reader: &mut R,
+    start_offset: usize,
+    page_size: PageSize,
+    policy: ChecksumPolicy,
+    pb: Option<&ProgressBar>,
+    stop: &AtomicBool,
+    mut handle: F,
+) -> usize
+where
+    R: Read,
+    F: FnMut(usize, PageValidationResult),
+{
+    let mut buffer = Vec::new();
+    // File offset of buffer[0]
+    let mut buffer_offset = start_offset;
+    let mut head_pointer: usize = 0;
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let mut step_size = STEP_SIZE;
+        if (buffer.len() - head_pointer) < page_size.bytes() {
+            buffer.drain(0..head_pointer);
+            buffer_offset += head_pointer;
+            head_pointer = 0;
+            if let Some(b) = pb {
+                b.set_message(format!("@ {}", buffer_offset));
+            }
+            let current_len = buffer.len();
+            buffer.resize(CACHE_BUFFER_MAX_SIZE, 0);
+            match reader.read(&mut buffer[current_len..]) {
+                Ok(bytes) => {
+                    if bytes == 0 {
+                        break;
+                    }
+                    buffer.resize(current_len + bytes, 0)
+                }
+                Err(_) => break,
             }
+            continue;
         }
-    };
 
-    trace!("Bad page: {:#?}", page);
-    PageValidationResult::NotAPage
+        let result = validate_page(
+            &buffer[head_pointer..][..page_size.bytes()],
+            page_size,
+            policy,
+        );
+        if let PageValidationResult::Valid(_) = result {
+            step_size = page_size.bytes();
+        }
+        handle(buffer_offset + head_pointer, result);
+
+        head_pointer += step_size;
+        if let Some(b) = pb {
+            b.inc(step_size as u64)
+        }
+    }
+
+    buffer_offset + head_pointer
+}
+
+/// Scans each of the ascending byte `regions` of `reader`, which must be
+/// positioned at `start_offset`, like [`scan_pages`]
+#[allow(clippy::too_many_arguments)]
+fn scan_regions<R, F>(
+    reader: &mut R,
+    start_offset: usize,
+    regions: &[Range<usize>],
+    page_size: PageSize,
+    policy: ChecksumPolicy,
+    pb: Option<&ProgressBar>,
+    stop: &AtomicBool,
+    mut handle: F,
+) -> usize
+where
+    R: Read,
+    F: FnMut(usize, PageValidationResult),
+{
+    let mut position = start_offset;
+    for region in regions {
+        if let Err(e) = io::copy(
+            &mut reader.take((region.start - position) as u64),
+            &mut io::sink(),
+        ) {
+            debug!("Failed to skip to {:#x}: {:?}", region.start, e);
+            break;
+        }
+        if let Some(b) = pb {
+            b.set_position(region.start as u64);
+        }
+        position = scan_pages(
+            &mut reader.take((region.end - region.start) as u64),
+            region.start,
+            page_size,
+            policy,
+            pb,
+            stop,
+            &mut handle,
+        );
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    position
 }
 
 fn main() {
-    let args = Arguments::parse();
+    let args = Arguments::parse_checked(std::env::args_os()).unwrap_or_else(|e| e.exit());
 
     let subscriber = tracing_subscriber::FmtSubscriber::builder()
         .with_max_level(match args.verbose {
@@ -122,23 +363,57 @@ fn main() {
         }
     }
 
-    let file = File::open(args.file).expect("Can't open provided file");
-    let metadata = file.metadata().expect("No metadata?");
+    let start_offset = args.start_offset - args.start_offset % STEP_SIZE;
+    let regions = if args.doublewrite_only {
+        let mut file = File::open(&args.file).expect("Can't open provided file");
+        let regions = doublewrite_regions(&mut file, args.page_size)
+            .expect("Can't find the doublewrite buffer");
+        info!("Scanning the doublewrite buffer at {:x?}", regions);
+        regions
+    } else {
+        std::iter::once(start_offset..usize::MAX).collect()
+    };
+    // Stdin has no length and can only skip ahead by reading
+    let (file, length): (Box<dyn Read>, Option<u64>) = if is_stdin(&args.file) {
+        let mut stdin = open_input(&args.file).expect("Can't open stdin");
+        io::copy(&mut (&mut stdin).take(start_offset as u64), &mut io::sink())
+            .expect("Failed to skip to start offset");
+        (stdin, None)
+    } else {
+        let mut file = File::open(&args.file).expect("Can't open provided file");
+        let metadata = file.metadata().expect("No metadata?");
+        file.seek(SeekFrom::Start(start_offset as u64))
+            .expect("Failed to seek to start offset");
+        (Box::new(file), Some(metadata.len()))
+    };
 
     let pb: Option<ProgressBar> = if args.verbose == 0 {
-        Some(ProgressBar::new(metadata.len()))
+        Some(match length {
+            Some(length) => ProgressBar::new(length),
+            None => ProgressBar::new_spinner(),
+        })
     } else {
         None
     };
 
     if let Some(pb) = &pb {
+        let template = match length {
+            Some(_) => "[{eta}] [{bar:40}] ({bytes_per_sec}) {bytes}/{total_bytes} {msg}",
+            None => "[{elapsed}] ({bytes_per_sec}) {bytes} {msg}",
+        };
         pb.set_style(
-            ProgressStyle::with_template(
-                "[{eta}] [{bar:40}] ({bytes_per_sec}) {bytes}/{total_bytes} {msg}",
-            )
-            .unwrap()
-            .progress_chars("=> "),
+            ProgressStyle::with_template(template)
+                .unwrap()
+                .progress_chars("=> "),
         );
+        pb.set_position(start_offset as u64);
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed))
+            .expect("Failed to install SIGINT handler");
     }
 
     let mut reader = BufReader::new(file);
@@ -146,36 +421,27 @@ fn main() {
     let mut valid_counter = 0usize;
     let mut valid_index_counter = 0usize;
     let mut failed_checksum = 0usize;
+    let mut doublewrite_copies = 0usize;
+    let mut doublewrite_filter = DoublewriteFilter::default();
 
-    #[allow(clippy::identity_op)]
-    const CACHE_BUFFER_MAX_SIZE: usize = 1 * 1024 * 1024;
-    const STEP_SIZE: usize = 4096;
-    const PAGE_SIZE: usize = 16384;
-
-    let mut buffer = Vec::new();
-    let mut head_pointer: usize = 0;
-    loop {
-        let mut step_size = STEP_SIZE;
-        if (buffer.len() - head_pointer) < PAGE_SIZE {
-            buffer.drain(0..head_pointer);
-            head_pointer = 0;
-            let current_len = buffer.len();
-            buffer.resize(CACHE_BUFFER_MAX_SIZE, 0);
-            match reader.read(&mut buffer[current_len..]) {
-                Ok(bytes) => {
-                    if bytes == 0 {
-                        break;
-                    }
-                    buffer.resize(current_len + bytes, 0)
-                }
-                Err(_) => break,
-            }
-            continue;
-        }
-
-        match validate_page(&buffer[head_pointer..][..PAGE_SIZE]) {
+    let end_offset = scan_regions(
+        &mut reader,
+        start_offset,
+        &regions,
+        args.page_size,
+        ChecksumPolicy {
+            allow_none: args.allow_no_checksum,
+        },
+        pb.as_ref(),
+        &interrupted,
+        |offset, result| match result {
             PageValidationResult::Valid(page) => {
                 trace!("Page validated {page:x?}");
+                if args.skip_doublewrite && doublewrite_filter.is_copy(offset, &page) {
+                    debug!("Skipping doublewrite copy at {offset:#x}");
+                    doublewrite_copies += 1;
+                    return;
+                }
                 valid_counter += 1;
 
                 // Handling is differnt if we are only grouping by table space
@@ -219,19 +485,256 @@ fn main() {
                         }
                     }
                 }
-                step_size = PAGE_SIZE;
             }
             PageValidationResult::InvalidChecksum => {
                 failed_checksum += 1;
             }
             PageValidationResult::NotAPage | PageValidationResult::EmptyPage => {}
-        }
+        },
+    );
 
-        head_pointer += step_size;
-        if let Some(b) = pb.as_ref() {
-            b.inc(step_size as u64)
-        }
+    if let Some(pb) = &pb {
+        pb.finish();
+    }
+    if interrupted.load(Ordering::Relaxed) {
+        info!("Interrupted at offset {end_offset}, resume with --start-offset {end_offset}");
     }
 
     info!("found {valid_counter} pages that have valid checksum ({valid_index_counter} index pages), {failed_checksum} pages only failed checksum");
+    if args.skip_doublewrite {
+        info!("Skipped {doublewrite_copies} doublewrite buffer copies");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::atomic::AtomicBool};
+
+    use innodb::innodb::{
+        page::{
+            writer::PageWriter, ChecksumKind, ChecksumPolicy, Page, PageSize, PageType,
+            BUF_NO_CHECKSUM_MAGIC,
+        },
+        test_data,
+    };
+
+    use super::{
+        doublewrite_regions, scan_pages, scan_regions, validate_page, Arguments, DoublewriteFilter,
+        PageValidationResult, STEP_SIZE,
+    };
+
+    const PAGE_SIZE: usize = 16384;
+
+    fn fixture() -> Vec<u8> {
+        let mut data = test_data("t_empty.ibd");
+        data.extend(test_data("float_sample.ibd"));
+        data
+    }
+
+    fn valid_pages(data: &[u8], start_offset: usize) -> Vec<(usize, u32, u32)> {
+        valid_pages_with_size(data, start_offset, PageSize::default())
+    }
+
+    fn valid_pages_with_size(
+        data: &[u8],
+        start_offset: usize,
+        page_size: PageSize,
+    ) -> Vec<(usize, u32, u32)> {
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(start_offset as u64);
+        let mut found = Vec::new();
+        scan_pages(
+            &mut cursor,
+            start_offset,
+            page_size,
+            ChecksumPolicy::default(),
+            None,
+            &AtomicBool::new(false),
+            |offset, result| {
+                if let PageValidationResult::Valid(page) = result {
+                    found.push((offset, page.header.space_id, page.header.offset));
+                }
+            },
+        );
+        found
+    }
+
+    #[test]
+    fn test_scan_from_start_offset() {
+        let data = fixture();
+        let full = valid_pages(&data, 0);
+        assert_eq!(full.len(), 9);
+
+        let start_offset = 7 * PAGE_SIZE + STEP_SIZE;
+        let expected: Vec<_> = full
+            .into_iter()
+            .filter(|(offset, _, _)| *offset >= start_offset)
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(valid_pages(&data, start_offset), expected);
+    }
+
+    #[test]
+    fn test_scan_8k_pages() {
+        // Behind a 4K gap so the pages are not aligned to their size
+        let mut data = vec![0u8; STEP_SIZE];
+        data.extend(test_data("float_sample_8k.ibd"));
+
+        let found = valid_pages_with_size(&data, 0, PageSize::Size8K);
+        let expected: Vec<_> = (0..5)
+            .map(|i| (STEP_SIZE + i * 8192, 351, i as u32))
+            .collect();
+        assert_eq!(found, expected);
+        assert!(valid_pages(&data, 0).is_empty());
+    }
+
+    #[test]
+    fn test_validate_full_crc32() {
+        let mut page = test_data("full_crc32.page");
+        assert!(matches!(
+            validate_page(&page, PageSize::default(), ChecksumPolicy::default()),
+            PageValidationResult::Valid(_)
+        ));
+
+        // Once the checksum fails the page isn't known to be full_crc32, so
+        // the LSN in its first trailer field doesn't count and it looks torn
+        page[200] ^= 0xFF;
+        assert!(matches!(
+            validate_page(&page, PageSize::default(), ChecksumPolicy::default()),
+            PageValidationResult::NotAPage
+        ));
+    }
+
+    #[test]
+    fn test_validate_no_checksum() {
+        let mut page = test_data("float_sample.ibd")[4 * PAGE_SIZE..][..PAGE_SIZE].to_vec();
+        page[0..4].copy_from_slice(&BUF_NO_CHECKSUM_MAGIC.to_be_bytes());
+        page[PAGE_SIZE - 8..PAGE_SIZE - 4].copy_from_slice(&BUF_NO_CHECKSUM_MAGIC.to_be_bytes());
+
+        assert!(matches!(
+            validate_page(&page, PageSize::default(), ChecksumPolicy::default()),
+            PageValidationResult::InvalidChecksum
+        ));
+        assert!(matches!(
+            validate_page(
+                &page,
+                PageSize::default(),
+                ChecksumPolicy { allow_none: true }
+            ),
+            PageValidationResult::Valid(_)
+        ));
+    }
+
+    /// Start of a system tablespace: a TRX_SYS page with the doublewrite
+    /// buffer at pages 64 and 128 and, in the buffer, a copy of a float_sample
+    /// page and a LEGACY_DBLWR page
+    fn system_tablespace() -> Vec<u8> {
+        let t_empty = test_data("t_empty.ibd");
+        let float_sample = test_data("float_sample.ibd");
+        fn page_at(data: &[u8], n: usize) -> Page<'_> {
+            Page::from_bytes(&data[n * PAGE_SIZE..(n + 1) * PAGE_SIZE]).unwrap()
+        }
+
+        let mut data = vec![0u8; 67 * PAGE_SIZE];
+        let mut trx_sys = PageWriter::new(&page_at(&t_empty, 3));
+        trx_sys.set_page_type(PageType::TrxSys);
+        trx_sys.set_space_id(0);
+        trx_sys.set_offset(5);
+        let dblwr = PAGE_SIZE - 200;
+        trx_sys.data_mut()[dblwr + 10..dblwr + 22].copy_from_slice(
+            &[536853855u32, 64, 128]
+                .iter()
+                .flat_map(|word| word.to_be_bytes())
+                .collect::<Vec<_>>(),
+        );
+        data[5 * PAGE_SIZE..6 * PAGE_SIZE].copy_from_slice(&trx_sys.finalize(ChecksumKind::Crc32));
+        data[64 * PAGE_SIZE..65 * PAGE_SIZE].copy_from_slice(page_at(&float_sample, 4).raw_data);
+        let mut legacy = PageWriter::new(&page_at(&t_empty, 3));
+        legacy.set_page_type(PageType::LegacyDblwr);
+        data[65 * PAGE_SIZE..66 * PAGE_SIZE].copy_from_slice(&legacy.finalize(ChecksumKind::Crc32));
+        // Before the buffer, and a system tablespace page in its place in
+        // the buffer, both kept
+        data[4 * PAGE_SIZE..5 * PAGE_SIZE].copy_from_slice(page_at(&float_sample, 3).raw_data);
+        let mut in_place = PageWriter::new(&page_at(&t_empty, 3));
+        in_place.set_space_id(0);
+        in_place.set_offset(66);
+        data[66 * PAGE_SIZE..67 * PAGE_SIZE]
+            .copy_from_slice(&in_place.finalize(ChecksumKind::Crc32));
+        data
+    }
+
+    #[test]
+    fn test_skip_doublewrite() {
+        let data = system_tablespace();
+        let found = valid_pages(&data, 0);
+        assert_eq!(found.len(), 5);
+
+        let mut filter = DoublewriteFilter::default();
+        let kept: Vec<_> = found
+            .into_iter()
+            .filter(|(offset, _, _)| {
+                let page = Page::from_bytes(&data[*offset..*offset + PAGE_SIZE]).unwrap();
+                !filter.is_copy(*offset, &page)
+            })
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                (4 * PAGE_SIZE, 351, 3),
+                (5 * PAGE_SIZE, 0, 5),
+                (66 * PAGE_SIZE, 0, 66)
+            ]
+        );
+
+        // The buffer only counts for the system tablespace the TRX_SYS page
+        // belongs to
+        let mut filter = DoublewriteFilter::default();
+        let copy = Page::from_bytes(&data[64 * PAGE_SIZE..65 * PAGE_SIZE]).unwrap();
+        assert!(!filter.is_copy(64 * PAGE_SIZE, &copy));
+    }
+
+    #[test]
+    fn test_scan_doublewrite_only() {
+        let data = system_tablespace();
+        let regions = doublewrite_regions(&mut Cursor::new(&data), PageSize::default()).unwrap();
+        assert_eq!(
+            regions,
+            vec![
+                64 * PAGE_SIZE..128 * PAGE_SIZE,
+                128 * PAGE_SIZE..192 * PAGE_SIZE
+            ]
+        );
+        assert!(doublewrite_regions(&mut Cursor::new(fixture()), PageSize::default()).is_err());
+
+        // The fixture stops in the first block
+        let mut found = Vec::new();
+        scan_regions(
+            &mut Cursor::new(&data),
+            0,
+            &regions,
+            PageSize::default(),
+            ChecksumPolicy::default(),
+            None,
+            &AtomicBool::new(false),
+            |offset, result| {
+                if let PageValidationResult::Valid(page) = result {
+                    found.push((offset, page.header.space_id, page.header.offset));
+                }
+            },
+        );
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0], (64 * PAGE_SIZE, 351, 4));
+        assert_eq!(found[2], (66 * PAGE_SIZE, 0, 66));
+    }
+
+    #[test]
+    fn test_doublewrite_only_needs_file() {
+        let err =
+            Arguments::parse_checked(["page_extractor", "--doublewrite-only", "-"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+        assert!(
+            Arguments::parse_checked(["page_extractor", "--doublewrite-only", "ibdata1"]).is_ok()
+        );
+        assert!(Arguments::parse_checked(["page_extractor", "-"]).is_ok());
+    }
 }
@@ -1,7 +1,10 @@
 use clap::Parser;
-use innodb::innodb::page::{Page, PageType, FIL_PAGE_SIZE};
+use innodb::innodb::{
+    input::open_input,
+    page::{ChecksumPolicy, Page, PageSize, PageValidation},
+};
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use tracing::{info, warn, Level};
 
@@ -16,12 +19,24 @@ struct Arguments {
     #[arg(short='v', action = clap::ArgAction::Count, help="verbose level")]
     verbose: u8,
 
+    #[arg(
+        long = "page-size",
+        value_name = "SIZE",
+        help = "innodb_page_size of the pages, read from the tablespace header when the file starts with one, 16K otherwise"
+    )]
+    page_size: Option<PageSize>,
+
+    #[arg(
+        long = "allow-no-checksum",
+        help = "Accept pages written with innodb_checksum_algorithm=none"
+    )]
+    allow_no_checksum: bool,
+
+    #[arg(help = "Page file to sort, - reads stdin")]
     file: PathBuf,
     output: PathBuf,
 }
 
-const ZEROS_BUFFER: [u8; FIL_PAGE_SIZE] = [0u8; FIL_PAGE_SIZE];
-
 fn main() {
     let args = Arguments::parse();
 
@@ -35,7 +50,7 @@ fn main() {
         .finish();
     _ = tracing::subscriber::set_global_default(subscriber);
 
-    let file = File::open(args.file).expect("Failed to open input file");
+    let file = open_input(&args.file).expect("Failed to open input file");
 
     let mut output_len: usize = 0;
     let mut output_opt = if args.dry_run {
@@ -45,71 +60,84 @@ fn main() {
     };
 
     let mut reader = BufReader::new(file);
-    let mut page_buffer: Vec<u8> = Vec::new();
-    page_buffer.resize(FIL_PAGE_SIZE, 0);
+    // Enough of the first page to detect the page size, read again below
+    let mut head = Vec::new();
+    (&mut reader)
+        .take(PageSize::Size4K.bytes() as u64)
+        .read_to_end(&mut head)
+        .expect("Failed to read input file");
+    let page_size = args
+        .page_size
+        .unwrap_or_else(|| PageSize::detect(&head).unwrap_or_default());
+    info!("Sorting {} pages", page_size);
+    let mut reader = Cursor::new(head).chain(reader);
+
+    let policy = ChecksumPolicy {
+        allow_none: args.allow_no_checksum,
+    };
+    let zeros_buffer = vec![0u8; page_size.bytes()];
+    let mut page_buffer: Vec<u8> = vec![0; page_size.bytes()];
 
     let mut pages_processed = 0u32;
     let mut largest_page_number = 0u32;
     let mut sorted = true;
 
-    loop {
-        match reader.read_exact(&mut page_buffer) {
-            Ok(_) => {
-                pages_processed += 1;
-
-                let page = Page::from_bytes(&page_buffer).expect("Failed to construct page");
-                // only allocated page is empty
-                if page.header.page_type == PageType::Allocated {
-                    continue;
-                }
-
-                if page.crc32_checksum() != page.header.new_checksum {
-                    warn!("Invalid page detected: {:?}", page)
-                } else {
-                    largest_page_number = std::cmp::max(largest_page_number, page.header.offset);
-                }
-
-                if page.header.offset != (pages_processed - 1) {
-                    sorted = false;
-                }
-
-                let page_offset_in_file = page.header.offset as usize * FIL_PAGE_SIZE;
-
-                if let Some(output) = output_opt.as_mut() {
-                    // If the target file is "shorter" than where we need to write, fill it with zeros
-                    while output_len < page_offset_in_file {
-                        output
-                            .seek(SeekFrom::Start(output_len as u64))
-                            .expect("Seek success");
-                        output
-                            .write_all(&ZEROS_BUFFER)
-                            .expect("Failed to write spacer");
-                        output_len += ZEROS_BUFFER.len();
-                    }
-
-                    debug_assert!((page_offset_in_file == output_len)
-                              || (page_offset_in_file + FIL_PAGE_SIZE < output_len),
-                              "either we should be tacking on at the end, or completely within the current file");
-                    output
-                        .seek(SeekFrom::Start(page_offset_in_file as u64))
-                        .expect("Failed to seek to page location");
-                    output
-                        .write_all(&page_buffer)
-                        .expect("Failed to write page data");
-                    if page_offset_in_file == output_len {
-                        output_len += page_buffer.len();
-                    }
-
-                    debug_assert!(
-                        output_len % FIL_PAGE_SIZE == 0,
-                        "output must be page aligned"
-                    );
-                }
+    while reader.read_exact(&mut page_buffer).is_ok() {
+        pages_processed += 1;
+
+        let page =
+            Page::from_bytes_with_size(&page_buffer, page_size).expect("Failed to construct page");
+        match page.validate_with(policy) {
+            PageValidation::Empty => continue,
+            validation if validation.is_valid() => {
+                largest_page_number = std::cmp::max(largest_page_number, page.header.offset);
             }
-            Err(_) => break,
+            validation => warn!("Invalid page detected ({:?}): {:?}", validation, page),
+        }
+
+        if page.header.offset != (pages_processed - 1) {
+            sorted = false;
+        }
+
+        let page_offset_in_file = page.header.offset as usize * page_size.bytes();
+
+        if let Some(output) = output_opt.as_mut() {
+            // If the target file is "shorter" than where we need to write, fill it with zeros
+            while output_len < page_offset_in_file {
+                output
+                    .seek(SeekFrom::Start(output_len as u64))
+                    .expect("Seek success");
+                output
+                    .write_all(&zeros_buffer)
+                    .expect("Failed to write spacer");
+                output_len += zeros_buffer.len();
+            }
+
+            debug_assert!(
+                (page_offset_in_file == output_len)
+                    || (page_offset_in_file + page_size.bytes() < output_len),
+                "either we should be tacking on at the end, or completely within the current file"
+            );
+            output
+                .seek(SeekFrom::Start(page_offset_in_file as u64))
+                .expect("Failed to seek to page location");
+            output
+                .write_all(&page_buffer)
+                .expect("Failed to write page data");
+            if page_offset_in_file == output_len {
+                output_len += page_buffer.len();
+            }
+
+            debug_assert!(
+                output_len.is_multiple_of(page_size.bytes()),
+                "output must be page aligned"
+            );
         }
     }
 
-    info!("Processed {} pages, max page number is {}", pages_processed, largest_page_number);
+    info!(
+        "Processed {} pages, max page number is {}",
+        pages_processed, largest_page_number
+    );
     info!("Original file is sorted = {:?}", sorted);
 }
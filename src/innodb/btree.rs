@@ -0,0 +1,699 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    buffer_manager::{BufferManager, PageGuard},
+    file_list::FIL_NULL,
+    page::{
+        index::{
+            record::{Record, RecordType},
+            IndexPage,
+        },
+        PageType,
+    },
+    table::{
+        field::{key_prefix_cmp, FieldValue},
+        row::Row,
+        TableDefinition,
+    },
+};
+
+/// A clustered index, walked from its root page through the buffer manager
+#[derive(Clone, Copy)]
+pub struct BTreeIndex<'a> {
+    root: u32,
+    space_id: u32,
+    buffer_mgr: &'a dyn BufferManager,
+}
+
+impl<'a> BTreeIndex<'a> {
+    pub fn new(root: u32, space_id: u32, buffer_mgr: &'a dyn BufferManager) -> Self {
+        BTreeIndex {
+            root,
+            space_id,
+            buffer_mgr,
+        }
+    }
+
+    fn pin(&self, offset: u32) -> Result<PageGuard<'a>> {
+        let guard = self.buffer_mgr.pin(self.space_id, offset)?;
+        if guard.header.page_type != PageType::Index {
+            return Err(anyhow!(
+                "Page {} of the index at {} is a {:?} page",
+                offset,
+                self.root,
+                guard.header.page_type
+            ));
+        }
+        Ok(guard)
+    }
+
+    /// Descends from the root to the leaf level, `choose` picks the node
+    /// pointer to follow on every non-leaf page
+    fn descend<F>(&self, table: &TableDefinition, mut choose: F) -> Result<(PageGuard<'a>, u64)>
+    where
+        F: FnMut(&IndexPage) -> Result<Option<usize>>,
+    {
+        let key_table = Arc::new(table.key_definition());
+        let mut offset = self.root;
+        let mut index_id = None;
+        let mut expected_level = None;
+        loop {
+            let guard = self.pin(offset)?;
+            let page = guard.page();
+            let index = IndexPage::try_from_page_ref(&page)?;
+            let header = &index.index_header;
+            if *index_id.get_or_insert(header.index_id) != header.index_id {
+                return Err(anyhow!(
+                    "Page {} belongs to index {} instead of {:?}",
+                    offset,
+                    header.index_id,
+                    index_id
+                ));
+            }
+            // Levels have to go down by one each step, which also bounds the depth
+            if expected_level.is_some_and(|level| level != header.page_level) {
+                return Err(anyhow!(
+                    "Page {} is on level {}, expected {:?}",
+                    offset,
+                    header.page_level,
+                    expected_level
+                ));
+            }
+            if header.page_level == 0 {
+                return Ok((guard, header.index_id));
+            }
+
+            let record = match choose(&index)? {
+                Some(record_offset) => index.record_at(record_offset)?,
+                // The leftmost node pointer covers everything below the first key
+                None => index
+                    .records()
+                    .next()
+                    .ok_or_else(|| anyhow!("Node pointer page {} has no records", offset))??,
+            };
+            offset = Row::try_from_record_and_table(&record, &key_table)?.child_page_number()?;
+            expected_level = Some(header.page_level - 1);
+        }
+    }
+
+    /// Follows the first node pointer of every level down to the leaf level,
+    /// returning the leaf page number and the index id
+    pub fn leftmost_leaf(&self, table: &TableDefinition) -> Result<(u32, u64)> {
+        let (guard, index_id) = self.descend(table, |_| Ok(None))?;
+        Ok((guard.header.offset, index_id))
+    }
+
+    /// Finds the row with the full cluster key `key`. The leaf page stays
+    /// pinned for as long as the returned row is alive.
+    pub fn lookup(
+        &self,
+        key: &[FieldValue],
+        table: &Arc<TableDefinition>,
+    ) -> Result<Option<PinnedRow<'a>>> {
+        let (guard, _) = self.descend(table, |index| {
+            Ok(index.search_floor(key, table)?.map(|record| record.offset))
+        })?;
+        let index = IndexPage::try_from_page_ref(&guard.page())?;
+        let Some(offset) = index.search(key, table)?.map(|record| record.offset) else {
+            return Ok(None);
+        };
+        // Parsed once here so PinnedRow::row can't fail later
+        let record = Record::try_from_offset(guard.raw_data(), offset)?;
+        Row::try_from_record_and_table(&record, table)?;
+        Ok(Some(PinnedRow {
+            guard,
+            offset,
+            table: table.clone(),
+        }))
+    }
+
+    /// Leaf pages in key order, following the next page pointers
+    pub fn leaves(&self, table: &TableDefinition) -> Leaves<'a> {
+        self.leaves_from(self.leftmost_leaf(table))
+    }
+
+    fn leaves_from(&self, start: Result<(u32, u64)>) -> Leaves<'a> {
+        let (pending, index_id) = match start {
+            Ok((offset, index_id)) => (Some(Ok(offset)), index_id),
+            Err(e) => (Some(Err(e)), 0),
+        };
+        Leaves {
+            btree: *self,
+            index_id,
+            pending,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Rows of the leaf level in key order. Delete marked records are skipped.
+    pub fn rows(&self, table: &Arc<TableDefinition>) -> Rows<'a> {
+        Rows {
+            leaves: self.leaves(table),
+            table: table.clone(),
+            buffer_mgr: self.buffer_mgr,
+            pending: VecDeque::new(),
+            min: None,
+            max: None,
+            done: false,
+        }
+    }
+
+    /// Rows with keys between `min` and `max`, both inclusive. Bounds may be
+    /// a prefix of the cluster key, `None` leaves that side open.
+    pub fn range(
+        &self,
+        table: &Arc<TableDefinition>,
+        min: Option<&[FieldValue]>,
+        max: Option<&[FieldValue]>,
+    ) -> Rows<'a> {
+        let start = match min {
+            Some(min) => self
+                .descend(table, |index| {
+                    Ok(index.search_lower(min, table)?.map(|record| record.offset))
+                })
+                .map(|(guard, index_id)| (guard.header.offset, index_id)),
+            None => self.leftmost_leaf(table),
+        };
+        Rows {
+            leaves: self.leaves_from(start),
+            table: table.clone(),
+            buffer_mgr: self.buffer_mgr,
+            pending: VecDeque::new(),
+            min: min.map(|min| min.to_vec()),
+            max: max.map(|max| max.to_vec()),
+            done: false,
+        }
+    }
+}
+
+/// A row found by [`BTreeIndex::lookup`] together with the pin on its page
+pub struct PinnedRow<'a> {
+    guard: PageGuard<'a>,
+    offset: usize,
+    table: Arc<TableDefinition>,
+}
+
+impl PinnedRow<'_> {
+    pub fn record(&self) -> Record<'_> {
+        Record::try_from_offset(self.guard.raw_data(), self.offset)
+            .expect("Record was parsed by lookup")
+    }
+
+    pub fn row(&self) -> Row<'_> {
+        Row::try_from_record_and_table(&self.record(), &self.table)
+            .expect("Row was parsed by lookup")
+    }
+}
+
+/// Iterator over the leaf pages of a [`BTreeIndex`]. A page that was already
+/// visited, or does not belong to the leaf level, ends it with an error.
+pub struct Leaves<'a> {
+    btree: BTreeIndex<'a>,
+    index_id: u64,
+    pending: Option<Result<u32>>,
+    visited: HashSet<u32>,
+}
+
+impl<'a> Leaves<'a> {
+    fn visit(&mut self, offset: u32) -> Result<PageGuard<'a>> {
+        if !self.visited.insert(offset) {
+            return Err(anyhow!("Leaf level loops back to page {}", offset));
+        }
+        let guard = self.btree.pin(offset)?;
+        let page = guard.page();
+        let index = IndexPage::try_from_page_ref(&page)?;
+        if index.index_header.page_level != 0 || index.index_header.index_id != self.index_id {
+            return Err(anyhow!(
+                "Page {} is on level {} of index {}, expected a leaf of index {}",
+                offset,
+                index.index_header.page_level,
+                index.index_header.index_id,
+                self.index_id
+            ));
+        }
+        if guard.header.next != FIL_NULL {
+            self.pending = Some(Ok(guard.header.next));
+        }
+        Ok(guard)
+    }
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = Result<PageGuard<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = match self.pending.take()? {
+            Ok(offset) => offset,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.visit(offset))
+    }
+}
+
+/// Iterator over the parsed rows of a [`BTreeIndex`]
+pub struct Rows<'a> {
+    leaves: Leaves<'a>,
+    table: Arc<TableDefinition>,
+    buffer_mgr: &'a dyn BufferManager,
+    pending: VecDeque<Result<Vec<FieldValue>>>,
+    min: Option<Vec<FieldValue>>,
+    max: Option<Vec<FieldValue>>,
+    /// Set once a key past `max` was seen
+    done: bool,
+}
+
+impl Rows<'_> {
+    /// Whether the row is below the range, or sets `done` when it is past it
+    fn out_of_range(&mut self, row: &Row) -> Result<bool> {
+        if self.min.is_none() && self.max.is_none() {
+            return Ok(false);
+        }
+        let key = row.cluster_key()?;
+        if let Some(min) = &self.min {
+            if key_prefix_cmp(&key, min)? == Ordering::Less {
+                return Ok(true);
+            }
+        }
+        if let Some(max) = &self.max {
+            if key_prefix_cmp(&key, max)? == Ordering::Greater {
+                self.done = true;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn parse_leaf(&mut self, guard: &PageGuard) -> Result<()> {
+        let page = guard.page();
+        let index = IndexPage::try_from_page_ref(&page)?;
+        for record in index.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    self.pending.push_back(Err(e));
+                    break;
+                }
+            };
+            if record.header.record_type != RecordType::Conventional
+                || record.header.info_flags.deleted
+            {
+                continue;
+            }
+            let row = match Row::try_from_record_and_table(&record, &self.table) {
+                Ok(row) => row,
+                Err(e) => {
+                    self.pending.push_back(Err(e));
+                    continue;
+                }
+            };
+            match self.out_of_range(&row) {
+                Ok(true) if self.done => break,
+                Ok(true) => continue,
+                Ok(false) => self.pending.push_back(row.parse_values(self.buffer_mgr)),
+                Err(e) => self.pending.push_back(Err(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Result<Vec<FieldValue>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Some(row);
+            }
+            if self.done {
+                return None;
+            }
+            match self.leaves.next()? {
+                Ok(guard) => {
+                    if let Err(e) = self.parse_leaf(&guard) {
+                        return Some(Err(e));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+
+    use crate::innodb::{
+        buffer_manager::memory::MemoryBufferManager,
+        file_list::FIL_NULL,
+        page::{Page, FIL_PAGE_SIZE},
+        table::{
+            field::{Field, FieldType, FieldValue},
+            TableDefinition,
+        },
+        test_data,
+        testing::with_records,
+    };
+
+    use super::BTreeIndex;
+
+    fn empty_page(offset: u32, next: u32, level: u16) -> Vec<u8> {
+        let data = test_data("t_empty.ibd");
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+        buf[4..8].copy_from_slice(&offset.to_be_bytes());
+        buf[12..16].copy_from_slice(&next.to_be_bytes());
+        buf[64..66].copy_from_slice(&level.to_be_bytes());
+        buf
+    }
+
+    /// Page of `(key, child)` INT records, leaves get the hidden columns
+    /// instead of the child page number
+    fn build_page(offset: u32, next: u32, level: u16, keys: &[(i32, u32)]) -> Vec<u8> {
+        let records: Vec<(Vec<u8>, Vec<u8>)> = keys
+            .iter()
+            .map(|(key, child)| {
+                let mut data = ((*key as u32) ^ 0x8000_0000).to_be_bytes().to_vec();
+                match level {
+                    0 => data.extend_from_slice(&[0u8; 13]),
+                    _ => data.extend_from_slice(&child.to_be_bytes()),
+                }
+                (Vec::new(), data)
+            })
+            .collect();
+        with_records(empty_page(offset, next, level), &records)
+    }
+
+    fn leaf(offset: u32, next: u32, keys: &[i32]) -> Vec<u8> {
+        let keys: Vec<(i32, u32)> = keys.iter().map(|k| (*k, 0)).collect();
+        build_page(offset, next, 0, &keys)
+    }
+
+    fn int_table() -> Arc<TableDefinition> {
+        Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![Field::new("id", FieldType::Int(true), false)],
+            data_columns: vec![],
+        })
+    }
+
+    /// Two levels where the leaves are stored out of key order in the file
+    fn build_tree(last_leaf_next: u32) -> (MemoryBufferManager, u32) {
+        let space_id = Page::from_bytes(&empty_page(0, FIL_NULL, 0))
+            .unwrap()
+            .header
+            .space_id;
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in [
+            build_page(3, FIL_NULL, 1, &[(0, 6), (30, 5), (60, 4)]),
+            leaf(4, last_leaf_next, &[60, 70]),
+            leaf(5, 4, &[30, 40, 50]),
+            leaf(6, 5, &[0, 10, 20]),
+        ] {
+            buffer_mgr.add_page(&page).unwrap();
+        }
+        (buffer_mgr, space_id)
+    }
+
+    #[test]
+    fn test_rows_in_key_order() {
+        let (buffer_mgr, space_id) = build_tree(FIL_NULL);
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        let table = int_table();
+
+        assert_eq!(btree.leftmost_leaf(&table).unwrap().0, 6);
+        let leaves = btree
+            .leaves(&table)
+            .map(|leaf| leaf.map(|guard| guard.header.offset))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(leaves, vec![6, 5, 4]);
+
+        let keys = btree
+            .rows(&table)
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+            .into_iter()
+            .map(|values| values[0].clone())
+            .collect::<Vec<_>>();
+        let expected: Vec<FieldValue> = (0..8).map(|i| FieldValue::SignedInt(i * 10)).collect();
+        assert_eq!(keys, expected);
+        assert_eq!(buffer_mgr.pin_count(space_id, 3), 0);
+        assert_eq!(buffer_mgr.pin_count(space_id, 4), 0);
+    }
+
+    #[test]
+    fn test_leaf_loop() {
+        let (buffer_mgr, space_id) = build_tree(6);
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+
+        let rows: Vec<_> = btree.rows(&int_table()).collect();
+        assert_eq!(rows.len(), 9);
+        assert!(rows[..8].iter().all(|r| r.is_ok()));
+        assert!(rows[8].is_err());
+    }
+
+    #[test]
+    fn test_leftmost_leaf_wrong_level() {
+        let (mut buffer_mgr, space_id) = build_tree(FIL_NULL);
+        // Root pointing at itself
+        buffer_mgr
+            .add_page(&build_page(3, FIL_NULL, 1, &[(0, 3)]))
+            .unwrap();
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        assert!(btree.leftmost_leaf(&int_table()).is_err());
+        assert!(btree.rows(&int_table()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_lookup_min_rec() {
+        let (mut buffer_mgr, space_id) = build_tree(FIL_NULL);
+        // The leftmost pointer keeps a stale key, larger than its siblings
+        let mut root = build_page(3, FIL_NULL, 1, &[(1000, 6), (30, 5), (60, 4)]);
+        root[125 - 5] |= 0x10;
+        buffer_mgr.add_page(&root).unwrap();
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        let table = int_table();
+
+        for id in (0..8).map(|i| i * 10) {
+            let row = btree
+                .lookup(&[FieldValue::SignedInt(id)], &table)
+                .unwrap()
+                .unwrap_or_else(|| panic!("{} not found", id));
+            assert_eq!(
+                row.row().cluster_key().unwrap(),
+                vec![FieldValue::SignedInt(id)]
+            );
+        }
+        // Smaller than every key in the root
+        assert!(btree
+            .lookup(&[FieldValue::SignedInt(-1)], &table)
+            .unwrap()
+            .is_none());
+        let keys = btree
+            .range(&table, Some(&[FieldValue::SignedInt(-100)]), None)
+            .count();
+        assert_eq!(keys, 8);
+    }
+
+    /// 300 even keys from 0 to 598 in leaves of 50 under a single root
+    fn build_wide_tree() -> (MemoryBufferManager, u32) {
+        let space_id = Page::from_bytes(&empty_page(0, FIL_NULL, 0))
+            .unwrap()
+            .header
+            .space_id;
+        let mut buffer_mgr = MemoryBufferManager::new();
+        let leaves = 6;
+        let pointers: Vec<(i32, u32)> = (0..leaves).map(|i| (i * 100, 4 + i as u32)).collect();
+        buffer_mgr
+            .add_page(&build_page(3, FIL_NULL, 1, &pointers))
+            .unwrap();
+        for i in 0..leaves {
+            let next = if i + 1 == leaves {
+                FIL_NULL
+            } else {
+                5 + i as u32
+            };
+            let keys: Vec<i32> = (0..50).map(|k| i * 100 + k * 2).collect();
+            buffer_mgr
+                .add_page(&leaf(4 + i as u32, next, &keys))
+                .unwrap();
+        }
+        (buffer_mgr, space_id)
+    }
+
+    #[test]
+    fn test_range() {
+        let (buffer_mgr, space_id) = build_wide_tree();
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        let table = int_table();
+
+        let range = |min: Option<i64>, max: Option<i64>| {
+            let min = min.map(|min| vec![FieldValue::SignedInt(min)]);
+            let max = max.map(|max| vec![FieldValue::SignedInt(max)]);
+            btree
+                .range(&table, min.as_deref(), max.as_deref())
+                .collect::<Result<Vec<_>>>()
+                .unwrap()
+                .into_iter()
+                .map(|values| values[0].as_i64().expect("integer key"))
+                .collect::<Vec<_>>()
+        };
+        let evens = |from: i64, to: i64| (from..=to).step_by(2).collect::<Vec<_>>();
+
+        assert_eq!(range(None, None), evens(0, 598));
+        // Bounds on leaf boundaries and on keys are inclusive
+        assert_eq!(range(Some(100), Some(200)), evens(100, 200));
+        assert_eq!(range(Some(98), Some(100)), vec![98, 100]);
+        assert_eq!(range(Some(250), Some(250)), vec![250]);
+        // Bounds between keys
+        assert_eq!(range(Some(99), Some(301)), evens(100, 300));
+        assert_eq!(range(Some(251), Some(251)), Vec::<i64>::new());
+        // Open ends
+        assert_eq!(range(None, Some(9)), evens(0, 8));
+        assert_eq!(range(Some(591), None), evens(592, 598));
+        assert_eq!(range(Some(-10), Some(-1)), Vec::<i64>::new());
+        assert_eq!(range(Some(1000), None), Vec::<i64>::new());
+        assert_eq!(range(Some(10), Some(5)), Vec::<i64>::new());
+
+        for page in 3..10 {
+            assert_eq!(buffer_mgr.pin_count(space_id, page), 0);
+        }
+    }
+
+    #[test]
+    fn test_range_stops_at_max() {
+        let (mut buffer_mgr, space_id) = build_wide_tree();
+        // A broken leaf past the upper bound is never read
+        buffer_mgr
+            .add_page(&build_page(7, FIL_NULL, 1, &[(300, 7)]))
+            .unwrap();
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        let table = int_table();
+
+        let max = [FieldValue::SignedInt(297)];
+        let rows = btree
+            .range(&table, Some(&[FieldValue::SignedInt(200)]), Some(&max))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(rows.len(), 49);
+        assert!(btree.range(&table, None, None).any(|row| row.is_err()));
+    }
+
+    /// `(id INT, name VARCHAR(10) utf8mb4)` keyed on both columns
+    fn composite_table() -> Arc<TableDefinition> {
+        Arc::new(
+            TableDefinition::try_from_sql_statement(
+                "CREATE TABLE t (id INT NOT NULL, name VARCHAR(10) NOT NULL, \
+                 PRIMARY KEY (id, name)) DEFAULT CHARSET=utf8mb4",
+            )
+            .unwrap(),
+        )
+    }
+
+    fn composite_page(offset: u32, next: u32, level: u16, keys: &[(i32, &str, u32)]) -> Vec<u8> {
+        let records: Vec<(Vec<u8>, Vec<u8>)> = keys
+            .iter()
+            .map(|(id, name, child)| {
+                let mut data = ((*id as u32) ^ 0x8000_0000).to_be_bytes().to_vec();
+                data.extend_from_slice(name.as_bytes());
+                match level {
+                    0 => data.extend_from_slice(&[0u8; 13]),
+                    _ => data.extend_from_slice(&child.to_be_bytes()),
+                }
+                (vec![name.len() as u8], data)
+            })
+            .collect();
+        with_records(empty_page(offset, next, level), &records)
+    }
+
+    fn key(id: i64, name: &str) -> Vec<FieldValue> {
+        vec![
+            FieldValue::SignedInt(id),
+            FieldValue::String(name.to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_lookup() {
+        let (buffer_mgr, space_id) = build_tree(FIL_NULL);
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        let table = int_table();
+
+        for id in (0..8).map(|i| i * 10) {
+            let row = btree
+                .lookup(&[FieldValue::SignedInt(id)], &table)
+                .unwrap()
+                .unwrap_or_else(|| panic!("{} not found", id));
+            assert_eq!(
+                row.row().cluster_key().unwrap(),
+                vec![FieldValue::SignedInt(id)]
+            );
+        }
+        assert_eq!(buffer_mgr.pin_count(space_id, 5), 0);
+
+        let row = btree
+            .lookup(&[FieldValue::SignedInt(40)], &table)
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer_mgr.pin_count(space_id, 5), 1);
+        drop(row);
+        assert_eq!(buffer_mgr.pin_count(space_id, 5), 0);
+
+        for missing in [-5, 5, 35, 55, 1000] {
+            assert!(btree
+                .lookup(&[FieldValue::SignedInt(missing)], &table)
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_lookup_composite_key() {
+        let space_id = Page::from_bytes(&empty_page(0, FIL_NULL, 0))
+            .unwrap()
+            .header
+            .space_id;
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in [
+            composite_page(3, FIL_NULL, 1, &[(1, "a", 5), (2, "café", 4)]),
+            composite_page(5, 4, 0, &[(1, "a", 0), (1, "日本", 0), (2, "a", 0)]),
+            composite_page(
+                4,
+                FIL_NULL,
+                0,
+                &[(2, "café", 0), (2, "日本", 0), (3, "a", 0)],
+            ),
+        ] {
+            buffer_mgr.add_page(&page).unwrap();
+        }
+        let btree = BTreeIndex::new(3, space_id, &buffer_mgr);
+        let table = composite_table();
+
+        for (id, name) in [
+            (1, "a"),
+            (1, "日本"),
+            (2, "a"),
+            (2, "café"),
+            (2, "日本"),
+            (3, "a"),
+        ] {
+            let row = btree
+                .lookup(&key(id, name), &table)
+                .unwrap()
+                .unwrap_or_else(|| panic!("({}, {}) not found", id, name));
+            assert_eq!(row.row().cluster_key().unwrap(), key(id, name));
+        }
+        for (id, name) in [(0, "z"), (1, "b"), (2, "cafe"), (2, "日"), (4, "a")] {
+            assert!(btree.lookup(&key(id, name), &table).unwrap().is_none());
+        }
+    }
+}
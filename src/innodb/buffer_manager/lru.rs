@@ -1,29 +1,135 @@
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    collections::{hash_map::Entry, BTreeSet, HashMap},
     fs::File,
     io::{Read, Seek, SeekFrom},
     path::{Path, PathBuf},
-    slice,
-    time::SystemTime,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
 };
 
 use super::{BufferManager, PageGuard};
 use crate::innodb::{
-    page::{Page, FIL_PAGE_SIZE},
+    doublewrite::DoublewriteBuffer,
+    encryption::PageDecryptor,
+    page::{ChecksumPolicy, OwnedPage, Page, PageSize},
     InnoDBError,
 };
-use anyhow::{anyhow, Result};
-use tracing::trace;
+use anyhow::{anyhow, Context, Result};
+use tracing::{debug, trace, warn};
 
-const LRU_PAGE_COUNT: usize = 16;
+/// Frames of a buffer manager built without a capacity, 256KB of 16K pages
+pub const LRU_PAGE_COUNT: usize = 16;
+
+/// Bookkeeping of the frames, kept behind one lock so a frame is never
+/// seen half way through being handed to another page.
+#[derive(Debug)]
+struct FrameState {
+    frames: Vec<Option<Arc<OwnedPage>>>,
+    /// (space_id, offset) of the page in each frame
+    frame_keys: Vec<Option<(u32, u32)>>,
+    page_pin_counter: Vec<u32>,
+    page_pin_map: HashMap<(u32, u32), usize>,
+    /// When each frame was last pinned or loaded, from `clock`
+    last_used: Vec<u64>,
+    /// Unpinned frames holding a page by (last_used, frame), the first is
+    /// the next to be evicted
+    unpinned: BTreeSet<(u64, usize)>,
+    /// Frames that never held a page, the lowest is at the end
+    empty: Vec<usize>,
+    clock: u64,
+}
+
+impl FrameState {
+    fn with_capacity(frames: usize) -> Self {
+        FrameState {
+            frames: vec![None; frames],
+            frame_keys: vec![None; frames],
+            page_pin_counter: vec![0; frames],
+            page_pin_map: HashMap::new(),
+            last_used: vec![0; frames],
+            unpinned: BTreeSet::new(),
+            empty: (0..frames).rev().collect(),
+            clock: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// An empty frame, or the least recently used unpinned one with its
+    /// page unmapped. The caller loads a page into it.
+    fn find_free_frame(&mut self) -> Option<usize> {
+        if let Some(frame) = self.empty.pop() {
+            return Some(frame);
+        }
+        let (_, frame) = self.unpinned.pop_first()?;
+        let Some(key) = self.frame_keys[frame].take() else {
+            warn!("Unpinned frame {} holds no page", frame);
+            return None;
+        };
+        self.page_pin_map.remove(&key);
+        Some(frame)
+    }
+
+    /// Guards still holding the frame's previous page keep their copy.
+    /// Prefetched pages are loaded unpinned.
+    fn load_frame(
+        &mut self,
+        frame: usize,
+        key: (u32, u32),
+        page: Arc<OwnedPage>,
+        timestamp: u64,
+        pinned: bool,
+    ) {
+        self.frames[frame] = Some(page);
+        self.frame_keys[frame] = Some(key);
+        self.page_pin_map.insert(key, frame);
+        self.last_used[frame] = timestamp;
+        self.page_pin_counter[frame] = pinned as u32;
+        if !pinned {
+            self.unpinned.insert((timestamp, frame));
+        }
+    }
+
+    fn pin_frame(&mut self, frame: usize) -> Arc<OwnedPage> {
+        if self.page_pin_counter[frame] == 0 {
+            self.unpinned.remove(&(self.last_used[frame], frame));
+        }
+        self.page_pin_counter[frame] += 1;
+        self.last_used[frame] = self.tick();
+        self.frames[frame]
+            .clone()
+            .expect("Mapped frame holds a page")
+    }
+
+    /// False when the frame wasn't pinned
+    fn unpin_frame(&mut self, frame: usize) -> bool {
+        let counter = &mut self.page_pin_counter[frame];
+        if *counter == 0 {
+            return false;
+        }
+        *counter -= 1;
+        if *counter == 0 {
+            self.unpinned.insert((self.last_used[frame], frame));
+        }
+        true
+    }
+}
 
 pub struct LRUBufferManager {
-    backing_store: Vec<[u8; FIL_PAGE_SIZE]>,
-    page_pin_counter: RefCell<Vec<u32>>,
+    state: Mutex<FrameState>,
+    page_size: PageSize,
     page_directory: PathBuf,
-    page_pin_map: RefCell<HashMap<(u32, u32), usize>>,
-    lru_list: RefCell<Vec<u64>>,
+    file_handles: Mutex<HashMap<u32, File>>,
+    read_ahead: usize,
+    read_count: AtomicUsize,
+    decryptor: Option<Arc<PageDecryptor>>,
+    doublewrite: Option<Arc<DoublewriteBuffer>>,
+    checksum_policy: ChecksumPolicy,
 }
 
 impl LRUBufferManager {
@@ -31,124 +137,588 @@ impl LRUBufferManager {
     where
         P: AsRef<Path>,
     {
-        let mut buffer_manager = LRUBufferManager {
-            backing_store: Vec::new(),
-            page_pin_counter: RefCell::new(Vec::new()),
+        Self::with_page_size(dir, PageSize::default())
+    }
+
+    pub fn with_page_size<P>(dir: P, page_size: PageSize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_page_size_and_capacity(dir, page_size, LRU_PAGE_COUNT)
+    }
+
+    /// Buffers up to `frames` pages, which is also the most pages that can
+    /// be pinned at once
+    pub fn with_capacity<P>(dir: P, frames: usize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_page_size_and_capacity(dir, PageSize::default(), frames)
+    }
+
+    pub fn with_page_size_and_capacity<P>(dir: P, page_size: PageSize, frames: usize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        assert!(frames > 0, "Buffer manager needs at least one frame");
+        LRUBufferManager {
+            state: Mutex::new(FrameState::with_capacity(frames)),
+            page_size,
             page_directory: dir.as_ref().to_owned(),
-            page_pin_map: RefCell::new(HashMap::new()),
-            lru_list: RefCell::new(Vec::new()),
+            file_handles: Mutex::new(HashMap::new()),
+            read_ahead: 0,
+            read_count: AtomicUsize::new(0),
+            decryptor: None,
+            doublewrite: None,
+            checksum_policy: ChecksumPolicy::default(),
+        }
+    }
+
+    /// On a miss also read up to `pages` following pages of the same space
+    /// into unused frames, capped so a single miss can't flush the whole pool.
+    pub fn set_read_ahead(&mut self, pages: usize) {
+        let frames = self.state.get_mut().unwrap().frames.len();
+        self.read_ahead = std::cmp::min(pages, frames / 2);
+    }
+
+    /// Decrypts encrypted pages as they are read
+    pub fn set_decryptor(&mut self, decryptor: Arc<PageDecryptor>) {
+        self.decryptor = Some(decryptor);
+    }
+
+    /// Restores torn pages from copies in the doublewrite buffer
+    pub fn set_doublewrite(&mut self, doublewrite: Arc<DoublewriteBuffer>) {
+        self.doublewrite = Some(doublewrite);
+    }
+
+    /// Which checksums pages read from the files have to pass
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Number of read calls issued against the page files so far
+    pub fn read_count(&self) -> usize {
+        self.read_count.load(Ordering::Relaxed)
+    }
+
+    fn state(&self) -> MutexGuard<'_, FrameState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Frees the least recently used unpinned frame, fails when every
+    /// frame is pinned
+    pub fn find_free(&self) -> Result<usize> {
+        let mut state = self.state();
+        state.find_free_frame().ok_or_else(|| {
+            debug!("No free frame, state: {:#?}", state);
+            anyhow!(InnoDBError::BufferExhausted)
+        })
+    }
+
+    /// Reads up to `count` pages starting at `offset`, the result is
+    /// truncated to whole pages.
+    fn read_pages(&self, space_id: u32, offset: u32, count: usize) -> Result<Vec<u8>> {
+        let mut file_handles = self.file_handles.lock().unwrap();
+        let file = match file_handles.entry(space_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(File::open(
+                self.page_directory.join(format!("{:08}.pages", space_id)),
+            )?),
         };
-        buffer_manager
-            .backing_store
-            .resize(LRU_PAGE_COUNT, [0u8; FIL_PAGE_SIZE]);
-        buffer_manager
-            .page_pin_counter
-            .borrow_mut()
-            .resize(LRU_PAGE_COUNT, 0);
-        buffer_manager
-            .lru_list
-            .borrow_mut()
-            .resize(LRU_PAGE_COUNT, 0);
-        buffer_manager
-    }
-
-    pub fn find_free(&self) -> usize {
-        let mut min_timestamp = u64::MAX;
-        let mut result_frame = 0;
-        let page_pin_counter = self.page_pin_counter.borrow();
-        for (idx, timestamp) in self.lru_list.borrow().iter().enumerate() {
-            if *timestamp == 0 {
-                return idx;
+        let page_size = self.page_size.bytes();
+        file.seek(SeekFrom::Start(offset as u64 * page_size as u64))?;
+
+        let mut buf = vec![0u8; count * page_size];
+        let mut filled = 0;
+        while filled < buf.len() {
+            let bytes = file.read(&mut buf[filled..])?;
+            self.read_count.fetch_add(1, Ordering::Relaxed);
+            if bytes == 0 {
+                break;
             }
-            // find unpinned page
-            if *timestamp < min_timestamp && page_pin_counter[idx] == 0 {
-                min_timestamp = *timestamp;
-                result_frame = idx;
+            filled += bytes;
+        }
+        buf.truncate(filled - filled % page_size);
+        Ok(buf)
+    }
+
+    fn decrypt(&self, buf: &mut [u8]) -> Result<()> {
+        match &self.decryptor {
+            Some(decryptor) => {
+                decryptor.decrypt_page(buf, |space_id| self.read_pages(space_id, 0, 1))
             }
+            None => Ok(()),
         }
-        if min_timestamp != u64::MAX {
-            let mut borrowed_pin_map = self.page_pin_map.borrow_mut();
-            let ((space_id, offset), _) = borrowed_pin_map
-                .iter()
-                .find(|(_, val)| **val == result_frame)
-                .unwrap_or_else(|| panic!("can't find the frame({result_frame}), {:#?}, pinmap: {:#?}",
-                    self, borrowed_pin_map))
-                .to_owned();
-            let (space_id, offset) = (*space_id, *offset);
-            borrowed_pin_map.remove(&(space_id, offset));
-            self.lru_list.borrow_mut()[result_frame] = 0;
-            result_frame
-        } else {
-            panic!("pin too many pages, \nState: {:#?}", self);
+    }
+
+    /// Places pages following a miss into unused frames, pages that don't
+    /// look right are dropped silently as nobody asked for them yet.
+    /// They count as used at `timestamp` like the page that missed.
+    fn prefetch(&self, space_id: u32, first_offset: u32, pages: &mut [u8], timestamp: u64) {
+        for (idx, data) in pages.chunks_exact_mut(self.page_size.bytes()).enumerate() {
+            let offset = first_offset + idx as u32;
+            // Decrypted without holding the frames, which are only locked to
+            // install the page
+            let buffered = self.state().page_pin_map.contains_key(&(space_id, offset));
+            if buffered || self.decrypt(data).is_err() {
+                continue;
+            }
+            let page = match OwnedPage::try_from_bytes_with_size(data, self.page_size) {
+                Ok(page) => page,
+                Err(_) => continue,
+            };
+            if page.page().validate_position(offset, space_id).is_err()
+                || !page.page().validate_with(self.checksum_policy).is_valid()
+            {
+                continue;
+            }
+            let mut state = self.state();
+            if state.page_pin_map.contains_key(&(space_id, offset)) {
+                continue;
+            }
+            let Some(frame) = state.find_free_frame() else {
+                break;
+            };
+            state.load_frame(frame, (space_id, offset), Arc::new(page), timestamp, false);
+            trace!("Prefetched {}, {}", space_id, offset);
         }
     }
 }
 
 impl std::fmt::Debug for LRUBufferManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
         f.debug_struct("LRUBufferManager")
-            .field("page_pin_counter", &self.page_pin_counter)
+            .field("page_pin_counter", &state.page_pin_counter)
             .field("page_directory", &self.page_directory)
-            .field("page_pin_map", &self.page_pin_map)
-            .field("lru_list", &self.lru_list)
+            .field("page_pin_map", &state.page_pin_map)
+            .field("last_used", &state.last_used)
             .finish()
     }
 }
 
 impl BufferManager for LRUBufferManager {
-    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard> {
+    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard<'_>> {
         trace!("Pinning {}, {}", space_id, offset);
-        let cur_sys_time = SystemTime::now();
-        let current_time = cur_sys_time
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
 
         // If we have the page already pinned
-        if let Some(frame_number) = self.page_pin_map.borrow().get(&(space_id, offset)) {
-            self.page_pin_counter.borrow_mut()[*frame_number] += 1;
-            self.lru_list.borrow_mut()[*frame_number] = current_time;
-            let page = Page::from_bytes(&self.backing_store[*frame_number])?;
-            return Ok(PageGuard::new(page, self));
-        }
-
-        // If we don't have page already pinned
-        let mut file = File::open(self.page_directory.join(format!("{:08}.pages", space_id)))?;
-        file.seek(SeekFrom::Start(offset as u64 * FIL_PAGE_SIZE as u64))?;
-        let free_frame = self.find_free();
-        file.read_exact(unsafe {
-            let selected_frame = &self.backing_store[free_frame];
-            slice::from_raw_parts_mut(selected_frame.as_ptr() as *mut u8, FIL_PAGE_SIZE)
-        })?;
+        {
+            let mut state = self.state();
+            if let Some(frame_number) = state.page_pin_map.get(&(space_id, offset)).copied() {
+                let page = state.pin_frame(frame_number);
+                return Ok(PageGuard::new(page, self));
+            }
+        }
+
+        // If we don't have page already pinned, read it without holding the
+        // frames so other threads can keep pinning buffered pages
+        let mut pages = self.read_pages(space_id, offset, 1 + self.read_ahead)?;
+        if pages.is_empty() {
+            return Err(anyhow!(InnoDBError::PageNotFound));
+        }
+        let page_size = self.page_size.bytes();
+        if let Some(doublewrite) = &self.doublewrite {
+            doublewrite.restore(space_id, offset, &mut pages[..page_size]);
+        }
+        self.decrypt(&mut pages[..page_size])?;
 
         // Validate page *FIRST*
-        let page = Page::from_bytes(&self.backing_store[free_frame])?;
+        let page = Arc::new(OwnedPage::try_from_bytes_with_size(
+            &pages[..page_size],
+            self.page_size,
+        )?);
         if page.header.space_id == 0 && page.header.offset == 0 {
             return Err(anyhow!(InnoDBError::PageNotFound));
         }
-        assert_eq!(page.header.space_id, space_id);
-        assert_eq!(page.header.offset, offset);
-        assert_eq!(page.header.new_checksum, page.crc32_checksum());
+        page.page().validate_position(offset, space_id)?;
+        let validation = page.page().validate_with(self.checksum_policy);
+        if !validation.is_valid() {
+            return Err(anyhow!(InnoDBError::InvalidChecksum)).context(format!(
+                "Page {} of space {}: {:?}",
+                offset, space_id, validation
+            ));
+        }
 
-        // Can't fail from this point on, so we update internal state
+        let timestamp = {
+            let mut state = self.state();
+            // Another thread may have loaded the page while we were reading
+            if let Some(frame_number) = state.page_pin_map.get(&(space_id, offset)).copied() {
+                let page = state.pin_frame(frame_number);
+                return Ok(PageGuard::new(page, self));
+            }
+            let Some(free_frame) = state.find_free_frame() else {
+                debug!("No free frame for {}, {}", space_id, offset);
+                return Err(anyhow!(InnoDBError::BufferExhausted));
+            };
+            // Can't fail from this point on, so we update internal state
+            let timestamp = state.tick();
+            state.load_frame(
+                free_frame,
+                (space_id, offset),
+                page.clone(),
+                timestamp,
+                true,
+            );
+            timestamp
+        };
 
-        self.lru_list.borrow_mut()[free_frame] = current_time;
-        self.page_pin_counter.borrow_mut()[free_frame] += 1;
-        self.page_pin_map
-            .borrow_mut()
-            .insert((space_id, offset), free_frame);
+        self.prefetch(space_id, offset + 1, &mut pages[page_size..], timestamp);
 
-        return Ok(PageGuard::new(page, self));
+        Ok(PageGuard::new(page, self))
     }
 
     fn unpin(&self, page: Page) {
         let space_id = page.header.space_id;
         let offset = page.header.offset;
+        // A default page carries no data and would otherwise unpin (0, 0)
+        if page.raw_data.is_empty() {
+            return;
+        }
         trace!("Unpinning {}, {}", space_id, offset);
-        if let Some(frame_number) = self.page_pin_map.borrow().get(&(space_id, offset)) {
-            self.page_pin_counter.borrow_mut()[*frame_number] -= 1;
-        } else {
-            panic!("Unpinning a non-pinned page");
+        let mut state = self.state();
+        let Some(frame_number) = state.page_pin_map.get(&(space_id, offset)).copied() else {
+            warn!("Unpinning {}, {} which is not buffered", space_id, offset);
+            return;
+        };
+        let unpinned = state.unpin_frame(frame_number);
+        debug_assert!(
+            unpinned,
+            "Pin count underflow unpinning {}, {}",
+            space_id, offset
+        );
+        if !unpinned {
+            warn!("Unpinning {}, {} which is not pinned", space_id, offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use crate::innodb::{
+        buffer_manager::{BufferManager, PageGuard},
+        encryption::{MasterKey, PageDecryptor},
+        page::{
+            writer::PageWriter, ChecksumKind, ChecksumPolicy, OwnedPage, Page, PageType,
+            FIL_PAGE_SIZE,
+        },
+        test_data_path, InnoDBError,
+    };
+
+    use super::{FrameState, LRUBufferManager, LRU_PAGE_COUNT};
+
+    fn page_directory(name: &str) -> PathBuf {
+        page_directory_from(name, "float_sample.ibd")
+    }
+
+    fn page_directory_from(name: &str, fixture: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lru_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(test_data_path(fixture), dir.join("00000351.pages")).unwrap();
+        dir
+    }
+
+    fn scan(buffer_mgr: &LRUBufferManager) {
+        for offset in 0..5 {
+            let page = buffer_mgr.pin(351, offset).unwrap();
+            assert_eq!(page.header.offset, offset);
         }
     }
+
+    #[test]
+    fn test_sequential_scan_without_read_ahead() {
+        let dir = page_directory("no_read_ahead");
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        scan(&buffer_mgr);
+        assert_eq!(buffer_mgr.read_count(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sequential_scan_with_read_ahead() {
+        let dir = page_directory("read_ahead");
+        let mut buffer_mgr = LRUBufferManager::new(&dir);
+        buffer_mgr.set_read_ahead(4);
+        scan(&buffer_mgr);
+        assert_eq!(buffer_mgr.read_count(), 1);
+
+        // Prefetched pages are not pinned
+        assert!(buffer_mgr.state().page_pin_counter.iter().all(|c| *c == 0));
+
+        // Still accounted correctly when pinned at the same time
+        let first = buffer_mgr.pin(351, 1).unwrap();
+        let second = buffer_mgr.pin(351, 1).unwrap();
+        let frame = buffer_mgr.state().page_pin_map[&(351, 1)];
+        assert_eq!(buffer_mgr.state().page_pin_counter[frame], 2);
+        drop(first);
+        drop(second);
+        assert_eq!(buffer_mgr.state().page_pin_counter[frame], 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pin_from_threads() {
+        let dir = page_directory("threads");
+        let mut buffer_mgr = LRUBufferManager::new(&dir);
+        buffer_mgr.set_read_ahead(2);
+        let held = buffer_mgr.pin(351, 4).unwrap();
+        std::thread::scope(|scope| {
+            for thread in 0..4u32 {
+                let buffer_mgr = &buffer_mgr;
+                scope.spawn(move || {
+                    for round in 0..200u32 {
+                        let first = buffer_mgr.pin(351, (thread + round) % 5).unwrap();
+                        let second = buffer_mgr.pin(351, (thread + round * 3) % 5).unwrap();
+                        assert_eq!(first.header.offset, (thread + round) % 5);
+                        assert_eq!(second.header.offset, (thread + round * 3) % 5);
+                    }
+                });
+            }
+            // Guards can be released from another thread
+            scope.spawn(move || drop(held));
+        });
+        assert!(buffer_mgr.state().page_pin_counter.iter().all(|c| *c == 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A page file of `pages` copies of the fixture's index page
+    fn numbered_pages(name: &str, pages: u32) -> PathBuf {
+        let dir = page_directory(name);
+        let index = fixture_page(&dir, 4);
+        let mut data = Vec::with_capacity(pages as usize * FIL_PAGE_SIZE);
+        for offset in 0..pages {
+            let mut writer = PageWriter::new(&Page::from_bytes(&index).unwrap());
+            writer.set_offset(offset);
+            data.extend_from_slice(&writer.finalize(ChecksumKind::Crc32));
+        }
+        fs::write(dir.join("00000351.pages"), data).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_capacity() {
+        let dir = numbered_pages("capacity", 1001);
+        for capacity in [1, 3, 1000] {
+            // One page more than fits, one at a time
+            let buffer_mgr = LRUBufferManager::with_capacity(&dir, capacity);
+            for offset in 0..=capacity as u32 {
+                assert_eq!(buffer_mgr.pin(351, offset).unwrap().header.offset, offset);
+            }
+            assert_eq!(buffer_mgr.read_count(), capacity + 1);
+            assert!(!buffer_mgr.state().page_pin_map.contains_key(&(351, 0)));
+            drop(buffer_mgr.pin(351, capacity as u32).unwrap());
+            assert_eq!(buffer_mgr.read_count(), capacity + 1);
+
+            // All but one frame stay pinned while the rest cycles through it
+            let buffer_mgr = LRUBufferManager::with_capacity(&dir, capacity);
+            let held = (0..capacity as u32 - 1)
+                .map(|offset| buffer_mgr.pin(351, offset).unwrap())
+                .collect::<Vec<_>>();
+            for offset in capacity as u32 - 1..=capacity as u32 {
+                assert_eq!(buffer_mgr.pin(351, offset).unwrap().header.offset, offset);
+            }
+            for (offset, page) in held.iter().enumerate() {
+                assert_eq!(page.header.offset, offset as u32);
+            }
+            assert_eq!(
+                buffer_mgr.state().page_pin_counter.iter().sum::<u32>(),
+                capacity as u32 - 1
+            );
+            drop(held);
+            assert!(buffer_mgr.state().page_pin_counter.iter().all(|c| *c == 0));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pin_misplaced_page() {
+        let dir = numbered_pages("misplaced", 4);
+        let path = dir.join("00000351.pages");
+        let mut data = fs::read(&path).unwrap();
+        let page = &mut data[2 * FIL_PAGE_SIZE..3 * FIL_PAGE_SIZE];
+        let mut writer = PageWriter::new(&Page::from_bytes(page).unwrap());
+        writer.set_offset(3);
+        page.copy_from_slice(&writer.finalize(ChecksumKind::Crc32));
+        fs::write(&path, data).unwrap();
+
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        let err = buffer_mgr.pin(351, 2).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InnoDBError>(),
+            Some(&InnoDBError::PagePositionMismatch {
+                expected: (351, 2),
+                has: (351, 3)
+            })
+        );
+        assert!(buffer_mgr.state().page_pin_map.is_empty());
+        assert_eq!(buffer_mgr.pin(351, 1).unwrap().header.offset, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pin_bad_checksum() {
+        let dir = numbered_pages("bad_checksum", 4);
+        let path = dir.join("00000351.pages");
+        let mut data = fs::read(&path).unwrap();
+        data[2 * FIL_PAGE_SIZE + 200] ^= 0xFF;
+        fs::write(&path, data).unwrap();
+
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        let err = buffer_mgr.pin(351, 2).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InnoDBError>(),
+            Some(&InnoDBError::InvalidChecksum)
+        );
+        assert!(buffer_mgr.state().page_pin_map.is_empty());
+        assert_eq!(buffer_mgr.pin(351, 1).unwrap().header.offset, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pin_checksum_policy() {
+        let dir = numbered_pages("no_checksum", 4);
+        let path = dir.join("00000351.pages");
+        let mut data = fs::read(&path).unwrap();
+        let page = &mut data[2 * FIL_PAGE_SIZE..3 * FIL_PAGE_SIZE];
+        let none = PageWriter::new(&Page::from_bytes(page).unwrap()).finalize(ChecksumKind::None);
+        page.copy_from_slice(&none);
+        // Torn pages fail whatever the policy
+        let torn = &mut data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE];
+        torn[FIL_PAGE_SIZE - 1] ^= 0xFF;
+        fs::write(&path, data).unwrap();
+
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        let err = buffer_mgr.pin(351, 2).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InnoDBError>(),
+            Some(&InnoDBError::InvalidChecksum)
+        );
+
+        let mut buffer_mgr = LRUBufferManager::new(&dir);
+        buffer_mgr.set_checksum_policy(ChecksumPolicy { allow_none: true });
+        assert_eq!(buffer_mgr.pin(351, 2).unwrap().header.offset, 2);
+        let err = buffer_mgr.pin(351, 3).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InnoDBError>(),
+            Some(&InnoDBError::InvalidChecksum)
+        );
+        assert!(format!("{:?}", err).contains("TornPage"), "{:?}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let dir = numbered_pages("evict_order", 5);
+        let buffer_mgr = LRUBufferManager::with_capacity(&dir, 3);
+        for offset in [0, 1, 2, 0] {
+            drop(buffer_mgr.pin(351, offset).unwrap());
+        }
+        let held = buffer_mgr.pin(351, 2).unwrap();
+        drop(buffer_mgr.pin(351, 3).unwrap());
+        drop(buffer_mgr.pin(351, 4).unwrap());
+        let mut buffered = buffer_mgr
+            .state()
+            .page_pin_map
+            .keys()
+            .map(|key| key.1)
+            .collect::<Vec<_>>();
+        buffered.sort();
+        // 1 was the oldest, then 0 while 2 stayed pinned
+        assert_eq!(buffered, [2, 3, 4]);
+        drop(held);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pin_every_frame() {
+        let dir = numbered_pages("exhausted", LRU_PAGE_COUNT as u32 + 1);
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        let mut held = (0..LRU_PAGE_COUNT as u32)
+            .map(|offset| buffer_mgr.pin(351, offset).unwrap())
+            .collect::<Vec<_>>();
+        assert!(buffer_mgr.find_free().is_err());
+        let err = buffer_mgr.pin(351, LRU_PAGE_COUNT as u32).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InnoDBError>(),
+            Some(&InnoDBError::BufferExhausted)
+        );
+        // Buffered pages can still be pinned again
+        drop(buffer_mgr.pin(351, 0).unwrap());
+
+        held.pop();
+        let page = buffer_mgr.pin(351, LRU_PAGE_COUNT as u32).unwrap();
+        assert_eq!(page.header.offset, LRU_PAGE_COUNT as u32);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_free_frame_without_page() {
+        let mut state = FrameState::with_capacity(1);
+        assert_eq!(state.find_free_frame(), Some(0));
+        // Listed as unpinned without ever being loaded
+        state.unpinned.insert((0, 0));
+        assert_eq!(state.find_free_frame(), None);
+    }
+
+    fn fixture_page(dir: &Path, offset: usize) -> Vec<u8> {
+        let data = fs::read(dir.join("00000351.pages")).unwrap();
+        data[offset * FIL_PAGE_SIZE..(offset + 1) * FIL_PAGE_SIZE].to_vec()
+    }
+
+    #[test]
+    fn test_drop_guard_for_unpinned_page() {
+        let dir = page_directory("never_pinned");
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        let buf = fixture_page(&dir, 4);
+
+        let page = Arc::new(OwnedPage::try_from_bytes(&buf).unwrap());
+        drop(PageGuard::new(page, &buffer_mgr));
+        drop(PageGuard::new(Arc::default(), &buffer_mgr));
+        assert!(buffer_mgr.state().page_pin_map.is_empty());
+
+        let page = buffer_mgr.pin(351, 4).unwrap();
+        let frame = buffer_mgr.state().page_pin_map[&(351, 4)];
+        drop(PageGuard::new(Arc::default(), &buffer_mgr));
+        assert_eq!(buffer_mgr.state().page_pin_counter[frame], 1);
+        drop(page);
+        assert_eq!(buffer_mgr.state().page_pin_counter[frame], 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Pin count underflow")]
+    fn test_double_unpin() {
+        let dir = page_directory("double_unpin");
+        let buffer_mgr = LRUBufferManager::new(&dir);
+        let buf = fixture_page(&dir, 4);
+        drop(buffer_mgr.pin(351, 4).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        buffer_mgr.unpin(Page::from_bytes(&buf).unwrap());
+    }
+
+    #[test]
+    fn test_decrypt_pages() {
+        let dir = page_directory_from("encrypted", "encrypted_sample.ibd");
+        let mut buffer_mgr = LRUBufferManager::new(&dir);
+        buffer_mgr.set_read_ahead(4);
+        let master_key = MasterKey::Raw(std::array::from_fn(|i| 0x40 + i as u8));
+        buffer_mgr.set_decryptor(Arc::new(PageDecryptor::new(master_key)));
+        scan(&buffer_mgr);
+        let page = buffer_mgr.pin(351, 4).unwrap();
+        assert_eq!(page.header.page_type, PageType::Index);
+        drop(page);
+
+        // Without the key the page is refused
+        let mut buffer_mgr = LRUBufferManager::new(&dir);
+        buffer_mgr.set_decryptor(Arc::new(PageDecryptor::new(MasterKey::Raw([0u8; 32]))));
+        assert!(buffer_mgr.pin(351, 4).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
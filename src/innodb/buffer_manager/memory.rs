@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::{trace, warn};
+
+use crate::innodb::{
+    doublewrite::DoublewriteBuffer,
+    encryption::PageDecryptor,
+    page::{FILHeader, OwnedPage, Page, PageSize},
+    InnoDBError,
+};
+
+use super::{BufferManager, PageGuard};
+
+/// Serves pages from memory, mostly useful for building pages in tests.
+#[derive(Default)]
+pub struct MemoryBufferManager {
+    pages: HashMap<(u32, u32), Arc<OwnedPage>>,
+    pin_counts: Mutex<HashMap<(u32, u32), u32>>,
+    page_size: PageSize,
+    decryptor: Option<Arc<PageDecryptor>>,
+    doublewrite: Option<Arc<DoublewriteBuffer>>,
+}
+
+impl MemoryBufferManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_page_size(page_size: PageSize) -> Self {
+        MemoryBufferManager {
+            page_size,
+            ..Self::default()
+        }
+    }
+
+    /// Decrypts encrypted pages as they are added
+    pub fn set_decryptor(&mut self, decryptor: Arc<PageDecryptor>) {
+        self.decryptor = Some(decryptor);
+    }
+
+    /// Restores torn pages from copies in the doublewrite buffer
+    pub fn set_doublewrite(&mut self, doublewrite: Arc<DoublewriteBuffer>) {
+        self.doublewrite = Some(doublewrite);
+    }
+
+    /// Adds a page, keyed by the space id and offset in its FIL header. An
+    /// encrypted page needs page 0 of its space to be added before it.
+    pub fn add_page(&mut self, buf: &[u8]) -> Result<()> {
+        if buf.len() != self.page_size.bytes() {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        let header = FILHeader::from_bytes(buf)?;
+        let mut page: Box<[u8]> = buf.into();
+        if let Some(doublewrite) = &self.doublewrite {
+            doublewrite.restore(header.space_id, header.offset, &mut page);
+        }
+        if let Some(decryptor) = &self.decryptor {
+            decryptor.decrypt_page(&mut page, |space_id| {
+                let first_page = self
+                    .pages
+                    .get(&(space_id, 0))
+                    .ok_or(InnoDBError::PageNotFound)?;
+                Ok(first_page.raw_data().to_vec())
+            })?;
+        }
+        let page = OwnedPage::try_from_boxed_with_size(page, self.page_size)?;
+        self.pages
+            .insert((header.space_id, header.offset), Arc::new(page));
+        Ok(())
+    }
+
+    /// Adds every page of a tablespace file under `space_id`
+    pub fn add_space(&mut self, space_id: u32, data: &[u8]) -> Result<()> {
+        let page_size = self.page_size.bytes();
+        if !data.len().is_multiple_of(page_size) {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        for (offset, buf) in data.chunks_exact(page_size).enumerate() {
+            let mut page: Box<[u8]> = buf.into();
+            if let Some(doublewrite) = &self.doublewrite {
+                doublewrite.restore(space_id, offset as u32, &mut page);
+            }
+            if let Some(decryptor) = &self.decryptor {
+                decryptor.decrypt_page(&mut page, |_| Ok(data[..page_size].to_vec()))?;
+            }
+            let page = OwnedPage::try_from_boxed_with_size(page, self.page_size)?;
+            self.pages.insert((space_id, offset as u32), Arc::new(page));
+        }
+        Ok(())
+    }
+
+    pub fn pin_count(&self, space_id: u32, offset: u32) -> u32 {
+        self.pin_counts
+            .lock()
+            .unwrap()
+            .get(&(space_id, offset))
+            .cloned()
+            .unwrap_or(0)
+    }
+}
+
+impl BufferManager for MemoryBufferManager {
+    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard<'_>> {
+        let page = self
+            .pages
+            .get(&(space_id, offset))
+            .ok_or(InnoDBError::PageNotFound)?;
+        trace!("Pinning {}, {}", space_id, offset);
+        *self
+            .pin_counts
+            .lock()
+            .unwrap()
+            .entry((space_id, offset))
+            .or_insert(0) += 1;
+        Ok(PageGuard::new(page.clone(), self))
+    }
+
+    fn unpin(&self, page: Page) {
+        let key = (page.header.space_id, page.header.offset);
+        trace!("Unpinning {}, {}", key.0, key.1);
+        match self.pin_counts.lock().unwrap().get_mut(&key) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => warn!("Unpinning {}, {} which is not pinned", key.0, key.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::innodb::{
+        buffer_manager::BufferManager,
+        encryption::{MasterKey, PageDecryptor},
+        page::{PageSize, PageType, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::MemoryBufferManager;
+
+    #[test]
+    fn test_pin_from_memory() {
+        let data = test_data("float_sample.ibd");
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_space(351, &data).unwrap();
+        assert!(buffer_mgr.add_page(&data[..FIL_PAGE_SIZE - 1]).is_err());
+
+        let page = buffer_mgr.pin(351, 4).unwrap();
+        assert_eq!(page.header.page_type, PageType::Index);
+        let again = buffer_mgr.pin(351, 4).unwrap();
+        assert_eq!(buffer_mgr.pin_count(351, 4), 2);
+        drop(page);
+        drop(again);
+        assert_eq!(buffer_mgr.pin_count(351, 4), 0);
+
+        assert!(buffer_mgr.pin(351, 100).is_err());
+        assert!(buffer_mgr.pin(1, 0).is_err());
+    }
+
+    #[test]
+    fn test_pin_8k_pages() {
+        let data = test_data("float_sample_8k.ibd");
+        let mut buffer_mgr = MemoryBufferManager::with_page_size(PageSize::Size8K);
+        buffer_mgr.add_space(351, &data).unwrap();
+        assert!(buffer_mgr.add_page(&data[..FIL_PAGE_SIZE]).is_err());
+
+        let page = buffer_mgr.pin(351, 4).unwrap();
+        assert_eq!(page.header.page_type, PageType::Index);
+        assert_eq!(page.size(), PageSize::Size8K);
+        assert_eq!(page.page().crc32_checksum(), page.header.new_checksum);
+    }
+
+    #[test]
+    fn test_decrypt_on_add() {
+        let data = test_data("encrypted_sample.ibd");
+        let master_key = MasterKey::Raw(std::array::from_fn(|i| 0x40 + i as u8));
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.set_decryptor(Arc::new(PageDecryptor::new(master_key)));
+        // Page 0 isn't there yet to give up the key
+        assert!(buffer_mgr
+            .add_page(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE])
+            .is_err());
+
+        buffer_mgr.add_space(351, &data).unwrap();
+        let page = buffer_mgr.pin(351, 4).unwrap();
+        assert_eq!(page.header.page_type, PageType::Index);
+        assert!(page.checksum_matches().is_some());
+    }
+}
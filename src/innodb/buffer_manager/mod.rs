@@ -1,24 +1,32 @@
-use std::ops::Deref;
+use std::{ops::Deref, sync::Arc};
 
-use super::page::Page;
+use super::page::{OwnedPage, Page};
 use anyhow::{anyhow, Result};
 
 pub mod lru;
+pub mod memory;
 pub mod simple;
 
-pub trait BufferManager {
-    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard>;
+/// Buffer managers are shared between threads parsing pages in parallel,
+/// guards can be sent to another thread and unpin from there.
+pub trait BufferManager: Send + Sync {
+    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard<'_>>;
     fn unpin(&self, page: Page);
 }
 
+/// A pinned page, shared with the buffer manager's frame so it stays
+/// valid even once the frame is handed to another page.
 pub struct PageGuard<'a> {
-    page: Page<'a>,
+    page: Arc<OwnedPage>,
     buffer_manager: &'a dyn BufferManager,
+    // Default pages carry no data and were never pinned
+    pinned: bool,
 }
 
 impl<'a> PageGuard<'a> {
-    pub fn new(page: Page<'a>, buffer_manager: &'a dyn BufferManager) -> Self {
+    pub fn new(page: Arc<OwnedPage>, buffer_manager: &'a dyn BufferManager) -> Self {
         PageGuard {
+            pinned: !page.raw_data().is_empty(),
             page,
             buffer_manager,
         }
@@ -26,7 +34,7 @@ impl<'a> PageGuard<'a> {
 }
 
 impl<'a> Deref for PageGuard<'a> {
-    type Target = Page<'a>;
+    type Target = OwnedPage;
 
     fn deref(&self) -> &Self::Target {
         &self.page
@@ -35,14 +43,17 @@ impl<'a> Deref for PageGuard<'a> {
 
 impl<'a> Drop for PageGuard<'a> {
     fn drop(&mut self) {
-        self.buffer_manager.unpin(std::mem::take(&mut self.page));
+        if self.pinned {
+            self.pinned = false;
+            self.buffer_manager.unpin(self.page.page());
+        }
     }
 }
 
 pub struct DummyBufferMangaer;
 
 impl BufferManager for DummyBufferMangaer {
-    fn pin(&self, _space_id: u32, _offset: u32) -> Result<PageGuard> {
+    fn pin(&self, _space_id: u32, _offset: u32) -> Result<PageGuard<'_>> {
         Err(anyhow!("Dummy buffer manager can't open"))
     }
 
@@ -50,3 +61,20 @@ impl BufferManager for DummyBufferMangaer {
         panic!("This doens't open how can we close");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::innodb::page::OwnedPage;
+
+    use super::{DummyBufferMangaer, PageGuard};
+
+    #[test]
+    fn test_drop_default_page_guard() {
+        drop(PageGuard::new(
+            Arc::new(OwnedPage::default()),
+            &DummyBufferMangaer,
+        ));
+    }
+}
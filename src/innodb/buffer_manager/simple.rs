@@ -1,21 +1,40 @@
 use anyhow::Result;
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fs::File,
     io::{BufReader, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
-    slice,
+    sync::{Arc, RwLock},
 };
 use tracing::trace;
 
-use crate::innodb::page::{Page, FIL_PAGE_SIZE};
+use crate::innodb::{
+    doublewrite::DoublewriteBuffer,
+    encryption::PageDecryptor,
+    page::{OwnedPage, Page, PageSize},
+};
 
 use super::{BufferManager, PageGuard};
 
+/// Pages cached by a buffer manager built without a capacity, 1GB of 16K
+/// pages
+pub const SIMPLE_CACHE_PAGES: usize = 65536;
+
+/// Pages by (space_id, offset), the oldest is dropped first once full.
+/// Guards of dropped pages keep their own reference.
+#[derive(Default)]
+struct PageCache {
+    pages: HashMap<(u32, u32), Arc<OwnedPage>>,
+    order: VecDeque<(u32, u32)>,
+}
+
 pub struct SimpleBufferManager {
     page_directory: PathBuf,
-    page_cache: RefCell<HashMap<(u32, u32), Box<[u8]>>>,
+    page_cache: RwLock<PageCache>,
+    capacity: usize,
+    page_size: PageSize,
+    decryptor: Option<Arc<PageDecryptor>>,
+    doublewrite: Option<Arc<DoublewriteBuffer>>,
 }
 
 impl SimpleBufferManager {
@@ -23,40 +42,101 @@ impl SimpleBufferManager {
     where
         P: AsRef<Path>,
     {
+        Self::with_page_size(dir, PageSize::default())
+    }
+
+    pub fn with_page_size<P>(dir: P, page_size: PageSize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_page_size_and_capacity(dir, page_size, SIMPLE_CACHE_PAGES)
+    }
+
+    /// Caches up to `pages` pages, read again once they were dropped
+    pub fn with_capacity<P>(dir: P, pages: usize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_page_size_and_capacity(dir, PageSize::default(), pages)
+    }
+
+    pub fn with_page_size_and_capacity<P>(dir: P, page_size: PageSize, pages: usize) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        assert!(pages > 0, "Page cache needs room for at least one page");
         SimpleBufferManager {
             page_directory: dir.as_ref().to_owned(),
-            page_cache: RefCell::new(HashMap::new()),
+            page_cache: RwLock::new(PageCache::default()),
+            capacity: pages,
+            page_size,
+            decryptor: None,
+            doublewrite: None,
         }
     }
 
-    fn get_page(&self, space_id: u32, offset: u32) -> Result<&[u8]> {
-        if let Some(buf) = self.page_cache.borrow().get(&(space_id, offset)) {
-            assert_eq!(buf.len(), FIL_PAGE_SIZE);
-            let ptr = buf.as_ptr();
-            return Ok(unsafe { slice::from_raw_parts(ptr, FIL_PAGE_SIZE) });
+    /// Decrypts encrypted pages as they are read
+    pub fn set_decryptor(&mut self, decryptor: Arc<PageDecryptor>) {
+        self.decryptor = Some(decryptor);
+    }
+
+    /// Restores torn pages from copies in the doublewrite buffer
+    pub fn set_doublewrite(&mut self, doublewrite: Arc<DoublewriteBuffer>) {
+        self.doublewrite = Some(doublewrite);
+    }
+
+    fn get_page(&self, space_id: u32, offset: u32) -> Result<Arc<OwnedPage>> {
+        let page_size = self.page_size.bytes();
+        if let Some(page) = self
+            .page_cache
+            .read()
+            .unwrap()
+            .pages
+            .get(&(space_id, offset))
+        {
+            return Ok(page.clone());
         }
 
         let path_path = self.page_directory.join(format!("{:08}.pages", space_id));
         let mut buf_reader = BufReader::new(File::open(&path_path)?);
-        buf_reader.seek(SeekFrom::Start(offset as u64 * FIL_PAGE_SIZE as u64))?;
-        let mut buf = Box::new([0u8; FIL_PAGE_SIZE]);
-        buf_reader.read_exact(buf.as_mut())?;
-        self.page_cache.borrow_mut().insert((space_id, offset), buf);
-        let ptr = self
-            .page_cache
-            .borrow()
-            .get(&(space_id, offset))
-            .expect("???")
-            .as_ptr();
-        return Ok(unsafe { slice::from_raw_parts(ptr, FIL_PAGE_SIZE) });
+        buf_reader.seek(SeekFrom::Start(offset as u64 * page_size as u64))?;
+        let mut buf = vec![0u8; page_size].into_boxed_slice();
+        buf_reader.read_exact(&mut buf)?;
+        if let Some(doublewrite) = &self.doublewrite {
+            doublewrite.restore(space_id, offset, &mut buf);
+        }
+        if let Some(decryptor) = &self.decryptor {
+            decryptor.decrypt_page(&mut buf, |_| {
+                let mut first_page = vec![0u8; page_size];
+                buf_reader.seek(SeekFrom::Start(0))?;
+                buf_reader.read_exact(&mut first_page)?;
+                Ok(first_page)
+            })?;
+        }
+        let page = Arc::new(OwnedPage::try_from_boxed_with_size(buf, self.page_size)?);
+        page.page().validate_position(offset, space_id)?;
+        let mut cache = self.page_cache.write().unwrap();
+        // Another thread may have read the same page meanwhile, keep theirs
+        if let Some(page) = cache.pages.get(&(space_id, offset)) {
+            return Ok(page.clone());
+        }
+        if cache.pages.len() >= self.capacity {
+            if let Some(oldest) = cache.order.pop_front() {
+                trace!("Dropping ({}, {}) from the cache", oldest.0, oldest.1);
+                cache.pages.remove(&oldest);
+            }
+        }
+        cache.pages.insert((space_id, offset), page.clone());
+        cache.order.push_back((space_id, offset));
+        Ok(page)
     }
 }
 
 impl BufferManager for SimpleBufferManager {
-    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard> {
-        let buf = self.get_page(space_id, offset)?;
+    fn pin(&self, space_id: u32, offset: u32) -> Result<PageGuard<'_>> {
+        let page = self.get_page(space_id, offset)?;
         trace!("Opened ({}, {})", space_id, offset);
-        Ok(PageGuard::new(Page::from_bytes(buf)?, self))
+        Ok(PageGuard::new(page, self))
     }
 
     fn unpin(&self, page: Page) {
@@ -67,3 +147,39 @@ impl BufferManager for SimpleBufferManager {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::innodb::{buffer_manager::BufferManager, test_data_path};
+
+    use super::SimpleBufferManager;
+
+    #[test]
+    fn test_bounded_cache() {
+        let dir = std::env::temp_dir().join(format!("simple_bounded_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(
+            test_data_path("float_sample.ibd"),
+            dir.join("00000351.pages"),
+        )
+        .unwrap();
+
+        let buffer_mgr = SimpleBufferManager::with_capacity(&dir, 2);
+        let first = buffer_mgr.pin(351, 0).unwrap();
+        for offset in 1..5 {
+            assert_eq!(buffer_mgr.pin(351, offset).unwrap().header.offset, offset);
+            assert!(buffer_mgr.page_cache.read().unwrap().pages.len() <= 2);
+        }
+        let cache = buffer_mgr.page_cache.read().unwrap();
+        assert!(!cache.pages.contains_key(&(351, 0)));
+        assert_eq!(cache.order, [(351, 3), (351, 4)]);
+        drop(cache);
+
+        // A guard outlives the dropped cache entry
+        assert_eq!(first.header.offset, 0);
+        assert_eq!(buffer_mgr.pin(351, 0).unwrap().raw_data(), first.raw_data());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -93,6 +93,53 @@ impl InnoDBCharset {
         }
     }
 
+    /// The name [`InnoDBCharset::with_name`] takes
+    pub fn name(&self) -> &'static str {
+        match self {
+            InnoDBCharset::Armscii8 => "armscii8",
+            InnoDBCharset::Ascii => "ascii",
+            InnoDBCharset::Big5 => "big5",
+            InnoDBCharset::Binary => "binary",
+            InnoDBCharset::Cp1250 => "cp1250",
+            InnoDBCharset::Cp1251 => "cp1251",
+            InnoDBCharset::Cp1256 => "cp1256",
+            InnoDBCharset::Cp1257 => "cp1257",
+            InnoDBCharset::Cp850 => "cp850",
+            InnoDBCharset::Cp852 => "cp852",
+            InnoDBCharset::Cp866 => "cp866",
+            InnoDBCharset::Cp932 => "cp932",
+            InnoDBCharset::Dec8 => "dec8",
+            InnoDBCharset::Eucjpms => "eucjpms",
+            InnoDBCharset::Euckr => "euckr",
+            InnoDBCharset::Gb18030 => "gb18030",
+            InnoDBCharset::Gb2312 => "gb2312",
+            InnoDBCharset::Gbk => "gbk",
+            InnoDBCharset::Geostd8 => "geostd8",
+            InnoDBCharset::Greek => "greek",
+            InnoDBCharset::Hebrew => "hebrew",
+            InnoDBCharset::Hp8 => "hp8",
+            InnoDBCharset::Keybcs2 => "keybcs2",
+            InnoDBCharset::Koi8r => "koi8r",
+            InnoDBCharset::Koi8u => "koi8u",
+            InnoDBCharset::Latin1 => "latin1",
+            InnoDBCharset::Latin2 => "latin2",
+            InnoDBCharset::Latin5 => "latin5",
+            InnoDBCharset::Latin7 => "latin7",
+            InnoDBCharset::Macce => "macce",
+            InnoDBCharset::Macroman => "macroman",
+            InnoDBCharset::Sjis => "sjis",
+            InnoDBCharset::Swe7 => "swe7",
+            InnoDBCharset::Tis620 => "tis620",
+            InnoDBCharset::Ucs2 => "ucs2",
+            InnoDBCharset::Ujis => "ujis",
+            InnoDBCharset::Utf16 => "utf16",
+            InnoDBCharset::Utf16le => "utf16le",
+            InnoDBCharset::Utf32 => "utf32",
+            InnoDBCharset::Utf8mb3 => "utf8mb3",
+            InnoDBCharset::Utf8mb4 => "utf8mb4",
+        }
+    }
+
     pub fn max_len(&self) -> u64 {
         match self {
             InnoDBCharset::Armscii8 => 1,
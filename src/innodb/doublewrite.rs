@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use tracing::{debug, info};
+
+use crate::innodb::page::{trx_sys::TrxSysHeader, FILHeader, OwnedPage, Page, PageSize};
+
+/// Page number of the TRX_SYS page in the system tablespace
+const TRX_SYS_PAGE_NO: u32 = 5;
+
+/// Pages of the system tablespace holding the doublewrite buffer, found
+/// through its TRX_SYS page. `reader` reads ibdata1.
+pub fn locate<R: Read + Seek>(reader: &mut R, page_size: PageSize) -> Result<[Range<u32>; 2]> {
+    let mut buf = vec![0u8; page_size.bytes()];
+    reader.seek(SeekFrom::Start(
+        TRX_SYS_PAGE_NO as u64 * page_size.bytes() as u64,
+    ))?;
+    reader.read_exact(&mut buf)?;
+    let page = Page::from_bytes_with_size(&buf, page_size)?;
+    if !page.validate().is_valid() {
+        return Err(anyhow!(
+            "TRX_SYS page of the system tablespace is corrupted"
+        ));
+    }
+    let doublewrite = TrxSysHeader::try_from_page(&page)?
+        .doublewrite
+        .ok_or_else(|| anyhow!("The system tablespace has no doublewrite buffer"))?;
+    Ok(doublewrite.pages(&page))
+}
+
+/// Copies of pages from the doublewrite buffer of the system tablespace,
+/// used to restore pages torn while being written in place
+#[derive(Debug)]
+pub struct DoublewriteBuffer {
+    page_size: PageSize,
+    /// Intact copies by the space id and page number in their header
+    copies: HashMap<(u32, u32), Vec<OwnedPage>>,
+}
+
+impl DoublewriteBuffer {
+    pub fn open<P: AsRef<Path>>(path: P, page_size: PageSize) -> Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?), page_size)
+    }
+
+    /// Reads both blocks of the buffer, keeping the copies that are intact
+    pub fn read_from<R: Read + Seek>(reader: &mut R, page_size: PageSize) -> Result<Self> {
+        let mut copies: HashMap<(u32, u32), Vec<OwnedPage>> = HashMap::new();
+        let mut skipped = 0usize;
+        for block in locate(reader, page_size)? {
+            reader.seek(SeekFrom::Start(
+                block.start as u64 * page_size.bytes() as u64,
+            ))?;
+            for page_number in block {
+                let mut buf = vec![0u8; page_size.bytes()].into_boxed_slice();
+                reader.read_exact(&mut buf)?;
+                let page = OwnedPage::try_from_boxed_with_size(buf, page_size)?;
+                if !page.page().validate().is_valid() {
+                    skipped += 1;
+                    continue;
+                }
+                debug!(
+                    "Doublewrite page {} holds ({}, {}) at lsn {}",
+                    page_number, page.header.space_id, page.header.offset, page.header.lsn
+                );
+                copies
+                    .entry((page.header.space_id, page.header.offset))
+                    .or_default()
+                    .push(page);
+            }
+        }
+        info!(
+            "Loaded {} pages from the doublewrite buffer, {} empty or corrupted",
+            copies.values().map(Vec::len).sum::<usize>(),
+            skipped
+        );
+        Ok(DoublewriteBuffer { page_size, copies })
+    }
+
+    /// Number of intact copies
+    pub fn len(&self) -> usize {
+        self.copies.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    /// The newest copy of a page with an LSN of at least `min_lsn`
+    pub fn find(&self, space_id: u32, page_number: u32, min_lsn: u64) -> Option<&OwnedPage> {
+        self.copies
+            .get(&(space_id, page_number))?
+            .iter()
+            .filter(|copy| copy.header.lsn >= min_lsn)
+            .max_by_key(|copy| copy.header.lsn)
+    }
+
+    /// Overwrites `buf`, read as `page_number` of `space_id`, with a copy
+    /// when it is torn. The copy must be at least as new as the LSN in the
+    /// header of `buf`, unless that header belongs to another page. Returns
+    /// whether `buf` was replaced.
+    pub fn restore(&self, space_id: u32, page_number: u32, buf: &mut [u8]) -> bool {
+        if buf.len() != self.page_size.bytes()
+            || Page::from_bytes_with_size(buf, self.page_size)
+                .is_ok_and(|page| page.validate().is_valid())
+        {
+            return false;
+        }
+        let min_lsn = match FILHeader::from_bytes(buf) {
+            Ok(header) if header.space_id == space_id && header.offset == page_number => header.lsn,
+            _ => 0,
+        };
+        match self.find(space_id, page_number, min_lsn) {
+            Some(copy) => {
+                info!(
+                    "Restored torn page ({}, {}) from the doublewrite buffer",
+                    space_id, page_number
+                );
+                buf.copy_from_slice(copy.raw_data());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::{create_dir_all, write},
+        io::Cursor,
+        sync::Arc,
+    };
+
+    use crate::innodb::{
+        buffer_manager::{
+            lru::LRUBufferManager, memory::MemoryBufferManager, simple::SimpleBufferManager,
+            BufferManager,
+        },
+        page::{writer::PageWriter, ChecksumKind, Page, PageSize, PageType, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{locate, DoublewriteBuffer};
+
+    /// System tablespace with the doublewrite buffer at pages 64 and 128,
+    /// holding pages 3 and 4 of float_sample and, in the second block, an
+    /// older copy of page 4
+    fn system_tablespace() -> Vec<u8> {
+        let sample = test_data("float_sample.ibd");
+        let page_at = |n: usize| Page::from_bytes(&sample[n * FIL_PAGE_SIZE..][..FIL_PAGE_SIZE]);
+
+        let mut data = vec![0u8; 192 * FIL_PAGE_SIZE];
+        let mut trx_sys = PageWriter::new(&page_at(2).unwrap());
+        trx_sys.set_page_type(PageType::TrxSys);
+        trx_sys.set_space_id(0);
+        trx_sys.set_offset(5);
+        let dblwr = FIL_PAGE_SIZE - 200;
+        trx_sys.data_mut()[dblwr + 10..dblwr + 22].copy_from_slice(
+            &[536853855u32, 64, 128]
+                .iter()
+                .flat_map(|word| word.to_be_bytes())
+                .collect::<Vec<_>>(),
+        );
+        data[5 * FIL_PAGE_SIZE..6 * FIL_PAGE_SIZE]
+            .copy_from_slice(&trx_sys.finalize(ChecksumKind::Crc32));
+        data[64 * FIL_PAGE_SIZE..65 * FIL_PAGE_SIZE].copy_from_slice(page_at(3).unwrap().raw_data);
+        data[65 * FIL_PAGE_SIZE..66 * FIL_PAGE_SIZE].copy_from_slice(page_at(4).unwrap().raw_data);
+        let mut older = PageWriter::new(&page_at(4).unwrap());
+        older.set_lsn(page_at(4).unwrap().header.lsn - 1);
+        data[128 * FIL_PAGE_SIZE..129 * FIL_PAGE_SIZE]
+            .copy_from_slice(&older.finalize(ChecksumKind::Crc32));
+        // A torn copy is left out
+        data[129 * FIL_PAGE_SIZE..130 * FIL_PAGE_SIZE]
+            .copy_from_slice(page_at(1).unwrap().raw_data);
+        data[130 * FIL_PAGE_SIZE - 1] ^= 1;
+        data
+    }
+
+    /// float_sample with the second half of page 4 never written
+    fn torn_sample() -> Vec<u8> {
+        let mut data = test_data("float_sample.ibd");
+        data[4 * FIL_PAGE_SIZE + FIL_PAGE_SIZE / 2..5 * FIL_PAGE_SIZE].fill(0);
+        data
+    }
+
+    #[test]
+    fn test_read_doublewrite() {
+        let mut ibdata = Cursor::new(system_tablespace());
+        assert_eq!(
+            locate(&mut ibdata, PageSize::default()).unwrap(),
+            [64..128, 128..192]
+        );
+        let doublewrite = DoublewriteBuffer::read_from(&mut ibdata, PageSize::default()).unwrap();
+        assert_eq!(doublewrite.len(), 3);
+        assert!(doublewrite.find(351, 1, 0).is_none());
+
+        let sample = test_data("float_sample.ibd");
+        let lsn = Page::from_bytes(&sample[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE])
+            .unwrap()
+            .header
+            .lsn;
+        assert_eq!(doublewrite.find(351, 4, 0).unwrap().header.lsn, lsn);
+        assert!(doublewrite.find(351, 4, lsn + 1).is_none());
+
+        let torn = torn_sample();
+        let mut buf = torn[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        assert!(doublewrite.restore(351, 4, &mut buf));
+        assert!(buf == sample[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]);
+        // Intact pages are left alone
+        assert!(!doublewrite.restore(351, 4, &mut buf));
+        // A torn page newer than every copy can't be restored
+        let mut newer = torn[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        newer[16..24].copy_from_slice(&(lsn + 1).to_be_bytes());
+        assert!(!doublewrite.restore(351, 4, &mut newer));
+
+        assert!(
+            DoublewriteBuffer::read_from(&mut Cursor::new(sample), PageSize::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_buffer_manager_fallback() {
+        let doublewrite = Arc::new(
+            DoublewriteBuffer::read_from(
+                &mut Cursor::new(system_tablespace()),
+                PageSize::default(),
+            )
+            .unwrap(),
+        );
+        let sample = test_data("float_sample.ibd");
+        let expected = &sample[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE];
+        let torn = torn_sample();
+
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.set_doublewrite(doublewrite.clone());
+        buffer_mgr.add_space(351, &torn).unwrap();
+        assert!(buffer_mgr.pin(351, 4).unwrap().raw_data() == expected);
+
+        let dir = std::env::temp_dir().join(format!("doublewrite_{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("00000351.pages"), &torn).unwrap();
+        let mut simple = SimpleBufferManager::new(&dir);
+        simple.set_doublewrite(doublewrite.clone());
+        assert!(simple.pin(351, 4).unwrap().raw_data() == expected);
+        let mut lru = LRUBufferManager::new(&dir);
+        lru.set_doublewrite(doublewrite);
+        assert!(lru.pin(351, 4).unwrap().raw_data() == expected);
+        assert!(
+            SimpleBufferManager::new(&dir)
+                .pin(351, 4)
+                .unwrap()
+                .raw_data()
+                != expected
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::innodb::InnoDBError;
+
+const KEYRING_HEADER: &[u8] = b"Keyring file version:2.0";
+/// "EOF" followed by the SHA-256 of everything before it
+const KEYRING_TRAILER_LEN: usize = 3 + 32;
+/// Key data is stored xored with this
+const KEYRING_OBFUSCATION: &[u8] = b"*305=Ljt0*!@$Hnm(*-9-w;:";
+const KEY_FIELD_SIZE: usize = 8;
+
+/// Keys read from a keyring_file plugin file
+#[derive(Debug, Default, Clone)]
+pub struct Keyring {
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl Keyring {
+    /// Parses a version 2.0 keyring file, the trailing digest is not
+    /// checked so a truncated copy still gives up the keys before the cut.
+    pub fn from_bytes(buf: &[u8]) -> Result<Keyring> {
+        let mut data = buf
+            .strip_prefix(KEYRING_HEADER)
+            .ok_or_else(|| anyhow!("Not a version 2.0 keyring file"))?;
+        let mut keys = HashMap::new();
+        while data.len() > KEYRING_TRAILER_LEN {
+            let field = |idx: usize| -> Result<usize> {
+                let bytes = data
+                    .get(idx * KEY_FIELD_SIZE..(idx + 1) * KEY_FIELD_SIZE)
+                    .ok_or(InnoDBError::InvalidLength)?;
+                Ok(u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+            };
+            let pod_size = field(0)?;
+            let lengths = [field(1)?, field(2)?, field(3)?, field(4)?];
+            let fields_len = lengths
+                .iter()
+                .try_fold(5 * KEY_FIELD_SIZE, |total, len| total.checked_add(*len));
+            if fields_len.is_none_or(|len| len > pod_size) || pod_size > data.len() {
+                return Err(anyhow!(InnoDBError::InvalidLength));
+            }
+
+            let mut offset = 5 * KEY_FIELD_SIZE;
+            let mut next = |len: usize| {
+                let bytes = &data[offset..offset + len];
+                offset += len;
+                bytes
+            };
+            let key_id = String::from_utf8_lossy(next(lengths[0])).into_owned();
+            let _key_type = next(lengths[1]);
+            let _user_id = next(lengths[2]);
+            let key = next(lengths[3])
+                .iter()
+                .zip(KEYRING_OBFUSCATION.iter().cycle())
+                .map(|(byte, mask)| byte ^ mask)
+                .collect();
+            keys.insert(key_id, key);
+            data = &data[pod_size..];
+        }
+        Ok(Keyring { keys })
+    }
+
+    pub fn get(&self, key_id: &str) -> Option<&[u8]> {
+        self.keys.get(key_id).map(Vec::as_slice)
+    }
+
+    /// InnoDB master key `id` of server `uuid`, keys written before server
+    /// uuids were recorded are matched by their id alone
+    pub fn master_key(&self, uuid: &str, id: u32) -> Option<&[u8]> {
+        if !uuid.is_empty() {
+            return self.get(&format!("INNODBKey-{uuid}-{id}"));
+        }
+        let suffix = format!("-{id}");
+        self.keys
+            .iter()
+            .find(|(key_id, _)| key_id.starts_with("INNODBKey-") && key_id.ends_with(&suffix))
+            .map(|(_, key)| key.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::test_data;
+
+    use super::Keyring;
+
+    const UUID: &str = "8a94f357-aab4-11df-86ab-c80aa9429562";
+
+    #[test]
+    fn test_keyring_file() {
+        let buf = test_data("encrypted_keyring");
+        let keyring = Keyring::from_bytes(&buf).unwrap();
+        assert_eq!(keyring.len(), 2);
+        let master_key: Vec<u8> = (0x40..0x60).collect();
+        assert_eq!(keyring.master_key(UUID, 1), Some(master_key.as_slice()));
+        assert_eq!(
+            keyring.get(&format!("INNODBKey-{UUID}-0")),
+            Some([0u8; 32].as_slice())
+        );
+        assert_eq!(keyring.master_key("", 1), Some(master_key.as_slice()));
+        assert_eq!(keyring.master_key(UUID, 2), None);
+
+        // A key cut short
+        assert!(Keyring::from_bytes(&buf[..100]).is_err());
+        assert!(Keyring::from_bytes(b"Keyring file version:1.0").is_err());
+    }
+}
@@ -0,0 +1,486 @@
+//! Tablespace encryption: the tablespace key is kept on page 0 encrypted
+//! with a master key from the keyring, every other page body is encrypted
+//! with the tablespace key.
+
+pub mod keyring;
+
+use std::{collections::HashMap, fmt::Debug, sync::RwLock};
+
+use aes::{
+    cipher::{block_padding::NoPadding, BlockDecrypt, BlockDecryptMut, KeyInit, KeyIvInit},
+    Aes256,
+};
+use anyhow::{anyhow, Context, Result};
+
+use self::keyring::Keyring;
+use super::{
+    page::{
+        xdes::{xdes_per_page, xdes_size, XDES_ARR_OFFSET},
+        ChecksumPolicy, Page, PageSize, PageType, FIL_HEADER_SIZE,
+    },
+    InnoDBError,
+};
+
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+pub const AES_BLOCK_SIZE: usize = 16;
+const ENCRYPTION_MAGIC_V1: &[u8; 3] = b"lCA";
+const ENCRYPTION_MAGIC_V2: &[u8; 3] = b"lCB";
+const ENCRYPTION_MAGIC_V3: &[u8; 3] = b"lCC";
+const SERVER_UUID_LEN: usize = 36;
+/// Tablespace key followed by its IV
+const KEY_INFO_LEN: usize = 2 * ENCRYPTION_KEY_LEN;
+
+/// Where an encrypted page keeps the type it had before encryption
+const FIL_PAGE_ORIGINAL_TYPE: usize = 28;
+const FIL_PAGE_TYPE_OFFSET: usize = 24;
+const FIL_PAGE_SPACE_ID_OFFSET: usize = 34;
+
+/// Space reserved for the encryption info on page 0, used or not
+pub(crate) const ENCRYPTION_INFO_MAX_SIZE: usize = 3 + 4 + SERVER_UUID_LEN + KEY_INFO_LEN + 4 + 4;
+
+/// Encryption info follows the XDES array whose size depends on the page
+/// size
+pub(crate) fn encryption_info_offset(size: PageSize) -> usize {
+    XDES_ARR_OFFSET + xdes_per_page(size) * xdes_size(size)
+}
+
+/// Encryption info on page 0 of an encrypted tablespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionInfo {
+    pub version: u8,
+    pub master_key_id: u32,
+    /// Server whose keyring holds the master key, empty for version 1
+    pub server_uuid: String,
+    key_info: [u8; KEY_INFO_LEN],
+    checksum: u32,
+}
+
+impl EncryptionInfo {
+    /// `None` when the tablespace isn't encrypted
+    pub fn try_from_page(page: &Page) -> Result<Option<EncryptionInfo>> {
+        if page.header.page_type != PageType::FspHdr {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::FspHdr,
+                has: page.header.page_type
+            }));
+        }
+        Self::try_from_bytes(page.raw_data, page.size())
+    }
+
+    /// `buf` is page 0, or at least the part of it up to the end of the
+    /// encryption info
+    pub fn try_from_bytes(buf: &[u8], size: PageSize) -> Result<Option<EncryptionInfo>> {
+        let offset = encryption_info_offset(size);
+        let buf = buf
+            .get(offset..offset + ENCRYPTION_INFO_MAX_SIZE)
+            .ok_or(InnoDBError::InvalidLength)?;
+        let version = match buf[..3].try_into().unwrap() {
+            ENCRYPTION_MAGIC_V1 => 1,
+            ENCRYPTION_MAGIC_V2 => 2,
+            ENCRYPTION_MAGIC_V3 => 3,
+            _ => return Ok(None),
+        };
+        let master_key_id = u32::from_be_bytes(buf[3..7].try_into().unwrap());
+        let (server_uuid, key_info) = match version {
+            1 => (String::new(), &buf[7..]),
+            _ => (
+                String::from_utf8_lossy(&buf[7..7 + SERVER_UUID_LEN]).into_owned(),
+                &buf[7 + SERVER_UUID_LEN..],
+            ),
+        };
+        Ok(Some(EncryptionInfo {
+            version,
+            master_key_id,
+            server_uuid,
+            key_info: key_info[..KEY_INFO_LEN].try_into().unwrap(),
+            checksum: u32::from_be_bytes(
+                key_info[KEY_INFO_LEN..KEY_INFO_LEN + 4].try_into().unwrap(),
+            ),
+        }))
+    }
+
+    /// Magic at the start of the encryption info, it gives the version
+    pub fn magic(&self) -> &'static [u8; 3] {
+        match self.version {
+            1 => ENCRYPTION_MAGIC_V1,
+            2 => ENCRYPTION_MAGIC_V2,
+            _ => ENCRYPTION_MAGIC_V3,
+        }
+    }
+
+    /// Tablespace key, still encrypted with the master key
+    pub fn wrapped_key(&self) -> &[u8] {
+        &self.key_info[..ENCRYPTION_KEY_LEN]
+    }
+
+    /// IV of the tablespace key, still encrypted with the master key
+    pub fn wrapped_iv(&self) -> &[u8] {
+        &self.key_info[ENCRYPTION_KEY_LEN..]
+    }
+
+    /// CRC32 of the decrypted key and IV
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// Decrypts the tablespace key, the checksum of the key tells whether
+    /// the master key is the right one
+    pub fn tablespace_key(&self, master_key: &[u8; ENCRYPTION_KEY_LEN]) -> Result<TablespaceKey> {
+        let mut key_info = self.key_info;
+        let aes = Aes256::new(master_key.into());
+        for block in key_info.chunks_exact_mut(AES_BLOCK_SIZE) {
+            aes.decrypt_block(block.into());
+        }
+        if crc32c::crc32c(&key_info) != self.checksum {
+            return Err(anyhow!(
+                "Master key {} doesn't decrypt the tablespace key, wrong master key",
+                self.master_key_id
+            ));
+        }
+        Ok(TablespaceKey {
+            key: key_info[..ENCRYPTION_KEY_LEN].try_into().unwrap(),
+            iv: key_info[ENCRYPTION_KEY_LEN..ENCRYPTION_KEY_LEN + AES_BLOCK_SIZE]
+                .try_into()
+                .unwrap(),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct TablespaceKey {
+    pub key: [u8; ENCRYPTION_KEY_LEN],
+    pub iv: [u8; AES_BLOCK_SIZE],
+}
+
+impl Debug for TablespaceKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TablespaceKey { .. }")
+    }
+}
+
+fn is_encrypted(buf: &[u8]) -> bool {
+    let page_type = u16::from_be_bytes([buf[FIL_PAGE_TYPE_OFFSET], buf[FIL_PAGE_TYPE_OFFSET + 1]]);
+    PageType::try_from(page_type).is_ok_and(|page_type| page_type.is_encrypted())
+}
+
+/// Decrypts an encrypted page in place, `buf` is left alone when the
+/// decrypted page fails its checksum which means the key is wrong.
+pub fn decrypt_page(buf: &mut [u8], key: &TablespaceKey) -> Result<()> {
+    let size = PageSize::from_len(buf.len()).ok_or(InnoDBError::InvalidLength)?;
+    let header = Page::from_bytes_with_size(buf, size)?.header;
+    let restored_type = match header.page_type {
+        PageType::Encrypted => {
+            u16::from_be_bytes([buf[FIL_PAGE_ORIGINAL_TYPE], buf[FIL_PAGE_ORIGINAL_TYPE + 1]])
+        }
+        PageType::EncryptedRtree => PageType::RTree as u16,
+        PageType::CompressedAndEncrypted => {
+            return Err(anyhow!(
+                "Page {} of space {} is compressed, transparent page compression is not supported",
+                header.offset,
+                header.space_id
+            ))
+        }
+        _ => return Ok(()),
+    };
+
+    let mut page = buf.to_vec();
+    let decrypt_cbc = |buf: &mut [u8]| {
+        cbc::Decryptor::<Aes256>::new(&key.key.into(), &key.iv.into())
+            .decrypt_padded_mut::<NoPadding>(buf)
+            .map(|_| ())
+            .map_err(|_| anyhow!(InnoDBError::InvalidLength))
+    };
+    let data = &mut page[FIL_HEADER_SIZE..];
+    // A tail shorter than a block was encrypted a second time together
+    // with the end of the last whole block
+    let main_len = data.len() - data.len() % AES_BLOCK_SIZE;
+    if main_len != data.len() {
+        let tail = data.len() - 2 * AES_BLOCK_SIZE;
+        decrypt_cbc(&mut data[tail..])?;
+    }
+    decrypt_cbc(&mut data[..main_len])?;
+    page[FIL_PAGE_TYPE_OFFSET..FIL_PAGE_TYPE_OFFSET + 2]
+        .copy_from_slice(&restored_type.to_be_bytes());
+    page[FIL_PAGE_ORIGINAL_TYPE..FIL_PAGE_ORIGINAL_TYPE + 2].fill(0);
+
+    let policy = ChecksumPolicy { allow_none: true };
+    if Page::from_bytes_with_size(&page, size)?
+        .validate_checksum(policy)
+        .is_none()
+    {
+        return Err(anyhow!(InnoDBError::InvalidChecksum)).context(format!(
+            "Page {} of space {} fails its checksum after decryption, wrong key or corrupted page",
+            header.offset, header.space_id
+        ));
+    }
+    buf.copy_from_slice(&page);
+    Ok(())
+}
+
+/// Where master keys come from
+#[derive(Debug, Clone)]
+pub enum MasterKey {
+    /// Looked up by the id and server in the encryption info
+    Keyring(Keyring),
+    /// Used for whatever master key a tablespace asks for
+    Raw([u8; ENCRYPTION_KEY_LEN]),
+}
+
+impl MasterKey {
+    pub fn from_hex(hex: &str) -> Result<MasterKey> {
+        let hex = hex.trim();
+        if hex.len() != 2 * ENCRYPTION_KEY_LEN || !hex.is_ascii() {
+            return Err(anyhow!(
+                "A master key is {} hex digits",
+                2 * ENCRYPTION_KEY_LEN
+            ));
+        }
+        let mut key = [0u8; ENCRYPTION_KEY_LEN];
+        for (idx, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16)
+                .map_err(|e| anyhow!("Invalid master key: {}", e))?;
+        }
+        Ok(MasterKey::Raw(key))
+    }
+
+    fn get(&self, info: &EncryptionInfo) -> Result<[u8; ENCRYPTION_KEY_LEN]> {
+        match self {
+            MasterKey::Raw(key) => Ok(*key),
+            MasterKey::Keyring(keyring) => keyring
+                .master_key(&info.server_uuid, info.master_key_id)
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Master key {} of server {} is not in the keyring",
+                        info.master_key_id,
+                        info.server_uuid
+                    )
+                }),
+        }
+    }
+}
+
+/// Decrypts pages as buffer managers load them, the key of each
+/// tablespace is read from its page 0 the first time it's needed.
+#[derive(Debug)]
+pub struct PageDecryptor {
+    master_key: Option<MasterKey>,
+    tablespace_keys: RwLock<HashMap<u32, TablespaceKey>>,
+}
+
+impl PageDecryptor {
+    pub fn new(master_key: MasterKey) -> Self {
+        PageDecryptor {
+            master_key: Some(master_key),
+            tablespace_keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Decrypts with tablespace keys that are already known, without a
+    /// master key only the spaces added here can be decrypted
+    pub fn from_tablespace_keys<I>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = (u32, TablespaceKey)>,
+    {
+        PageDecryptor {
+            master_key: None,
+            tablespace_keys: RwLock::new(keys.into_iter().collect()),
+        }
+    }
+
+    /// Uses `key` for `space_id` instead of reading it from page 0
+    pub fn add_tablespace_key(&self, space_id: u32, key: TablespaceKey) {
+        self.tablespace_keys.write().unwrap().insert(space_id, key);
+    }
+
+    /// Loads the tablespace key from page 0, false when the tablespace
+    /// isn't encrypted
+    pub fn add_space(&self, first_page: &[u8]) -> Result<bool> {
+        let size = PageSize::from_len(first_page.len()).ok_or(InnoDBError::InvalidLength)?;
+        let page = Page::from_bytes_with_size(first_page, size)?;
+        let Some(info) = EncryptionInfo::try_from_page(&page)? else {
+            return Ok(false);
+        };
+        let master_key = self.master_key.as_ref().ok_or_else(|| {
+            anyhow!(
+                "Tablespace {} is encrypted and there is no master key for it",
+                page.header.space_id
+            )
+        })?;
+        let key = info
+            .tablespace_key(&master_key.get(&info)?)
+            .with_context(|| format!("Tablespace {}", page.header.space_id))?;
+        self.tablespace_keys
+            .write()
+            .unwrap()
+            .insert(page.header.space_id, key);
+        Ok(true)
+    }
+
+    /// Decrypts `buf` in place if it holds an encrypted page, `first_page`
+    /// reads page 0 of a space whose key isn't known yet.
+    pub fn decrypt_page<F>(&self, buf: &mut [u8], first_page: F) -> Result<()>
+    where
+        F: FnOnce(u32) -> Result<Vec<u8>>,
+    {
+        if buf.len() < FIL_HEADER_SIZE || !is_encrypted(buf) {
+            return Ok(());
+        }
+        let space_id = u32::from_be_bytes(
+            buf[FIL_PAGE_SPACE_ID_OFFSET..FIL_PAGE_SPACE_ID_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        );
+        if !self.tablespace_keys.read().unwrap().contains_key(&space_id)
+            && !self.add_space(&first_page(space_id)?)?
+        {
+            return Err(anyhow!(
+                "Space {} has encrypted pages but no encryption info",
+                space_id
+            ));
+        }
+        decrypt_page(buf, &self.tablespace_keys.read().unwrap()[&space_id])
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        page::{Page, PageSize, PageType, FIL_PAGE_SIZE},
+        test_data, InnoDBError,
+    };
+
+    use super::{
+        encryption_info_offset, keyring::Keyring, EncryptionInfo, MasterKey, PageDecryptor,
+    };
+
+    fn master_key() -> [u8; 32] {
+        std::array::from_fn(|i| 0x40 + i as u8)
+    }
+
+    #[test]
+    fn test_encryption_info_offset() {
+        assert_eq!(encryption_info_offset(PageSize::Size16K), 10390);
+        assert_eq!(encryption_info_offset(PageSize::Size8K), 3734);
+        assert_eq!(encryption_info_offset(PageSize::Size4K), 1558);
+    }
+
+    #[test]
+    fn test_encryption_info() {
+        let data = test_data("encrypted_sample.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        let info = EncryptionInfo::try_from_page(&page).unwrap().unwrap();
+        assert_eq!(info.version, 3);
+        assert_eq!(info.master_key_id, 1);
+        assert_eq!(info.server_uuid, "8a94f357-aab4-11df-86ab-c80aa9429562");
+        assert_eq!(info.magic(), b"lCC");
+        assert_eq!(info.wrapped_key().len(), 32);
+        assert_eq!(info.wrapped_iv().len(), 32);
+        let offset = encryption_info_offset(PageSize::Size16K);
+        assert_eq!(info.wrapped_key(), &data[offset + 43..offset + 75]);
+        assert_eq!(
+            info.checksum().to_be_bytes(),
+            data[offset + 107..offset + 111]
+        );
+        assert!(EncryptionInfo::try_from_bytes(&data[..4096], PageSize::Size16K).is_err());
+        let key = info.tablespace_key(&master_key()).unwrap();
+        assert_eq!(key.key[..3], [3, 10, 17]);
+        assert!(info.tablespace_key(&[0u8; 32]).is_err());
+
+        let plain = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&plain[..FIL_PAGE_SIZE]).unwrap();
+        assert_eq!(EncryptionInfo::try_from_page(&page).unwrap(), None);
+    }
+
+    #[test]
+    fn test_decrypt_tablespace() {
+        let plain = test_data("float_sample.ibd");
+        let mut data = test_data("encrypted_sample.ibd");
+        let keyring = Keyring::from_bytes(&test_data("encrypted_keyring")).unwrap();
+        let decryptor = PageDecryptor::new(MasterKey::Keyring(keyring));
+        let first_page = data[..FIL_PAGE_SIZE].to_vec();
+
+        let index = &mut data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE];
+        assert_eq!(
+            Page::from_bytes(index).unwrap().header.page_type,
+            PageType::Encrypted
+        );
+        decryptor
+            .decrypt_page(index, |space_id| {
+                assert_eq!(space_id, 351);
+                Ok(first_page.clone())
+            })
+            .unwrap();
+        assert_eq!(index, &plain[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]);
+
+        // The key is known now, unencrypted pages are left alone
+        for page in data.chunks_exact_mut(FIL_PAGE_SIZE).skip(1) {
+            decryptor
+                .decrypt_page(page, |_| panic!("Key was loaded"))
+                .unwrap();
+        }
+        assert_eq!(data[FIL_PAGE_SIZE..], plain[FIL_PAGE_SIZE..]);
+    }
+
+    #[test]
+    fn test_decrypt_with_tablespace_key() {
+        let plain = test_data("float_sample.ibd");
+        let data = test_data("encrypted_sample.ibd");
+        let key = EncryptionInfo::try_from_page(&Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap())
+            .unwrap()
+            .unwrap()
+            .tablespace_key(&master_key())
+            .unwrap();
+
+        let decryptor = PageDecryptor::from_tablespace_keys([(351, key)]);
+        let mut index = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        decryptor
+            .decrypt_page(&mut index, |_| panic!("Key was given"))
+            .unwrap();
+        assert_eq!(index, plain[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]);
+
+        // Other spaces need page 0 and a master key
+        let mut other = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        other[34..38].copy_from_slice(&352u32.to_be_bytes());
+        let err = decryptor
+            .decrypt_page(&mut other, |_| Ok(data[..FIL_PAGE_SIZE].to_vec()))
+            .unwrap_err();
+        assert!(err.to_string().contains("no master key"), "{err}");
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let data = test_data("encrypted_sample.ibd");
+        let wrong = PageDecryptor::new(MasterKey::Raw([0u8; 32]));
+        let mut page = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        let err = wrong
+            .decrypt_page(&mut page, |_| Ok(data[..FIL_PAGE_SIZE].to_vec()))
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("wrong master key"), "{err:?}");
+
+        // A tablespace key that gets past page 0 but not the page checksum
+        let mut key =
+            EncryptionInfo::try_from_page(&Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap())
+                .unwrap()
+                .unwrap()
+                .tablespace_key(&master_key())
+                .unwrap();
+        key.key[0] ^= 1;
+        let err = super::decrypt_page(&mut page, &key).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&InnoDBError::InvalidChecksum));
+        assert!(err.to_string().contains("wrong key"), "{err}");
+        assert_eq!(page, data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]);
+    }
+
+    #[test]
+    fn test_master_key_from_hex() {
+        let hex: String = master_key().iter().map(|b| format!("{b:02x}")).collect();
+        match MasterKey::from_hex(&hex).unwrap() {
+            MasterKey::Raw(key) => assert_eq!(key, master_key()),
+            other => panic!("{other:?}"),
+        }
+        assert!(MasterKey::from_hex("abcd").is_err());
+        assert!(MasterKey::from_hex(&"zz".repeat(32)).is_err());
+    }
+}
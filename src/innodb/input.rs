@@ -0,0 +1,43 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Input path that reads from stdin instead of a file
+pub const STDIN_PATH: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+/// Opens the input of a binary, `-` reads stdin. Stdin can't seek, tools that
+/// need to seek in their input have to buffer it themselves.
+pub fn open_input(path: &Path) -> io::Result<Box<dyn Read>> {
+    if is_stdin(path) {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::read, io::Read, path::PathBuf};
+
+    use crate::innodb::test_data_path;
+
+    use super::{is_stdin, open_input};
+
+    #[test]
+    fn test_open_input_file() {
+        let path = test_data_path("t_empty.ibd");
+        let mut data = Vec::new();
+        open_input(&path).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, read(&path).unwrap());
+
+        assert!(is_stdin(&PathBuf::from("-")));
+        assert!(!is_stdin(&PathBuf::from("./-")));
+        assert!(open_input(&PathBuf::from("does/not/exist")).is_err());
+    }
+}
@@ -1,8 +1,21 @@
+pub mod btree;
 pub mod buffer_manager;
 pub mod charset;
+pub mod doublewrite;
+pub mod encryption;
 pub mod file_list;
+pub mod input;
 pub mod page;
+pub mod scan;
+pub mod sdi;
 pub mod table;
+pub mod tablespace;
+#[doc(hidden)]
+pub mod testing;
+pub mod undo;
+
+#[doc(hidden)]
+pub use testing::{test_data, test_data_path};
 
 use std::{
     error::Error,
@@ -17,7 +30,24 @@ pub enum InnoDBError {
     InvalidChecksum,
     InvalidPage,
     PageNotFound,
-    InvalidPageType { expected: PageType, has: PageType },
+    InvalidPageType {
+        expected: PageType,
+        has: PageType,
+    },
+    InvalidRecordOffset {
+        offset: usize,
+        next: Option<u16>,
+    },
+    /// The FIL header holds another (space_id, offset) than the position
+    /// the page was read from
+    PagePositionMismatch {
+        expected: (u32, u32),
+        has: (u32, u32),
+    },
+    /// Every frame of the buffer manager holds a pinned page
+    BufferExhausted,
+    /// The table definition has no cluster (primary key) columns
+    MissingPrimaryKey,
 }
 
 impl Display for InnoDBError {
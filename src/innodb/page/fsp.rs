@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Result};
+
+use crate::innodb::{
+    buffer_manager::BufferManager, encryption::EncryptionInfo, file_list::FileListBaseNode,
+    InnoDBError,
+};
+
+use super::{
+    xdes::{XdesEntry, XdesListEntries},
+    FILHeader, Page, PageSize, PageType, FIL_PAGE_BODY_OFFSET,
+};
+
+/// Length of the tablespace header, the XDES array starts right after it
+pub const FSP_HEADER_SIZE: usize = 112;
+
+const FSP_FLAGS_POST_ANTELOPE: u32 = 1;
+const FSP_FLAGS_POS_ZIP_SSIZE: u32 = 1;
+const FSP_FLAGS_MASK_ZIP_SSIZE: u32 = 0xF << FSP_FLAGS_POS_ZIP_SSIZE;
+const FSP_FLAGS_ATOMIC_BLOBS: u32 = 1 << 5;
+const FSP_FLAGS_DATA_DIR: u32 = 1 << 10;
+const FSP_FLAGS_SHARED: u32 = 1 << 11;
+const FSP_FLAGS_TEMPORARY: u32 = 1 << 12;
+const FSP_FLAGS_ENCRYPTION: u32 = 1 << 13;
+const FSP_FLAGS_SDI: u32 = 1 << 14;
+
+/// FSP_SPACE_FLAGS, describing the format of every page in the tablespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FspFlags(pub u32);
+
+impl FspFlags {
+    /// Compact or dynamic rows are possible, always set since 5.7
+    pub fn post_antelope(&self) -> bool {
+        self.0 & FSP_FLAGS_POST_ANTELOPE != 0
+    }
+
+    /// Size of the compressed pages as a shift of 512, 0 when the pages
+    /// aren't compressed
+    pub fn zip_ssize(&self) -> u32 {
+        (self.0 & FSP_FLAGS_MASK_ZIP_SSIZE) >> FSP_FLAGS_POS_ZIP_SSIZE
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.zip_ssize() != 0
+    }
+
+    /// Externally stored columns keep no prefix in the record
+    pub fn atomic_blobs(&self) -> bool {
+        self.0 & FSP_FLAGS_ATOMIC_BLOBS != 0
+    }
+
+    /// Created with DATA DIRECTORY outside of the data directory
+    pub fn data_dir(&self) -> bool {
+        self.0 & FSP_FLAGS_DATA_DIR != 0
+    }
+
+    /// A general tablespace shared by several tables
+    pub fn shared(&self) -> bool {
+        self.0 & FSP_FLAGS_SHARED != 0
+    }
+
+    pub fn temporary(&self) -> bool {
+        self.0 & FSP_FLAGS_TEMPORARY != 0
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.0 & FSP_FLAGS_ENCRYPTION != 0
+    }
+
+    pub fn has_sdi(&self) -> bool {
+        self.0 & FSP_FLAGS_SDI != 0
+    }
+
+    /// Size of the pages in the buffer pool
+    pub fn logical_page_size(&self) -> Result<PageSize> {
+        PageSize::from_fsp_flags(self.0)
+    }
+
+    /// Bytes per page in the file, less than the logical size for
+    /// compressed tablespaces
+    pub fn physical_page_size(&self) -> Result<usize> {
+        match self.zip_ssize() {
+            0 => Ok(self.logical_page_size()?.bytes()),
+            ssize @ 1..=5 => Ok(512 << ssize),
+            ssize => Err(anyhow!(
+                "Unsupported compressed page size shift {} in FSP flags",
+                ssize
+            )),
+        }
+    }
+}
+
+/// Tablespace header at the start of page 0
+#[derive(Debug, Clone)]
+pub struct FspHeaderPage {
+    pub space_id: u32,
+    /// Size of the tablespace in pages
+    pub size: u32,
+    /// Pages from here on haven't been initialized yet
+    pub free_limit: u32,
+    pub flags: FspFlags,
+    /// Used pages in the FSP_FREE_FRAG list
+    pub frag_n_used: u32,
+    /// Extents with no page in use
+    pub free: FileListBaseNode,
+    /// Extents with some pages handed out as fragment pages
+    pub free_frag: FileListBaseNode,
+    /// Fragment extents with every page in use
+    pub full_frag: FileListBaseNode,
+    /// Next file segment id to hand out
+    pub seg_id: u64,
+    /// INODE pages without a free slot
+    pub seg_inodes_full: FileListBaseNode,
+    /// INODE pages with a free slot
+    pub seg_inodes_free: FileListBaseNode,
+    /// Encryption info of an encrypted tablespace, `None` when it isn't
+    /// encrypted or only the start of page 0 was parsed
+    pub encryption: Option<EncryptionInfo>,
+}
+
+impl FspHeaderPage {
+    pub fn try_from_page(page: &Page) -> Result<Self> {
+        Self::try_from_bytes(page.raw_data)
+    }
+
+    /// `buf` only needs to hold the start of page 0, the header is at the
+    /// same place for every page size. The encryption info is only read
+    /// when `buf` is all of page 0.
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
+        let header = FILHeader::from_bytes(buf)?;
+        if header.page_type != PageType::FspHdr {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::FspHdr,
+                has: header.page_type
+            }));
+        }
+        let page = buf;
+        let buf = buf
+            .get(FIL_PAGE_BODY_OFFSET..FIL_PAGE_BODY_OFFSET + FSP_HEADER_SIZE)
+            .ok_or(InnoDBError::InvalidLength)?;
+        let word = |at: usize| u32::from_be_bytes(buf[at..at + 4].try_into().unwrap());
+        let flags = FspFlags(word(16));
+        let encryption = match flags.physical_page_size().map(PageSize::from_len) {
+            Ok(Some(size)) if page.len() >= size.bytes() => {
+                EncryptionInfo::try_from_bytes(page, size)?
+            }
+            _ => None,
+        };
+        Ok(FspHeaderPage {
+            space_id: word(0),
+            size: word(8),
+            free_limit: word(12),
+            flags,
+            frag_n_used: word(20),
+            free: FileListBaseNode::try_from_bytes(&buf[24..])?,
+            free_frag: FileListBaseNode::try_from_bytes(&buf[40..])?,
+            full_frag: FileListBaseNode::try_from_bytes(&buf[56..])?,
+            seg_id: u64::from_be_bytes(buf[72..80].try_into().unwrap()),
+            seg_inodes_full: FileListBaseNode::try_from_bytes(&buf[80..])?,
+            seg_inodes_free: FileListBaseNode::try_from_bytes(&buf[96..])?,
+            encryption,
+        })
+    }
+
+    pub fn logical_page_size(&self) -> Result<PageSize> {
+        self.flags.logical_page_size()
+    }
+
+    pub fn physical_page_size(&self) -> Result<usize> {
+        self.flags.physical_page_size()
+    }
+
+    pub fn has_sdi(&self) -> bool {
+        self.flags.has_sdi()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags.is_encrypted()
+    }
+
+    /// Descriptors of the extents on one of the lists of this header,
+    /// loaded from the FSP_HDR and XDES pages through `buffer_mgr`
+    pub fn extents<'a>(
+        &self,
+        list: &FileListBaseNode,
+        buffer_mgr: &'a dyn BufferManager,
+    ) -> XdesListEntries<'a> {
+        XdesListEntries::new(list, self.space_id, buffer_mgr)
+    }
+
+    /// Descriptors of the extents on the FREE, FREE_FRAG and FULL_FRAG
+    /// lists, in that order. Extents of file segments are on the lists of
+    /// their inode instead.
+    pub fn iter_extents<'a>(
+        &self,
+        buffer_mgr: &'a dyn BufferManager,
+    ) -> impl Iterator<Item = Result<XdesEntry>> + 'a {
+        self.extents(&self.free, buffer_mgr)
+            .chain(self.extents(&self.free_frag, buffer_mgr))
+            .chain(self.extents(&self.full_frag, buffer_mgr))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::page::{Page, PageSize, FIL_PAGE_SIZE};
+
+    use crate::innodb::test_data;
+
+    use super::{FspFlags, FspHeaderPage};
+
+    #[test]
+    fn test_fsp_header() {
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        let fsp = FspHeaderPage::try_from_page(&page).unwrap();
+        assert_eq!(fsp.space_id, 351);
+        assert_eq!(fsp.size, 7);
+        assert_eq!(fsp.free_limit, 64);
+        assert_eq!(fsp.frag_n_used, 5);
+        assert_eq!(fsp.seg_id, 5);
+        assert_eq!(fsp.free.list_len, 0);
+        assert!(fsp.free.first_node.is_null());
+        assert_eq!(fsp.free_frag.list_len, 1);
+        assert_eq!(fsp.free_frag.first_node.offset, 158);
+        assert_eq!(fsp.seg_inodes_free.list_len, 1);
+        assert_eq!(fsp.seg_inodes_free.first_node.page_number, 2);
+
+        assert!(fsp.flags.post_antelope());
+        assert!(fsp.flags.atomic_blobs());
+        assert!(fsp.has_sdi());
+        assert!(!fsp.is_encrypted());
+        assert!(!fsp.flags.is_compressed());
+        assert_eq!(fsp.logical_page_size().unwrap(), PageSize::Size16K);
+        assert_eq!(fsp.physical_page_size().unwrap(), FIL_PAGE_SIZE);
+
+        // Not page 0, or too short for the header
+        assert!(FspHeaderPage::try_from_bytes(&data[4 * FIL_PAGE_SIZE..]).is_err());
+        assert!(FspHeaderPage::try_from_bytes(&data[..100]).is_err());
+        assert!(fsp.encryption.is_none());
+    }
+
+    #[test]
+    fn test_encryption_info() {
+        let data = test_data("encrypted_sample.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        let encryption = FspHeaderPage::try_from_page(&page)
+            .unwrap()
+            .encryption
+            .unwrap();
+        assert_eq!(encryption.magic(), b"lCC");
+        assert_eq!(encryption.master_key_id, 1);
+
+        // The encryption info is past the first 4K of a 16K page
+        let head = FspHeaderPage::try_from_bytes(&data[..4096]).unwrap();
+        assert!(head.is_encrypted());
+        assert!(head.encryption.is_none());
+    }
+
+    #[test]
+    fn test_fsp_flags() {
+        let fsp = FspHeaderPage::try_from_bytes(&test_data("float_sample_8k.ibd")).unwrap();
+        assert_eq!(fsp.logical_page_size().unwrap(), PageSize::Size8K);
+        let fsp = FspHeaderPage::try_from_bytes(&test_data("encrypted_sample.ibd")).unwrap();
+        assert!(fsp.is_encrypted());
+
+        // 16K tablespace of 8K compressed pages
+        let compressed = FspFlags(0x21 | 4 << 1);
+        assert!(compressed.is_compressed());
+        assert_eq!(compressed.physical_page_size().unwrap(), 8192);
+        assert_eq!(compressed.logical_page_size().unwrap(), PageSize::Size16K);
+        assert!(FspFlags(7 << 1).physical_page_size().is_err());
+        assert!(!FspFlags(0).has_sdi());
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result};
+
+use crate::innodb::InnoDBError;
+
+use super::{Page, PageSize, PageType, FIL_PAGE_SIZE};
+
+/// The bitmap starts where the index page records would
+const IBUF_BITMAP_OFFSET: usize = 94;
+const IBUF_BITS_PER_PAGE: usize = 4;
+
+/// Pages described by one bitmap page of the default size, every group
+/// starts with a header or descriptor page followed by its bitmap page.
+/// Groups are always as many pages as a page has bytes.
+pub const IBUF_BITMAP_PAGES: u32 = FIL_PAGE_SIZE as u32;
+
+/// Change buffer state of a single page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IbufPageBits {
+    /// Free space bucket: 0 is under 1/32 of the page, 1 under 1/16,
+    /// 2 under 1/8 and 3 anything above that
+    pub free_space: u8,
+    /// Changes for this page are waiting in the change buffer
+    pub buffered: bool,
+    /// The page belongs to the change buffer itself
+    pub ibuf: bool,
+}
+
+impl IbufPageBits {
+    /// Lower bound of the free space bucket in bytes
+    pub fn min_free_bytes(&self, size: PageSize) -> usize {
+        match self.free_space {
+            0 => 0,
+            bucket => (size.bytes() / 32) << (bucket - 1),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct IbufBitmap<'a> {
+    pub page: Page<'a>,
+}
+
+impl<'a> IbufBitmap<'a> {
+    pub fn try_from_page(page: Page<'a>) -> Result<Self> {
+        if page.header.page_type != PageType::IbufBitmap {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::IbufBitmap,
+                has: page.header.page_type
+            }));
+        }
+        Ok(IbufBitmap { page })
+    }
+
+    /// Number of pages in the group this bitmap describes
+    pub fn group_pages(&self) -> u32 {
+        self.page.size().bytes() as u32
+    }
+
+    /// First page of the group this bitmap describes
+    pub fn first_page(&self) -> u32 {
+        self.page.header.offset - self.page.header.offset % self.group_pages()
+    }
+
+    /// Bits of page `offset`, `None` when it belongs to another group
+    pub fn get(&self, offset: u32) -> Option<IbufPageBits> {
+        let index = offset.checked_sub(self.first_page())?;
+        if index >= self.group_pages() {
+            return None;
+        }
+        let bit_offset = index as usize * IBUF_BITS_PER_PAGE;
+        let byte = self.page.raw_data[IBUF_BITMAP_OFFSET + bit_offset / 8];
+        let bit = |n: usize| (byte >> (bit_offset % 8 + n)) & 1;
+        Some(IbufPageBits {
+            // The first bit is the high bit of the bucket
+            free_space: (bit(0) << 1) | bit(1),
+            buffered: bit(2) != 0,
+            ibuf: bit(3) != 0,
+        })
+    }
+
+    /// Bits of every page in the group
+    pub fn entries(&self) -> impl Iterator<Item = (u32, IbufPageBits)> + '_ {
+        let first = self.first_page();
+        (first..first + self.group_pages()).map(|offset| (offset, self.get(offset).unwrap()))
+    }
+
+    /// Pages with changes still in the change buffer, their copy in the
+    /// tablespace is missing those writes
+    pub fn buffered_pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entries()
+            .filter(|(_, bits)| bits.buffered)
+            .map(|(offset, _)| offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::page::{Page, PageSize, FIL_PAGE_SIZE};
+
+    use crate::innodb::test_data;
+
+    use super::{IbufBitmap, IbufPageBits, IBUF_BITMAP_PAGES};
+
+    fn bitmap_page() -> Vec<u8> {
+        let data = test_data("float_sample.ibd");
+        data[FIL_PAGE_SIZE..2 * FIL_PAGE_SIZE].to_vec()
+    }
+
+    #[test]
+    fn test_ibuf_bitmap_fixture() {
+        let buf = bitmap_page();
+        let bitmap = IbufBitmap::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(bitmap.first_page(), 0);
+        // Nothing is buffered in a freshly created tablespace
+        for offset in 0..7 {
+            assert_eq!(bitmap.get(offset), Some(IbufPageBits::default()));
+        }
+        assert_eq!(bitmap.entries().count(), IBUF_BITMAP_PAGES as usize);
+        assert_eq!(bitmap.get(IBUF_BITMAP_PAGES), None);
+        assert_eq!(bitmap.buffered_pages().count(), 0);
+    }
+
+    #[test]
+    fn test_ibuf_bitmap_bits() {
+        let mut buf = bitmap_page();
+        // Page 4 gets the low nibble and page 5 the high nibble of byte 2
+        buf[94 + 2] = 0b1001_0110;
+        let bitmap = IbufBitmap::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        let page_4 = bitmap.get(4).unwrap();
+        assert_eq!(
+            page_4,
+            IbufPageBits {
+                free_space: 1,
+                buffered: true,
+                ibuf: false
+            }
+        );
+        assert_eq!(page_4.min_free_bytes(PageSize::Size16K), 512);
+        assert_eq!(page_4.min_free_bytes(PageSize::Size4K), 128);
+        let page_5 = bitmap.get(5).unwrap();
+        assert_eq!(
+            page_5,
+            IbufPageBits {
+                free_space: 2,
+                buffered: false,
+                ibuf: true
+            }
+        );
+        assert_eq!(page_5.min_free_bytes(PageSize::Size16K), 1024);
+        assert_eq!(bitmap.get(3), Some(IbufPageBits::default()));
+        assert_eq!(bitmap.buffered_pages().collect::<Vec<_>>(), [4]);
+    }
+
+    #[test]
+    fn test_ibuf_bitmap_8k() {
+        // Same bitmap on an 8K page, which only covers 8192 pages
+        let mut buf = bitmap_page();
+        buf.truncate(8192 - 8);
+        buf.extend_from_slice(&[0u8; 8]);
+        buf[4..8].copy_from_slice(&(8192u32 + 1).to_be_bytes());
+        buf[94 + 2] = 0b0110;
+        let bitmap =
+            IbufBitmap::try_from_page(Page::from_bytes_with_size(&buf, PageSize::Size8K).unwrap())
+                .unwrap();
+        assert_eq!(bitmap.group_pages(), 8192);
+        assert_eq!(bitmap.first_page(), 8192);
+        assert!(bitmap.get(8192 + 4).unwrap().buffered);
+        assert_eq!(bitmap.get(4), None);
+        assert_eq!(bitmap.entries().count(), 8192);
+    }
+
+    #[test]
+    fn test_ibuf_bitmap_wrong_type() {
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        assert!(IbufBitmap::try_from_page(page).is_err());
+    }
+}
@@ -1,12 +1,27 @@
 pub mod record;
 
+use std::{cmp::Ordering, fmt::Display, sync::Arc};
+
 use anyhow::{anyhow, Error, Result};
 use num_enum::TryFromPrimitive;
-use record::Record;
+use record::{Record, RecordType};
+
+use crate::innodb::{
+    buffer_manager::{BufferManager, PageGuard},
+    file_list::FIL_NULL,
+    table::{
+        field::{key_prefix_cmp, FieldValue},
+        row::Row,
+        TableDefinition,
+    },
+    InnoDBError,
+};
 
-use crate::innodb::InnoDBError;
+use super::{inode::FsegHeader, Page, PageType, FIL_PAGE_BODY_OFFSET, FIL_TRAILER_SIZE};
 
-use super::{Page, PageType};
+const PAGE_DIR_SLOT_SIZE: usize = 2;
+const PAGE_BTR_SEG_LEAF: usize = 36;
+const PAGE_BTR_SEG_TOP: usize = 46;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
@@ -25,6 +40,18 @@ pub enum PageDirection {
     NoDirection = 5,
 }
 
+impl Display for PageDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PageDirection::Left => "PAGE_LEFT",
+            PageDirection::Right => "PAGE_RIGHT",
+            PageDirection::SameRec => "PAGE_SAME_REC",
+            PageDirection::SamePage => "PAGE_SAME_PAGE",
+            PageDirection::NoDirection => "PAGE_NO_DIRECTION",
+        })
+    }
+}
+
 /*
  Actual Layout
  +----------------------------------------------+----------------------------------------------+
@@ -56,7 +83,8 @@ pub struct IndexHeader {
     pub first_garbage_record_offset: u16,
     pub garbage_space: u16,
     pub last_insert_position: u16,
-    pub page_direction: PageDirection,
+    pub page_direction: Option<PageDirection>, // None for unknown codes
+    pub page_direction_raw: u16,
     pub number_of_inserts_in_page_direction: u16,
     pub number_of_records: u16,
     pub maximum_transaction_id: u64,
@@ -71,6 +99,7 @@ impl IndexHeader {
         }
 
         let format_and_num_heap_records_raw = u16::from_be_bytes([data[4], data[5]]);
+        let page_direction_raw = u16::from_be_bytes([data[12], data[13]]);
 
         Ok(IndexHeader {
             number_of_directory_slots: u16::from_be_bytes([data[0], data[1]]),
@@ -84,9 +113,8 @@ impl IndexHeader {
             first_garbage_record_offset: u16::from_be_bytes([data[6], data[7]]),
             garbage_space: u16::from_be_bytes([data[8], data[9]]),
             last_insert_position: u16::from_be_bytes([data[10], data[11]]),
-            page_direction: PageDirection::try_from_primitive(u16::from_be_bytes([
-                data[12], data[13],
-            ]))?,
+            page_direction: PageDirection::try_from_primitive(page_direction_raw).ok(),
+            page_direction_raw,
             number_of_inserts_in_page_direction: u16::from_be_bytes([data[14], data[15]]),
             number_of_records: u16::from_be_bytes([data[16], data[17]]),
             maximum_transaction_id: u64::from_be_bytes([
@@ -100,6 +128,70 @@ impl IndexHeader {
     }
 }
 
+/// A disagreement between the index header and the records on the page,
+/// found by [`IndexPage::verify`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageInconsistency {
+    /// The record or garbage list is broken, the error says where
+    BrokenList { garbage: bool, error: String },
+    /// Records owned by the directory slots don't add up to the list length
+    OwnedRecords { owned: usize, records: usize },
+    /// Records in the heap are neither in the record list nor garbage
+    HeapRecords { header: u16, found: usize },
+    /// Slot count in the header differs from the records owning a group
+    DirectorySlots { header: u16, owners: usize },
+    /// A record starts at or after the top of the heap
+    HeapTop { heap_top: u16, record: usize },
+    /// User record count in the header differs from the record list
+    RecordCount { header: u16, found: usize },
+    /// User record count in the header differs from the records owned by
+    /// the directory slots
+    DirectoryCount { header: u16, directory: usize },
+}
+
+impl Display for PageInconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageInconsistency::BrokenList { garbage, error } => write!(
+                f,
+                "{} list is broken: {}",
+                if *garbage { "Garbage" } else { "Record" },
+                error
+            ),
+            PageInconsistency::OwnedRecords { owned, records } => write!(
+                f,
+                "Directory owns {} records but the list has {}",
+                owned, records
+            ),
+            PageInconsistency::HeapRecords { header, found } => write!(
+                f,
+                "Heap has {} records but {} are listed or garbage",
+                header, found
+            ),
+            PageInconsistency::DirectorySlots { header, owners } => write!(
+                f,
+                "Header has {} directory slots but {} records own a group",
+                header, owners
+            ),
+            PageInconsistency::HeapTop { heap_top, record } => write!(
+                f,
+                "Record at {} is past the heap top at {}",
+                record, heap_top
+            ),
+            PageInconsistency::RecordCount { header, found } => write!(
+                f,
+                "Header counts {} records but the list has {}",
+                header, found
+            ),
+            PageInconsistency::DirectoryCount { header, directory } => write!(
+                f,
+                "Header counts {} records but the directory owns {}",
+                header, directory
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct IndexPage<'a> {
     pub page: Page<'a>,
@@ -121,15 +213,1172 @@ impl<'a> IndexPage<'a> {
         })
     }
 
-    pub fn record_at(&self, offset: usize) -> Result<Record> {
-        Record::try_from_offset(self.page.raw_data, offset)
+    /// Like [`IndexPage::try_from_page`] but leaves `page` usable, only the
+    /// headers are copied and the buffer stays shared. Works on a
+    /// [`PageGuard`](crate::innodb::buffer_manager::PageGuard) through deref.
+    pub fn try_from_page_ref(page: &Page<'a>) -> Result<Self> {
+        Self::try_from_page(Page {
+            header: page.header.clone(),
+            trailer: page.trailer.clone(),
+            raw_data: page.raw_data,
+        })
+    }
+
+    pub fn record_at(&self, offset: usize) -> Result<Record<'_>> {
+        match self.index_header.format {
+            IndexFormat::Compact => Record::try_from_offset(self.page.raw_data, offset),
+            IndexFormat::Redundant => Record::try_from_offset_redundant(
+                self.page.raw_data,
+                offset,
+                self.index_header.page_level == 0,
+            ),
+        }
+    }
+
+    /// FSEG header of the leaf pages, only set on the root page
+    pub fn leaf_segment(&self) -> Result<FsegHeader> {
+        FsegHeader::try_from_bytes(&self.page.body()[PAGE_BTR_SEG_LEAF..])
+    }
+
+    /// FSEG header of the non-leaf pages, only set on the root page
+    pub fn non_leaf_segment(&self) -> Result<FsegHeader> {
+        FsegHeader::try_from_bytes(&self.page.body()[PAGE_BTR_SEG_TOP..])
+    }
+
+    /// The root has no siblings and is the only page whose segment headers
+    /// are set, they point at an INODE page of the same space
+    pub fn is_root(&self) -> bool {
+        self.page.header.prev == FIL_NULL
+            && self.page.header.next == FIL_NULL
+            && self.non_leaf_segment().is_ok_and(|fseg| {
+                fseg.space_id == self.page.header.space_id
+                    && fseg.page_number != 0
+                    && fseg.page_number != FIL_NULL
+            })
+    }
+
+    pub fn infimum_offset(&self) -> usize {
+        match self.index_header.format {
+            IndexFormat::Compact => 99,
+            IndexFormat::Redundant => 101,
+        }
+    }
+
+    pub fn supremum_offset(&self) -> usize {
+        match self.index_header.format {
+            IndexFormat::Compact => 112,
+            IndexFormat::Redundant => 116,
+        }
+    }
+
+    pub fn infimum(&self) -> Result<Record<'_>> {
+        self.system_record(self.infimum_offset(), b"infimum\0")
+    }
+
+    pub fn supremum(&self) -> Result<Record<'_>> {
+        self.system_record(self.supremum_offset(), b"supremum")
+    }
+
+    fn system_record(&self, offset: usize, name: &[u8]) -> Result<Record<'_>> {
+        let data = &self.page.raw_data[offset..offset + name.len()];
+        if data != name {
+            return Err(anyhow!(
+                "Expected {:?} record at offset {} of {:?} page {}, found {:?}",
+                String::from_utf8_lossy(name.strip_suffix(b"\0").unwrap_or(name)),
+                offset,
+                self.index_header.format,
+                self.page.header.offset,
+                String::from_utf8_lossy(data)
+            ));
+        }
+        self.record_at(offset)
+    }
+
+    /// Follows the next pointer of `record`, which must be on this page
+    pub fn next_record(&self, record: &Record) -> Result<Record<'_>> {
+        self.record_at(record.next_offset()?)
+    }
+
+    /// Pins the page after this one on the same level, `None` at the end
+    pub fn next_sibling<'b>(
+        &self,
+        buffer_mgr: &'b dyn BufferManager,
+    ) -> Result<Option<PageGuard<'b>>> {
+        self.sibling(self.page.header.next, true, buffer_mgr)
+    }
+
+    /// Pins the page before this one on the same level, `None` at the start
+    pub fn prev_sibling<'b>(
+        &self,
+        buffer_mgr: &'b dyn BufferManager,
+    ) -> Result<Option<PageGuard<'b>>> {
+        self.sibling(self.page.header.prev, false, buffer_mgr)
+    }
+
+    /// Pins the sibling and checks it is on the same index and level, and
+    /// links back to this page
+    fn sibling<'b>(
+        &self,
+        offset: u32,
+        next: bool,
+        buffer_mgr: &'b dyn BufferManager,
+    ) -> Result<Option<PageGuard<'b>>> {
+        if offset == FIL_NULL {
+            return Ok(None);
+        }
+        let guard = buffer_mgr.pin(self.page.header.space_id, offset)?;
+        let (index_id, level) = {
+            let sibling = IndexPage::try_from_page_ref(&guard.page())?;
+            (
+                sibling.index_header.index_id,
+                sibling.index_header.page_level,
+            )
+        };
+        if index_id != self.index_header.index_id || level != self.index_header.page_level {
+            return Err(anyhow!(
+                "Sibling {} of page {} is on level {} of index {}, expected level {} of index {}",
+                offset,
+                self.page.header.offset,
+                level,
+                index_id,
+                self.index_header.page_level,
+                self.index_header.index_id
+            ));
+        }
+        let back = if next {
+            guard.header.prev
+        } else {
+            guard.header.next
+        };
+        if back != self.page.header.offset {
+            return Err(anyhow!(
+                "Sibling {} of page {} links back to {}",
+                offset,
+                self.page.header.offset,
+                back
+            ));
+        }
+        Ok(Some(guard))
+    }
+
+    /// Offset of the record owning directory slot `slot`, slot 0 is the infimum
+    pub fn directory_slot(&self, slot: usize) -> Option<u16> {
+        if slot >= self.index_header.number_of_directory_slots as usize {
+            return None;
+        }
+        let end = self.page.raw_data.len() - FIL_TRAILER_SIZE;
+        let pos = end
+            .checked_sub((slot + 1) * PAGE_DIR_SLOT_SIZE)
+            .filter(|pos| *pos >= FIL_PAGE_BODY_OFFSET)?;
+        Some(u16::from_be_bytes([
+            self.page.raw_data[pos],
+            self.page.raw_data[pos + 1],
+        ]))
+    }
+
+    /// All directory slots, stopping early if they would run into the headers
+    pub fn directory(&self) -> Vec<u16> {
+        (0..self.index_header.number_of_directory_slots as usize)
+            .map_while(|slot| self.directory_slot(slot))
+            .collect()
+    }
+
+    pub fn record_at_slot(&self, slot: usize) -> Result<Record<'_>> {
+        let offset = self.directory_slot(slot).ok_or_else(|| {
+            anyhow!(
+                "Directory slot {} out of range, page has {} slots",
+                slot,
+                self.index_header.number_of_directory_slots
+            )
+        })?;
+        self.record_at(offset as usize)
+    }
+
+    /// User records counted from the directory alone, adding up what every
+    /// slot owner owns less the infimum and supremum. This doesn't depend on
+    /// the record list or the header count, so it still works past a broken
+    /// link.
+    pub fn directory_record_count(&self) -> Result<usize> {
+        let mut owned = 0;
+        for slot in 0..self.index_header.number_of_directory_slots as usize {
+            owned += self.record_at_slot(slot)?.header.num_records_owned as usize;
+        }
+        owned
+            .checked_sub(2)
+            .ok_or_else(|| anyhow!("Directory owns only {} records", owned))
     }
 
-    pub fn infimum(&self) -> Result<Record> {
-        self.record_at(99)
+    /// Checks the directory fits above the heap and is bounded by the infimum
+    /// and supremum
+    pub fn validate_directory(&self) -> Result<()> {
+        let slots = self.index_header.number_of_directory_slots as usize;
+        if slots < 2 {
+            return Err(anyhow!("Page directory has only {} slots", slots));
+        }
+        let heap_top = self.index_header.heap_top_position as usize;
+        let fits = (self.page.raw_data.len() - FIL_TRAILER_SIZE)
+            .checked_sub(slots * PAGE_DIR_SLOT_SIZE)
+            .is_some_and(|directory_start| directory_start >= heap_top);
+        if !fits {
+            return Err(anyhow!(
+                "Page directory of {} slots overlaps the heap top at {}",
+                slots,
+                heap_top
+            ));
+        }
+
+        let directory = self.directory();
+        if directory[0] as usize != self.infimum_offset() {
+            return Err(anyhow!(
+                "First directory slot points to {} instead of the infimum",
+                directory[0]
+            ));
+        }
+        if directory[slots - 1] as usize != self.supremum_offset() {
+            return Err(anyhow!(
+                "Last directory slot points to {} instead of the supremum",
+                directory[slots - 1]
+            ));
+        }
+        Ok(())
+    }
+
+    /// Finds the record whose cluster key starts with `key`, first by binary
+    /// searching the directory then walking the owned group.
+    pub fn search(
+        &self,
+        key: &[FieldValue],
+        table: &Arc<TableDefinition>,
+    ) -> Result<Option<Record<'_>>> {
+        Ok(self.seek(key, table)?.1)
+    }
+
+    /// Finds the last record whose key is less than or equal to `key`, which
+    /// is the node pointer to follow on non-leaf pages
+    pub fn search_floor(
+        &self,
+        key: &[FieldValue],
+        table: &Arc<TableDefinition>,
+    ) -> Result<Option<Record<'_>>> {
+        let (less, equal) = self.seek(key, table)?;
+        Ok(equal.or(less))
+    }
+
+    /// Finds the last record whose key is strictly less than `key`. Used for
+    /// lower bounds, where records matching a key prefix can start in the
+    /// child of the previous node pointer.
+    pub fn search_lower(
+        &self,
+        key: &[FieldValue],
+        table: &Arc<TableDefinition>,
+    ) -> Result<Option<Record<'_>>> {
+        Ok(self.seek(key, table)?.0)
+    }
+
+    /// The last record before `key` and the record matching it
+    fn seek(
+        &self,
+        key: &[FieldValue],
+        table: &Arc<TableDefinition>,
+    ) -> Result<(Option<Record<'_>>, Option<Record<'_>>)> {
+        if self.index_header.format != IndexFormat::Compact {
+            return Err(anyhow!("Searching REDUNDANT pages is not supported"));
+        }
+        self.validate_directory()?;
+
+        // Node pointers only carry the key columns and the child page number
+        let table = if self.index_header.page_level == 0 {
+            table.clone()
+        } else {
+            Arc::new(table.key_definition())
+        };
+        let cmp = |record: &Record| -> Result<Ordering> {
+            // The leftmost node pointer of a level stands for minus infinity,
+            // its stored key can be stale
+            if record.header.info_flags.min_rec
+                && record.header.record_type == RecordType::NodePointer
+            {
+                return Ok(Ordering::Less);
+            }
+            let record_key = Row::try_from_record_and_table(record, &table)?.cluster_key()?;
+            key_prefix_cmp(&record_key, key)
+        };
+
+        // Slot owners are the largest record of their group, the infimum and
+        // supremum slots are never compared.
+        let mut low = 0;
+        let mut high = self.index_header.number_of_directory_slots as usize - 1;
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if cmp(&self.record_at_slot(mid)?)? == Ordering::Less {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        let mut record = self.record_at_slot(low)?;
+        let mut less = (low != 0).then(|| record.clone());
+        // Bounded by the heap size in case the list loops
+        for _ in 0..self.index_header.number_of_heap_records {
+            record = self.next_record(&record)?;
+            if record.header.record_type == RecordType::Supremum {
+                return Ok((less, None));
+            }
+            match cmp(&record)? {
+                Ordering::Less => less = Some(record.clone()),
+                Ordering::Equal => return Ok((less, Some(record))),
+                Ordering::Greater => return Ok((less, None)),
+            }
+        }
+        Err(anyhow!(
+            "Record list on page {} does not reach the supremum",
+            self.page.header.offset
+        ))
+    }
+
+    /// Walks the record list checking that cluster keys never decrease.
+    /// Returns the offset of the first record whose key is smaller than the
+    /// one before it, which points at a next pointer linking the wrong record.
+    pub fn verify_key_order(&self, table: &Arc<TableDefinition>) -> Result<Option<usize>> {
+        let table = if self.index_header.page_level == 0 {
+            table.clone()
+        } else {
+            Arc::new(table.key_definition())
+        };
+        let mut previous: Option<Vec<FieldValue>> = None;
+        for record in self.records() {
+            let record = record?;
+            // Stale key of the leftmost node pointer, see seek
+            if record.header.info_flags.min_rec
+                && record.header.record_type == RecordType::NodePointer
+            {
+                continue;
+            }
+            let key = Row::try_from_record_and_table(&record, &table)?.cluster_key()?;
+            if let Some(previous) = &previous {
+                if key_prefix_cmp(&key, previous)? == Ordering::Less {
+                    return Ok(Some(record.offset));
+                }
+            }
+            previous = Some(key);
+        }
+        Ok(None)
+    }
+
+    /// Cross checks the header against the record list, the directory and
+    /// the garbage list. An empty result means the page is consistent.
+    pub fn verify(&self) -> Vec<PageInconsistency> {
+        let mut report = Vec::new();
+        let header = &self.index_header;
+
+        let mut records = 0;
+        let mut user_records = 0;
+        let mut owned = 0;
+        let mut owners = 0;
+        let mut complete = true;
+        for record in self.all_records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    report.push(PageInconsistency::BrokenList {
+                        garbage: false,
+                        error: e.to_string(),
+                    });
+                    complete = false;
+                    break;
+                }
+            };
+            records += 1;
+            if !matches!(
+                record.header.record_type,
+                RecordType::Infimum | RecordType::Supremum
+            ) {
+                user_records += 1;
+            }
+            owned += record.header.num_records_owned as usize;
+            owners += (record.header.num_records_owned > 0) as usize;
+            if record.offset >= header.heap_top_position as usize {
+                report.push(PageInconsistency::HeapTop {
+                    heap_top: header.heap_top_position,
+                    record: record.offset,
+                });
+            }
+        }
+        // A second opinion on the header count, unlike the list counts
+        // below this is also available for a broken list
+        if let Ok(directory) = self.directory_record_count() {
+            if directory != header.number_of_records as usize {
+                report.push(PageInconsistency::DirectoryCount {
+                    header: header.number_of_records,
+                    directory,
+                });
+            }
+        }
+        // The counts of a partial list would only repeat the broken link
+        if !complete {
+            return report;
+        }
+
+        if owned != records {
+            report.push(PageInconsistency::OwnedRecords { owned, records });
+        }
+        if owners != header.number_of_directory_slots as usize {
+            report.push(PageInconsistency::DirectorySlots {
+                header: header.number_of_directory_slots,
+                owners,
+            });
+        }
+        if user_records != header.number_of_records as usize {
+            report.push(PageInconsistency::RecordCount {
+                header: header.number_of_records,
+                found: user_records,
+            });
+        }
+
+        match self.garbage_records().collect::<Result<Vec<_>>>() {
+            Ok(garbage) => {
+                let found = records + garbage.len();
+                if found != header.number_of_heap_records as usize {
+                    report.push(PageInconsistency::HeapRecords {
+                        header: header.number_of_heap_records,
+                        found,
+                    });
+                }
+            }
+            Err(e) => report.push(PageInconsistency::BrokenList {
+                garbage: true,
+                error: e.to_string(),
+            }),
+        }
+        report
+    }
+
+    /// Finds the record with heap number `heap_no`, 0 and 1 being the
+    /// infimum and supremum. Besides the record and garbage lists this also
+    /// walks from every directory slot owner, so records behind a broken link
+    /// are still found as long as a later slot owns them.
+    pub fn record_by_heap_no(&self, heap_no: u16) -> Result<Option<Record<'_>>> {
+        match heap_no {
+            0 => return self.infimum().map(Some),
+            1 => return self.supremum().map(Some),
+            _ if heap_no >= self.index_header.number_of_heap_records => return Ok(None),
+            _ => {}
+        }
+        let from_slots = self.directory().into_iter().map(|offset| Records {
+            pending: Some(self.record_at(offset as usize)),
+            include_system: true,
+            ..self.records()
+        });
+        let found = [self.all_records(), self.garbage_records()]
+            .into_iter()
+            .chain(from_slots)
+            // A broken link only ends the walk it was found on
+            .flat_map(|records| records.map_while(Result::ok))
+            .find(|record| record.header.order == heap_no);
+        Ok(found)
+    }
+
+    /// User records in list order, from after the infimum up to the supremum
+    pub fn records(&self) -> Records<'_> {
+        Records {
+            page: self,
+            pending: Some(self.infimum()),
+            include_system: false,
+            garbage: false,
+            visited: vec![0; self.page.raw_data.len().div_ceil(64)],
+            steps: 0,
+        }
+    }
+
+    /// Same as [`IndexPage::records`] but also yields the infimum and supremum
+    pub fn all_records(&self) -> Records<'_> {
+        Records {
+            include_system: true,
+            ..self.records()
+        }
+    }
+
+    /// Deleted records on the free list, starting from the page's garbage
+    /// offset. These are not purged yet and can still be parsed.
+    pub fn garbage_records(&self) -> Records<'_> {
+        let first = self.index_header.first_garbage_record_offset as usize;
+        Records {
+            pending: (first != 0).then(|| self.record_at(first)),
+            garbage: true,
+            ..self.records()
+        }
+    }
+}
+
+/// Iterator following the next record pointers of a page. A broken link or
+/// a loop is yielded as an error and ends the iteration.
+pub struct Records<'a> {
+    page: &'a IndexPage<'a>,
+    pending: Option<Result<Record<'a>>>,
+    include_system: bool,
+    // Following the garbage list, which ends on a zero next pointer
+    garbage: bool,
+    // Bitset of visited record offsets
+    visited: Vec<u64>,
+    steps: usize,
+}
+
+impl<'a> Records<'a> {
+    fn visit(&mut self, record: &Record) -> Result<()> {
+        let (word, bit) = (record.offset / 64, record.offset % 64);
+        if self.visited[word] & (1 << bit) != 0 {
+            return Err(anyhow!(
+                "Record list loops back to offset {} on page {}",
+                record.offset,
+                self.page.page.header.offset
+            ));
+        }
+        self.visited[word] |= 1 << bit;
+
+        // The list can never be longer than the heap, system records included
+        self.steps += 1;
+        let heap_records = self.page.index_header.number_of_heap_records as usize;
+        if self.steps > heap_records {
+            return Err(anyhow!(
+                "Record list on page {} is longer than the {} records in the heap",
+                self.page.page.header.offset,
+                heap_records
+            ));
+        }
+        Ok(())
     }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<Record<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self
+                .pending
+                .take()?
+                .and_then(|record| self.visit(&record).map(|_| record))
+            {
+                Ok(record) => record,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if self.garbage {
+                if matches!(
+                    record.header.record_type,
+                    RecordType::Infimum | RecordType::Supremum
+                ) {
+                    return Some(Err(anyhow!(
+                        "Garbage list on page {} reaches the {:?}",
+                        self.page.page.header.offset,
+                        record.header.record_type
+                    )));
+                }
+                if !record.is_list_end() {
+                    self.pending = Some(self.page.next_record(&record));
+                }
+                return Some(Ok(record));
+            }
+
+            match record.header.record_type {
+                RecordType::Supremum => {
+                    return self.include_system.then_some(Ok(record));
+                }
+                RecordType::Infimum => {
+                    self.pending = Some(self.page.next_record(&record));
+                    if self.include_system {
+                        return Some(Ok(record));
+                    }
+                }
+                _ => {
+                    self.pending = Some(self.page.next_record(&record));
+                    return Some(Ok(record));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+
+    use crate::innodb::{
+        buffer_manager::{memory::MemoryBufferManager, PageGuard},
+        file_list::FIL_NULL,
+        page::{Page, PageSize, FIL_PAGE_SIZE},
+        table::{
+            field::{Field, FieldType, FieldValue},
+            row::Row,
+            TableDefinition,
+        },
+        test_data,
+    };
+
+    use super::{
+        record::{Record, RecordType, RedundantHeader},
+        IndexFormat, IndexHeader, IndexPage, PageDirection, PageInconsistency,
+    };
+
+    fn fixture_page(file: &str, page_number: usize) -> Vec<u8> {
+        let data = test_data(file);
+        data[page_number * FIL_PAGE_SIZE..(page_number + 1) * FIL_PAGE_SIZE].to_vec()
+    }
+
+    #[test]
+    fn test_records_empty_page() {
+        let buf = fixture_page("t_empty.ibd", 3);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        assert_eq!(index.records().count(), 0);
+        let types = index
+            .all_records()
+            .map(|r| r.map(|r| r.header.record_type))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(types, vec![RecordType::Infimum, RecordType::Supremum]);
+    }
+
+    #[test]
+    fn test_records() {
+        let buf = fixture_page("float_sample.ibd", 4);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        let records = index.records().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records
+            .iter()
+            .all(|r| r.header.record_type == RecordType::Conventional));
+        assert_eq!(index.all_records().count(), 4);
+    }
+
+    #[test]
+    fn test_records_8k() {
+        let data = test_data("float_sample_8k.ibd");
+        let page = Page::from_bytes_with_size(&data[4 * 8192..5 * 8192], PageSize::Size8K).unwrap();
+        let index = IndexPage::try_from_page(page).unwrap();
+
+        // The directory moved to the end of the smaller page
+        assert_eq!(index.directory(), vec![99, 112]);
+        index.validate_directory().unwrap();
+        assert_eq!(index.records().count(), 2);
+        assert_eq!(index.verify(), vec![]);
+    }
+
+    #[test]
+    fn test_try_from_page_ref() {
+        let buf = fixture_page("float_sample.ibd", 4);
+        let page = Page::from_bytes(&buf).unwrap();
+        let index = IndexPage::try_from_page_ref(&page).unwrap();
+
+        assert_eq!(index.records().count(), 2);
+        assert_eq!(index.page, page);
+        assert_eq!(page.crc32_checksum(), page.header.new_checksum);
+
+        let buf = fixture_page("float_sample.ibd", 0);
+        assert!(IndexPage::try_from_page_ref(&Page::from_bytes(&buf).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_verify_clean_page() {
+        for (file, page) in [("float_sample.ibd", 4), ("t_empty.ibd", 3)] {
+            let buf = fixture_page(file, page);
+            let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+            assert_eq!(index.verify(), vec![], "{} page {}", file, page);
+        }
+    }
+
+    #[test]
+    fn test_verify_truncated_page() {
+        // Torn write that only reached the first record
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        buf[170..].fill(0);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        let report = index.verify();
+        assert_eq!(report.len(), 1);
+        assert!(matches!(
+            report[0],
+            PageInconsistency::BrokenList { garbage: false, .. }
+        ));
+    }
+
+    #[test]
+    fn test_verify_header_mismatch() {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        // Heap top below the second record and one record too many
+        buf[40..42].copy_from_slice(&150u16.to_be_bytes());
+        buf[54..56].copy_from_slice(&3u16.to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(
+            index.verify(),
+            vec![
+                PageInconsistency::HeapTop {
+                    heap_top: 150,
+                    record: 179
+                },
+                PageInconsistency::DirectoryCount {
+                    header: 3,
+                    directory: 2
+                },
+                PageInconsistency::RecordCount {
+                    header: 3,
+                    found: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directory_record_count() {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(index.directory_record_count().unwrap(), 2);
+
+        // With the list cut after the first record only the directory still
+        // disagrees with the tampered header
+        buf[54..56].copy_from_slice(&7u16.to_be_bytes());
+        buf[127 - 2..127].copy_from_slice(&0x4000u16.to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(index.directory_record_count().unwrap(), 2);
+        let report = index.verify();
+        assert!(matches!(
+            report[0],
+            PageInconsistency::BrokenList { garbage: false, .. }
+        ));
+        assert_eq!(
+            report[1..],
+            [PageInconsistency::DirectoryCount {
+                header: 7,
+                directory: 2
+            }]
+        );
+    }
+
+    fn sibling_page(offset: u32, prev: u32, next: u32) -> Vec<u8> {
+        let mut buf = fixture_page("t_empty.ibd", 3);
+        buf[4..8].copy_from_slice(&offset.to_be_bytes());
+        buf[8..12].copy_from_slice(&prev.to_be_bytes());
+        buf[12..16].copy_from_slice(&next.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_siblings() {
+        let pages = [
+            sibling_page(3, FIL_NULL, 4),
+            sibling_page(4, 3, 5),
+            sibling_page(5, 4, FIL_NULL),
+        ];
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in pages.iter() {
+            buffer_mgr.add_page(page).unwrap();
+        }
+        let index = |i: usize| IndexPage::try_from_page(Page::from_bytes(&pages[i]).unwrap());
+        let offset = |guard: Option<PageGuard>| guard.map(|guard| guard.header.offset);
+
+        let first = index(0).unwrap();
+        assert_eq!(offset(first.next_sibling(&buffer_mgr).unwrap()), Some(4));
+        assert_eq!(offset(first.prev_sibling(&buffer_mgr).unwrap()), None);
+        let last = index(2).unwrap();
+        assert_eq!(offset(last.prev_sibling(&buffer_mgr).unwrap()), Some(4));
+        assert_eq!(offset(last.next_sibling(&buffer_mgr).unwrap()), None);
+
+        // Middle page that doesn't link back, then one of another index
+        buffer_mgr.add_page(&sibling_page(4, FIL_NULL, 5)).unwrap();
+        assert!(first.next_sibling(&buffer_mgr).is_err());
+        let mut other = sibling_page(4, 3, 5);
+        other[38 + 28..38 + 36].copy_from_slice(&1234u64.to_be_bytes());
+        buffer_mgr.add_page(&other).unwrap();
+        assert!(first.next_sibling(&buffer_mgr).is_err());
+        assert!(last.prev_sibling(&buffer_mgr).is_err());
+    }
+
+    #[test]
+    fn test_record_by_heap_no() {
+        let buf = fixture_page("float_sample.ibd", 4);
+        let offsets = |buf: &[u8]| {
+            let index = IndexPage::try_from_page(Page::from_bytes(buf).unwrap()).unwrap();
+            (0..5)
+                .map(|heap_no| {
+                    index
+                        .record_by_heap_no(heap_no)
+                        .unwrap()
+                        .map(|record| record.offset)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            offsets(&buf),
+            vec![Some(99), Some(112), Some(127), Some(179), None]
+        );
+
+        // Breaking the link after heap number 3 loses only heap number 4,
+        // the rest is owned by later directory slots
+        let mut buf = build_int_page(&(0..10).collect::<Vec<_>>());
+        let third = 120 + RECORD_SIZE + 5;
+        buf[third - 2..third].fill(0);
+        let found = offsets(&buf);
+        assert!(found[3].is_some());
+        assert_eq!(found[4], None);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        for heap_no in 5..12 {
+            let record = index.record_by_heap_no(heap_no).unwrap().unwrap();
+            assert_eq!(record.header.order, heap_no);
+        }
+        assert!(index.record_by_heap_no(12).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_records_broken_link() {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        // Point the infimum before the start of the page
+        buf[97..99].copy_from_slice(&(-200i16).to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        let records: Vec<_> = index.records().collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_err());
+    }
+
+    /// Unlinks the second record of the float sample page and puts it on the
+    /// garbage list
+    fn float_page_with_garbage() -> Vec<u8> {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        buf[125..127].copy_from_slice(&(112i16 - 127).to_be_bytes());
+        buf[177..179].copy_from_slice(&0i16.to_be_bytes());
+        buf[179 - 5] |= 0x20;
+        buf[44..46].copy_from_slice(&179u16.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_garbage_records() {
+        let buf = fixture_page("float_sample.ibd", 4);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(index.garbage_records().count(), 0);
+
+        let buf = float_page_with_garbage();
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        let garbage = index.garbage_records().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(garbage.len(), 1);
+        assert_eq!(garbage[0].offset, 179);
+        assert!(garbage[0].header.info_flags.deleted);
+        assert_eq!(index.records().count(), 1);
+    }
+
+    #[test]
+    fn test_garbage_records_loop() {
+        let mut buf = float_page_with_garbage();
+        // 179 -> 127 -> 179, the live list is left with only the system records
+        buf[97..99].copy_from_slice(&(112i16 - 99).to_be_bytes());
+        buf[125..127].copy_from_slice(&(179i16 - 127).to_be_bytes());
+        buf[177..179].copy_from_slice(&(127i16 - 179).to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        let garbage: Vec<_> = index.garbage_records().collect();
+        assert_eq!(garbage.len(), 3);
+        assert!(garbage[..2].iter().all(|r| r.is_ok()));
+        assert!(garbage[2].is_err());
+    }
+
+    #[test]
+    fn test_directory() {
+        let buf = fixture_page("t_empty.ibd", 3);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        assert_eq!(index.directory(), vec![99, 112]);
+        index.validate_directory().unwrap();
+        assert_eq!(
+            index.record_at_slot(0).unwrap().header.record_type,
+            RecordType::Infimum
+        );
+        assert_eq!(
+            index.record_at_slot(1).unwrap().header.record_type,
+            RecordType::Supremum
+        );
+        assert!(index.record_at_slot(2).is_err());
+    }
+
+    #[test]
+    fn test_directory_corrupted() {
+        let mut buf = fixture_page("t_empty.ibd", 3);
+        // Swap the two slots
+        buf[FIL_PAGE_SIZE - 10..FIL_PAGE_SIZE - 8].copy_from_slice(&112u16.to_be_bytes());
+        buf[FIL_PAGE_SIZE - 12..FIL_PAGE_SIZE - 10].copy_from_slice(&99u16.to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(index.validate_directory().is_err());
+
+        let mut buf = fixture_page("t_empty.ibd", 3);
+        // Slots that would reach into the headers
+        buf[38..40].copy_from_slice(&u16::MAX.to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(index.validate_directory().is_err());
+        assert!(index.directory().len() < u16::MAX as usize);
+    }
+
+    const RECORD_SIZE: usize = 5 + 4 + 6 + 7;
+
+    /// Leaf page of a table with a single INT primary key, grouping four
+    /// records per directory slot
+    fn build_int_page(keys: &[i32]) -> Vec<u8> {
+        let mut buf = fixture_page("t_empty.ibd", 3);
+        let origin = |i: usize| 120 + i * RECORD_SIZE + 5;
+        let mut owners = vec![99u16];
+        let mut prev = 99;
+        for (i, key) in keys.iter().enumerate() {
+            let rec = origin(i);
+            // Heap number and conventional record type
+            buf[rec - 4..rec - 2].copy_from_slice(&(((i + 2) as u16) << 3).to_be_bytes());
+            buf[rec..rec + 4].copy_from_slice(&((*key as u32) ^ 0x8000_0000).to_be_bytes());
+            buf[prev - 2..prev].copy_from_slice(&((rec as i16) - (prev as i16)).to_be_bytes());
+            if i % 4 == 3 {
+                buf[rec - 5] = 4;
+                owners.push(rec as u16);
+            }
+            prev = rec;
+        }
+        buf[prev - 2..prev].copy_from_slice(&(112 - (prev as i16)).to_be_bytes());
+        buf[112 - 5] = (keys.len() % 4 + 1) as u8;
+        owners.push(112);
+
+        for (slot, owner) in owners.iter().enumerate() {
+            let pos = FIL_PAGE_SIZE - 8 - (slot + 1) * 2;
+            buf[pos..pos + 2].copy_from_slice(&owner.to_be_bytes());
+        }
+        buf[38..40].copy_from_slice(&(owners.len() as u16).to_be_bytes());
+        buf[40..42].copy_from_slice(&(origin(keys.len()) as u16 - 5).to_be_bytes());
+        buf[42..44].copy_from_slice(&(0x8000 | (keys.len() as u16 + 2)).to_be_bytes());
+        buf[54..56].copy_from_slice(&(keys.len() as u16).to_be_bytes());
+        buf
+    }
+
+    fn int_table() -> Arc<TableDefinition> {
+        Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![Field::new("id", FieldType::Int(true), false)],
+            data_columns: vec![],
+        })
+    }
+
+    #[test]
+    fn test_search() {
+        let keys: Vec<i32> = (0..42).map(|i| i * 10 - 200).collect();
+        let buf = build_int_page(&keys);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        let table = int_table();
+
+        index.validate_directory().unwrap();
+        assert_eq!(index.records().count(), keys.len());
+
+        for key in keys.iter() {
+            let record = index
+                .search(&[FieldValue::SignedInt(*key as i64)], &table)
+                .unwrap()
+                .unwrap_or_else(|| panic!("key {} not found", key));
+            let row = Row::try_from_record_and_table(&record, &table).unwrap();
+            assert_eq!(
+                row.cluster_key().unwrap(),
+                vec![FieldValue::SignedInt(*key as i64)]
+            );
+        }
+
+        for missing in [-201, -195, 5, 215, 1000] {
+            assert!(index
+                .search(&[FieldValue::SignedInt(missing)], &table)
+                .unwrap()
+                .is_none());
+        }
+
+        let key_of = |record: Option<Record>| {
+            record.map(|record| {
+                Row::try_from_record_and_table(&record, &table)
+                    .unwrap()
+                    .cluster_key()
+                    .unwrap()
+            })
+        };
+        let floor = |key: i64| {
+            key_of(
+                index
+                    .search_floor(&[FieldValue::SignedInt(key)], &table)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(floor(-201), None);
+        for (key, expected) in [(-200, -200), (-195, -200), (5, 0), (30, 30), (1000, 210)] {
+            assert_eq!(floor(key), Some(vec![FieldValue::SignedInt(expected)]));
+        }
+
+        let lower = |key: i64| {
+            key_of(
+                index
+                    .search_lower(&[FieldValue::SignedInt(key)], &table)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(lower(-200), None);
+        for (key, expected) in [(-195, -200), (5, 0), (30, 20), (1000, 210)] {
+            assert_eq!(lower(key), Some(vec![FieldValue::SignedInt(expected)]));
+        }
+    }
+
+    #[test]
+    fn test_verify_key_order() {
+        let table = int_table();
+        let keys: Vec<i32> = (0..12).map(|i| i * 3 - 10).collect();
+        let buf = build_int_page(&keys);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(index.verify_key_order(&table).unwrap(), None);
+
+        // Same layout with the keys of the fifth and sixth records swapped
+        let mut shuffled = keys.clone();
+        shuffled.swap(4, 5);
+        let buf = build_int_page(&shuffled);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(
+            index.verify_key_order(&table).unwrap(),
+            Some(120 + 5 * RECORD_SIZE + 5)
+        );
+    }
+
+    fn write_redundant_header(buf: &mut [u8], origin: usize, heap_number: u16, next: u16) {
+        buf[origin - 6] = 1;
+        // heap number (13), field count (10) and the 1 byte offsets flag
+        let bits = ((heap_number as u32) << 11) | (1 << 1) | 1;
+        buf[origin - 5..origin - 2].copy_from_slice(&bits.to_be_bytes()[1..]);
+        buf[origin - 2..origin].copy_from_slice(&next.to_be_bytes());
+    }
+
+    fn build_redundant_page(count: usize) -> Vec<u8> {
+        let mut buf = fixture_page("t_empty.ibd", 3);
+        buf[94..1024].fill(0);
+        buf[42..44].copy_from_slice(&(count as u16 + 2).to_be_bytes());
+        buf[FIL_PAGE_SIZE - 10..FIL_PAGE_SIZE - 8].copy_from_slice(&101u16.to_be_bytes());
+        buf[FIL_PAGE_SIZE - 12..FIL_PAGE_SIZE - 10].copy_from_slice(&116u16.to_be_bytes());
+
+        let origin = |i: usize| 132 + i * 20;
+        let first = if count == 0 { 116 } else { origin(0) };
+        write_redundant_header(&mut buf, 101, 0, first as u16);
+        buf[101..109].copy_from_slice(b"infimum\0");
+        write_redundant_header(&mut buf, 116, 1, 0);
+        buf[116..125].copy_from_slice(b"supremum\0");
+        for i in 0..count {
+            let next = if i + 1 == count { 116 } else { origin(i + 1) };
+            write_redundant_header(&mut buf, origin(i), i as u16 + 2, next as u16);
+            buf[origin(i) - 7] = 4;
+        }
+        buf
+    }
+
+    #[test]
+    fn test_system_records_checked() {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        buf[99..107].copy_from_slice(b"garbage!");
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(index.infimum().is_err());
+        assert!(index.supremum().is_ok());
+        let records: Vec<_> = index.records().collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_err());
+
+        // Compact offsets on a redundant page land on no system record
+        let mut buf = build_redundant_page(2);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(index.records().count(), 2);
+        assert!(index.system_record(99, b"infimum\0").is_err());
+        buf[116..124].copy_from_slice(b"\0\0\0\0\0\0\0\0");
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(index.supremum().is_err());
+    }
+
+    #[test]
+    fn test_records_redundant() {
+        let buf = build_redundant_page(3);
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(index.index_header.format, IndexFormat::Redundant);
+        index.validate_directory().unwrap();
+
+        let records = index.all_records().collect::<Result<Vec<_>>>().unwrap();
+        let offsets: Vec<usize> = records.iter().map(|r| r.offset).collect();
+        assert_eq!(offsets, vec![101, 132, 152, 172, 116]);
+        assert_eq!(records[0].header.record_type, RecordType::Infimum);
+        assert_eq!(records[4].header.record_type, RecordType::Supremum);
+        for record in records[1..4].iter() {
+            assert_eq!(record.header.record_type, RecordType::Conventional);
+            assert_eq!(
+                record.header.redundant,
+                Some(RedundantHeader {
+                    num_fields: 1,
+                    one_byte_offsets: true
+                })
+            );
+        }
+        assert_eq!(index.records().count(), 3);
+
+        // Same records on a non leaf page are node pointers
+        let mut buf = build_redundant_page(2);
+        buf[64..66].copy_from_slice(&1u16.to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(index
+            .records()
+            .all(|r| r.unwrap().header.record_type == RecordType::NodePointer));
+    }
+
+    #[test]
+    fn test_records_cycle() {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        let (first, last) = {
+            let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+            let records = index.records().collect::<Result<Vec<_>>>().unwrap();
+            (records[0].offset, records[1].offset)
+        };
+        // Last user record points back to the first one
+        buf[last - 2..last].copy_from_slice(&((first as i16) - (last as i16)).to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        let records: Vec<_> = index.records().collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].is_ok());
+        assert!(records[1].is_ok());
+        let error = records[2].as_ref().unwrap_err().to_string();
+        assert!(error.contains(&format!("offset {}", first)), "{}", error);
+    }
+
+    #[test]
+    fn test_records_longer_than_heap() {
+        let mut buf = fixture_page("float_sample.ibd", 4);
+        // Heap only has room for the system records and one user record
+        buf[42..44].copy_from_slice(&(0x8000u16 | 3).to_be_bytes());
+        let index = IndexPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+
+        let records: Vec<_> = index.all_records().collect();
+        assert_eq!(records.len(), 4);
+        assert!(records[..3].iter().all(|r| r.is_ok()));
+        assert!(records[3].is_err());
+    }
+
+    #[test]
+    fn test_page_direction() {
+        let buf = fixture_page("float_sample.ibd", 4);
+        let header = IndexHeader::from_bytes(&buf[38..]).unwrap();
+        assert_eq!(
+            header.page_direction,
+            PageDirection::try_from(header.page_direction_raw).ok()
+        );
+
+        let mut buf = buf;
+        buf[38 + 12..38 + 14].copy_from_slice(&2u16.to_be_bytes());
+        let header = IndexHeader::from_bytes(&buf[38..]).unwrap();
+        assert_eq!(header.page_direction, Some(PageDirection::Right));
+        assert_eq!(header.page_direction.unwrap().to_string(), "PAGE_RIGHT");
 
-    pub fn supremum(&self) -> Result<Record> {
-        self.record_at(112)
+        // Unknown codes don't fail the header
+        buf[38 + 12..38 + 14].copy_from_slice(&9u16.to_be_bytes());
+        let header = IndexHeader::from_bytes(&buf[38..]).unwrap();
+        assert_eq!(header.page_direction, None);
+        assert_eq!(header.page_direction_raw, 9);
     }
 }
@@ -2,10 +2,11 @@ use std::fmt::Debug;
 
 use anyhow::{anyhow, Error, Result};
 use num_enum::TryFromPrimitive;
-use tracing::error;
 
 use crate::innodb::InnoDBError;
 
+use super::IndexHeader;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
 #[repr(u8)]
 pub enum RecordType {
@@ -19,36 +20,51 @@ pub enum RecordType {
 pub struct InfoFlags {
     pub min_rec: bool,
     pub deleted: bool,
+    /// REC_INFO_INSTANT_FLAG, the record stores its field count because the
+    /// table had columns added with ALGORITHM=INSTANT
+    pub instant: bool,
 }
 
 impl InfoFlags {
     pub fn try_from_primitive(flags: u8) -> Result<InfoFlags> {
-        if flags & (!0x3u8) != 0 {
+        if flags & (!0xBu8) != 0 {
             return Err(Error::msg("Unexpected bitfield value"));
         }
 
         Ok(InfoFlags {
             min_rec: (flags & 0x1) != 0,
             deleted: (flags & 0x2) != 0,
+            instant: (flags & 0x8) != 0,
         })
     }
 }
 
 pub const RECORD_HEADER_FIXED_LENGTH: usize = 5;
+pub const REDUNDANT_RECORD_HEADER_FIXED_LENGTH: usize = 6;
+
+/// Header bits only present in the REDUNDANT format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedundantHeader {
+    pub num_fields: u16,        // 10 bits
+    pub one_byte_offsets: bool, // field end offsets are 1 byte instead of 2
+}
 
 #[derive(Debug, Clone)]
 pub struct RecordHeader {
     pub info_flags: InfoFlags,   // 4 bit,
     pub num_records_owned: u8,   // 4-bit [Valid range 0-8]
     pub order: u16,              // 13 bits
-    pub record_type: RecordType, // 3 bits
+    pub record_type: RecordType, // 3 bits, derived from heap number for REDUNDANT
     pub next_record_offset: Option<u16>,
+    pub redundant: Option<RedundantHeader>,
 }
 
 impl RecordHeader {
     pub fn try_from_offset(buffer: &[u8], offset: usize) -> Result<RecordHeader> {
-        assert!(offset < u16::MAX as usize);
-        if offset < RECORD_HEADER_FIXED_LENGTH {
+        if offset < RECORD_HEADER_FIXED_LENGTH
+            || offset > buffer.len()
+            || offset >= u16::MAX as usize
+        {
             return Err(anyhow!(InnoDBError::InvalidLength));
         }
         let record_type_order = u16::from_be_bytes([buffer[offset - 4], buffer[offset - 3]]);
@@ -60,14 +76,70 @@ impl RecordHeader {
             record_type: RecordType::try_from_primitive((record_type_order & 0x7) as u8)?,
             next_record_offset: (offset as u16)
                 .checked_add_signed(i16::from_be_bytes([buffer[offset - 2], buffer[offset - 1]])),
+            redundant: None,
         })
     }
 
-    pub fn next_record_offset(&self) -> usize {
-        self.next_record_offset.unwrap() as usize
+    /// Parses the 6 byte REDUNDANT header, where the next pointer is an
+    /// absolute offset. The format has no record type, so user records are
+    /// reported as [`RecordType::Conventional`] or, when `leaf` is false,
+    /// [`RecordType::NodePointer`].
+    pub fn try_from_offset_redundant(
+        buffer: &[u8],
+        offset: usize,
+        leaf: bool,
+    ) -> Result<RecordHeader> {
+        if offset < REDUNDANT_RECORD_HEADER_FIXED_LENGTH
+            || offset > buffer.len()
+            || offset >= u16::MAX as usize
+        {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        let owned_flags = buffer[offset - 6];
+        let heap_number = u16::from_be_bytes([buffer[offset - 5], buffer[offset - 4]]) >> 3;
+        let fields_short = u16::from_be_bytes([buffer[offset - 4], buffer[offset - 3]]);
+        let next = u16::from_be_bytes([buffer[offset - 2], buffer[offset - 1]]);
+        Ok(RecordHeader {
+            info_flags: InfoFlags::try_from_primitive(owned_flags >> 4)?,
+            num_records_owned: owned_flags & 0xF,
+            order: heap_number,
+            record_type: match heap_number {
+                0 => RecordType::Infimum,
+                1 => RecordType::Supremum,
+                _ if leaf => RecordType::Conventional,
+                _ => RecordType::NodePointer,
+            },
+            next_record_offset: (next != 0).then_some(next),
+            redundant: Some(RedundantHeader {
+                num_fields: (fields_short & 0x7FE) >> 1,
+                one_byte_offsets: (fields_short & 0x1) != 0,
+            }),
+        })
     }
 }
 
+/// How a field is stored, all the COMPACT format needs to find its bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldShape {
+    pub nullable: bool,
+    /// `None` for variable length fields, which get an entry in the length
+    /// array
+    pub fixed_len: Option<usize>,
+    /// Lengths of fields that can exceed 255 bytes take 2 bytes from 128 on
+    pub max_len: usize,
+}
+
+/// Bytes of a single field, without any type interpretation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawField<'a> {
+    /// Empty for NULL fields, except fixed length ones in REDUNDANT records
+    /// which still take up their space
+    pub data: &'a [u8],
+    pub null: bool,
+    /// The data is a 20 byte reference to the off-page value
+    pub external: bool,
+}
+
 #[derive(Clone)]
 pub struct Record<'a> {
     pub header: RecordHeader,
@@ -85,7 +157,7 @@ impl<'a> Debug for Record<'a> {
 }
 
 impl<'a> Record<'a> {
-    pub fn try_from_offset(buffer: &'a [u8], offset: usize) -> Result<Record> {
+    pub fn try_from_offset(buffer: &'a [u8], offset: usize) -> Result<Record<'a>> {
         Ok(Record {
             header: RecordHeader::try_from_offset(buffer, offset)?,
             offset,
@@ -93,17 +165,208 @@ impl<'a> Record<'a> {
         })
     }
 
-    pub fn next(&self) -> Option<Record<'a>> {
-        if self.header.record_type == RecordType::Supremum {
-            return None;
+    pub fn try_from_offset_redundant(
+        buffer: &'a [u8],
+        offset: usize,
+        leaf: bool,
+    ) -> Result<Record<'a>> {
+        Ok(Record {
+            header: RecordHeader::try_from_offset_redundant(buffer, offset, leaf)?,
+            offset,
+            buf: buffer,
+        })
+    }
+
+    /// True when the next pointer is zero, which is how the garbage list ends
+    pub fn is_list_end(&self) -> bool {
+        match self.header.redundant {
+            Some(_) => self.header.next_record_offset.is_none(),
+            None => self.header.next_record_offset == Some(self.offset as u16),
+        }
+    }
+
+    /// Offset of the next record, checked to be a different record inside
+    /// the buffer
+    pub fn next_offset(&self) -> Result<usize> {
+        let invalid = || {
+            anyhow!(InnoDBError::InvalidRecordOffset {
+                offset: self.offset,
+                next: self.header.next_record_offset,
+            })
+        };
+        let next = self.header.next_record_offset.ok_or_else(invalid)? as usize;
+        let header_length = match self.header.redundant {
+            Some(_) => REDUNDANT_RECORD_HEADER_FIXED_LENGTH,
+            None => RECORD_HEADER_FIXED_LENGTH,
+        };
+        if next < header_length || next >= self.buf.len() || next == self.offset {
+            return Err(invalid());
         }
-        match Self::try_from_offset(self.buf, self.header.next_record_offset()) {
-            Ok(record) => Some(record),
-            Err(e) => {
-                error!("Non-Supremum record does not have next: {:?}", e);
-                None
+        Ok(next)
+    }
+
+    /// Bytes the record occupies after its origin, up to the fixed header of
+    /// the record stored after it or the heap top for the last record. This
+    /// is an upper bound: the variable length header of the following record
+    /// is included, and a record followed by a purged one also counts the
+    /// free space. The infimum and supremum have a fixed size.
+    pub fn payload_len(&self, index_header: &IndexHeader) -> Result<usize> {
+        let (header_length, supremum_length) = match self.header.redundant {
+            Some(_) => (REDUNDANT_RECORD_HEADER_FIXED_LENGTH, 9),
+            None => (RECORD_HEADER_FIXED_LENGTH, 8),
+        };
+        match self.header.record_type {
+            RecordType::Infimum => return Ok(8),
+            RecordType::Supremum => return Ok(supremum_length),
+            _ => {}
+        }
+        let end = match self.next_offset() {
+            Ok(next) if next > self.offset => next - header_length,
+            _ => index_header.heap_top_position as usize,
+        };
+        end.checked_sub(self.offset).ok_or_else(|| {
+            anyhow!(
+                "Record at {} starts after the heap top at {}",
+                self.offset,
+                index_header.heap_top_position
+            )
+        })
+    }
+
+    /// Splits the record into its fields using only the record header.
+    /// REDUNDANT records store every field end offset so `shapes` is
+    /// ignored, COMPACT records need the shape of each field on disk,
+    /// including the hidden columns of clustered leaf records.
+    pub fn raw_fields(&self, shapes: &[FieldShape]) -> Result<Vec<RawField<'a>>> {
+        match self.header.redundant {
+            Some(redundant) => self.raw_fields_redundant(redundant),
+            None => self.raw_fields_compact(shapes),
+        }
+    }
+
+    fn header_byte(&self, back: usize) -> Result<u8> {
+        self.offset
+            .checked_sub(back)
+            .map(|i| self.buf[i])
+            .ok_or_else(|| anyhow!(InnoDBError::InvalidLength))
+    }
+
+    fn field_data(&self, start: usize, len: usize) -> Result<&'a [u8]> {
+        self.buf.get(start..start + len).ok_or_else(|| {
+            anyhow!(
+                "Field at {} with length {} runs past the end of the page",
+                start,
+                len
+            )
+        })
+    }
+
+    fn raw_fields_redundant(&self, redundant: RedundantHeader) -> Result<Vec<RawField<'a>>> {
+        let mut fields = Vec::new();
+        let mut start = self.offset;
+        let mut back = REDUNDANT_RECORD_HEADER_FIXED_LENGTH;
+        for _ in 0..redundant.num_fields {
+            let (end, null, external) = if redundant.one_byte_offsets {
+                back += 1;
+                let info = self.header_byte(back)? as usize;
+                (info & 0x7F, info & 0x80 != 0, false)
+            } else {
+                back += 2;
+                let info =
+                    u16::from_be_bytes([self.header_byte(back)?, self.header_byte(back - 1)?]);
+                (
+                    (info & 0x3FFF) as usize,
+                    info & 0x8000 != 0,
+                    info & 0x4000 != 0,
+                )
+            };
+            let end = self.offset + end;
+            if end < start {
+                return Err(anyhow!(
+                    "Field end offset {} is before the previous field end {}",
+                    end,
+                    start
+                ));
             }
+            fields.push(RawField {
+                data: self.field_data(start, end - start)?,
+                null,
+                external,
+            });
+            start = end;
         }
+        Ok(fields)
+    }
+
+    fn raw_fields_compact(&self, shapes: &[FieldShape]) -> Result<Vec<RawField<'a>>> {
+        let mut back = RECORD_HEADER_FIXED_LENGTH;
+        let num_nullable = shapes.iter().filter(|s| s.nullable).count();
+        let null_bytes = (0..num_nullable.div_ceil(8))
+            .map(|_| {
+                back += 1;
+                self.header_byte(back)
+            })
+            .collect::<Result<Vec<u8>>>()?;
+
+        let mut fields = Vec::new();
+        let mut start = self.offset;
+        let mut null_bit = 0;
+        for shape in shapes {
+            if shape.nullable {
+                let null = (null_bytes[null_bit / 8] >> (null_bit % 8)) & 1 != 0;
+                null_bit += 1;
+                if null {
+                    fields.push(RawField {
+                        data: &[],
+                        null: true,
+                        external: false,
+                    });
+                    continue;
+                }
+            }
+
+            let (len, external) = match shape.fixed_len {
+                Some(len) => (len, false),
+                None => {
+                    back += 1;
+                    let mut len = self.header_byte(back)? as usize;
+                    let mut external = false;
+                    // Same length encoding as Row
+                    if shape.max_len > 255 && len & 0x80 != 0 {
+                        back += 1;
+                        let tmp = (len << 8) | self.header_byte(back)? as usize;
+                        len = tmp & 0x3FFF;
+                        external = tmp & 0x4000 != 0;
+                    }
+                    (len, external)
+                }
+            };
+            fields.push(RawField {
+                data: self.field_data(start, len)?,
+                null: false,
+                external,
+            });
+            start += len;
+        }
+        Ok(fields)
+    }
+
+    /// The next record in the list, `None` after the supremum
+    pub fn next(&self) -> Result<Option<Record<'a>>> {
+        if self.header.record_type == RecordType::Supremum {
+            return Ok(None);
+        }
+        let offset = self.next_offset()?;
+        let next = match self.header.redundant {
+            // Only node pointers tell us this is not a leaf page
+            Some(_) => Self::try_from_offset_redundant(
+                self.buf,
+                offset,
+                self.header.record_type != RecordType::NodePointer,
+            ),
+            None => Self::try_from_offset(self.buf, offset),
+        };
+        next.map(Some)
     }
 }
 
@@ -115,9 +378,15 @@ mod test {
         path::PathBuf,
     };
 
-    use crate::innodb::page::{
-        index::{record::RecordType, IndexPage},
-        Page, PageType, FIL_PAGE_SIZE,
+    use crate::innodb::{
+        page::{
+            index::{
+                record::{FieldShape, RawField, Record, RecordType},
+                IndexPage,
+            },
+            Page, PageType, FIL_PAGE_SIZE,
+        },
+        test_data, InnoDBError,
     };
 
     #[test]
@@ -146,4 +415,124 @@ mod test {
         assert!(!inf_header.info_flags.min_rec);
         assert!(!inf_header.info_flags.deleted);
     }
+
+    #[test]
+    fn test_record_payload_len() {
+        let data = test_data("t_empty.ibd");
+        let buf = &data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE];
+        let index = IndexPage::try_from_page(Page::from_bytes(buf).unwrap()).unwrap();
+        let header = &index.index_header;
+        assert_eq!(index.infimum().unwrap().payload_len(header).unwrap(), 8);
+        assert_eq!(index.supremum().unwrap().payload_len(header).unwrap(), 8);
+
+        let data = test_data("float_sample.ibd");
+        let buf = &data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE];
+        let index = IndexPage::try_from_page(Page::from_bytes(buf).unwrap()).unwrap();
+        let header = &index.index_header;
+        let records = index.records().collect::<anyhow::Result<Vec<_>>>().unwrap();
+        // Up to the next fixed header, then up to the heap top
+        assert_eq!(records[0].payload_len(header).unwrap(), 179 - 5 - 127);
+        assert_eq!(
+            records[1].payload_len(header).unwrap(),
+            header.heap_top_position as usize - 179
+        );
+
+        let mut header = header.clone();
+        header.heap_top_position = 150;
+        assert!(records[1].payload_len(&header).is_err());
+    }
+
+    #[test]
+    fn test_record_next_bounds() {
+        let data = test_data("t_empty.ibd");
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+
+        let infimum = Record::try_from_offset(&buf, 99).unwrap();
+        let supremum = infimum.next().unwrap().unwrap();
+        assert_eq!(supremum.header.record_type, RecordType::Supremum);
+        assert!(supremum.next().unwrap().is_none());
+
+        for (relative, next) in [
+            (0i16, Some(99)),
+            (i16::MAX, Some(99 + i16::MAX as u16)),
+            (-99, Some(0)),
+            (-100, None),
+        ] {
+            buf[97..99].copy_from_slice(&relative.to_be_bytes());
+            let infimum = Record::try_from_offset(&buf, 99).unwrap();
+            let error = infimum.next().unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<InnoDBError>(),
+                Some(&InnoDBError::InvalidRecordOffset { offset: 99, next })
+            );
+        }
+    }
+
+    #[test]
+    fn test_raw_fields_compact() {
+        let data = test_data("float_sample.ibd");
+        let mut buf = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        let shape = |nullable: bool, fixed_len: Option<usize>, max_len: usize| FieldShape {
+            nullable,
+            fixed_len,
+            max_len,
+        };
+        // char(20) in utf8mb4 is variable length, then the hidden columns
+        // and the two nullable floats
+        let shapes = [
+            shape(false, None, 80),
+            shape(false, Some(6), 6),
+            shape(false, Some(7), 7),
+            shape(true, Some(4), 4),
+            shape(true, Some(8), 8),
+        ];
+
+        let record = Record::try_from_offset(&buf, 127).unwrap();
+        let fields = record.raw_fields(&shapes).unwrap();
+        let lengths = fields.iter().map(|f| f.data.len()).collect::<Vec<_>>();
+        assert_eq!(lengths, vec![20, 6, 7, 4, 8]);
+        assert_eq!(fields[0].data, b"test1               ");
+        assert_eq!(fields[3].data, &1.1f32.to_le_bytes());
+        assert!(fields.iter().all(|f| !f.null && !f.external));
+
+        // NULL fields take no space, the next field starts right away
+        buf[121] = 0b01;
+        let record = Record::try_from_offset(&buf, 127).unwrap();
+        let fields = record.raw_fields(&shapes).unwrap();
+        assert_eq!(
+            fields[3],
+            RawField {
+                data: &[],
+                null: true,
+                external: false
+            }
+        );
+        assert_eq!(fields[4].data, &buf[160..168]);
+    }
+
+    #[test]
+    fn test_raw_fields_redundant() {
+        let mut buf = vec![0u8; 64];
+        let origin = 32;
+        // Heap number 2 and 3 fields with one byte offsets
+        buf[origin - 4] = 2 << 3;
+        buf[origin - 3] = 3 << 1 | 1;
+        // Field end offsets are stored backwards, the second one is NULL
+        buf[origin - 7] = 4;
+        buf[origin - 8] = 0x80 | 4;
+        buf[origin - 9] = 7;
+        buf[origin..origin + 7].copy_from_slice(b"abcdxyz");
+
+        let record = Record::try_from_offset_redundant(&buf, origin, true).unwrap();
+        let fields = record.raw_fields(&[]).unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].data, b"abcd");
+        assert!(fields[1].null && fields[1].data.is_empty());
+        assert_eq!(fields[2].data, b"xyz");
+
+        // Offsets going backwards are rejected
+        buf[origin - 9] = 2;
+        let record = Record::try_from_offset_redundant(&buf, origin, true).unwrap();
+        assert!(record.raw_fields(&[]).is_err());
+    }
 }
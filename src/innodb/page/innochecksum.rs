@@ -0,0 +1,294 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+
+use super::{
+    checksum_body, checksum_header, crc32_checksum, innodb_checksum, innodb_old_checksum, Page,
+    BUF_NO_CHECKSUM_MAGIC, FIL_HEADER_PARTIAL_OFFSET, FIL_PAGE_BODY_OFFSET,
+};
+
+/// Values of innochecksum's --strict-check, also innodb_checksum_algorithm
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InnochecksumAlgorithm {
+    /// Accepts crc32, falls back to none and innodb like the server does
+    #[default]
+    Crc32,
+    StrictCrc32,
+    /// Accepts innodb, falls back to none and crc32
+    InnoDb,
+    StrictInnoDb,
+    /// Accepts every page
+    None,
+    StrictNone,
+}
+
+impl InnochecksumAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InnochecksumAlgorithm::Crc32 => "crc32",
+            InnochecksumAlgorithm::StrictCrc32 => "strict_crc32",
+            InnochecksumAlgorithm::InnoDb => "innodb",
+            InnochecksumAlgorithm::StrictInnoDb => "strict_innodb",
+            InnochecksumAlgorithm::None => "none",
+            InnochecksumAlgorithm::StrictNone => "strict_none",
+        }
+    }
+
+    fn is_strict(&self) -> bool {
+        matches!(
+            self,
+            InnochecksumAlgorithm::StrictCrc32
+                | InnochecksumAlgorithm::StrictInnoDb
+                | InnochecksumAlgorithm::StrictNone
+        )
+    }
+}
+
+impl Display for InnochecksumAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for InnochecksumAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        [
+            InnochecksumAlgorithm::Crc32,
+            InnochecksumAlgorithm::StrictCrc32,
+            InnochecksumAlgorithm::InnoDb,
+            InnochecksumAlgorithm::StrictInnoDb,
+            InnochecksumAlgorithm::None,
+            InnochecksumAlgorithm::StrictNone,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.name().eq_ignore_ascii_case(s))
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid checksum algorithm {}, expected crc32, innodb, none or their strict_ variants",
+                s
+            )
+        })
+    }
+}
+
+/// Bytes `crc32_legacy_big_endian` reverses on the stack before feeding
+/// them to the CRC, a multiple of the 8 byte word
+const LEGACY_BLOCK_SIZE: usize = 256;
+
+/// CRC-32C of `buf` as MySQL 5.6 and early 5.7 computed it on big endian
+/// machines, every aligned 8 byte word is fed in reverse. `start` is the
+/// offset of `buf` in the page, pages are aligned in memory.
+fn crc32_legacy_big_endian(buf: &[u8], start: usize) -> u32 {
+    let head = std::cmp::min((8 - start % 8) % 8, buf.len());
+    let mut crc = crc32c::crc32c(&buf[..head]);
+    // Reversed a block of words at a time, appending each word on its own
+    // is slower than copying the whole page
+    let mut blocks = buf[head..].chunks_exact(LEGACY_BLOCK_SIZE);
+    let mut reversed = [0u8; LEGACY_BLOCK_SIZE];
+    for block in &mut blocks {
+        for (word, out) in block.chunks_exact(8).zip(reversed.chunks_exact_mut(8)) {
+            out.copy_from_slice(word);
+            out.reverse();
+        }
+        crc = crc32c::crc32c_append(crc, &reversed);
+    }
+    let mut words = blocks.remainder().chunks_exact(8);
+    for word in &mut words {
+        let mut reversed: [u8; 8] = word.try_into().unwrap();
+        reversed.reverse();
+        crc = crc32c::crc32c_append(crc, &reversed);
+    }
+    crc32c::crc32c_append(crc, words.remainder())
+}
+
+/// "crc32" checksum with the byte order bug of big endian servers, which
+/// innochecksum still accepts
+pub fn crc32_legacy_big_endian_checksum(buf: &[u8]) -> u32 {
+    crc32_legacy_big_endian(checksum_header(buf), FIL_HEADER_PARTIAL_OFFSET)
+        ^ crc32_legacy_big_endian(checksum_body(buf), FIL_PAGE_BODY_OFFSET)
+}
+
+/// What innochecksum computes for a page, printed by its --verbose
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InnochecksumReport {
+    /// Checksum stored in the FIL header
+    pub field1: u32,
+    /// Checksum stored in the trailer
+    pub field2: u32,
+    pub crc32: u32,
+    pub crc32_legacy: u32,
+    /// "innodb" checksum for the header field
+    pub innodb_new: u32,
+    /// "innodb" checksum for the trailer field, over the start of the header
+    pub innodb_old: u32,
+    /// High 32 bits of the LSN, for pages older than the trailer checksum
+    pub lsn_high: u32,
+    /// The low LSN bits in the header and trailer agree
+    pub lsn_matches: bool,
+    /// Every byte is zero, a page that was never written
+    pub is_empty: bool,
+}
+
+impl InnochecksumReport {
+    pub fn new(page: &Page) -> Self {
+        let buf = page.raw_data;
+        InnochecksumReport {
+            field1: page.header.new_checksum,
+            field2: page.trailer.old_checksum,
+            crc32: crc32_checksum(buf),
+            crc32_legacy: crc32_legacy_big_endian_checksum(buf),
+            innodb_new: innodb_checksum(buf),
+            innodb_old: innodb_old_checksum(buf),
+            lsn_high: (page.header.lsn >> 32) as u32,
+            lsn_matches: page.header.lsn as u32 == page.trailer.lsn_low_32,
+            is_empty: buf.iter().all(|b| *b == 0),
+        }
+    }
+
+    fn crc32_valid(&self) -> bool {
+        self.field1 == self.field2
+            && (self.field1 == self.crc32 || self.field1 == self.crc32_legacy)
+    }
+
+    fn innodb_valid(&self) -> bool {
+        (self.field2 == self.innodb_old || self.field2 == self.lsn_high)
+            && (self.field1 == 0 || self.field1 == self.innodb_new)
+    }
+
+    fn none_valid(&self) -> bool {
+        self.field1 == BUF_NO_CHECKSUM_MAGIC && self.field2 == BUF_NO_CHECKSUM_MAGIC
+    }
+
+    /// Whether innochecksum passes the page with `algorithm`
+    pub fn is_valid(&self, algorithm: InnochecksumAlgorithm) -> bool {
+        if algorithm == InnochecksumAlgorithm::None {
+            return true;
+        }
+        if !self.lsn_matches {
+            return false;
+        }
+        if self.is_empty {
+            return true;
+        }
+        let (valid, others) = match algorithm {
+            InnochecksumAlgorithm::Crc32 | InnochecksumAlgorithm::StrictCrc32 => {
+                (self.crc32_valid(), self.innodb_valid())
+            }
+            InnochecksumAlgorithm::InnoDb | InnochecksumAlgorithm::StrictInnoDb => {
+                (self.innodb_valid(), self.crc32_valid())
+            }
+            _ => (self.none_valid(), false),
+        };
+        valid || (!algorithm.is_strict() && (self.none_valid() || others))
+    }
+}
+
+impl Display for InnochecksumReport {
+    /// Same numbers, in decimal, as innochecksum --verbose
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "crc32 calculated = {} (legacy = {}); innodb calculated = {}, old style = {}; recorded checksum field1 = {} field2 = {}",
+            self.crc32, self.crc32_legacy, self.innodb_new, self.innodb_old, self.field1, self.field2
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::page::{
+        writer::PageWriter, ChecksumKind, Page, BUF_NO_CHECKSUM_MAGIC, FIL_PAGE_SIZE,
+    };
+
+    use crate::innodb::test_data;
+
+    use super::{InnochecksumAlgorithm, InnochecksumReport};
+
+    fn index_page() -> Vec<u8> {
+        let data = test_data("float_sample.ibd");
+        data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec()
+    }
+
+    #[test]
+    fn test_innochecksum_values() {
+        // float_sample.ibd was written by a server with the default crc32
+        // algorithm, innochecksum -v calculates the value it recorded
+        let buf = index_page();
+        let report = Page::from_bytes(&buf).unwrap().innochecksum();
+        assert_eq!(report.crc32, 3256290427);
+        assert_eq!(report.field1, 3256290427);
+        assert_eq!(report.field2, 3256290427);
+        assert!(report.lsn_matches);
+        assert!(report
+            .to_string()
+            .starts_with("crc32 calculated = 3256290427 "));
+
+        assert!(report.is_valid(InnochecksumAlgorithm::StrictCrc32));
+        assert!(report.is_valid(InnochecksumAlgorithm::InnoDb));
+        assert!(!report.is_valid(InnochecksumAlgorithm::StrictInnoDb));
+        assert!(!report.is_valid(InnochecksumAlgorithm::StrictNone));
+
+        // t_empty.ibd was written with innodb_checksum_algorithm=innodb, the
+        // new and old style values are the ones the server recorded
+        let data = test_data("t_empty.ibd");
+        let report = Page::from_bytes(&data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE])
+            .unwrap()
+            .innochecksum();
+        assert_eq!(report.innodb_new, 1268509086);
+        assert_eq!(report.field1, 1268509086);
+        assert_eq!(report.innodb_old, 3983286166);
+        assert_eq!(report.field2, 3983286166);
+        assert!(report.is_valid(InnochecksumAlgorithm::StrictInnoDb));
+        assert!(report.is_valid(InnochecksumAlgorithm::Crc32));
+        assert!(!report.is_valid(InnochecksumAlgorithm::StrictCrc32));
+    }
+
+    #[test]
+    fn test_innochecksum_algorithms() {
+        let buf = index_page();
+        let page = Page::from_bytes(&buf).unwrap();
+        let innodb = PageWriter::new(&page).finalize(ChecksumKind::InnoDb);
+        let report = Page::from_bytes(&innodb).unwrap().innochecksum();
+        assert_eq!(report.field1, report.innodb_new);
+        assert_eq!(report.field2, report.innodb_old);
+        assert!(report.is_valid(InnochecksumAlgorithm::StrictInnoDb));
+        assert!(report.is_valid(InnochecksumAlgorithm::Crc32));
+        assert!(!report.is_valid(InnochecksumAlgorithm::StrictCrc32));
+
+        let none = PageWriter::new(&page).finalize(ChecksumKind::None);
+        let report = Page::from_bytes(&none).unwrap().innochecksum();
+        assert_eq!(report.field1, BUF_NO_CHECKSUM_MAGIC);
+        assert!(report.is_valid(InnochecksumAlgorithm::StrictNone));
+        assert!(report.is_valid(InnochecksumAlgorithm::InnoDb));
+        assert!(!report.is_valid(InnochecksumAlgorithm::StrictCrc32));
+
+        // Legacy big endian crc32 in both fields
+        let mut legacy = buf.clone();
+        let checksum = InnochecksumReport::new(&page).crc32_legacy.to_be_bytes();
+        legacy[..4].copy_from_slice(&checksum);
+        legacy[FIL_PAGE_SIZE - 8..FIL_PAGE_SIZE - 4].copy_from_slice(&checksum);
+        let report = Page::from_bytes(&legacy).unwrap().innochecksum();
+        assert!(report.is_valid(InnochecksumAlgorithm::StrictCrc32));
+
+        // Torn pages fail everything but none
+        let mut torn = buf.clone();
+        torn[FIL_PAGE_SIZE - 1] ^= 1;
+        let report = Page::from_bytes(&torn).unwrap().innochecksum();
+        assert!(!report.is_valid(InnochecksumAlgorithm::Crc32));
+        assert!(report.is_valid(InnochecksumAlgorithm::None));
+
+        let empty = vec![0u8; FIL_PAGE_SIZE];
+        let report = Page::from_bytes(&empty).unwrap().innochecksum();
+        assert!(report.is_valid(InnochecksumAlgorithm::StrictCrc32));
+
+        assert_eq!(
+            "Strict_InnoDB".parse::<InnochecksumAlgorithm>().unwrap(),
+            InnochecksumAlgorithm::StrictInnoDb
+        );
+        assert!("adler32".parse::<InnochecksumAlgorithm>().is_err());
+    }
+}
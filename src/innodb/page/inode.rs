@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+
+use crate::innodb::{
+    buffer_manager::BufferManager,
+    file_list::{FileListBaseNode, FileListInnerNode, FIL_NULL},
+    InnoDBError,
+};
+
+use super::{Page, PageType, FIL_PAGE_BODY_OFFSET, FIL_TRAILER_SIZE};
+
+/// The inode array follows the node linking the page into
+/// FSP_SEG_INODES_FULL or FSP_SEG_INODES_FREE
+pub const FSEG_ARR_OFFSET: usize = FIL_PAGE_BODY_OFFSET + 12;
+pub const FSEG_INODE_SIZE: usize = FSEG_FRAG_ARR_OFFSET + FSEG_FRAG_ARR_N_SLOTS * 4;
+pub const FSEG_FRAG_ARR_N_SLOTS: usize = 32;
+const FSEG_MAGIC_N_VALUE: u32 = 97937874;
+const FSEG_MAGIC_N_OFFSET: usize = 12 + 3 * 16;
+const FSEG_FRAG_ARR_OFFSET: usize = FSEG_MAGIC_N_OFFSET + 4;
+const FSEG_HEADER_SIZE: usize = 10;
+
+/// FSEG header, where the inode of a file segment lives. Index root
+/// pages have one for the leaf and one for the non-leaf segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsegHeader {
+    pub space_id: u32,
+    pub page_number: u32,
+    pub offset: u16,
+}
+
+impl FsegHeader {
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
+        let buf = buf
+            .get(..FSEG_HEADER_SIZE)
+            .ok_or(InnoDBError::InvalidLength)?;
+        Ok(FsegHeader {
+            space_id: u32::from_be_bytes(buf[0..4].try_into().unwrap()),
+            page_number: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            offset: u16::from_be_bytes(buf[8..10].try_into().unwrap()),
+        })
+    }
+
+    /// Reads the inode this header points at
+    pub fn resolve(&self, buffer_mgr: &dyn BufferManager) -> Result<FsegInode> {
+        let guard = buffer_mgr.pin(self.space_id, self.page_number)?;
+        let page = guard.page();
+        InodePage::try_from_page(page)?.inode_at(self.offset as usize)
+    }
+}
+
+/// A file segment, the pages it got one by one and the extents it owns
+#[derive(Debug, Clone)]
+pub struct FsegInode {
+    /// Offset of the inode on its page
+    pub offset: usize,
+    pub fseg_id: u64,
+    /// Used pages in the `not_full` extents
+    pub not_full_n_used: u32,
+    /// Extents with no page in use
+    pub free: FileListBaseNode,
+    /// Extents with some pages in use
+    pub not_full: FileListBaseNode,
+    /// Extents with every page in use
+    pub full: FileListBaseNode,
+    /// Fragment pages, handed out before the segment gets whole extents.
+    /// Unused slots are [`FIL_NULL`].
+    pub frag_arr: [u32; FSEG_FRAG_ARR_N_SLOTS],
+}
+
+impl FsegInode {
+    pub fn try_from_bytes(buf: &[u8], offset: usize) -> Result<Self> {
+        let buf = buf
+            .get(..FSEG_INODE_SIZE)
+            .ok_or(InnoDBError::InvalidLength)?;
+        let magic = u32::from_be_bytes(
+            buf[FSEG_MAGIC_N_OFFSET..FSEG_FRAG_ARR_OFFSET]
+                .try_into()
+                .unwrap(),
+        );
+        if magic != FSEG_MAGIC_N_VALUE {
+            return Err(anyhow!(
+                "Bad magic {} in file segment inode at offset {}",
+                magic,
+                offset
+            ));
+        }
+        let mut frag_arr = [FIL_NULL; FSEG_FRAG_ARR_N_SLOTS];
+        for (slot, bytes) in frag_arr
+            .iter_mut()
+            .zip(buf[FSEG_FRAG_ARR_OFFSET..].chunks_exact(4))
+        {
+            *slot = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        Ok(FsegInode {
+            offset,
+            fseg_id: u64::from_be_bytes(buf[0..8].try_into().unwrap()),
+            not_full_n_used: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            free: FileListBaseNode::try_from_bytes(&buf[12..])?,
+            not_full: FileListBaseNode::try_from_bytes(&buf[28..])?,
+            full: FileListBaseNode::try_from_bytes(&buf[44..])?,
+            frag_arr,
+        })
+    }
+
+    /// Fragment pages in use, in slot order
+    pub fn frag_pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.frag_arr
+            .iter()
+            .copied()
+            .filter(|page_number| *page_number != FIL_NULL)
+    }
+}
+
+/// A page of file segment inodes
+#[derive(Debug)]
+pub struct InodePage<'a> {
+    pub page: Page<'a>,
+    /// Node in FSP_SEG_INODES_FULL or FSP_SEG_INODES_FREE
+    pub list_node: FileListInnerNode,
+}
+
+impl<'a> InodePage<'a> {
+    pub fn try_from_page(page: Page<'a>) -> Result<Self> {
+        if page.header.page_type != PageType::Inode {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::Inode,
+                has: page.header.page_type
+            }));
+        }
+        Ok(InodePage {
+            list_node: FileListInnerNode::try_from_bytes(page.body())?,
+            page,
+        })
+    }
+
+    /// Number of inode slots on the page
+    pub fn capacity(&self) -> usize {
+        (self.page.raw_data.len() - FSEG_ARR_OFFSET - FIL_TRAILER_SIZE - 2) / FSEG_INODE_SIZE
+    }
+
+    /// Inodes of the segments in use on the page, free slots are skipped
+    pub fn inodes(&self) -> impl Iterator<Item = Result<FsegInode>> + '_ {
+        (0..self.capacity())
+            .map(|slot| FSEG_ARR_OFFSET + slot * FSEG_INODE_SIZE)
+            .filter(|offset| self.page.raw_data[*offset..*offset + 8] != [0; 8])
+            .map(|offset| self.inode_at(offset))
+    }
+
+    /// The inode at `offset`, as found in an [`FsegHeader`]
+    pub fn inode_at(&self, offset: usize) -> Result<FsegInode> {
+        if offset < FSEG_ARR_OFFSET || !(offset - FSEG_ARR_OFFSET).is_multiple_of(FSEG_INODE_SIZE) {
+            return Err(anyhow!(
+                "Offset {} of INODE page {} is not at an inode slot",
+                offset,
+                self.page.header.offset
+            ));
+        }
+        let buf = self.page.raw_data.get(offset..).unwrap_or_default();
+        let inode = FsegInode::try_from_bytes(buf, offset)?;
+        if inode.fseg_id == 0 {
+            return Err(anyhow!(
+                "Inode slot at offset {} of page {} is free",
+                offset,
+                self.page.header.offset
+            ));
+        }
+        Ok(inode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        buffer_manager::memory::MemoryBufferManager,
+        page::{index::IndexPage, Page, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{InodePage, FSEG_ARR_OFFSET, FSEG_INODE_SIZE};
+
+    #[test]
+    fn test_inode_page() {
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[2 * FIL_PAGE_SIZE..3 * FIL_PAGE_SIZE]).unwrap();
+        let inode_page = InodePage::try_from_page(page).unwrap();
+        assert_eq!(inode_page.capacity(), 85);
+        assert!(inode_page.list_node.prev.is_null());
+
+        let inodes = inode_page.inodes().collect::<Result<Vec<_>, _>>().unwrap();
+        let ids = inodes.iter().map(|inode| inode.fseg_id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        // SDI and table index, each with its root page in the non-leaf
+        // segment and an empty leaf segment
+        let frags = inodes
+            .iter()
+            .map(|inode| inode.frag_pages().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(frags, [vec![3], vec![], vec![4], vec![]]);
+        assert!(inodes.iter().all(|inode| inode.full.list_len == 0));
+
+        assert!(inode_page.inode_at(FSEG_ARR_OFFSET + 1).is_err());
+        // The slot after the last segment is free
+        assert!(inode_page
+            .inode_at(FSEG_ARR_OFFSET + 4 * FSEG_INODE_SIZE)
+            .is_err());
+
+        let mut buf = data[2 * FIL_PAGE_SIZE..3 * FIL_PAGE_SIZE].to_vec();
+        buf[FSEG_ARR_OFFSET + 60] ^= 0xff;
+        let inode_page = InodePage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(inode_page.inodes().next().unwrap().is_err());
+
+        let index = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        assert!(InodePage::try_from_page(index).is_err());
+    }
+
+    #[test]
+    fn test_resolve_root_segments() {
+        let data = test_data("float_sample.ibd");
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_space(351, &data).unwrap();
+        let page = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        let index = IndexPage::try_from_page(page).unwrap();
+
+        let top = index.non_leaf_segment().unwrap();
+        assert_eq!((top.space_id, top.page_number, top.offset), (351, 2, 0x1b2));
+        let inode = top.resolve(&buffer_mgr).unwrap();
+        assert_eq!(inode.fseg_id, 3);
+        assert_eq!(inode.frag_pages().collect::<Vec<_>>(), [4]);
+
+        let leaf = index.leaf_segment().unwrap().resolve(&buffer_mgr).unwrap();
+        assert_eq!(leaf.fseg_id, 4);
+        assert_eq!(leaf.frag_pages().count(), 0);
+    }
+}
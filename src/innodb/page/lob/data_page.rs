@@ -5,6 +5,8 @@ use crate::innodb::{
     InnoDBError,
 };
 
+use super::read_clamped;
+
 #[derive(Debug, Clone)]
 pub struct LobDataHeader {
     pub version: u8,
@@ -56,17 +58,68 @@ impl<'a> LobData<'a> {
         }
     }
 
+    /// Copies LOB data stored on this page starting at `offset` into `buf`,
+    /// returns 0 when `offset` is at or past the end of the data.
     pub fn read(&self, offset: usize, buf: &mut [u8]) -> usize {
-        let data_len = self.header.data_len as usize;
-        let data = &self.body()[..data_len];
-        assert!(offset < data.len(), "offset too large");
-        let data = &data[offset..];
-        let bytes_to_copy = std::cmp::min(buf.len(), data.len());
-        buf[..bytes_to_copy].copy_from_slice(&data[..bytes_to_copy]);
-        bytes_to_copy
+        let body = self.body();
+        let data_len = std::cmp::min(self.header.data_len as usize, body.len());
+        read_clamped(&body[..data_len], offset, buf)
     }
 
     pub fn body(&self) -> &[u8] {
         &self.page.body()[LobDataHeader::size()..]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::innodb::{
+        page::{Page, PageType, FIL_PAGE_SIZE},
+        testing::blank_page,
+    };
+
+    use super::LobData;
+
+    fn build_data_page(data_len: u32) -> Vec<u8> {
+        let mut buf = blank_page(0, 0, PageType::LobData);
+        buf[39..43].copy_from_slice(&data_len.to_be_bytes());
+        for (idx, b) in buf[49..49 + 16].iter_mut().enumerate() {
+            *b = idx as u8;
+        }
+        buf
+    }
+
+    #[test]
+    fn test_lob_data_read() {
+        let buf = build_data_page(16);
+        let page = Page::from_bytes(&buf).unwrap();
+        let data_page = LobData::try_from_page(&page).unwrap();
+
+        let mut out = [0u8; 8];
+        assert_eq!(data_page.read(0, &mut out), 8);
+        assert_eq!(out, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(data_page.read(12, &mut out), 4);
+        assert_eq!(&out[..4], &[12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn test_lob_data_read_past_end() {
+        let buf = build_data_page(16);
+        let page = Page::from_bytes(&buf).unwrap();
+        let data_page = LobData::try_from_page(&page).unwrap();
+
+        let mut out = [0u8; 8];
+        assert_eq!(data_page.read(16, &mut out), 0);
+        assert_eq!(data_page.read(17, &mut out), 0);
+    }
+
+    #[test]
+    fn test_lob_data_len_larger_than_page() {
+        let buf = build_data_page(u32::MAX);
+        let page = Page::from_bytes(&buf).unwrap();
+        let data_page = LobData::try_from_page(&page).unwrap();
+
+        let mut out = vec![0u8; FIL_PAGE_SIZE];
+        assert_eq!(data_page.read(0, &mut out), data_page.body().len());
+    }
+}
@@ -1,10 +1,12 @@
 use crate::innodb::{
-    file_list::{FileListBaseNode, FileListInnerNode},
+    buffer_manager::BufferManager,
+    file_list::{FileAddress, FileListBaseNode, FileListInnerNode, FIL_NULL},
+    table::blob_header::ExternReference,
     InnoDBError,
 };
 use anyhow::{anyhow, Ok, Result};
 
-use super::{Page, PageType};
+use super::{Page, PageType, FIL_PAGE_BODY_OFFSET};
 
 pub mod data_page;
 
@@ -24,6 +26,17 @@ pub mod data_page;
  *
  */
 
+/// Copies `data[offset..]` into `buf` returning the number of bytes copied,
+/// nothing is copied if `offset` is out of range.
+pub(crate) fn read_clamped(data: &[u8], offset: usize, buf: &mut [u8]) -> usize {
+    let Some(data) = data.get(offset..) else {
+        return 0;
+    };
+    let bytes_to_copy = std::cmp::min(buf.len(), data.len());
+    buf[..bytes_to_copy].copy_from_slice(&data[..bytes_to_copy]);
+    bytes_to_copy
+}
+
 #[derive(Debug, Clone)]
 pub struct LobFirstHeader {
     pub version: u8,
@@ -39,7 +52,7 @@ pub struct LobFirstHeader {
 
 impl LobFirstHeader {
     pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
-        if buf.len() < 54 {
+        if buf.len() < Self::size() {
             return Err(anyhow!("Buffer is too small for LobHeader"));
         }
 
@@ -74,16 +87,24 @@ impl LobFirstHeader {
         })
     }
 
+    /// Matches LOB_PAGE_DATA - FIL_PAGE_DATA in lob0first.h
     pub fn size() -> usize {
         1 + // Version
         1 + // Flags
         4 + // LOB Version
+        6 + // Last trx id
+        4 + // Last undo number
+        4 + // Data length
         6 + // trx id
-        4 + // undo id
-            4 + 6 + FileListBaseNode::size() + FileListBaseNode::size()
+        FileListBaseNode::size() + // Index list
+        FileListBaseNode::size() // Free list
     }
 }
 
+/// Index entries stored on the first page, lob0first.h::node_count() is the
+/// same for every page size
+pub const LOB_FIRST_NODE_COUNT: usize = 10;
+
 #[derive(Debug)]
 pub struct LobFirst<'a> {
     pub page: &'a Page<'a>,
@@ -104,20 +125,103 @@ impl<'a> LobFirst<'a> {
         }
     }
 
+    /// Copies LOB data stored on this page starting at `offset` into `buf`,
+    /// returns 0 when `offset` is at or past the end of the data.
     pub fn read(&self, offset: usize, buf: &mut [u8]) -> usize {
-        let index_array_size = LobIndexEntry::size() * 10; // Hardcoded? somehow see mysql: lob0first.h::node_count()
-        let data_len = self.header.data_length as usize;
-        let data = &self.body()[index_array_size..][..data_len];
-        assert!(offset < data.len(), "offset too large");
-        let data = &data[offset..];
-        let bytes_to_copy = std::cmp::min(buf.len(), data.len());
-        buf[..bytes_to_copy].copy_from_slice(&data[..bytes_to_copy]);
-        bytes_to_copy
+        let index_array_size = LobIndexEntry::size() * LOB_FIRST_NODE_COUNT;
+        let data = self.body().get(index_array_size..).unwrap_or(&[]);
+        let data_len = std::cmp::min(self.header.data_length as usize, data.len());
+        read_clamped(&data[..data_len], offset, buf)
     }
 
     pub fn body(&self) -> &[u8] {
         &self.page.body()[LobFirstHeader::size()..]
     }
+
+    /// Walks the index list, yielding the entry describing each chunk of the
+    /// LOB in order. Entries living on other pages are loaded through
+    /// `buffer_mgr`.
+    pub fn index_entries<'b>(&'b self, buffer_mgr: &'b dyn BufferManager) -> LobIndexEntries<'b> {
+        LobIndexEntries::new(self, &self.header.index_list_head, buffer_mgr)
+    }
+
+    /// Same as [`LobFirst::index_entries`] but for the list of unused entries.
+    pub fn free_entries<'b>(&'b self, buffer_mgr: &'b dyn BufferManager) -> LobIndexEntries<'b> {
+        LobIndexEntries::new(self, &self.header.free_list_head, buffer_mgr)
+    }
+}
+
+/// Iterator over a file list of [`LobIndexEntry`] rooted in a [`LobFirst`]
+/// page. Iteration stops after the first error.
+pub struct LobIndexEntries<'a> {
+    first: &'a LobFirst<'a>,
+    buffer_mgr: &'a dyn BufferManager,
+    next: FileAddress,
+    remaining: u32,
+}
+
+impl<'a> LobIndexEntries<'a> {
+    fn new(
+        first: &'a LobFirst<'a>,
+        base: &FileListBaseNode,
+        buffer_mgr: &'a dyn BufferManager,
+    ) -> Self {
+        LobIndexEntries {
+            first,
+            buffer_mgr,
+            next: base.first_node,
+            remaining: base.list_len,
+        }
+    }
+
+    fn load(&self, location: FileAddress) -> Result<LobIndexEntry> {
+        let offset = location.offset as usize;
+        if location.page_number == self.first.page.header.offset {
+            let buf = self
+                .first
+                .page
+                .raw_data
+                .get(offset..)
+                .ok_or(InnoDBError::InvalidLength)?;
+            return LobIndexEntry::try_from_bytes(buf);
+        }
+
+        let space_id = self.first.page.header.space_id;
+        let guard = self.buffer_mgr.pin(space_id, location.page_number)?;
+        let buf = guard
+            .raw_data()
+            .get(offset..)
+            .ok_or(InnoDBError::InvalidLength)?;
+        LobIndexEntry::try_from_bytes(buf)
+    }
+}
+
+impl<'a> Iterator for LobIndexEntries<'a> {
+    type Item = Result<LobIndexEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next.is_null() {
+            return None;
+        }
+
+        let location = self.next;
+        // Stop here no matter what, only continue once this node is good
+        self.next = FileAddress::new(FIL_NULL, 0);
+
+        if self.remaining == 0 {
+            return Some(Err(anyhow!(
+                "LOB index list is longer than its base node claims, at {:?}",
+                location
+            )));
+        }
+        self.remaining -= 1;
+
+        let entry = self.load(location);
+        if let Result::Ok(entry) = &entry {
+            self.next = entry.file_list_node.next;
+        }
+        Some(entry)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -220,7 +324,6 @@ impl LobIndexEntry {
             bytes[offset + 2],
             bytes[offset + 3],
         ]);
-        offset += 4;
 
         Ok(LobIndexEntry {
             file_list_node,
@@ -239,3 +342,464 @@ impl LobIndexEntry {
         60
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LobFinding {
+    UnexpectedPageType {
+        expected: PageType,
+        has: PageType,
+    },
+    SpaceIdMismatch {
+        expected: u32,
+        has: u32,
+    },
+    PageUnavailable(String),
+    /// Index entry for the first page disagrees with LobFirstHeader.data_length
+    FirstPageLengthMismatch {
+        header: u32,
+        entry: u16,
+    },
+    /// No index entry points at the first page, so its data_length of
+    /// LobFirstHeader.data_length bytes isn't part of the chain
+    FirstPageNotIndexed {
+        header: u32,
+    },
+    /// The index entries add up to another length than the extern reference
+    LengthMismatch {
+        expected: u64,
+        total: u64,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct LobPageReport {
+    pub page_number: u32,
+    pub data_length: u16,
+    pub findings: Vec<LobFinding>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LobReport {
+    pub pages: Vec<LobPageReport>,
+    /// Sum of the data_length of every index entry
+    pub total_data_length: u64,
+    /// Length recorded in the extern reference of the clustered record
+    pub expected_length: u64,
+    /// Set when walking the index list itself failed
+    pub index_list_error: Option<String>,
+    /// Findings about the chain as a whole
+    pub findings: Vec<LobFinding>,
+}
+
+impl LobReport {
+    pub fn is_consistent(&self) -> bool {
+        self.index_list_error.is_none()
+            && self.findings.is_empty()
+            && self.pages.iter().all(|p| p.findings.is_empty())
+    }
+}
+
+/// Walks the LOB index of `extern_ref` checking every referenced page without
+/// copying any data out. Only failing to load the first page is an error,
+/// everything past that is recorded in the report.
+pub fn verify_chain(
+    extern_ref: &ExternReference,
+    buffer_mgr: &dyn BufferManager,
+) -> Result<LobReport> {
+    let space_id = extern_ref.space_id;
+    let first_page = buffer_mgr.pin(space_id, extern_ref.page_number)?;
+    let first_page = first_page.page();
+    let lob_first = LobFirst::try_from_page(&first_page)?;
+
+    let mut report = LobReport {
+        expected_length: extern_ref.length,
+        ..Default::default()
+    };
+
+    for entry in lob_first.index_entries(buffer_mgr) {
+        let entry = match entry {
+            Result::Ok(entry) => entry,
+            Err(e) => {
+                report.index_list_error = Some(format!("{:?}", e));
+                break;
+            }
+        };
+
+        let mut findings = Vec::new();
+        if entry.page_number == extern_ref.page_number {
+            if entry.data_length as u32 != lob_first.header.data_length {
+                findings.push(LobFinding::FirstPageLengthMismatch {
+                    header: lob_first.header.data_length,
+                    entry: entry.data_length,
+                });
+            }
+        } else {
+            match buffer_mgr.pin(space_id, entry.page_number) {
+                Result::Ok(page) => {
+                    if page.header.page_type != PageType::LobData {
+                        findings.push(LobFinding::UnexpectedPageType {
+                            expected: PageType::LobData,
+                            has: page.header.page_type,
+                        });
+                    }
+                    if page.header.space_id != space_id {
+                        findings.push(LobFinding::SpaceIdMismatch {
+                            expected: space_id,
+                            has: page.header.space_id,
+                        });
+                    }
+                }
+                Err(e) => findings.push(LobFinding::PageUnavailable(format!("{:?}", e))),
+            }
+        }
+
+        report.total_data_length += entry.data_length as u64;
+        report.pages.push(LobPageReport {
+            page_number: entry.page_number,
+            data_length: entry.data_length,
+            findings,
+        });
+    }
+
+    // A list whose nodes end before its base node's length stops without an
+    // error, only the lengths tell
+    if report.index_list_error.is_none()
+        && !report
+            .pages
+            .iter()
+            .any(|p| p.page_number == extern_ref.page_number)
+    {
+        report.findings.push(LobFinding::FirstPageNotIndexed {
+            header: lob_first.header.data_length,
+        });
+    }
+    if report.total_data_length != report.expected_length {
+        report.findings.push(LobFinding::LengthMismatch {
+            expected: report.expected_length,
+            total: report.total_data_length,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Header of a page in the old BLOB format, before the data
+const BLOB_HDR_PART_LEN: usize = 0;
+const BLOB_HDR_NEXT_PAGE_NO: usize = 4;
+const BLOB_HDR_SIZE: usize = 8;
+
+/// Reads a value stored in the old BLOB format, a plain list of BLOB or
+/// SDI_BLOB pages each holding the length of its part and the next page.
+/// SDI keeps using it in MySQL 8.0.
+pub fn read_blob_chain(
+    extern_ref: &ExternReference,
+    buffer_mgr: &dyn BufferManager,
+) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(extern_ref.length as usize);
+    let mut page_number = extern_ref.page_number;
+    let mut offset = extern_ref.offset as usize;
+    while (data.len() as u64) < extern_ref.length {
+        if page_number == FIL_NULL {
+            return Err(anyhow!(
+                "BLOB chain ends after {} of {} bytes",
+                data.len(),
+                extern_ref.length
+            ));
+        }
+        let guard = buffer_mgr.pin(extern_ref.space_id, page_number)?;
+        if !matches!(guard.header.page_type, PageType::Blob | PageType::SdiBlob) {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::Blob,
+                has: guard.header.page_type
+            }));
+        }
+        let buf = guard.raw_data();
+        let header = buf
+            .get(offset..offset + BLOB_HDR_SIZE)
+            .ok_or(InnoDBError::InvalidLength)?;
+        let part_len = u32::from_be_bytes(
+            header[BLOB_HDR_PART_LEN..BLOB_HDR_NEXT_PAGE_NO]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let next = u32::from_be_bytes(header[BLOB_HDR_NEXT_PAGE_NO..].try_into().unwrap());
+        let part = buf
+            .get(offset + BLOB_HDR_SIZE..offset + BLOB_HDR_SIZE + part_len)
+            .ok_or_else(|| {
+                anyhow!(
+                    "BLOB page {} has a part of {} bytes, more than fits",
+                    page_number,
+                    part_len
+                )
+            })?;
+        // A chain can't loop forever when every page adds something
+        if part.is_empty() {
+            return Err(anyhow!("BLOB page {} holds no data", page_number));
+        }
+        data.extend_from_slice(part);
+        page_number = next;
+        // Only the first page can start somewhere else
+        offset = FIL_PAGE_BODY_OFFSET;
+    }
+    data.truncate(extern_ref.length as usize);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::innodb::{
+        buffer_manager::{memory::MemoryBufferManager, DummyBufferMangaer},
+        file_list::FIL_NULL,
+        page::{Page, PageType},
+        table::blob_header::ExternReference,
+        testing::{blank_page, lob_pages},
+    };
+
+    use super::{
+        read_blob_chain, verify_chain, LobFinding, LobFirst, LobFirstHeader, LobIndexEntry,
+    };
+
+    const FIRST_PAGE: u32 = 7;
+    const ENTRY_BASE: usize = 38 + 58;
+
+    fn entry_offset(idx: usize) -> u16 {
+        (ENTRY_BASE + idx * LobIndexEntry::size()) as u16
+    }
+
+    fn write_base_node(buf: &mut [u8], at: usize, len: u32, first: u16) {
+        buf[at..at + 4].copy_from_slice(&len.to_be_bytes());
+        buf[at + 4..at + 8].copy_from_slice(&FIRST_PAGE.to_be_bytes());
+        buf[at + 8..at + 10].copy_from_slice(&first.to_be_bytes());
+    }
+
+    fn write_entry(buf: &mut [u8], at: u16, next: Option<u16>, page_number: u32, len: u16) {
+        let at = at as usize;
+        let (next_page, next_offset) = match next {
+            Some(offset) => (FIRST_PAGE, offset),
+            None => (FIL_NULL, 0),
+        };
+        buf[at + 6..at + 10].copy_from_slice(&next_page.to_be_bytes());
+        buf[at + 10..at + 12].copy_from_slice(&next_offset.to_be_bytes());
+        buf[at + 48..at + 52].copy_from_slice(&page_number.to_be_bytes());
+        buf[at + 52..at + 54].copy_from_slice(&len.to_be_bytes());
+    }
+
+    const SPACE_ID: u32 = 5;
+
+    fn build_lob_first(index_list_len: u32) -> Vec<u8> {
+        let mut buf = blank_page(SPACE_ID, FIRST_PAGE, PageType::LobFirst);
+        // data_length of the first page
+        buf[38 + 16..38 + 20].copy_from_slice(&100u32.to_be_bytes());
+
+        // Index list: 0 -> 2 -> 1, free list: 3
+        write_base_node(&mut buf, 38 + 26, index_list_len, entry_offset(0));
+        write_base_node(&mut buf, 38 + 42, 1, entry_offset(3));
+        write_entry(&mut buf, entry_offset(0), Some(entry_offset(2)), 7, 100);
+        write_entry(&mut buf, entry_offset(2), Some(entry_offset(1)), 8, 200);
+        write_entry(&mut buf, entry_offset(1), None, 9, 300);
+        write_entry(&mut buf, entry_offset(3), None, 0, 0);
+        buf
+    }
+
+    #[test]
+    fn test_lob_first_header_minimum_length() {
+        assert_eq!(LobFirstHeader::size(), 58);
+
+        let mut buf = vec![0u8; LobFirstHeader::size()];
+        buf[16..20].copy_from_slice(&1234u32.to_be_bytes());
+        buf[42..46].copy_from_slice(&1u32.to_be_bytes());
+        let header = LobFirstHeader::try_from_bytes(&buf).unwrap();
+        assert_eq!(header.data_length, 1234);
+        assert_eq!(header.free_list_head.list_len, 1);
+
+        assert!(LobFirstHeader::try_from_bytes(&buf[..LobFirstHeader::size() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_lob_first_read_past_end() {
+        let buf = build_lob_first(3);
+        let page = Page::from_bytes(&buf).unwrap();
+        let lob_first = LobFirst::try_from_page(&page).unwrap();
+
+        let mut out = [0u8; 200];
+        assert_eq!(lob_first.read(0, &mut out), 100);
+        assert_eq!(lob_first.read(60, &mut out), 40);
+        assert_eq!(lob_first.read(100, &mut out), 0);
+        assert_eq!(lob_first.read(101, &mut out), 0);
+        assert_eq!(lob_first.read(usize::MAX, &mut out), 0);
+    }
+
+    #[test]
+    fn test_index_entries_follow_list() {
+        let buf = build_lob_first(3);
+        let page = Page::from_bytes(&buf).unwrap();
+        let lob_first = LobFirst::try_from_page(&page).unwrap();
+
+        let entries = lob_first
+            .index_entries(&DummyBufferMangaer)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        let pages: Vec<(u32, u16)> = entries
+            .iter()
+            .map(|e| (e.page_number, e.data_length))
+            .collect();
+        assert_eq!(pages, vec![(7, 100), (8, 200), (9, 300)]);
+
+        let free: Vec<_> = lob_first.free_entries(&DummyBufferMangaer).collect();
+        assert_eq!(free.len(), 1);
+        assert!(free[0].is_ok());
+    }
+
+    #[test]
+    fn test_index_entries_longer_than_base_node() {
+        let buf = build_lob_first(2);
+        let page = Page::from_bytes(&buf).unwrap();
+        let lob_first = LobFirst::try_from_page(&page).unwrap();
+
+        let entries: Vec<_> = lob_first.index_entries(&DummyBufferMangaer).collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_ok());
+        assert!(entries[1].is_ok());
+        assert!(entries[2].is_err());
+    }
+
+    fn memory_buffer_manager(pages: &[Vec<u8>]) -> MemoryBufferManager {
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in pages {
+            buffer_mgr.add_page(page).unwrap();
+        }
+        buffer_mgr
+    }
+
+    /// Chunks of 100, 200 and 300 bytes on pages 7, 8 and 9
+    fn chain_pages() -> Vec<Vec<u8>> {
+        let data = [0u8; 600];
+        lob_pages(
+            SPACE_ID,
+            FIRST_PAGE,
+            &[&data[..100], &data[100..300], &data[300..]],
+        )
+    }
+
+    fn extern_ref(length: u64) -> ExternReference {
+        ExternReference {
+            space_id: SPACE_ID,
+            page_number: FIRST_PAGE,
+            offset: 1,
+            owner: true,
+            inherit: false,
+            length,
+        }
+    }
+
+    #[test]
+    fn test_verify_chain_reports_bad_pages() {
+        let mut pages = chain_pages();
+        pages[2] = blank_page(SPACE_ID, 9, PageType::Index);
+        let buffer_mgr = memory_buffer_manager(&pages);
+
+        let report = verify_chain(&extern_ref(600), &buffer_mgr).unwrap();
+        assert_eq!(report.total_data_length, 600);
+        assert_eq!(report.pages.len(), 3);
+        assert!(report.pages[0].findings.is_empty());
+        assert!(report.pages[1].findings.is_empty());
+        assert_eq!(
+            report.pages[2].findings,
+            vec![LobFinding::UnexpectedPageType {
+                expected: PageType::LobData,
+                has: PageType::Index
+            }]
+        );
+        assert!(!report.is_consistent());
+    }
+
+    #[test]
+    fn test_verify_chain_length_mismatch() {
+        let pages = chain_pages();
+        let buffer_mgr = memory_buffer_manager(&pages);
+
+        let report = verify_chain(&extern_ref(600), &buffer_mgr).unwrap();
+        assert!(report.is_consistent());
+
+        let report = verify_chain(&extern_ref(500), &buffer_mgr).unwrap();
+        assert!(!report.is_consistent());
+        assert!(report.pages.iter().all(|p| p.findings.is_empty()));
+        assert_eq!(
+            report.findings,
+            vec![LobFinding::LengthMismatch {
+                expected: 500,
+                total: 600
+            }]
+        );
+
+        let buffer_mgr = memory_buffer_manager(&pages[..1]);
+        let report = verify_chain(&extern_ref(600), &buffer_mgr).unwrap();
+        assert!(matches!(
+            report.pages[1].findings[..],
+            [LobFinding::PageUnavailable(_)]
+        ));
+    }
+
+    #[test]
+    fn test_verify_chain_truncated() {
+        // The list ends after page 8 although the base node counts 3 entries
+        let mut pages = chain_pages();
+        write_entry(&mut pages[0], entry_offset(1), None, 8, 200);
+        let buffer_mgr = memory_buffer_manager(&pages);
+
+        let report = verify_chain(&extern_ref(600), &buffer_mgr).unwrap();
+        assert!(report.index_list_error.is_none());
+        assert_eq!(report.pages.len(), 2);
+        assert_eq!(
+            report.findings,
+            vec![LobFinding::LengthMismatch {
+                expected: 600,
+                total: 300
+            }]
+        );
+        assert!(!report.is_consistent());
+
+        // The list skips the first page, its 100 bytes are missing
+        let mut pages = chain_pages();
+        write_base_node(&mut pages[0], 38 + 26, 2, entry_offset(1));
+        let buffer_mgr = memory_buffer_manager(&pages);
+        let report = verify_chain(&extern_ref(500), &buffer_mgr).unwrap();
+        assert_eq!(
+            report.findings,
+            vec![LobFinding::FirstPageNotIndexed { header: 100 }]
+        );
+        assert!(!report.is_consistent());
+    }
+
+    #[test]
+    fn test_read_blob_chain() {
+        // 30 bytes on page 7 after a 10 byte local prefix, 20 on page 8
+        let mut first = blank_page(SPACE_ID, 7, PageType::SdiBlob);
+        first[48..52].copy_from_slice(&30u32.to_be_bytes());
+        first[52..56].copy_from_slice(&8u32.to_be_bytes());
+        first[56..86].fill(b'a');
+        let mut second = blank_page(SPACE_ID, 8, PageType::SdiBlob);
+        second[38..42].copy_from_slice(&20u32.to_be_bytes());
+        second[42..46].copy_from_slice(&FIL_NULL.to_be_bytes());
+        second[46..66].fill(b'b');
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_page(&first).unwrap();
+        buffer_mgr.add_page(&second).unwrap();
+
+        let mut extern_ref = ExternReference {
+            space_id: SPACE_ID,
+            page_number: 7,
+            offset: 48,
+            owner: true,
+            inherit: false,
+            length: 50,
+        };
+        let data = read_blob_chain(&extern_ref, &buffer_mgr).unwrap();
+        assert_eq!(data, [[b'a'; 30].as_slice(), &[b'b'; 20]].concat());
+
+        extern_ref.length = 60;
+        assert!(read_blob_chain(&extern_ref, &buffer_mgr).is_err());
+        extern_ref.page_number = 9;
+        assert!(read_blob_chain(&extern_ref, &buffer_mgr).is_err());
+    }
+}
@@ -1,23 +1,48 @@
+pub mod fsp;
+pub mod ibuf;
 pub mod index;
+pub mod innochecksum;
+pub mod inode;
 pub mod lob;
+pub mod rseg;
+pub mod rtree;
+pub mod trx_sys;
+pub mod undo;
+pub mod writer;
+pub mod xdes;
+pub mod zip;
 
-use std::fmt::Debug;
+use std::{
+    fmt::{Debug, Display},
+    io::Read,
+    str::FromStr,
+};
 
-use anyhow::{Error, Result};
-use crc::{Crc, CRC_32_ISCSI};
+use anyhow::{anyhow, Error, Result};
 use num_enum::TryFromPrimitive;
 use tracing::debug;
 
+use super::InnoDBError;
+
+use fsp::FspHeaderPage;
+use innochecksum::InnochecksumReport;
+
 // #define UT_HASH_RANDOM_MASK     1463735687
 // #define UT_HASH_RANDOM_MASK2    1653893711
 const HASH_RANDOM_MASK: u32 = 1_463_735_687;
 const HASH_RANDOM_MASK2: u32 = 1_653_893_711;
 
+/// Default innodb_page_size, see [`PageSize`] for the others
 pub const FIL_PAGE_SIZE: usize = 16384;
 const FIL_TRAILER_SIZE: usize = 8;
 
+const FSP_FLAGS_POS_PAGE_SSIZE: u32 = 6;
+const FSP_FLAGS_MASK_PAGE_SSIZE: u32 = 0xF << FSP_FLAGS_POS_PAGE_SSIZE;
+
 const FIL_HEADER_OFFSET: usize = 0;
-const FIL_HEADER_SIZE: usize = 38;
+pub(crate) const FIL_HEADER_SIZE: usize = 38;
+
+const FIL_PAGE_FILE_FLUSH_LSN_OFFSET: usize = 26;
 
 /// Skips CHECKSUM field (4 bytes)
 const FIL_HEADER_PARTIAL_OFFSET: usize = 4;
@@ -26,9 +51,6 @@ const FIL_HEADER_PARTIAL_OFFSET: usize = 4;
 const FIL_HEADER_PARTIAL_SIZE: usize = FIL_HEADER_SIZE - 4 - 8 - 4;
 
 const FIL_PAGE_BODY_OFFSET: usize = FIL_HEADER_OFFSET + FIL_HEADER_SIZE;
-const FIL_PAGE_BODY_SIZE: usize = FIL_PAGE_SIZE - FIL_HEADER_SIZE - FIL_TRAILER_SIZE;
-
-const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
 
 fn fold_pair(n1: u32, n2: u32) -> u32 {
     ((((n1 ^ n2 ^ HASH_RANDOM_MASK2) << 8).wrapping_add(n1)) ^ HASH_RANDOM_MASK).wrapping_add(n2)
@@ -42,6 +64,209 @@ fn fold_bytes(buf: &[u8]) -> u32 {
     fold
 }
 
+/// Supported values of innodb_page_size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PageSize {
+    Size4K,
+    Size8K,
+    #[default]
+    Size16K,
+    Size32K,
+    Size64K,
+}
+
+impl PageSize {
+    pub const ALL: [PageSize; 5] = [
+        PageSize::Size4K,
+        PageSize::Size8K,
+        PageSize::Size16K,
+        PageSize::Size32K,
+        PageSize::Size64K,
+    ];
+
+    pub fn bytes(self) -> usize {
+        match self {
+            PageSize::Size4K => 4096,
+            PageSize::Size8K => 8192,
+            PageSize::Size16K => 16384,
+            PageSize::Size32K => 32768,
+            PageSize::Size64K => 65536,
+        }
+    }
+
+    pub fn from_len(len: usize) -> Option<PageSize> {
+        Self::ALL.into_iter().find(|size| size.bytes() == len)
+    }
+
+    /// Page size encoded in FSP_SPACE_FLAGS, 0 is used by tablespaces
+    /// created before the page size was configurable
+    pub fn from_fsp_flags(flags: u32) -> Result<PageSize> {
+        let ssize = (flags & FSP_FLAGS_MASK_PAGE_SSIZE) >> FSP_FLAGS_POS_PAGE_SSIZE;
+        match ssize {
+            0 => Ok(PageSize::Size16K),
+            3..=7 => Ok(Self::from_len(512 << ssize).unwrap()),
+            _ => Err(anyhow!(
+                "Unsupported page size shift {} in FSP flags",
+                ssize
+            )),
+        }
+    }
+
+    /// Reads the page size from the tablespace header, `buf` only needs to
+    /// hold the start of page 0 which is at the same place for every size
+    pub fn detect(buf: &[u8]) -> Result<PageSize> {
+        FspHeaderPage::try_from_bytes(buf)?.logical_page_size()
+    }
+}
+
+impl Display for PageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}K", self.bytes() / 1024)
+    }
+}
+
+impl FromStr for PageSize {
+    type Err = Error;
+
+    /// Accepts both `16K` and `16384`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let len = match s.strip_suffix(['k', 'K']) {
+            Some(kb) => kb.parse::<usize>().map(|kb| kb * 1024),
+            None => s.parse::<usize>(),
+        };
+        len.ok()
+            .and_then(PageSize::from_len)
+            .ok_or_else(|| anyhow!("Invalid page size {}, expected 4K, 8K, 16K, 32K or 64K", s))
+    }
+}
+
+/// The only length check for page buffers, everything slicing a page
+/// relies on it
+fn check_page_length(len: usize, size: PageSize) -> Result<()> {
+    if len != size.bytes() {
+        return Err(anyhow!("Page is {} bytes instead of {}", len, size.bytes()));
+    }
+    Ok(())
+}
+
+/// Panics with a clear message instead of an out of range slice when `buf`
+/// can't hold the FIL header and trailer
+fn assert_page_frame(buf: &[u8]) {
+    assert!(
+        buf.len() >= FIL_HEADER_SIZE + FIL_TRAILER_SIZE,
+        "Page buffer of {} bytes can't hold the FIL header and trailer",
+        buf.len()
+    );
+}
+
+fn checksum_header(buf: &[u8]) -> &[u8] {
+    assert_page_frame(buf);
+    &buf[FIL_HEADER_PARTIAL_OFFSET..][..FIL_HEADER_PARTIAL_SIZE]
+}
+
+/// Part of a page of any size covered by the checksums, between the header
+/// and the trailer
+fn checksum_body(buf: &[u8]) -> &[u8] {
+    assert_page_frame(buf);
+    &buf[FIL_PAGE_BODY_OFFSET..buf.len() - FIL_TRAILER_SIZE]
+}
+
+/// "innodb" checksum of a whole page, the page size is the length of `buf`
+pub fn innodb_checksum(buf: &[u8]) -> u32 {
+    fold_bytes(checksum_header(buf)).wrapping_add(fold_bytes(checksum_body(buf)))
+}
+
+/// "innodb" checksum the trailer holds, over the header up to
+/// FIL_PAGE_FILE_FLUSH_LSN
+pub fn innodb_old_checksum(buf: &[u8]) -> u32 {
+    assert_page_frame(buf);
+    fold_bytes(&buf[..FIL_PAGE_FILE_FLUSH_LSN_OFFSET])
+}
+
+/// "crc32" checksum of a whole page, the page size is the length of `buf`
+pub fn crc32_checksum(buf: &[u8]) -> u32 {
+    crc32c::crc32c(checksum_header(buf)) ^ crc32c::crc32c(checksum_body(buf))
+}
+
+/// "full_crc32" checksum, a single CRC over everything but the checksum
+/// itself which takes the last 4 bytes of the page
+pub fn full_crc32_checksum(buf: &[u8]) -> u32 {
+    assert_page_frame(buf);
+    crc32c::crc32c(&buf[..buf.len() - 4])
+}
+
+/// Stored in both checksum fields by innodb_checksum_algorithm=none
+pub const BUF_NO_CHECKSUM_MAGIC: u32 = 0xDEAD_BEEF;
+
+/// Checksum algorithms a page can be written with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumKind {
+    Crc32,
+    InnoDb,
+    /// The checksum is in the last 4 bytes instead of the FIL header, which
+    /// leaves [`FILTrailer::lsn_low_32`] holding the checksum and
+    /// [`FILTrailer::old_checksum`] the low LSN bits
+    FullCrc32,
+    /// No checksum, both fields hold [`BUF_NO_CHECKSUM_MAGIC`]
+    None,
+}
+
+/// Which checksums [`Page::validate_checksum`] accepts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChecksumPolicy {
+    /// Any page with the magic value passes, which makes random data much
+    /// more likely to pass for a page
+    pub allow_none: bool,
+}
+
+/// What [`Page::validate`] makes of a page buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageValidation {
+    ValidCrc32,
+    ValidInnoDb,
+    ValidFullCrc32,
+    /// Written with innodb_checksum_algorithm=none, only when the policy
+    /// allows it
+    ValidNone,
+    /// The low LSN bits of the header and trailer differ, the write of the
+    /// page was interrupted
+    TornPage {
+        header_lsn: u64,
+        trailer_lsn: u32,
+    },
+    /// The LSNs agree but no checksum matches
+    BadChecksum {
+        stored: u32,
+        crc32: u32,
+        innodb: u32,
+    },
+    /// Allocated but never written
+    Empty,
+    /// Encrypted, the checksum and trailer can only be checked once the
+    /// page is decrypted
+    Encrypted,
+    /// The page type is unknown, most likely not a page at all
+    NotAPage,
+}
+
+impl PageValidation {
+    pub fn is_valid(&self) -> bool {
+        self.checksum_kind().is_some()
+    }
+
+    /// The algorithm of a valid page
+    pub fn checksum_kind(&self) -> Option<ChecksumKind> {
+        match self {
+            PageValidation::ValidCrc32 => Some(ChecksumKind::Crc32),
+            PageValidation::ValidInnoDb => Some(ChecksumKind::InnoDb),
+            PageValidation::ValidFullCrc32 => Some(ChecksumKind::FullCrc32),
+            PageValidation::ValidNone => Some(ChecksumKind::None),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Default, PartialEq)]
 pub struct Page<'a> {
     // pub space_id: u32,
@@ -61,36 +286,232 @@ impl<'a> Debug for Page<'a> {
 
 impl<'a> Page<'a> {
     pub fn from_bytes(buf: &'a [u8]) -> Result<Page<'a>> {
-        if buf.len() != 16384 {
-            return Err(Error::msg("Page is 16kB"));
-        }
+        Self::from_bytes_with_size(buf, PageSize::default())
+    }
+
+    pub fn from_bytes_with_size(buf: &'a [u8], size: PageSize) -> Result<Page<'a>> {
+        check_page_length(buf.len(), size)?;
 
-        let header = FILHeader::from_bytes(&buf[0..38])?;
+        let header = FILHeader::from_bytes(&buf[0..FIL_HEADER_SIZE])?;
 
         Ok(Page {
             // space_id: header.space_id,
             header,
-            trailer: FILTrailer::from_bytes(&buf[(FIL_PAGE_SIZE - FIL_TRAILER_SIZE)..])?,
+            trailer: FILTrailer::from_bytes(&buf[(size.bytes() - FIL_TRAILER_SIZE)..])?,
             raw_data: buf,
         })
     }
 
+    /// Size of the page buffer, the default size for the empty default page
+    pub fn size(&self) -> PageSize {
+        PageSize::from_len(self.raw_data.len()).unwrap_or_default()
+    }
+
+    /// Checks that the page is the one expected where it was read from,
+    /// pages of sorted page files sit at the index of their offset
+    pub fn validate_position(&self, expected_offset: u32, expected_space: u32) -> Result<()> {
+        let has = (self.header.space_id, self.header.offset);
+        if has != (expected_space, expected_offset) {
+            return Err(anyhow!(InnoDBError::PagePositionMismatch {
+                expected: (expected_space, expected_offset),
+                has,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Header bytes covered by the checksum. Like [`Page::body`] this panics
+    /// on pages that weren't built by [`Page::from_bytes`], such as the
+    /// empty default page.
     pub fn partial_page_header(&self) -> &[u8] {
-        &self.raw_data[FIL_HEADER_PARTIAL_OFFSET..][..FIL_HEADER_PARTIAL_SIZE]
+        checksum_header(self.raw_data)
     }
 
     pub fn body(&self) -> &[u8] {
-        &self.raw_data[FIL_PAGE_BODY_OFFSET..][..FIL_PAGE_BODY_SIZE]
+        checksum_body(self.raw_data)
     }
 
     pub fn innodb_checksum(&self) -> u32 {
-        let header_checksum = fold_bytes(self.partial_page_header());
-        let body_checksum = fold_bytes(self.body());
-        header_checksum.wrapping_add(body_checksum)
+        innodb_checksum(self.raw_data)
     }
 
     pub fn crc32_checksum(&self) -> u32 {
-        CRC32C.checksum(self.partial_page_header()) ^ CRC32C.checksum(self.body())
+        crc32_checksum(self.raw_data)
+    }
+
+    pub fn full_crc32_checksum(&self) -> u32 {
+        full_crc32_checksum(self.raw_data)
+    }
+
+    /// Checksums as innochecksum computes them, to cross-check its output
+    pub fn innochecksum(&self) -> InnochecksumReport {
+        InnochecksumReport::new(self)
+    }
+
+    /// The algorithm whose checksum matches the stored one, `None` when the
+    /// page is corrupted. Pages without a checksum are not accepted, see
+    /// [`Page::validate_checksum`].
+    pub fn checksum_matches(&self) -> Option<ChecksumKind> {
+        self.validate_checksum(ChecksumPolicy::default())
+    }
+
+    /// Like [`Page::checksum_matches`], also accepting the algorithms allowed by
+    /// `policy`
+    pub fn validate_checksum(&self, policy: ChecksumPolicy) -> Option<ChecksumKind> {
+        self.match_checksum(policy).ok()
+    }
+
+    /// Tries the algorithms cheapest and most common first, stopping at the
+    /// first match. On a mismatch returns the crc32 and innodb checksums it
+    /// computed so they don't need to be computed again for the report.
+    fn match_checksum(&self, policy: ChecksumPolicy) -> Result<ChecksumKind, (u32, u32)> {
+        if policy.allow_none
+            && self.header.new_checksum == BUF_NO_CHECKSUM_MAGIC
+            && self.trailer.old_checksum == BUF_NO_CHECKSUM_MAGIC
+        {
+            return Ok(ChecksumKind::None);
+        }
+        let crc32 = self.crc32_checksum();
+        if crc32 == self.header.new_checksum {
+            return Ok(ChecksumKind::Crc32);
+        }
+        let innodb = self.innodb_checksum();
+        if innodb == self.header.new_checksum {
+            Ok(ChecksumKind::InnoDb)
+        } else if self.is_full_crc32() {
+            Ok(ChecksumKind::FullCrc32)
+        } else {
+            Err((crc32, innodb))
+        }
+    }
+
+    /// [`Page::validate_with`] with the default policy
+    pub fn validate(&self) -> PageValidation {
+        self.validate_with(ChecksumPolicy::default())
+    }
+
+    /// Checks the page type, the LSN in the trailer and the checksum, in
+    /// that order. A page passes only when its LSNs agree, as the checksums
+    /// other than full_crc32 leave the trailer out.
+    pub fn validate_with(&self, policy: ChecksumPolicy) -> PageValidation {
+        match self.header.page_type {
+            PageType::Unknown => return PageValidation::NotAPage,
+            PageType::Allocated if self.header.new_checksum == 0 => return PageValidation::Empty,
+            page_type if page_type.is_encrypted() => return PageValidation::Encrypted,
+            _ => {}
+        }
+        if !self.is_lsn_consistent() {
+            return PageValidation::TornPage {
+                header_lsn: self.header.lsn,
+                trailer_lsn: self.trailer.lsn_low_32,
+            };
+        }
+        match self.match_checksum(policy) {
+            Ok(ChecksumKind::Crc32) => PageValidation::ValidCrc32,
+            Ok(ChecksumKind::InnoDb) => PageValidation::ValidInnoDb,
+            Ok(ChecksumKind::FullCrc32) => PageValidation::ValidFullCrc32,
+            Ok(ChecksumKind::None) => PageValidation::ValidNone,
+            Err((crc32, innodb)) => PageValidation::BadChecksum {
+                stored: self.header.new_checksum,
+                crc32,
+                innodb,
+            },
+        }
+    }
+
+    /// The low 32 bits of the header LSN are repeated in the trailer, a
+    /// page where they differ was torn while being written. full_crc32
+    /// keeps them in the first trailer field as the last holds the checksum,
+    /// which is only looked at once the page checks out as full_crc32.
+    pub fn is_lsn_consistent(&self) -> bool {
+        let lsn_low_32 = self.header.lsn as u32;
+        lsn_low_32 == self.trailer.lsn_low_32
+            || (lsn_low_32 == self.trailer.old_checksum && self.is_full_crc32())
+    }
+
+    /// Whether the last trailer field holds the full_crc32 checksum
+    fn is_full_crc32(&self) -> bool {
+        self.full_crc32_checksum() == self.trailer.lsn_low_32
+    }
+
+    /// Logical page of `size` of a ROW_FORMAT=COMPRESSED index page, see
+    /// [`zip::decompress`]
+    pub fn decompress(&self, size: PageSize) -> Result<OwnedPage> {
+        zip::decompress(self.raw_data, size)
+    }
+
+    /// Reads a single page into a buffer owned by the returned page
+    pub fn read_from<R: Read>(reader: R) -> Result<OwnedPage> {
+        OwnedPage::from_reader(reader)
+    }
+}
+
+/// A page that owns its buffer, for callers that don't need zero copy.
+/// [`OwnedPage::page`] borrows it as a [`Page`].
+#[derive(Clone, Default, PartialEq)]
+pub struct OwnedPage {
+    pub header: FILHeader,
+    pub trailer: FILTrailer,
+    buf: Box<[u8]>,
+}
+
+impl OwnedPage {
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        Self::from_reader_with_size(reader, PageSize::default())
+    }
+
+    pub fn from_reader_with_size<R: Read>(mut reader: R, size: PageSize) -> Result<Self> {
+        let mut buf = vec![0u8; size.bytes()].into_boxed_slice();
+        reader.read_exact(&mut buf)?;
+        Self::try_from_boxed_with_size(buf, size)
+    }
+
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
+        Self::try_from_bytes_with_size(buf, PageSize::default())
+    }
+
+    pub fn try_from_bytes_with_size(buf: &[u8], size: PageSize) -> Result<Self> {
+        Self::try_from_boxed_with_size(buf.into(), size)
+    }
+
+    /// Takes over `buf` without copying it
+    pub fn try_from_boxed_with_size(buf: Box<[u8]>, size: PageSize) -> Result<Self> {
+        check_page_length(buf.len(), size)?;
+        let page = Page::from_bytes_with_size(&buf, size)?;
+        Ok(OwnedPage {
+            header: page.header,
+            trailer: page.trailer,
+            buf,
+        })
+    }
+
+    pub fn page(&self) -> Page<'_> {
+        Page {
+            header: self.header.clone(),
+            trailer: self.trailer.clone(),
+            raw_data: &self.buf,
+        }
+    }
+
+    pub fn raw_data(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn size(&self) -> PageSize {
+        self.page().size()
+    }
+
+    pub fn checksum_matches(&self) -> Option<ChecksumKind> {
+        self.page().checksum_matches()
+    }
+}
+
+impl Debug for OwnedPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedPage")
+            .field("header", &self.header)
+            .field("trailer", &self.trailer)
+            .finish()
     }
 }
 
@@ -170,6 +591,17 @@ impl Default for PageType {
     }
 }
 
+impl PageType {
+    /// Pages encrypted with their tablespace key, they can't be parsed until
+    /// decrypted which restores the original type
+    pub fn is_encrypted(&self) -> bool {
+        matches!(
+            self,
+            PageType::Encrypted | PageType::CompressedAndEncrypted | PageType::EncryptedRtree
+        )
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct FILHeader {
     pub new_checksum: u32,
@@ -177,7 +609,11 @@ pub struct FILHeader {
     pub prev: u32,
     pub next: u32,
     pub lsn: u64,
+    /// [`PageType::Unknown`] for codes this crate doesn't know, like the
+    /// MariaDB only ones, see `page_type_raw`
     pub page_type: PageType,
+    /// The stored page type code, whether it is known or not
+    pub page_type_raw: u16,
     pub flush_lsn: u64,
     pub space_id: u32,
 }
@@ -196,8 +632,8 @@ impl FILHeader {
             buffer[16], buffer[17], buffer[18], buffer[19], buffer[20], buffer[21], buffer[22],
             buffer[23],
         ]);
-        let page_type_value = u16::from_be_bytes([buffer[24], buffer[25]]);
-        let page_type = match PageType::try_from_primitive(page_type_value) {
+        let page_type_raw = u16::from_be_bytes([buffer[24], buffer[25]]);
+        let page_type = match PageType::try_from_primitive(page_type_raw) {
             Ok(page_type) => page_type,
             Err(e) => {
                 debug!("Invalid FIL PageType: {:?}", e);
@@ -217,10 +653,29 @@ impl FILHeader {
             next,
             lsn,
             page_type,
+            page_type_raw,
             flush_lsn,
             space_id,
         })
     }
+
+    /// The 38 bytes [`FILHeader::from_bytes`] reads
+    pub fn to_bytes(&self) -> [u8; FIL_HEADER_SIZE] {
+        let mut buf = [0u8; FIL_HEADER_SIZE];
+        buf[0..4].copy_from_slice(&self.new_checksum.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.offset.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.prev.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.next.to_be_bytes());
+        buf[16..24].copy_from_slice(&self.lsn.to_be_bytes());
+        let page_type = match self.page_type {
+            PageType::Unknown => self.page_type_raw,
+            page_type => page_type as u16,
+        };
+        buf[24..26].copy_from_slice(&page_type.to_be_bytes());
+        buf[26..34].copy_from_slice(&self.flush_lsn.to_be_bytes());
+        buf[34..38].copy_from_slice(&self.space_id.to_be_bytes());
+        buf
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
@@ -243,4 +698,378 @@ impl FILTrailer {
             lsn_low_32,
         })
     }
+
+    /// The 8 bytes [`FILTrailer::from_bytes`] reads
+    pub fn to_bytes(&self) -> [u8; FIL_TRAILER_SIZE] {
+        let mut buf = [0u8; FIL_TRAILER_SIZE];
+        buf[0..4].copy_from_slice(&self.old_checksum.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.lsn_low_32.to_be_bytes());
+        buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    use crate::innodb::test_data;
+
+    use super::{
+        crc32_checksum, full_crc32_checksum, innodb_checksum, writer::PageWriter, ChecksumKind,
+        ChecksumPolicy, FILHeader, FILTrailer, OwnedPage, Page, PageSize, PageType, PageValidation,
+        BUF_NO_CHECKSUM_MAGIC, FIL_HEADER_SIZE, FIL_PAGE_SIZE, FIL_TRAILER_SIZE,
+    };
+
+    /// Deterministic page content for the sizes we have no fixture of
+    fn pattern_page(size: usize) -> Vec<u8> {
+        (0..size).map(|i| ((i * 7 + 3) % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_fil_header_to_bytes() {
+        let data = test_data("float_sample.ibd");
+        for page_number in 0..data.len() / FIL_PAGE_SIZE {
+            let buf = &data[page_number * FIL_PAGE_SIZE..(page_number + 1) * FIL_PAGE_SIZE];
+            let page = Page::from_bytes(buf).unwrap();
+            assert_eq!(page.header.to_bytes(), buf[..FIL_HEADER_SIZE]);
+            assert_eq!(
+                page.trailer.to_bytes(),
+                buf[FIL_PAGE_SIZE - FIL_TRAILER_SIZE..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_fil_header_round_trip() {
+        let page_types = [
+            PageType::Allocated,
+            PageType::UndoLog,
+            PageType::FspHdr,
+            PageType::Xdes,
+            PageType::LobFirst,
+            PageType::SDI,
+            PageType::RTree,
+            PageType::Index,
+        ];
+        // xorshift, any bytes are valid apart from the page type
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..1000 {
+            let mut header = [0u8; FIL_HEADER_SIZE];
+            for chunk in header.chunks_mut(8) {
+                chunk.copy_from_slice(&next().to_be_bytes()[..chunk.len()]);
+            }
+            let page_type = page_types[next() as usize % page_types.len()];
+            header[24..26].copy_from_slice(&(page_type as u16).to_be_bytes());
+            let parsed = FILHeader::from_bytes(&header).unwrap();
+            assert_eq!(parsed.page_type, page_type);
+            assert_eq!(parsed.to_bytes(), header);
+
+            // Unknown codes survive too
+            let code = 30 + (next() % 17820) as u16;
+            header[24..26].copy_from_slice(&code.to_be_bytes());
+            let parsed = FILHeader::from_bytes(&header).unwrap();
+            assert_eq!(parsed.page_type, PageType::Unknown);
+            assert_eq!(parsed.page_type_raw, code);
+            assert_eq!(parsed.to_bytes(), header);
+
+            let trailer = next().to_be_bytes();
+            assert_eq!(
+                FILTrailer::from_bytes(&trailer).unwrap().to_bytes(),
+                trailer
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrong_page_length() {
+        for len in [0, 38, FIL_PAGE_SIZE - 1, FIL_PAGE_SIZE + 1] {
+            let buf = vec![0u8; len];
+            let e = Page::from_bytes(&buf).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                format!("Page is {} bytes instead of {}", len, FIL_PAGE_SIZE)
+            );
+            assert!(OwnedPage::try_from_bytes(&buf).is_err());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "can't hold the FIL header and trailer")]
+    fn test_body_of_default_page() {
+        Page::default().body();
+    }
+
+    #[test]
+    fn test_checksum_16k() {
+        let data = test_data("float_sample.ibd");
+        let buf = &data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE];
+        let page = Page::from_bytes(buf).unwrap();
+        assert_eq!(crc32_checksum(buf), 0xc217_0c7b);
+        assert_eq!(crc32_checksum(buf), page.header.new_checksum);
+        assert_eq!(innodb_checksum(buf), page.innodb_checksum());
+    }
+
+    #[test]
+    fn test_checksum_matches() {
+        let data = test_data("float_sample.ibd");
+        let mut buf = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        let page = Page::from_bytes(&buf).unwrap();
+        assert_eq!(page.checksum_matches(), Some(ChecksumKind::Crc32));
+
+        let checksum = page.innodb_checksum();
+        buf[0..4].copy_from_slice(&checksum.to_be_bytes());
+        let page = Page::from_bytes(&buf).unwrap();
+        assert_eq!(page.checksum_matches(), Some(ChecksumKind::InnoDb));
+
+        buf[200] ^= 0xFF;
+        assert_eq!(Page::from_bytes(&buf).unwrap().checksum_matches(), None);
+    }
+
+    #[test]
+    fn test_no_checksum() {
+        let data = test_data("float_sample.ibd");
+        let mut buf = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        buf[0..4].copy_from_slice(&BUF_NO_CHECKSUM_MAGIC.to_be_bytes());
+        buf[FIL_PAGE_SIZE - 8..FIL_PAGE_SIZE - 4]
+            .copy_from_slice(&BUF_NO_CHECKSUM_MAGIC.to_be_bytes());
+        let allow_none = ChecksumPolicy { allow_none: true };
+
+        let page = Page::from_bytes(&buf).unwrap();
+        assert_eq!(page.checksum_matches(), None);
+        assert_eq!(page.validate_checksum(allow_none), Some(ChecksumKind::None));
+
+        // Both fields need the magic value
+        buf[FIL_PAGE_SIZE - 8] = 0;
+        let page = Page::from_bytes(&buf).unwrap();
+        assert_eq!(page.validate_checksum(allow_none), None);
+
+        // A real checksum is still reported as such
+        let page = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        assert_eq!(
+            page.validate_checksum(allow_none),
+            Some(ChecksumKind::Crc32)
+        );
+    }
+
+    #[test]
+    fn test_full_crc32() {
+        // The float_sample index page written with innodb_checksum_algorithm=full_crc32
+        let mut buf = test_data("full_crc32.page");
+        let page = Page::from_bytes(&buf).unwrap();
+        assert_eq!(full_crc32_checksum(&buf), 0xa58d_823c);
+        assert_eq!(page.full_crc32_checksum(), page.trailer.lsn_low_32);
+        assert_eq!(page.trailer.old_checksum, page.header.lsn as u32);
+        assert_eq!(page.checksum_matches(), Some(ChecksumKind::FullCrc32));
+
+        // Unlike the other algorithms the FIL header fields are covered
+        buf[30] ^= 0xFF;
+        assert_eq!(Page::from_bytes(&buf).unwrap().checksum_matches(), None);
+    }
+
+    #[test]
+    fn test_checksum_other_page_sizes() {
+        // Reference values from an independent CRC-32C and fold implementation
+        for (size, crc32, innodb) in [
+            (8192, 0x0bf9_6459, 0xc6f3_dc91),
+            (32768, 0x1e9a_6d00, 0x950c_256f),
+        ] {
+            let mut buf = pattern_page(size);
+            assert_eq!(crc32_checksum(&buf), crc32, "crc32 of {} page", size);
+            assert_eq!(innodb_checksum(&buf), innodb, "innodb of {} page", size);
+
+            // Checksum, flush LSN, space id and trailer are not covered
+            for offset in [0, 26, 37, size - 8, size - 1] {
+                buf[offset] ^= 0xFF;
+            }
+            assert_eq!(crc32_checksum(&buf), crc32);
+            assert_eq!(innodb_checksum(&buf), innodb);
+
+            buf[size - 9] ^= 0xFF;
+            assert_ne!(crc32_checksum(&buf), crc32);
+            assert_ne!(innodb_checksum(&buf), innodb);
+        }
+    }
+
+    #[test]
+    fn test_lsn_consistency() {
+        let data = test_data("float_sample.ibd");
+        let mut buf = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        assert!(Page::from_bytes(&buf).unwrap().is_lsn_consistent());
+
+        // Torn write: the header got the new LSN, the trailer didn't
+        let lsn = u64::from_be_bytes(buf[16..24].try_into().unwrap());
+        buf[16..24].copy_from_slice(&(lsn + 0x200).to_be_bytes());
+        let page = Page::from_bytes(&buf).unwrap();
+        assert!(!page.is_lsn_consistent());
+        assert_eq!(page.checksum_matches(), None);
+
+        // Only the checksum is stale, the LSNs still agree
+        buf[16..24].copy_from_slice(&lsn.to_be_bytes());
+        buf[0] ^= 0xff;
+        let page = Page::from_bytes(&buf).unwrap();
+        assert!(page.is_lsn_consistent());
+        assert_eq!(page.checksum_matches(), None);
+
+        // A stored checksum equal to the new LSN doesn't make a crc32 page
+        // consistent, only full_crc32 keeps the LSN in that field
+        let size = buf.len();
+        buf[0] ^= 0xff;
+        buf[16..24].copy_from_slice(&(lsn + 0x200).to_be_bytes());
+        buf[size - 8..size - 4].copy_from_slice(&((lsn + 0x200) as u32).to_be_bytes());
+        assert!(!Page::from_bytes(&buf).unwrap().is_lsn_consistent());
+
+        let mut full_crc32 = test_data("full_crc32.page");
+        assert!(Page::from_bytes(&full_crc32).unwrap().is_lsn_consistent());
+        full_crc32[16..24].copy_from_slice(&(lsn + 0x200).to_be_bytes());
+        assert!(!Page::from_bytes(&full_crc32).unwrap().is_lsn_consistent());
+    }
+
+    #[test]
+    fn test_validate() {
+        let data = test_data("float_sample.ibd");
+        let buf = data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec();
+        let page = Page::from_bytes(&buf).unwrap();
+        assert_eq!(page.validate(), PageValidation::ValidCrc32);
+        assert_eq!(page.validate().checksum_kind(), Some(ChecksumKind::Crc32));
+        let innodb = PageWriter::new(&page).finalize(ChecksumKind::InnoDb);
+        assert_eq!(
+            Page::from_bytes(&innodb).unwrap().validate(),
+            PageValidation::ValidInnoDb
+        );
+        let full_crc32 = test_data("full_crc32.page");
+        assert_eq!(
+            Page::from_bytes(&full_crc32).unwrap().validate(),
+            PageValidation::ValidFullCrc32
+        );
+
+        let none = PageWriter::new(&page).finalize(ChecksumKind::None);
+        let page = Page::from_bytes(&none).unwrap();
+        assert_eq!(
+            page.validate_with(ChecksumPolicy { allow_none: true }),
+            PageValidation::ValidNone
+        );
+        assert!(matches!(
+            page.validate(),
+            PageValidation::BadChecksum {
+                stored: BUF_NO_CHECKSUM_MAGIC,
+                ..
+            }
+        ));
+
+        let mut bad = buf.clone();
+        bad[200] ^= 0xFF;
+        let page = Page::from_bytes(&bad).unwrap();
+        let validation = page.validate();
+        assert_eq!(
+            validation,
+            PageValidation::BadChecksum {
+                stored: page.header.new_checksum,
+                crc32: page.crc32_checksum(),
+                innodb: page.innodb_checksum(),
+            }
+        );
+        assert!(!validation.is_valid());
+
+        // The checksum still matches, only the trailer is from another write
+        let mut torn = buf.clone();
+        torn[FIL_PAGE_SIZE - 4..].copy_from_slice(&0x1234u32.to_be_bytes());
+        assert_eq!(
+            Page::from_bytes(&torn).unwrap().validate(),
+            PageValidation::TornPage {
+                header_lsn: page.header.lsn,
+                trailer_lsn: 0x1234
+            }
+        );
+
+        let empty = vec![0u8; FIL_PAGE_SIZE];
+        assert_eq!(
+            Page::from_bytes(&empty).unwrap().validate(),
+            PageValidation::Empty
+        );
+        let encrypted = test_data("encrypted_sample.ibd");
+        assert_eq!(
+            Page::from_bytes(&encrypted[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE])
+                .unwrap()
+                .validate(),
+            PageValidation::Encrypted
+        );
+        let mut unknown = buf.clone();
+        unknown[24..26].copy_from_slice(&0x7777u16.to_be_bytes());
+        let page = Page::from_bytes(&unknown).unwrap();
+        assert_eq!(page.validate(), PageValidation::NotAPage);
+        assert_eq!(page.header.page_type_raw, 0x7777);
+    }
+
+    #[test]
+    fn test_owned_page_from_reader() {
+        let data = test_data("float_sample.ibd");
+        let mut cursor = Cursor::new(&data);
+        cursor
+            .seek(SeekFrom::Start(4 * FIL_PAGE_SIZE as u64))
+            .unwrap();
+        let owned = Page::read_from(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), 5 * FIL_PAGE_SIZE as u64);
+        drop(data);
+
+        assert_eq!(owned.header.page_type, PageType::Index);
+        assert_eq!(owned.header.offset, 4);
+        let page = owned.page();
+        assert_eq!(page.crc32_checksum(), page.header.new_checksum);
+        assert_eq!(owned.clone(), owned);
+
+        let mut short = Cursor::new(vec![0u8; FIL_PAGE_SIZE - 1]);
+        assert!(OwnedPage::from_reader(&mut short).is_err());
+        assert!(OwnedPage::try_from_bytes(&[0u8; 100]).is_err());
+    }
+
+    #[test]
+    fn test_page_size_parse() {
+        assert_eq!("16K".parse::<PageSize>().unwrap(), PageSize::Size16K);
+        assert_eq!("4k".parse::<PageSize>().unwrap(), PageSize::Size4K);
+        assert_eq!("65536".parse::<PageSize>().unwrap(), PageSize::Size64K);
+        assert!("12K".parse::<PageSize>().is_err());
+        assert!("big".parse::<PageSize>().is_err());
+        for size in PageSize::ALL {
+            assert_eq!(size.to_string().parse::<PageSize>().unwrap(), size);
+        }
+    }
+
+    #[test]
+    fn test_page_size_detect() {
+        let data = test_data("float_sample.ibd");
+        assert_eq!(PageSize::detect(&data).unwrap(), PageSize::Size16K);
+        // An index page has no FSP header
+        assert!(PageSize::detect(&data[4 * FIL_PAGE_SIZE..]).is_err());
+        assert!(PageSize::detect(&data[..40]).is_err());
+
+        let data = test_data("float_sample_8k.ibd");
+        assert_eq!(PageSize::detect(&data).unwrap(), PageSize::Size8K);
+
+        assert_eq!(PageSize::from_fsp_flags(7 << 6).unwrap(), PageSize::Size64K);
+        assert!(PageSize::from_fsp_flags(2 << 6).is_err());
+    }
+
+    #[test]
+    fn test_page_8k() {
+        // float_sample.ibd with every page laid out for 8K pages
+        let data = test_data("float_sample_8k.ibd");
+        let buf = &data[4 * 8192..5 * 8192];
+        assert!(Page::from_bytes(buf).is_err());
+        let page = Page::from_bytes_with_size(buf, PageSize::Size8K).unwrap();
+        assert_eq!(page.size(), PageSize::Size8K);
+        assert_eq!(page.header.page_type, PageType::Index);
+        assert_eq!(page.crc32_checksum(), page.header.new_checksum);
+        assert_eq!(page.trailer.lsn_low_32, page.header.lsn as u32);
+        assert_eq!(page.body().len(), 8192 - 38 - 8);
+
+        let owned = OwnedPage::from_reader_with_size(&data[4 * 8192..], PageSize::Size8K).unwrap();
+        assert_eq!(owned.page(), page);
+        assert!(OwnedPage::try_from_bytes_with_size(buf, PageSize::Size4K).is_err());
+    }
 }
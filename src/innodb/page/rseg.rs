@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+
+use crate::innodb::{
+    file_list::{FileAddress, FileListBaseNode, FIL_NULL},
+    InnoDBError,
+};
+
+use super::{inode::FsegHeader, trx_sys::TRX_SYS_N_RSEGS, Page, PageType, FIL_PAGE_BODY_OFFSET};
+
+/// "RSG" followed by the format version 1
+pub const RSEG_ARRAY_VERSION: u32 = 0x5253_4701;
+/// Version, size, file segment header and 200 reserved bytes
+const RSEG_ARRAY_SLOTS_OFFSET: usize = 4 + 4 + 10 + 200;
+const RSEG_ARRAY_SLOT_SIZE: usize = 4;
+/// Max size, history size, history list and file segment header
+const TRX_RSEG_UNDO_SLOTS_OFFSET: usize = 4 + 4 + 16 + 10;
+const TRX_RSEG_SLOT_SIZE: usize = 4;
+
+/// Rollback segment array of an undo tablespace (MySQL 8.0), page 3 of
+/// every undo tablespace
+#[derive(Debug, Clone)]
+pub struct RsegArray {
+    pub version: u32,
+    pub size: u32,
+    /// File segment holding the rollback segment headers
+    pub fseg_space_id: u32,
+    pub fseg: FileAddress,
+    /// Rollback segment header page of each slot, [`FIL_NULL`] if unused
+    pub slots: Vec<u32>,
+}
+
+impl RsegArray {
+    pub fn try_from_page(page: &Page) -> Result<Self> {
+        if page.header.page_type != PageType::RsegArray {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::RsegArray,
+                has: page.header.page_type
+            }));
+        }
+
+        let buf = &page.raw_data[FIL_PAGE_BODY_OFFSET..];
+        let version = u32::from_be_bytes(buf[0..4].try_into().unwrap());
+        if version != RSEG_ARRAY_VERSION {
+            return Err(anyhow!(
+                "Unknown rollback segment array version {:#x} on page {}",
+                version,
+                page.header.offset
+            ));
+        }
+        let slots = buf[RSEG_ARRAY_SLOTS_OFFSET..]
+            .chunks_exact(RSEG_ARRAY_SLOT_SIZE)
+            .take(TRX_SYS_N_RSEGS)
+            .map(|slot| u32::from_be_bytes(slot.try_into().unwrap()))
+            .collect();
+        Ok(RsegArray {
+            version,
+            size: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            fseg_space_id: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            fseg: FileAddress::try_from_bytes(&buf[12..18])?,
+            slots,
+        })
+    }
+
+    /// Rollback segment header page of `slot`, `None` for unused slots
+    pub fn rseg_page(&self, slot: usize) -> Option<u32> {
+        self.slots
+            .get(slot)
+            .copied()
+            .filter(|page_number| *page_number != FIL_NULL)
+    }
+
+    /// Slot numbers and header pages of the rollback segments in use
+    pub fn rsegs(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.slots
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, page_number)| *page_number != FIL_NULL)
+    }
+}
+
+/// Rollback segment header, the page a TRX_SYS or RSEG array slot points
+/// at
+#[derive(Debug, Clone)]
+pub struct RsegHeader {
+    pub space_id: u32,
+    /// Upper bound of pages the segment may use
+    pub max_size: u32,
+    /// Pages in the history list
+    pub history_size: u32,
+    /// Undo logs of committed transactions, kept for purge and MVCC
+    pub history: FileListBaseNode,
+    pub fseg: FsegHeader,
+    /// First page of the undo log in each slot, [`FIL_NULL`] if unused
+    pub undo_slots: Vec<u32>,
+    /// Highest transaction number added to the history (MySQL 8.0)
+    pub max_trx_no: u64,
+}
+
+impl RsegHeader {
+    pub fn try_from_page(page: &Page) -> Result<Self> {
+        if page.header.page_type != PageType::Sys {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::Sys,
+                has: page.header.page_type
+            }));
+        }
+
+        let buf = &page.raw_data[FIL_PAGE_BODY_OFFSET..];
+        // One slot per 16 bytes of page
+        let n_slots = page.raw_data.len() / 16;
+        let slots_end = TRX_RSEG_UNDO_SLOTS_OFFSET + n_slots * TRX_RSEG_SLOT_SIZE;
+        let undo_slots = buf[TRX_RSEG_UNDO_SLOTS_OFFSET..slots_end]
+            .chunks_exact(TRX_RSEG_SLOT_SIZE)
+            .map(|slot| u32::from_be_bytes(slot.try_into().unwrap()))
+            .collect();
+        Ok(RsegHeader {
+            space_id: page.header.space_id,
+            max_size: u32::from_be_bytes(buf[0..4].try_into().unwrap()),
+            history_size: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+            history: FileListBaseNode::try_from_bytes(&buf[8..24])?,
+            fseg: FsegHeader::try_from_bytes(&buf[24..34])?,
+            undo_slots,
+            max_trx_no: u64::from_be_bytes(buf[slots_end..slots_end + 8].try_into().unwrap()),
+        })
+    }
+
+    /// First page of the undo log in `slot`, `None` for unused slots
+    pub fn undo_page(&self, slot: usize) -> Option<u32> {
+        self.undo_slots
+            .get(slot)
+            .copied()
+            .filter(|page_number| *page_number != FIL_NULL)
+    }
+
+    /// Slot numbers and first pages of the active and cached undo logs
+    pub fn undo_logs(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.undo_slots
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, page_number)| *page_number != FIL_NULL)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        file_list::FIL_NULL,
+        page::{trx_sys::TRX_SYS_N_RSEGS, Page, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{RsegArray, RsegHeader, RSEG_ARRAY_VERSION};
+
+    fn empty_page() -> Vec<u8> {
+        let data = test_data("t_empty.ibd");
+        data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec()
+    }
+
+    /// Rollback segment array with segments on pages 4, 5 and 7
+    fn build_rseg_array_page() -> Vec<u8> {
+        let mut buf = empty_page();
+        buf[24..26].copy_from_slice(&21u16.to_be_bytes());
+        buf[38..42].copy_from_slice(&RSEG_ARRAY_VERSION.to_be_bytes());
+        buf[42..46].copy_from_slice(&3u32.to_be_bytes());
+        buf[46..50].copy_from_slice(&0xFFFF_FFEFu32.to_be_bytes());
+        buf[50..54].copy_from_slice(&2u32.to_be_bytes());
+        buf[54..56].copy_from_slice(&50u16.to_be_bytes());
+
+        let slots = 38 + 218;
+        buf[slots..slots + TRX_SYS_N_RSEGS * 4].fill(0xFF);
+        for (slot, page_number) in [(0, 4u32), (1, 5), (3, 7)] {
+            let pos = slots + slot * 4;
+            buf[pos..pos + 4].copy_from_slice(&page_number.to_be_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_rseg_array() {
+        let buf = build_rseg_array_page();
+        let array = RsegArray::try_from_page(&Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(array.size, 3);
+        assert_eq!(array.fseg_space_id, 0xFFFF_FFEF);
+        assert_eq!(array.fseg.page_number, 2);
+        assert_eq!(array.fseg.offset, 50);
+        assert_eq!(array.slots.len(), TRX_SYS_N_RSEGS);
+        assert_eq!(
+            array.rsegs().collect::<Vec<_>>(),
+            vec![(0, 4), (1, 5), (3, 7)]
+        );
+        assert_eq!(array.rseg_page(3), Some(7));
+        assert_eq!(array.rseg_page(2), None);
+        assert_eq!(array.rseg_page(TRX_SYS_N_RSEGS), None);
+    }
+
+    /// Rollback segment header of an undo tablespace with undo logs in
+    /// slots 0 and 5, and two logs in the history
+    fn build_rseg_header_page() -> Vec<u8> {
+        let mut buf = empty_page();
+        buf[24..26].copy_from_slice(&6u16.to_be_bytes());
+        buf[34..38].copy_from_slice(&0xFFFF_FFEFu32.to_be_bytes());
+        buf[38..42].copy_from_slice(&0xFFFF_FFFEu32.to_be_bytes());
+        buf[42..46].copy_from_slice(&2u32.to_be_bytes());
+        // History list of two undo logs, on pages 10 and 12
+        buf[46..50].copy_from_slice(&2u32.to_be_bytes());
+        buf[50..54].copy_from_slice(&10u32.to_be_bytes());
+        buf[54..56].copy_from_slice(&158u16.to_be_bytes());
+        buf[56..60].copy_from_slice(&12u32.to_be_bytes());
+        buf[60..62].copy_from_slice(&158u16.to_be_bytes());
+        buf[62..66].copy_from_slice(&0xFFFF_FFEFu32.to_be_bytes());
+        buf[66..70].copy_from_slice(&2u32.to_be_bytes());
+        buf[70..72].copy_from_slice(&242u16.to_be_bytes());
+
+        let slots = 38 + 34;
+        buf[slots..slots + 1024 * 4].fill(0xFF);
+        buf[slots..slots + 4].copy_from_slice(&8u32.to_be_bytes());
+        buf[slots + 20..slots + 24].copy_from_slice(&9u32.to_be_bytes());
+        let max_trx_no = slots + 1024 * 4;
+        buf[max_trx_no..max_trx_no + 8].copy_from_slice(&0x5555u64.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_rseg_header() {
+        let buf = build_rseg_header_page();
+        let header = RsegHeader::try_from_page(&Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(header.space_id, 0xFFFF_FFEF);
+        assert_eq!(header.max_size, 0xFFFF_FFFE);
+        assert_eq!(header.history_size, 2);
+        assert_eq!(header.history.list_len, 2);
+        assert_eq!(header.history.first_node.page_number, 10);
+        assert_eq!(header.history.last_node.page_number, 12);
+        assert_eq!(header.fseg.page_number, 2);
+        assert_eq!(header.fseg.offset, 242);
+        assert_eq!(header.undo_slots.len(), 1024);
+        assert_eq!(header.undo_slots[1], FIL_NULL);
+        assert_eq!(header.undo_logs().collect::<Vec<_>>(), vec![(0, 8), (5, 9)]);
+        assert_eq!(header.undo_page(5), Some(9));
+        assert_eq!(header.undo_page(6), None);
+        assert_eq!(header.undo_page(1024), None);
+        assert_eq!(header.max_trx_no, 0x5555);
+
+        let array = build_rseg_array_page();
+        assert!(RsegHeader::try_from_page(&Page::from_bytes(&array).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_rseg_array_invalid() {
+        let mut buf = build_rseg_array_page();
+        buf[41] = 2;
+        assert!(RsegArray::try_from_page(&Page::from_bytes(&buf).unwrap()).is_err());
+        buf[24..26].copy_from_slice(&17855u16.to_be_bytes());
+        assert!(RsegArray::try_from_page(&Page::from_bytes(&buf).unwrap()).is_err());
+    }
+}
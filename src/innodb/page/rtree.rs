@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+
+use crate::innodb::InnoDBError;
+
+use super::{
+    index::{
+        record::{Record, RecordType},
+        IndexHeader, IndexPage,
+    },
+    Page, PageType,
+};
+
+/// Size of the minimum bounding rectangle, 2 dimensions of (min, max) doubles
+pub const MBR_SIZE: usize = 32;
+
+/// Minimum bounding rectangle of an R-tree entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mbr {
+    pub min_x: f64,
+    pub max_x: f64,
+    pub min_y: f64,
+    pub max_y: f64,
+}
+
+impl Mbr {
+    /// The coordinates are written in little endian, unlike everything else
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
+        if buf.len() < MBR_SIZE {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        let coord = |i: usize| f64::from_le_bytes(buf[i * 8..(i + 1) * 8].try_into().unwrap());
+        Ok(Mbr {
+            min_x: coord(0),
+            max_x: coord(1),
+            min_y: coord(2),
+            max_y: coord(3),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RtreeEntry<'a> {
+    pub record: Record<'a>,
+    pub mbr: Mbr,
+    /// Child page for node pointers, leaf entries are followed by the
+    /// primary key of the clustered index record instead
+    pub child_page: Option<u32>,
+}
+
+/// Spatial index page, laid out like an index page but keyed by MBR
+#[derive(Debug)]
+pub struct RtreePage<'a> {
+    pub index: IndexPage<'a>,
+}
+
+impl<'a> RtreePage<'a> {
+    pub fn try_from_page(page: Page<'a>) -> Result<Self> {
+        if page.header.page_type != PageType::RTree {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::RTree,
+                has: page.header.page_type
+            }));
+        }
+
+        Ok(RtreePage {
+            index: IndexPage {
+                index_header: IndexHeader::from_bytes(page.body())?,
+                page,
+            },
+        })
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.index.index_header.page_level == 0
+    }
+
+    fn entry<'b>(&self, record: Record<'b>) -> Result<RtreeEntry<'b>> {
+        let data = &record.buf[record.offset..];
+        let mbr = Mbr::try_from_bytes(data)?;
+        let child_page = match record.header.record_type {
+            RecordType::NodePointer => {
+                let page_number = data
+                    .get(MBR_SIZE..MBR_SIZE + 4)
+                    .ok_or(InnoDBError::InvalidLength)?;
+                Some(u32::from_be_bytes(page_number.try_into().unwrap()))
+            }
+            _ => None,
+        };
+        Ok(RtreeEntry {
+            record,
+            mbr,
+            child_page,
+        })
+    }
+
+    /// Entries in list order, with the same loop protection as the records
+    pub fn entries(&self) -> impl Iterator<Item = Result<RtreeEntry<'_>>> {
+        self.index
+            .records()
+            .map(|record| record.and_then(|record| self.entry(record)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use anyhow::Result;
+
+    use crate::innodb::page::{Page, FIL_PAGE_SIZE};
+
+    use crate::innodb::test_data;
+
+    use super::{Mbr, RtreePage, MBR_SIZE};
+
+    const ENTRY_SIZE: usize = 5 + MBR_SIZE + 4;
+
+    /// R-tree page with one entry per MBR, node pointers point at page 10 + i
+    fn build_rtree_page(mbrs: &[Mbr], level: u16) -> Vec<u8> {
+        let data = test_data("t_empty.ibd");
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+        buf[24..26].copy_from_slice(&17854u16.to_be_bytes());
+        buf[64..66].copy_from_slice(&level.to_be_bytes());
+
+        let origin = |i: usize| 120 + i * ENTRY_SIZE + 5;
+        let mut prev = 99;
+        for (i, mbr) in mbrs.iter().enumerate() {
+            let rec = origin(i);
+            let record_type = if level > 0 { 1 } else { 0 };
+            buf[rec - 4..rec - 2]
+                .copy_from_slice(&((((i + 2) as u16) << 3) | record_type).to_be_bytes());
+            for (j, coord) in [mbr.min_x, mbr.max_x, mbr.min_y, mbr.max_y]
+                .iter()
+                .enumerate()
+            {
+                buf[rec + j * 8..rec + (j + 1) * 8].copy_from_slice(&coord.to_le_bytes());
+            }
+            buf[rec + MBR_SIZE..rec + MBR_SIZE + 4].copy_from_slice(&(10 + i as u32).to_be_bytes());
+            buf[prev - 2..prev].copy_from_slice(&((rec as i16) - (prev as i16)).to_be_bytes());
+            prev = rec;
+        }
+        buf[prev - 2..prev].copy_from_slice(&(112 - (prev as i16)).to_be_bytes());
+        buf[42..44].copy_from_slice(&(0x8000 | (mbrs.len() as u16 + 2)).to_be_bytes());
+        buf[54..56].copy_from_slice(&(mbrs.len() as u16).to_be_bytes());
+        buf
+    }
+
+    fn mbrs() -> Vec<Mbr> {
+        vec![
+            Mbr {
+                min_x: 0.0,
+                max_x: 1.0,
+                min_y: -2.5,
+                max_y: 2.5,
+            },
+            Mbr {
+                min_x: 10.0,
+                max_x: 20.0,
+                min_y: 30.0,
+                max_y: 40.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_rtree_node_pointers() {
+        let buf = build_rtree_page(&mbrs(), 1);
+        let page = RtreePage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(!page.is_leaf());
+
+        let entries = page.entries().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].mbr, mbrs()[0]);
+        assert_eq!(entries[1].mbr, mbrs()[1]);
+        assert_eq!(entries[0].child_page, Some(10));
+        assert_eq!(entries[1].child_page, Some(11));
+    }
+
+    #[test]
+    fn test_rtree_leaf() {
+        let buf = build_rtree_page(&mbrs(), 0);
+        let page = RtreePage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(page.is_leaf());
+
+        let entries = page.entries().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].mbr, mbrs()[1]);
+        assert!(entries.iter().all(|e| e.child_page.is_none()));
+    }
+
+    #[test]
+    fn test_rtree_wrong_page_type() {
+        let mut buf = build_rtree_page(&mbrs(), 0);
+        buf[24..26].copy_from_slice(&17855u16.to_be_bytes());
+        assert!(RtreePage::try_from_page(Page::from_bytes(&buf).unwrap()).is_err());
+    }
+}
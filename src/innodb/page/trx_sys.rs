@@ -0,0 +1,225 @@
+use std::ops::Range;
+
+use anyhow::{anyhow, Result};
+
+use crate::innodb::{
+    file_list::{FileAddress, FIL_NULL},
+    InnoDBError,
+};
+
+use super::{inode::FsegHeader, xdes::extent_pages, Page, PageType, FIL_PAGE_BODY_OFFSET};
+
+/// Slots in the rollback segment array, 128 since MySQL 5.5
+pub const TRX_SYS_N_RSEGS: usize = 128;
+const TRX_SYS_RSEGS_OFFSET: usize = 8 + 10;
+const RSEG_SLOT_SIZE: usize = 8;
+/// The doublewrite header sits this far before the end of the page
+const TRX_SYS_DOUBLEWRITE_FROM_END: usize = 200;
+const TRX_SYS_DOUBLEWRITE_MAGIC_N: u32 = 536853855;
+
+/// Doublewrite buffer of the system tablespace before MySQL 8.0.20, two
+/// extents of copies of pages about to be written in place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Doublewrite {
+    pub fseg: FsegHeader,
+    /// First page of each of the two blocks
+    pub block1: u32,
+    pub block2: u32,
+}
+
+impl Doublewrite {
+    /// `None` when the magic is missing, the buffer was never created
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Option<Self>> {
+        if buf.len() < 22 {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        let magic = u32::from_be_bytes(buf[10..14].try_into().unwrap());
+        if magic != TRX_SYS_DOUBLEWRITE_MAGIC_N {
+            return Ok(None);
+        }
+        Ok(Some(Doublewrite {
+            fseg: FsegHeader::try_from_bytes(buf)?,
+            block1: u32::from_be_bytes(buf[14..18].try_into().unwrap()),
+            block2: u32::from_be_bytes(buf[18..22].try_into().unwrap()),
+        }))
+    }
+
+    /// Pages of the system tablespace holding copies, a block is one extent
+    pub fn pages(&self, page: &Page) -> [Range<u32>; 2] {
+        let block_size = extent_pages(page.size()) as u32;
+        [
+            self.block1..self.block1 + block_size,
+            self.block2..self.block2 + block_size,
+        ]
+    }
+}
+
+/// Location of a rollback segment header page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RsegSlot {
+    pub space_id: u32,
+    pub page_number: u32,
+}
+
+impl RsegSlot {
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
+        if buf.len() < RSEG_SLOT_SIZE {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        Ok(RsegSlot {
+            space_id: u32::from_be_bytes(buf[0..4].try_into().unwrap()),
+            page_number: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+        })
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.page_number == FIL_NULL
+    }
+}
+
+/// Transaction system header, on page 5 of the system tablespace
+#[derive(Debug, Clone)]
+pub struct TrxSysHeader {
+    /// Upper bound of the transaction ids handed out, InnoDB writes it
+    /// ahead in steps of 256
+    pub max_trx_id: u64,
+    /// File segment holding the rollback segment headers
+    pub fseg_space_id: u32,
+    pub fseg: FileAddress,
+    pub rseg_slots: Vec<RsegSlot>,
+    pub doublewrite: Option<Doublewrite>,
+}
+
+impl TrxSysHeader {
+    pub fn try_from_page(page: &Page) -> Result<Self> {
+        if page.header.page_type != PageType::TrxSys {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::TrxSys,
+                has: page.header.page_type
+            }));
+        }
+
+        let buf = &page.raw_data[FIL_PAGE_BODY_OFFSET..];
+        let rseg_slots = buf[TRX_SYS_RSEGS_OFFSET..]
+            .chunks_exact(RSEG_SLOT_SIZE)
+            .take(TRX_SYS_N_RSEGS)
+            .map(RsegSlot::try_from_bytes)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(TrxSysHeader {
+            max_trx_id: u64::from_be_bytes(buf[0..8].try_into().unwrap()),
+            fseg_space_id: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+            fseg: FileAddress::try_from_bytes(&buf[12..18])?,
+            rseg_slots,
+            doublewrite: Doublewrite::try_from_bytes(
+                &page.raw_data[page.raw_data.len() - TRX_SYS_DOUBLEWRITE_FROM_END..],
+            )?,
+        })
+    }
+
+    /// Slot numbers and locations of the rollback segments in use
+    pub fn rsegs(&self) -> impl Iterator<Item = (usize, RsegSlot)> + '_ {
+        self.rseg_slots
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, slot)| !slot.is_null())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        file_list::FIL_NULL,
+        page::{Page, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{RsegSlot, TrxSysHeader, TRX_SYS_DOUBLEWRITE_MAGIC_N, TRX_SYS_N_RSEGS};
+
+    /// TrxSys page with the system rseg on page 6 and one more in the undo
+    /// tablespace 0xFFFFFFF0
+    fn build_trx_sys_page() -> Vec<u8> {
+        let data = test_data("t_empty.ibd");
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+        buf[24..26].copy_from_slice(&7u16.to_be_bytes());
+        buf[38..46].copy_from_slice(&0x1200u64.to_be_bytes());
+        buf[46..50].copy_from_slice(&0u32.to_be_bytes());
+        buf[50..54].copy_from_slice(&2u32.to_be_bytes());
+        buf[54..56].copy_from_slice(&242u16.to_be_bytes());
+
+        let rsegs = 38 + 18;
+        for slot in 0..TRX_SYS_N_RSEGS {
+            let pos = rsegs + slot * 8;
+            buf[pos..pos + 8].copy_from_slice(&[0xFF; 8]);
+        }
+        buf[rsegs..rsegs + 4].copy_from_slice(&0u32.to_be_bytes());
+        buf[rsegs + 4..rsegs + 8].copy_from_slice(&6u32.to_be_bytes());
+        let third = rsegs + 2 * 8;
+        buf[third..third + 4].copy_from_slice(&0xFFFF_FFF0u32.to_be_bytes());
+        buf[third + 4..third + 8].copy_from_slice(&3u32.to_be_bytes());
+
+        // Doublewrite blocks at the usual pages 64 and 128
+        let dblwr = FIL_PAGE_SIZE - 200;
+        buf[dblwr + 10..dblwr + 14].copy_from_slice(&TRX_SYS_DOUBLEWRITE_MAGIC_N.to_be_bytes());
+        buf[dblwr + 14..dblwr + 18].copy_from_slice(&64u32.to_be_bytes());
+        buf[dblwr + 18..dblwr + 22].copy_from_slice(&128u32.to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_trx_sys_header() {
+        let buf = build_trx_sys_page();
+        let header = TrxSysHeader::try_from_page(&Page::from_bytes(&buf).unwrap()).unwrap();
+
+        assert_eq!(header.max_trx_id, 0x1200);
+        assert_eq!(header.fseg_space_id, 0);
+        assert_eq!(header.fseg.page_number, 2);
+        assert_eq!(header.fseg.offset, 242);
+        assert_eq!(header.rseg_slots.len(), TRX_SYS_N_RSEGS);
+        assert_eq!(
+            header.rseg_slots[0],
+            RsegSlot {
+                space_id: 0,
+                page_number: 6
+            }
+        );
+        assert_eq!(header.rseg_slots[1].page_number, FIL_NULL);
+        assert_eq!(
+            header.rsegs().collect::<Vec<_>>(),
+            vec![
+                (
+                    0,
+                    RsegSlot {
+                        space_id: 0,
+                        page_number: 6
+                    }
+                ),
+                (
+                    2,
+                    RsegSlot {
+                        space_id: 0xFFFF_FFF0,
+                        page_number: 3
+                    }
+                ),
+            ]
+        );
+
+        let page = Page::from_bytes(&buf).unwrap();
+        let doublewrite = header.doublewrite.unwrap();
+        assert_eq!((doublewrite.block1, doublewrite.block2), (64, 128));
+        assert_eq!(doublewrite.pages(&page), [64..128, 128..192]);
+
+        let mut buf = buf.clone();
+        buf[FIL_PAGE_SIZE - 190] = 0;
+        let header = TrxSysHeader::try_from_page(&Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(header.doublewrite.is_none());
+    }
+
+    #[test]
+    fn test_trx_sys_wrong_page_type() {
+        let data = test_data("t_empty.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        assert!(TrxSysHeader::try_from_page(&page).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{anyhow, Result};
+use num_enum::TryFromPrimitive;
+
+use crate::innodb::{file_list::FileListInnerNode, InnoDBError};
+
+use super::{Page, PageType, FIL_PAGE_BODY_OFFSET};
+
+/// Undo page header: type, start, free and the page list node
+pub const TRX_UNDO_PAGE_HDR_SIZE: usize = 2 + 2 + 2 + 12;
+/// Length of a NULL column in undo records
+pub const UNIV_SQL_NULL: u32 = 0xFFFF_FFFF;
+/// Lengths from here on mark a column stored externally
+pub const UNIV_EXTERN_STORAGE_FIELD: u32 = UNIV_SQL_NULL - 16384;
+/// Update vector field numbers from here on are virtual columns
+pub const REC_MAX_N_FIELDS: u32 = 1024 - 1;
+
+const TRX_UNDO_CMPL_INFO_MULT: u8 = 16;
+const TRX_UNDO_MODIFY_BLOB: u8 = 64;
+const TRX_UNDO_UPD_EXTERN: u8 = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u16)]
+pub enum UndoPageType {
+    /// Undo of inserts, thrown away at commit
+    Insert = 1,
+    /// Undo of updates and deletes, kept for MVCC and purge
+    Update = 2,
+}
+
+#[derive(Debug, Clone)]
+pub struct UndoPageHeader {
+    pub page_type: UndoPageType,
+    /// Where the records of the latest undo log on the page start
+    pub start: u16,
+    /// First free byte of the page
+    pub free: u16,
+    /// Node in the list of pages of the undo segment
+    pub node: FileListInnerNode,
+}
+
+impl UndoPageHeader {
+    pub fn try_from_bytes(buf: &[u8]) -> Result<Self> {
+        let buf = buf
+            .get(..TRX_UNDO_PAGE_HDR_SIZE)
+            .ok_or(InnoDBError::InvalidLength)?;
+        let page_type = u16::from_be_bytes([buf[0], buf[1]]);
+        Ok(UndoPageHeader {
+            page_type: UndoPageType::try_from(page_type)
+                .map_err(|_| anyhow!("Unknown undo page type {}", page_type))?,
+            start: u16::from_be_bytes([buf[2], buf[3]]),
+            free: u16::from_be_bytes([buf[4], buf[5]]),
+            node: FileListInnerNode::try_from_bytes(&buf[6..])?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct UndoPage<'a> {
+    pub page: Page<'a>,
+    pub header: UndoPageHeader,
+}
+
+impl<'a> UndoPage<'a> {
+    pub fn try_from_page(page: Page<'a>) -> Result<Self> {
+        if page.header.page_type != PageType::UndoLog {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::UndoLog,
+                has: page.header.page_type
+            }));
+        }
+        Ok(UndoPage {
+            header: UndoPageHeader::try_from_bytes(page.body())?,
+            page,
+        })
+    }
+
+    /// The undo record at `offset`, as found in a roll pointer
+    pub fn record_at(&self, offset: usize) -> Result<UndoRecord<'a>> {
+        let first = FIL_PAGE_BODY_OFFSET + TRX_UNDO_PAGE_HDR_SIZE;
+        if offset < first || offset >= self.header.free as usize {
+            return Err(anyhow!(
+                "Undo record offset {} is outside the records of page {}",
+                offset,
+                self.page.header.offset
+            ));
+        }
+        UndoRecord::try_from_bytes(self.page.raw_data, offset)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum UndoRecordType {
+    /// Fresh insert, holds the key only
+    Insert = 11,
+    /// Update of a row that wasn't delete marked
+    UpdExist = 12,
+    /// Update of a delete marked row, the row was inserted again
+    UpdDel = 13,
+    /// Delete marking of a row
+    DelMark = 14,
+}
+
+/// Reads the variable length integers of undo records
+#[derive(Debug, Clone)]
+pub struct UndoReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> UndoReader<'a> {
+    pub fn new(buf: &'a [u8], pos: usize) -> Self {
+        UndoReader { buf, pos }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or(InnoDBError::InvalidLength)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u32> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0u32, |n, b| (n << 8) | *b as u32))
+    }
+
+    /// mach_read_compressed, 1 to 5 bytes with the length in the high bits
+    /// of the first
+    pub fn compressed(&mut self) -> Result<u32> {
+        let first = *self.buf.get(self.pos).ok_or(InnoDBError::InvalidLength)?;
+        match first {
+            0x00..=0x7F => self.uint(1),
+            0x80..=0xBF => Ok(self.uint(2)? & 0x3FFF),
+            0xC0..=0xDF => Ok(self.uint(3)? & 0x1F_FFFF),
+            0xE0..=0xEF => Ok(self.uint(4)? & 0x0FFF_FFFF),
+            0xF0 => {
+                self.pos += 1;
+                self.uint(4)
+            }
+            _ => Err(anyhow!(
+                "Unsupported compressed integer starting with {:#x} at {}",
+                first,
+                self.pos
+            )),
+        }
+    }
+
+    /// mach_u64_read_much_compressed, a compressed integer or 0xFF and two
+    /// of them for the high and low halves
+    pub fn much_compressed(&mut self) -> Result<u64> {
+        if self.buf.get(self.pos) != Some(&0xFF) {
+            return Ok(self.compressed()? as u64);
+        }
+        self.pos += 1;
+        let high = self.compressed()? as u64;
+        Ok(high << 32 | self.compressed()? as u64)
+    }
+
+    /// mach_u64_read_next_compressed, the high half compressed and the low
+    /// half in 4 bytes
+    pub fn u64_compressed(&mut self) -> Result<u64> {
+        let high = self.compressed()? as u64;
+        Ok(high << 32 | self.uint(4)? as u64)
+    }
+
+    /// A column: its length and bytes
+    pub fn column(&mut self) -> Result<UndoColumn<'a>> {
+        let len = self.compressed()?;
+        if len == UNIV_SQL_NULL {
+            return Ok(UndoColumn::Null);
+        }
+        if len >= UNIV_EXTERN_STORAGE_FIELD {
+            let len = len - UNIV_EXTERN_STORAGE_FIELD;
+            return Ok(UndoColumn::Extern(self.bytes(len as usize)?));
+        }
+        Ok(UndoColumn::Value(self.bytes(len as usize)?))
+    }
+}
+
+/// A column value stored in an undo record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoColumn<'a> {
+    Null,
+    Value(&'a [u8]),
+    /// Local prefix and reference of an extern column
+    Extern(&'a [u8]),
+}
+
+/// One update vector entry, the old value of a column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoUpdateField<'a> {
+    /// Position of the column in the clustered index, DB_TRX_ID and
+    /// DB_ROLL_PTR included
+    pub field_no: u32,
+    pub value: UndoColumn<'a>,
+}
+
+/// Start of an undo record, the part that doesn't need the table to parse
+#[derive(Debug, Clone)]
+pub struct UndoRecord<'a> {
+    buf: &'a [u8],
+    pub offset: usize,
+    /// Offset of the next record on the page
+    pub next: u16,
+    pub record_type: UndoRecordType,
+    /// UPD_NODE_NO_ORD_CHANGE and UPD_NODE_NO_SIZE_CHANGE bits
+    pub cmpl_info: u8,
+    /// An extern column was updated
+    pub updated_extern: bool,
+    pub undo_no: u64,
+    pub table_id: u64,
+    /// Info bits, DB_TRX_ID and DB_ROLL_PTR of the row before the change,
+    /// `None` for insert records
+    pub info_bits: Option<u8>,
+    pub trx_id: Option<u64>,
+    pub roll_ptr: Option<u64>,
+    /// Where the key columns start
+    key_offset: usize,
+}
+
+impl<'a> UndoRecord<'a> {
+    pub fn try_from_bytes(buf: &'a [u8], offset: usize) -> Result<Self> {
+        let mut reader = UndoReader::new(buf, offset);
+        let next = u16::from_be_bytes(reader.bytes(2)?.try_into().unwrap());
+        let type_cmpl = reader.u8()?;
+        if type_cmpl & TRX_UNDO_MODIFY_BLOB != 0 {
+            // LOB flags byte (MySQL 8.0)
+            reader.u8()?;
+        }
+        let type_cmpl_info = type_cmpl & !(TRX_UNDO_UPD_EXTERN | TRX_UNDO_MODIFY_BLOB);
+        let raw_type = type_cmpl_info % TRX_UNDO_CMPL_INFO_MULT;
+        let record_type = UndoRecordType::try_from(raw_type)
+            .map_err(|_| anyhow!("Unknown undo record type {} at offset {}", raw_type, offset))?;
+        let undo_no = reader.much_compressed()?;
+        let table_id = reader.much_compressed()?;
+        let (info_bits, trx_id, roll_ptr) = if record_type == UndoRecordType::Insert {
+            (None, None, None)
+        } else {
+            (
+                Some(reader.u8()?),
+                Some(reader.u64_compressed()?),
+                Some(reader.u64_compressed()?),
+            )
+        };
+        Ok(UndoRecord {
+            buf,
+            offset,
+            next,
+            record_type,
+            cmpl_info: type_cmpl_info / TRX_UNDO_CMPL_INFO_MULT,
+            updated_extern: type_cmpl & TRX_UNDO_UPD_EXTERN != 0,
+            undo_no,
+            table_id,
+            info_bits,
+            trx_id,
+            roll_ptr,
+            key_offset: reader.position(),
+        })
+    }
+
+    /// The row was delete marked before the change
+    pub fn was_deleted(&self) -> bool {
+        self.info_bits.is_some_and(|bits| bits & 0x20 != 0)
+    }
+
+    /// The `n_unique` key columns of the row and a reader right after them
+    pub fn key(&self, n_unique: usize) -> Result<(Vec<UndoColumn<'a>>, UndoReader<'a>)> {
+        let mut reader = UndoReader::new(self.buf, self.key_offset);
+        let key = (0..n_unique)
+            .map(|_| reader.column())
+            .collect::<Result<Vec<_>>>()?;
+        Ok((key, reader))
+    }
+
+    /// Old values of the columns the change updated. Delete marking changes
+    /// no column and has none.
+    pub fn update_vector(&self, n_unique: usize) -> Result<Vec<UndoUpdateField<'a>>> {
+        if self.record_type == UndoRecordType::DelMark {
+            return Ok(Vec::new());
+        }
+        if self.record_type == UndoRecordType::Insert {
+            return Err(anyhow!(
+                "Insert undo record at {} has no update vector",
+                self.offset
+            ));
+        }
+        let (_, mut reader) = self.key(n_unique)?;
+        let n_fields = reader.compressed()?;
+        (0..n_fields)
+            .map(|_| {
+                Ok(UndoUpdateField {
+                    field_no: reader.compressed()?,
+                    value: reader.column()?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::page::{Page, FIL_PAGE_SIZE};
+
+    use crate::innodb::test_data;
+
+    use super::{UndoColumn, UndoPage, UndoPageType, UndoReader, UndoRecordType};
+
+    /// Undo page with an UPD_EXIST record at 56: undo_no 3, table 1066, a
+    /// 2 byte key and the old 4 byte value of field 4
+    fn build_undo_page() -> Vec<u8> {
+        let data = test_data("t_empty.ibd");
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+        buf[24..26].copy_from_slice(&2u16.to_be_bytes());
+        let record: &[u8] = &[
+            0x00, 0x00, // next
+            12,   // UPD_EXIST
+            3,    // undo_no
+            0x84, 0x2a, // table id 1066
+            0x00, // info bits
+            0x00, 0x00, 0x00, 0x12, 0x34, // trx_id 0x1234
+            0xE0, 0x81, 0x00, 0x00, 0x00, 0x90, 0x01, 0x20, // roll_ptr
+            2, b'i', b'd', // key
+            1,    // n_fields
+            4, 4, 0x80, 0x00, 0x00, 0x07, // field 4
+        ];
+        buf[38..40].copy_from_slice(&2u16.to_be_bytes());
+        buf[40..42].copy_from_slice(&56u16.to_be_bytes());
+        buf[42..44].copy_from_slice(&(56 + record.len() as u16).to_be_bytes());
+        buf[56..56 + record.len()].copy_from_slice(record);
+        buf
+    }
+
+    #[test]
+    fn test_compressed_integers() {
+        let buf = [
+            0x7F, 0x81, 0x00, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0x00, 0x00, 0xF0, 0x12, 0x34, 0x56,
+            0x78, 0xFF, 0x01, 0x02, 0xF8,
+        ];
+        let mut reader = UndoReader::new(&buf, 0);
+        assert_eq!(reader.compressed().unwrap(), 0x7F);
+        assert_eq!(reader.compressed().unwrap(), 0x100);
+        assert_eq!(reader.compressed().unwrap(), 0x1_0000);
+        assert_eq!(reader.compressed().unwrap(), 0x100_0000);
+        assert_eq!(reader.compressed().unwrap(), 0x1234_5678);
+        assert_eq!(reader.much_compressed().unwrap(), 0x1_0000_0002);
+        assert!(reader.compressed().is_err());
+        assert!(UndoReader::new(&[0x81], 0).compressed().is_err());
+    }
+
+    #[test]
+    fn test_undo_record() {
+        let buf = build_undo_page();
+        let page = UndoPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert_eq!(page.header.page_type, UndoPageType::Update);
+        assert_eq!(page.header.start, 56);
+
+        let record = page.record_at(56).unwrap();
+        assert_eq!(record.record_type, UndoRecordType::UpdExist);
+        assert_eq!(record.undo_no, 3);
+        assert_eq!(record.table_id, 1066);
+        assert_eq!(record.trx_id, Some(0x1234));
+        assert_eq!(record.roll_ptr, Some(0x81_0000_0090_0120));
+        assert!(!record.was_deleted());
+
+        let (key, _) = record.key(1).unwrap();
+        assert_eq!(key, vec![UndoColumn::Value(b"id")]);
+        let update = record.update_vector(1).unwrap();
+        assert_eq!(update.len(), 1);
+        assert_eq!(update[0].field_no, 4);
+        assert_eq!(update[0].value, UndoColumn::Value(&[0x80, 0, 0, 7]));
+
+        assert!(page.record_at(20).is_err());
+        assert!(page.record_at(page.header.free as usize).is_err());
+        let mut other = buf.clone();
+        other[24..26].copy_from_slice(&17855u16.to_be_bytes());
+        assert!(UndoPage::try_from_page(Page::from_bytes(&other).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+
+use super::{
+    crc32_checksum, full_crc32_checksum, innodb_checksum, innodb_old_checksum, ChecksumKind, Page,
+    PageSize, PageType, BUF_NO_CHECKSUM_MAGIC, FIL_TRAILER_SIZE,
+};
+
+/// A copy of a page to patch and write back. [`PageWriter::finalize`]
+/// stores the checksums and the trailer LSN once the edits are done.
+#[derive(Clone)]
+pub struct PageWriter {
+    buf: Box<[u8]>,
+    size: PageSize,
+}
+
+impl PageWriter {
+    pub fn new(page: &Page) -> Self {
+        PageWriter {
+            buf: page.raw_data.into(),
+            size: page.size(),
+        }
+    }
+
+    pub fn try_from_bytes_with_size(buf: &[u8], size: PageSize) -> Result<Self> {
+        Ok(Self::new(&Page::from_bytes_with_size(buf, size)?))
+    }
+
+    /// The page as it is now, checksums are only right after finalizing
+    pub fn page(&self) -> Page<'_> {
+        Page::from_bytes_with_size(&self.buf, self.size).expect("Page buffer was checked")
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+
+    fn write(&mut self, at: usize, bytes: &[u8]) {
+        self.buf[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    pub fn set_offset(&mut self, offset: u32) {
+        self.write(4, &offset.to_be_bytes());
+    }
+
+    pub fn set_prev(&mut self, prev: u32) {
+        self.write(8, &prev.to_be_bytes());
+    }
+
+    pub fn set_next(&mut self, next: u32) {
+        self.write(12, &next.to_be_bytes());
+    }
+
+    /// The low 32 bits go to the trailer when finalizing
+    pub fn set_lsn(&mut self, lsn: u64) {
+        self.write(16, &lsn.to_be_bytes());
+    }
+
+    pub fn set_page_type(&mut self, page_type: PageType) {
+        self.write(24, &(page_type as u16).to_be_bytes());
+    }
+
+    pub fn set_flush_lsn(&mut self, flush_lsn: u64) {
+        self.write(26, &flush_lsn.to_be_bytes());
+    }
+
+    pub fn set_space_id(&mut self, space_id: u32) {
+        self.write(34, &space_id.to_be_bytes());
+    }
+
+    /// Stores the checksums of `kind` and the low 32 bits of the header LSN
+    /// in the trailer, and hands out the page bytes
+    pub fn finalize(mut self, kind: ChecksumKind) -> Box<[u8]> {
+        let len = self.buf.len();
+        let lsn_low_32 = self.page().header.lsn as u32;
+        let trailer = len - FIL_TRAILER_SIZE;
+        match kind {
+            ChecksumKind::FullCrc32 => {
+                // The checksum takes the trailer LSN's place at the very end
+                self.write(trailer, &lsn_low_32.to_be_bytes());
+                let checksum = full_crc32_checksum(&self.buf);
+                self.write(len - 4, &checksum.to_be_bytes());
+            }
+            ChecksumKind::Crc32 | ChecksumKind::InnoDb | ChecksumKind::None => {
+                self.write(len - 4, &lsn_low_32.to_be_bytes());
+                let (new_checksum, old_checksum) = match kind {
+                    ChecksumKind::Crc32 => {
+                        let checksum = crc32_checksum(&self.buf);
+                        (checksum, checksum)
+                    }
+                    ChecksumKind::InnoDb => {
+                        let checksum = innodb_checksum(&self.buf);
+                        self.write(0, &checksum.to_be_bytes());
+                        (checksum, innodb_old_checksum(&self.buf))
+                    }
+                    _ => (BUF_NO_CHECKSUM_MAGIC, BUF_NO_CHECKSUM_MAGIC),
+                };
+                self.write(0, &new_checksum.to_be_bytes());
+                self.write(trailer, &old_checksum.to_be_bytes());
+            }
+        }
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::page::{ChecksumKind, ChecksumPolicy, Page, PageType, FIL_PAGE_SIZE};
+
+    use crate::innodb::test_data;
+
+    use super::PageWriter;
+
+    fn index_page() -> Vec<u8> {
+        test_data("float_sample.ibd")[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec()
+    }
+
+    /// Offsets where `a` and `b` differ
+    fn diff(a: &[u8], b: &[u8]) -> Vec<usize> {
+        (0..a.len()).filter(|i| a[*i] != b[*i]).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let buf = index_page();
+        let writer = PageWriter::new(&Page::from_bytes(&buf).unwrap());
+        assert_eq!(&*writer.finalize(ChecksumKind::Crc32), buf.as_slice());
+
+        let full_crc32 = test_data("full_crc32.page");
+        let writer = PageWriter::new(&Page::from_bytes(&full_crc32).unwrap());
+        assert_eq!(
+            &*writer.finalize(ChecksumKind::FullCrc32),
+            full_crc32.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_other_algorithm() {
+        let buf = index_page();
+        let page = Page::from_bytes(&buf).unwrap();
+        let policy = ChecksumPolicy { allow_none: true };
+        for kind in [ChecksumKind::InnoDb, ChecksumKind::None] {
+            let out = PageWriter::new(&page).finalize(kind);
+            assert_eq!(
+                Page::from_bytes(&out).unwrap().validate_checksum(policy),
+                Some(kind)
+            );
+            // Only the checksum fields change
+            let changed = diff(&buf, &out);
+            assert!(!changed.is_empty());
+            assert!(
+                changed
+                    .iter()
+                    .all(|i| *i < 4 || (FIL_PAGE_SIZE - 8..FIL_PAGE_SIZE - 4).contains(i)),
+                "{changed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_patch_page() {
+        let buf = index_page();
+        let mut writer = PageWriter::try_from_bytes_with_size(&buf, Default::default()).unwrap();
+        writer.set_next(5);
+        writer.set_lsn(0x1234_5678_9abc);
+        writer.set_page_type(PageType::Index);
+        // Zero the second record's first column
+        writer.data_mut()[179..184].fill(0);
+        assert_eq!(writer.page().header.next, 5);
+        assert_eq!(writer.page().checksum_matches(), None);
+
+        let out = writer.finalize(ChecksumKind::Crc32);
+        let page = Page::from_bytes(&out).unwrap();
+        assert_eq!(page.checksum_matches(), Some(ChecksumKind::Crc32));
+        assert!(page.is_lsn_consistent());
+        assert_eq!(page.trailer.lsn_low_32, 0x5678_9abc);
+        assert_eq!(page.header.next, 5);
+        assert_eq!(&out[179..184], &[0u8; 5]);
+    }
+}
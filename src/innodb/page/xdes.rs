@@ -0,0 +1,356 @@
+use std::ops::Range;
+
+use anyhow::{anyhow, Result};
+use num_enum::TryFromPrimitive;
+
+use crate::innodb::{
+    buffer_manager::BufferManager,
+    file_list::{FileAddress, FileListBaseNode, FileListInnerNode, FIL_NULL},
+    InnoDBError,
+};
+
+use super::{fsp::FSP_HEADER_SIZE, Page, PageSize, PageType, FIL_PAGE_BODY_OFFSET};
+
+/// The descriptor array starts after the tablespace header, XDES pages
+/// leave that part unused
+pub const XDES_ARR_OFFSET: usize = FIL_PAGE_BODY_OFFSET + FSP_HEADER_SIZE;
+const XDES_FLST_NODE_OFFSET: usize = 8;
+const XDES_BITMAP_OFFSET: usize = 8 + 12 + 4;
+const XDES_BITS_PER_PAGE: usize = 2;
+const XDES_FREE_BIT: usize = 0;
+const XDES_CLEAN_BIT: usize = 1;
+
+/// Pages per extent, extents are 1M up to 16K pages and 64 pages above that
+pub fn extent_pages(size: PageSize) -> usize {
+    std::cmp::max(64, (1 << 20) / size.bytes())
+}
+
+/// Bytes of one descriptor
+pub fn xdes_size(size: PageSize) -> usize {
+    XDES_BITMAP_OFFSET + extent_pages(size) * XDES_BITS_PER_PAGE / 8
+}
+
+/// Extents described by a single descriptor page, which is also every how
+/// many pages there is one
+pub fn xdes_per_page(size: PageSize) -> usize {
+    size.bytes() / extent_pages(size)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum XdesState {
+    /// Past the free limit, never used
+    NotInited = 0,
+    /// In the FSP_FREE list
+    Free = 1,
+    /// Some pages handed out as fragment pages
+    FreeFrag = 2,
+    /// Every page handed out as fragment pages
+    FullFrag = 3,
+    /// Belongs to the file segment `fseg_id`
+    Fseg = 4,
+    /// Fragment extent of a file segment (MySQL 8.0)
+    FsegFrag = 5,
+}
+
+/// Descriptor of one extent
+#[derive(Debug, Clone)]
+pub struct XdesEntry {
+    /// Pages of the extent
+    pub pages: Range<u32>,
+    /// Owning file segment when the state is [`XdesState::Fseg`]
+    pub fseg_id: u64,
+    /// Node in the list the extent is on, given by its state
+    pub list_node: FileListInnerNode,
+    pub state: XdesState,
+    /// Two bits per page, free and clean
+    pub bitmap: Vec<u8>,
+}
+
+impl XdesEntry {
+    pub fn try_from_bytes(buf: &[u8], first_page: u32, size: PageSize) -> Result<Self> {
+        let buf = buf
+            .get(..xdes_size(size))
+            .ok_or(InnoDBError::InvalidLength)?;
+        let state = u32::from_be_bytes(buf[20..24].try_into().unwrap());
+        Ok(XdesEntry {
+            pages: first_page..first_page + extent_pages(size) as u32,
+            fseg_id: u64::from_be_bytes(buf[0..8].try_into().unwrap()),
+            list_node: FileListInnerNode::try_from_bytes(&buf[XDES_FLST_NODE_OFFSET..20])?,
+            state: XdesState::try_from(state).map_err(|_| {
+                anyhow!("Extent at page {} has unknown state {}", first_page, state)
+            })?,
+            bitmap: buf[XDES_BITMAP_OFFSET..].to_vec(),
+        })
+    }
+
+    fn bit(&self, page_number: u32, bit: usize) -> Option<bool> {
+        if !self.pages.contains(&page_number) {
+            return None;
+        }
+        let idx = (page_number - self.pages.start) as usize * XDES_BITS_PER_PAGE + bit;
+        Some(self.bitmap[idx / 8] & (1 << (idx % 8)) != 0)
+    }
+
+    /// Whether the page is in use, `None` for pages of other extents. Free
+    /// bits of extents that were never initialized mean nothing.
+    pub fn is_page_allocated(&self, page_number: u32) -> Option<bool> {
+        match self.state {
+            XdesState::NotInited | XdesState::Free => {
+                self.bit(page_number, XDES_FREE_BIT).map(|_| false)
+            }
+            _ => self.bit(page_number, XDES_FREE_BIT).map(|free| !free),
+        }
+    }
+
+    /// Clean bit of the page, unused by InnoDB but kept in the bitmap
+    pub fn is_page_clean(&self, page_number: u32) -> Option<bool> {
+        self.bit(page_number, XDES_CLEAN_BIT)
+    }
+
+    pub fn allocated_pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pages
+            .clone()
+            .filter(|page_number| self.is_page_allocated(*page_number) == Some(true))
+    }
+}
+
+/// Extent descriptors on a FSP_HDR or XDES page
+#[derive(Debug)]
+pub struct XdesPage<'a> {
+    pub page: Page<'a>,
+}
+
+impl<'a> XdesPage<'a> {
+    pub fn try_from_page(page: Page<'a>) -> Result<Self> {
+        if !matches!(page.header.page_type, PageType::FspHdr | PageType::Xdes) {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::Xdes,
+                has: page.header.page_type
+            }));
+        }
+        let size = page.size();
+        if !(page.header.offset as usize).is_multiple_of(size.bytes()) {
+            return Err(anyhow!(
+                "Descriptor page {} is not at a multiple of {} pages",
+                page.header.offset,
+                size.bytes()
+            ));
+        }
+        Ok(XdesPage { page })
+    }
+
+    /// Pages described by this descriptor page
+    pub fn pages(&self) -> Range<u32> {
+        let first = self.page.header.offset;
+        first..first + self.page.size().bytes() as u32
+    }
+
+    /// Every descriptor of the page, including those past the free limit
+    pub fn entries(&self) -> impl Iterator<Item = Result<XdesEntry>> + '_ {
+        let size = self.page.size();
+        let first = self.page.header.offset;
+        self.page.raw_data[XDES_ARR_OFFSET..]
+            .chunks_exact(xdes_size(size))
+            .take(xdes_per_page(size))
+            .enumerate()
+            .map(move |(idx, buf)| {
+                XdesEntry::try_from_bytes(buf, first + (idx * extent_pages(size)) as u32, size)
+            })
+    }
+
+    /// Whether `page_number` is in use, `None` if another descriptor page
+    /// describes it
+    pub fn is_page_allocated(&self, page_number: u32) -> Result<Option<bool>> {
+        if !self.pages().contains(&page_number) {
+            return Ok(None);
+        }
+        let size = self.page.size();
+        let idx = (page_number - self.page.header.offset) as usize / extent_pages(size);
+        let entry = self
+            .entries()
+            .nth(idx)
+            .ok_or(InnoDBError::InvalidLength)??;
+        Ok(entry.is_page_allocated(page_number))
+    }
+}
+
+/// Iterator over a list of extents, like the ones rooted in the tablespace
+/// header. The descriptors are loaded through the buffer manager, iteration
+/// stops after the first error.
+pub struct XdesListEntries<'a> {
+    buffer_mgr: &'a dyn BufferManager,
+    space_id: u32,
+    next: FileAddress,
+    remaining: u32,
+}
+
+impl<'a> XdesListEntries<'a> {
+    pub fn new(base: &FileListBaseNode, space_id: u32, buffer_mgr: &'a dyn BufferManager) -> Self {
+        XdesListEntries {
+            buffer_mgr,
+            space_id,
+            next: base.first_node,
+            remaining: base.list_len,
+        }
+    }
+
+    /// List nodes point at the node inside the descriptor, which also gives
+    /// the position of the descriptor in the array
+    fn load(&self, location: FileAddress) -> Result<XdesEntry> {
+        let guard = self.buffer_mgr.pin(self.space_id, location.page_number)?;
+        let xdes = XdesPage::try_from_page(guard.page())?;
+        let size = xdes.page.size();
+        let idx = (location.offset as usize)
+            .checked_sub(XDES_ARR_OFFSET + XDES_FLST_NODE_OFFSET)
+            .filter(|start| start % xdes_size(size) == 0)
+            .map(|start| start / xdes_size(size))
+            .filter(|idx| *idx < xdes_per_page(size))
+            .ok_or_else(|| anyhow!("No extent descriptor at {:?}", location))?;
+        let entry = xdes.entries().nth(idx).ok_or(InnoDBError::InvalidLength)?;
+        entry
+    }
+}
+
+impl<'a> Iterator for XdesListEntries<'a> {
+    type Item = Result<XdesEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next.is_null() {
+            return None;
+        }
+
+        let location = self.next;
+        // Stop here no matter what, only continue once this node is good
+        self.next = FileAddress::new(FIL_NULL, 0);
+
+        if self.remaining == 0 {
+            return Some(Err(anyhow!(
+                "Extent list is longer than its base node claims, at {:?}",
+                location
+            )));
+        }
+        self.remaining -= 1;
+
+        let entry = self.load(location);
+        if let Ok(entry) = &entry {
+            self.next = entry.list_node.next;
+        }
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        buffer_manager::memory::MemoryBufferManager,
+        page::{fsp::FspHeaderPage, Page, PageSize, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{extent_pages, xdes_per_page, xdes_size, XdesPage, XdesState};
+
+    #[test]
+    fn test_xdes_sizes() {
+        assert_eq!(extent_pages(PageSize::Size4K), 256);
+        assert_eq!(extent_pages(PageSize::Size16K), 64);
+        assert_eq!(extent_pages(PageSize::Size64K), 64);
+        assert_eq!(xdes_size(PageSize::Size16K), 40);
+        assert_eq!(xdes_size(PageSize::Size8K), 56);
+        assert_eq!(xdes_per_page(PageSize::Size16K), 256);
+    }
+
+    #[test]
+    fn test_fsp_hdr_descriptors() {
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        let xdes = XdesPage::try_from_page(page).unwrap();
+        assert_eq!(xdes.pages(), 0..16384);
+
+        let entries = xdes.entries().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 256);
+        let first = &entries[0];
+        assert_eq!(first.pages, 0..64);
+        assert_eq!(first.state, XdesState::FreeFrag);
+        // The FSP header counts five fragment pages in use
+        assert_eq!(first.allocated_pages().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+        assert_eq!(first.is_page_clean(5), Some(true));
+        assert_eq!(first.is_page_allocated(64), None);
+        assert!(entries[1..]
+            .iter()
+            .all(|entry| entry.state == XdesState::NotInited));
+        assert_eq!(entries[1].is_page_allocated(64), Some(false));
+
+        assert_eq!(xdes.is_page_allocated(4).unwrap(), Some(true));
+        assert_eq!(xdes.is_page_allocated(6).unwrap(), Some(false));
+        assert_eq!(xdes.is_page_allocated(1000).unwrap(), Some(false));
+        assert_eq!(xdes.is_page_allocated(16384).unwrap(), None);
+    }
+
+    #[test]
+    fn test_not_a_descriptor_page() {
+        let data = test_data("float_sample.ibd");
+        let index = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        assert!(XdesPage::try_from_page(index).is_err());
+
+        let mut buf = data[..FIL_PAGE_SIZE].to_vec();
+        // Unknown state of the first extent
+        buf[150 + 23] = 9;
+        let xdes = XdesPage::try_from_page(Page::from_bytes(&buf).unwrap()).unwrap();
+        assert!(xdes.entries().next().unwrap().is_err());
+        // Descriptor pages are only every 16384 pages
+        buf[4..8].copy_from_slice(&64u32.to_be_bytes());
+        assert!(XdesPage::try_from_page(Page::from_bytes(&buf).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_iter_extents() {
+        let mut data = test_data("float_sample.ibd");
+        // Put the second and third extent on the FREE list
+        let address =
+            |page: u32, offset: u16| [&page.to_be_bytes()[..], &offset.to_be_bytes()].concat();
+        let null = address(u32::MAX, 0);
+        data[62..78].copy_from_slice(
+            &[&2u32.to_be_bytes()[..], &address(0, 198), &address(0, 238)].concat(),
+        );
+        data[198..210].copy_from_slice(&[&null[..], &address(0, 238)].concat());
+        data[238..250].copy_from_slice(&[&address(0, 198)[..], &null].concat());
+        data[210..214].copy_from_slice(&(XdesState::Free as u32).to_be_bytes());
+        data[250..254].copy_from_slice(&(XdesState::Free as u32).to_be_bytes());
+
+        let header = FspHeaderPage::try_from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_space(header.space_id, &data).unwrap();
+
+        let free = header
+            .extents(&header.free, &buffer_mgr)
+            .map(|entry| entry.map(|entry| entry.pages))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(free, [64..128, 128..192]);
+
+        let states = header
+            .iter_extents(&buffer_mgr)
+            .map(|entry| entry.map(|entry| (entry.pages, entry.state)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            states,
+            [
+                (64..128, XdesState::Free),
+                (128..192, XdesState::Free),
+                (0..64, XdesState::FreeFrag),
+            ]
+        );
+        assert_eq!(buffer_mgr.pin_count(header.space_id, 0), 0);
+
+        // A list longer than its base node claims
+        data[62..66].copy_from_slice(&1u32.to_be_bytes());
+        let header = FspHeaderPage::try_from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        let mut free = header.extents(&header.free, &buffer_mgr);
+        assert!(free.next().unwrap().is_ok());
+        assert!(free.next().unwrap().is_err());
+        assert!(free.next().is_none());
+    }
+}
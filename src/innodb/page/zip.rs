@@ -0,0 +1,875 @@
+//! ROW_FORMAT=COMPRESSED index pages. The FIL and index headers are stored as
+//! is, followed by a zlib stream of the record heap without the 5 byte record
+//! headers and a log of the changes made since the page was compressed. The
+//! end of the page holds, from the back, the dense directory with one slot per
+//! record, then the uncompressed DB_TRX_ID and DB_ROLL_PTR columns or node
+//! pointers and finally the BLOB pointers.
+
+use anyhow::{anyhow, Result};
+use flate2::{Decompress, FlushDecompress, Status};
+
+use crate::innodb::{file_list::FIL_NULL, InnoDBError};
+
+use super::{
+    writer::PageWriter, ChecksumKind, FILHeader, OwnedPage, PageSize, PageType, FIL_HEADER_SIZE,
+    FIL_PAGE_SIZE,
+};
+
+/// Size of the headers kept uncompressed
+const PAGE_DATA: usize = 94;
+const INFIMUM: usize = 99;
+const SUPREMUM: usize = 112;
+/// End of the supremum record, where the compressed heap starts
+const PAGE_ZIP_START: usize = 120;
+const PAGE_DIR: usize = 8;
+const REC_N_NEW_EXTRA_BYTES: usize = 5;
+
+const DIR_SLOT_SIZE: usize = 2;
+const DIR_SLOT_MASK: u16 = 0x3fff;
+const DIR_SLOT_OWNED: u16 = 0x4000;
+const DIR_SLOT_DEL: u16 = 0x8000;
+
+const REC_INFO_MIN_REC_FLAG: u8 = 0x10;
+const REC_INFO_DELETED_FLAG: u8 = 0x20;
+const REC_STATUS_ORDINARY: u16 = 0;
+const REC_STATUS_NODE_PTR: u16 = 1;
+const REC_HEAP_NO_SHIFT: u16 = 3;
+
+const TRX_ID_ROLL_PTR_LEN: usize = 6 + 7;
+const NODE_PTR_SIZE: usize = 4;
+const EXTERN_FIELD_REF_SIZE: usize = 20;
+
+/// Column as described on the compressed page, where runs of NOT NULL fixed
+/// length columns are merged into one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ZipField {
+    /// 0 for variable length columns
+    fixed_len: usize,
+    /// Variable length column longer than 255 bytes, which can take 2 length
+    /// bytes and be stored externally
+    big: bool,
+    nullable: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ZipIndex {
+    fields: Vec<ZipField>,
+    n_nullable: usize,
+    /// Field starting with DB_TRX_ID and DB_ROLL_PTR on clustered leaf pages
+    trx_id_col: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FieldOffset {
+    start: usize,
+    end: usize,
+    external: bool,
+}
+
+/// Fields of a record relative to its origin
+#[derive(Debug)]
+struct RecordOffsets {
+    /// Null bitmap and length bytes in front of the record header
+    extra: usize,
+    fields: Vec<FieldOffset>,
+}
+
+impl RecordOffsets {
+    fn data_size(&self) -> usize {
+        self.fields.last().map_or(0, |field| field.end)
+    }
+
+    fn any_external(&self) -> bool {
+        self.fields.iter().any(|field| field.external)
+    }
+}
+
+impl ZipIndex {
+    /// Decodes the index description at the start of the stream, see
+    /// page_zip_fields_encode()
+    fn decode(buf: &[u8], leaf: bool) -> Result<Self> {
+        let mut values = Vec::new();
+        let mut bytes = buf.iter();
+        while let Some(&byte) = bytes.next() {
+            if byte & 0x80 != 0 {
+                let low = *bytes.next().ok_or(InnoDBError::InvalidLength)?;
+                values.push((true, ((byte as usize & 0x7f) << 8) | low as usize));
+            } else {
+                values.push((false, byte as usize));
+            }
+        }
+        let (_, last) = values
+            .pop()
+            .ok_or_else(|| anyhow!("Empty compressed index description"))?;
+
+        let fields: Vec<ZipField> = values
+            .into_iter()
+            .map(|(wide, value)| {
+                let nullable = value & 1 == 0;
+                match value {
+                    _ if wide => ZipField {
+                        fixed_len: value >> 1,
+                        big: false,
+                        nullable,
+                    },
+                    126.. => ZipField {
+                        fixed_len: 0,
+                        big: true,
+                        nullable,
+                    },
+                    0 | 1 => ZipField {
+                        fixed_len: 0,
+                        big: false,
+                        nullable,
+                    },
+                    _ => ZipField {
+                        fixed_len: value >> 1,
+                        big: false,
+                        nullable,
+                    },
+                }
+            })
+            .collect();
+        let nullable = fields.iter().filter(|field| field.nullable).count();
+
+        // Leaf pages end with the DB_TRX_ID position, node pointer pages with
+        // the null bitmap size of the whole index
+        if leaf {
+            let trx_id_col = match last {
+                0 => None,
+                col if col < fields.len() => {
+                    let field = fields[col];
+                    if field.nullable || field.fixed_len < TRX_ID_ROLL_PTR_LEN {
+                        return Err(anyhow!("Invalid DB_TRX_ID field {:?}", field));
+                    }
+                    Some(col)
+                }
+                col => return Err(anyhow!("DB_TRX_ID at {} of {} fields", col, fields.len())),
+            };
+            Ok(ZipIndex {
+                fields,
+                n_nullable: nullable,
+                trx_id_col,
+            })
+        } else if last < nullable {
+            Err(anyhow!("{} nullable fields out of {}", nullable, last))
+        } else {
+            Ok(ZipIndex {
+                fields,
+                n_nullable: last,
+                trx_id_col: None,
+            })
+        }
+    }
+
+    /// Parses the bytes in front of a record header, `next` returns them in
+    /// the order they're read, going back from the header
+    fn offsets(
+        &self,
+        node_ptr: bool,
+        mut next: impl FnMut() -> Result<u8>,
+    ) -> Result<RecordOffsets> {
+        let nulls = (0..self.n_nullable.div_ceil(8))
+            .map(|_| next())
+            .collect::<Result<Vec<_>>>()?;
+        let mut extra = nulls.len();
+        let mut null_index = 0;
+        let mut end = 0;
+        let mut fields = Vec::with_capacity(self.fields.len() + 1);
+        for field in &self.fields {
+            let start = end;
+            let mut external = false;
+            if field.nullable {
+                let null = nulls[null_index / 8] & (1 << (null_index % 8)) != 0;
+                null_index += 1;
+                if null {
+                    fields.push(FieldOffset {
+                        start,
+                        end,
+                        external,
+                    });
+                    continue;
+                }
+            }
+            if field.fixed_len > 0 {
+                end += field.fixed_len;
+            } else {
+                let mut len = next()? as usize;
+                extra += 1;
+                if field.big && len & 0x80 != 0 {
+                    len = (len << 8) | next()? as usize;
+                    extra += 1;
+                    external = len & 0x4000 != 0;
+                    len &= 0x3fff;
+                }
+                end += len;
+            }
+            fields.push(FieldOffset {
+                start,
+                end,
+                external,
+            });
+        }
+        if node_ptr {
+            fields.push(FieldOffset {
+                start: end,
+                end: end + NODE_PTR_SIZE,
+                external: false,
+            });
+        }
+        Ok(RecordOffsets { extra, fields })
+    }
+
+    /// Offsets of the record at `rec` of the logical page
+    fn page_offsets(&self, page: &[u8], rec: usize) -> Result<RecordOffsets> {
+        let node_ptr = (page[rec - 3] as u16 & 0x7) == REC_STATUS_NODE_PTR;
+        let mut at = rec - REC_N_NEW_EXTRA_BYTES;
+        let offsets = self.offsets(node_ptr, || {
+            at = at.checked_sub(1).filter(|&at| at >= PAGE_ZIP_START).ok_or(
+                InnoDBError::InvalidRecordOffset {
+                    offset: rec,
+                    next: None,
+                },
+            )?;
+            Ok(page[at])
+        })?;
+        if rec + offsets.data_size() > page.len() - PAGE_DIR {
+            return Err(anyhow!(InnoDBError::InvalidRecordOffset {
+                offset: rec,
+                next: None
+            }));
+        }
+        Ok(offsets)
+    }
+}
+
+fn adler32(parts: &[&[u8]]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in parts.iter().flat_map(|part| part.iter()) {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Inflates the zlib stream at the start of `buf` into the index description
+/// and the heap, returning them with the length of the stream.
+///
+/// The description is its own deflate block ended by a full flush, the empty
+/// stored block `00 00 ff ff`, and the heap can be inflated on its own from
+/// there. Candidates for the marker are checked against the stream checksum.
+fn inflate(buf: &[u8], size: PageSize) -> Result<(Vec<u8>, Vec<u8>, usize)> {
+    if buf.len() < 2
+        || buf[0] & 0x0f != 8
+        || !u16::from_be_bytes([buf[0], buf[1]]).is_multiple_of(31)
+    {
+        return Err(anyhow!("No zlib stream on the compressed page"));
+    }
+    for split in (2..buf.len().saturating_sub(3)).filter(|&i| buf[i..i + 4] == [0, 0, 0xff, 0xff]) {
+        let head = &buf[2..split + 4];
+        let mut fields = Vec::with_capacity(4096);
+        let mut decompress = Decompress::new(false);
+        match decompress.decompress_vec(head, &mut fields, FlushDecompress::Sync) {
+            Ok(Status::Ok) if decompress.total_in() == head.len() as u64 => {}
+            _ => continue,
+        }
+
+        let tail = &buf[split + 4..];
+        let mut heap = Vec::with_capacity(size.bytes());
+        let mut decompress = Decompress::new(false);
+        match decompress.decompress_vec(tail, &mut heap, FlushDecompress::Finish) {
+            Ok(Status::StreamEnd) => {}
+            _ => continue,
+        }
+        let end = split + 4 + decompress.total_in() as usize;
+        match buf.get(end..end + 4) {
+            Some(adler) if *adler == adler32(&[&fields, &heap]).to_be_bytes() => {
+                return Ok((fields, heap, end + 4));
+            }
+            _ => continue,
+        }
+    }
+    Err(anyhow!("Can't inflate the compressed page"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ZipKind {
+    NodePointer,
+    Secondary,
+    Clustered(usize),
+}
+
+/// Writes the inflated heap into the logical page, skipping what isn't part
+/// of the stream the way inflate() with a bounded output would
+struct HeapWriter<'a> {
+    heap: &'a [u8],
+    pos: usize,
+    out: usize,
+}
+
+impl HeapWriter<'_> {
+    /// Copies up to page offset `to`, returns whether the stream ran out
+    fn fill(&mut self, page: &mut [u8], to: usize) -> Result<bool> {
+        if to < self.out || to > page.len() - PAGE_DIR {
+            return Err(anyhow!(
+                "Compressed heap at {} can't continue at {}",
+                self.out,
+                to
+            ));
+        }
+        if to == self.out {
+            return Ok(false);
+        }
+        let len = (to - self.out).min(self.heap.len() - self.pos);
+        page[self.out..self.out + len].copy_from_slice(&self.heap[self.pos..self.pos + len]);
+        self.pos += len;
+        self.out += len;
+        Ok(self.pos == self.heap.len())
+    }
+
+    /// Like [`HeapWriter::fill`] where running out before `to` is an error
+    fn fill_all(&mut self, page: &mut [u8], to: usize) -> Result<()> {
+        self.fill(page, to)?;
+        if self.out != to {
+            return Err(anyhow!("Compressed heap ends inside the record"));
+        }
+        Ok(())
+    }
+}
+
+fn write_u16(page: &mut [u8], at: usize, value: u16) {
+    page[at..at + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn set_next(page: &mut [u8], rec: usize, next: usize) {
+    let relative = match next {
+        0 => 0,
+        next => (next as u16).wrapping_sub(rec as u16),
+    };
+    write_u16(page, rec - 2, relative);
+}
+
+/// Copies `src` to the page, where the data must fit before the directory
+fn copy_to(page: &mut [u8], at: usize, src: &[u8]) -> Result<()> {
+    let end = page.len() - PAGE_DIR;
+    page.get_mut(at..at + src.len())
+        .filter(|_| at + src.len() <= end)
+        .ok_or(InnoDBError::InvalidLength)?
+        .copy_from_slice(src);
+    Ok(())
+}
+
+/// Takes `len` bytes of the log at `*pos`, at least the end marker has to
+/// follow them
+fn take<'a>(log: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    if *pos + len >= log.len() {
+        return Err(anyhow!("Modification log overflows at {}", *pos));
+    }
+    let bytes = &log[*pos..*pos + len];
+    *pos += len;
+    Ok(bytes)
+}
+
+/// Replays the modification log onto the page, see page_zip_apply_log().
+/// Returns the length of the log without its end marker.
+fn apply_log(
+    page: &mut [u8],
+    log: &[u8],
+    recs: &[usize],
+    index: &ZipIndex,
+    heap_status: &mut u16,
+) -> Result<usize> {
+    let mut pos = 0;
+    loop {
+        let mut val = *log.get(pos).ok_or(InnoDBError::InvalidLength)? as usize;
+        pos += 1;
+        if val == 0 {
+            return Ok(pos - 1);
+        }
+        if val & 0x80 != 0 {
+            val = ((val & 0x7f) << 8) | *log.get(pos).ok_or(InnoDBError::InvalidLength)? as usize;
+            pos += 1;
+        }
+        // The entry is numbered by heap_no - 1, the low bit clears the record
+        let heap_index = val >> 1;
+        if pos >= log.len() || heap_index == 0 || heap_index > recs.len() {
+            return Err(anyhow!("Invalid modification log entry {:#x}", val));
+        }
+        let rec = recs[heap_index - 1];
+        let hs = (((heap_index + 1) as u16) << REC_HEAP_NO_SHIFT) | (*heap_status & 0x7);
+        if hs > *heap_status {
+            return Err(anyhow!("Modification log skips heap number {}", hs >> 3));
+        } else if hs == *heap_status {
+            if val & 1 != 0 {
+                return Err(anyhow!("Modification log clears a new record"));
+            }
+            *heap_status += 1 << REC_HEAP_NO_SHIFT;
+        }
+        write_u16(page, rec - 4, hs);
+
+        if val & 1 != 0 {
+            let size = index.page_offsets(page, rec)?.data_size();
+            page[rec..rec + size].fill(0);
+            continue;
+        }
+
+        let node_ptr = hs & 0x7 == REC_STATUS_NODE_PTR;
+        let mut at = pos;
+        let offsets = index.offsets(node_ptr, || {
+            let byte = *log.get(at).ok_or(InnoDBError::InvalidLength)?;
+            at += 1;
+            Ok(byte)
+        })?;
+        // The extra bytes are logged in reading order, back from the header
+        let extra = take(log, &mut pos, offsets.extra)?;
+        if rec - REC_N_NEW_EXTRA_BYTES < PAGE_ZIP_START + extra.len() {
+            return Err(anyhow!(InnoDBError::InvalidLength));
+        }
+        for (i, &byte) in extra.iter().enumerate() {
+            page[rec - REC_N_NEW_EXTRA_BYTES - 1 - i] = byte;
+        }
+
+        let size = offsets.data_size();
+        if offsets.any_external() {
+            if node_ptr {
+                return Err(anyhow!("Node pointer with an external field"));
+            }
+            // Everything but DB_TRX_ID, DB_ROLL_PTR and the BLOB pointers
+            let mut next = 0;
+            for (i, field) in offsets.fields.iter().enumerate() {
+                let skip = if Some(i) == index.trx_id_col {
+                    field.start..field.start + TRX_ID_ROLL_PTR_LEN
+                } else if field.external {
+                    if field.end - field.start < EXTERN_FIELD_REF_SIZE {
+                        return Err(anyhow!("External field shorter than its pointer"));
+                    }
+                    field.end - EXTERN_FIELD_REF_SIZE..field.end
+                } else {
+                    continue;
+                };
+                copy_to(page, rec + next, take(log, &mut pos, skip.start - next)?)?;
+                next = skip.end;
+            }
+            copy_to(page, rec + next, take(log, &mut pos, size - next)?)?;
+        } else if node_ptr {
+            copy_to(page, rec, take(log, &mut pos, size - NODE_PTR_SIZE)?)?;
+        } else if let Some(trx_id_col) = index.trx_id_col {
+            let trx_id = offsets.fields[trx_id_col];
+            if trx_id.end - trx_id.start < TRX_ID_ROLL_PTR_LEN {
+                return Err(anyhow!("DB_TRX_ID field is too short"));
+            }
+            copy_to(page, rec, take(log, &mut pos, trx_id.start)?)?;
+            let after = trx_id.start + TRX_ID_ROLL_PTR_LEN;
+            copy_to(page, rec + after, take(log, &mut pos, size - after)?)?;
+        } else {
+            copy_to(page, rec, take(log, &mut pos, size)?)?;
+        }
+    }
+}
+
+/// Rebuilds the logical page of `size` from a compressed index page of any
+/// KEY_BLOCK_SIZE, see page_zip_decompress(). The checksum fields of the
+/// result are filled in with crc32 like InnoDB does when writing a page.
+pub fn decompress(zip: &[u8], size: PageSize) -> Result<OwnedPage> {
+    // ROW_FORMAT=COMPRESSED isn't supported above 16K pages, the directory
+    // slots can't address more
+    if size.bytes() > FIL_PAGE_SIZE {
+        return Err(anyhow!(
+            "Compressed pages need a page size up to 16K, not {}",
+            size
+        ));
+    }
+    let page_size = size.bytes();
+    let zip_size = zip.len();
+    if !zip_size.is_power_of_two() || !(1024..=page_size).contains(&zip_size) {
+        return Err(anyhow!(InnoDBError::InvalidLength));
+    }
+    let header = FILHeader::from_bytes(&zip[..FIL_HEADER_SIZE])?;
+    if !matches!(
+        header.page_type,
+        PageType::Index | PageType::RTree | PageType::SDI
+    ) {
+        return Err(anyhow!(InnoDBError::InvalidPageType {
+            expected: PageType::Index,
+            has: header.page_type
+        }));
+    }
+    let read_u16 = |at: usize| u16::from_be_bytes([zip[at], zip[at + 1]]);
+    let n_dir_slots = read_u16(38) as usize;
+    let heap_top = read_u16(40) as usize;
+    let n_heap = read_u16(42);
+    let n_recs = read_u16(54) as usize;
+    let leaf = read_u16(64) == 0;
+    if n_heap & 0x8000 == 0 {
+        return Err(anyhow!("Compressed page in the redundant format"));
+    }
+    let n_dense = ((n_heap & 0x7fff) as usize)
+        .checked_sub(2)
+        .filter(|&n_dense| n_recs <= n_dense)
+        .ok_or_else(|| anyhow!("{} records on a heap of {}", n_recs, n_heap & 0x7fff))?;
+
+    let zip_dir_start = zip_size
+        .checked_sub(n_dense * DIR_SLOT_SIZE)
+        .filter(|&start| start > PAGE_DATA)
+        .ok_or(InnoDBError::InvalidLength)?;
+    let dir_slot = |i: usize| read_u16(zip_size - DIR_SLOT_SIZE * (i + 1));
+
+    let mut page = vec![0u8; page_size];
+    page[..PAGE_DATA].copy_from_slice(&zip[..PAGE_DATA]);
+
+    // The sparse directory, from the owners in the dense directory
+    let mut recs = Vec::with_capacity(n_dense);
+    let mut slots = vec![INFIMUM];
+    for i in 0..n_dense {
+        let slot = dir_slot(i);
+        let offset = (slot & DIR_SLOT_MASK) as usize;
+        if i < n_recs && slot & DIR_SLOT_OWNED != 0 {
+            slots.push(offset);
+        }
+        if (i >= n_recs && slot & !DIR_SLOT_MASK != 0)
+            || !(PAGE_ZIP_START + REC_N_NEW_EXTRA_BYTES..page_size - PAGE_DIR).contains(&offset)
+        {
+            return Err(anyhow!("Invalid dense directory slot {} {:#x}", i, slot));
+        }
+        recs.push(offset);
+    }
+    slots.push(SUPREMUM);
+    if slots.len() != n_dir_slots {
+        return Err(anyhow!(
+            "Dense directory owns {} slots, the header has {}",
+            slots.len(),
+            n_dir_slots
+        ));
+    }
+    for (i, &slot) in slots.iter().enumerate() {
+        write_u16(
+            &mut page,
+            page_size - PAGE_DIR - DIR_SLOT_SIZE * (i + 1),
+            slot as u16,
+        );
+    }
+    recs.sort_unstable();
+
+    page[INFIMUM - 5..INFIMUM - 2].copy_from_slice(&[0x01, 0x00, 0x02]);
+    page[INFIMUM..INFIMUM + 8].copy_from_slice(b"infimum\0");
+    page[SUPREMUM - 4..SUPREMUM - 2].copy_from_slice(&[0x00, 0x0b]);
+    page[SUPREMUM..SUPREMUM + 8].copy_from_slice(b"supremum");
+
+    let (fields, heap, stream_len) = inflate(&zip[PAGE_DATA..zip_dir_start - 1], size)?;
+    let index = ZipIndex::decode(&fields, leaf)?;
+    let (kind, uncompressed) = match index.trx_id_col {
+        _ if !leaf => (ZipKind::NodePointer, NODE_PTR_SIZE),
+        Some(col) => (ZipKind::Clustered(col), TRX_ID_ROLL_PTR_LEN),
+        None => (ZipKind::Secondary, 0),
+    };
+    let storage = zip_dir_start
+        .checked_sub(n_dense * uncompressed)
+        .ok_or(InnoDBError::InvalidLength)?;
+    let log_start = PAGE_DATA + stream_len;
+    if log_start >= storage {
+        return Err(anyhow!(
+            "Compressed stream overlaps the uncompressed columns"
+        ));
+    }
+
+    // The heap in address order, which is also heap number order
+    let status = if leaf {
+        REC_STATUS_ORDINARY
+    } else {
+        REC_STATUS_NODE_PTR
+    };
+    let mut heap_status = (2 << REC_HEAP_NO_SHIFT) | status;
+    let mut writer = HeapWriter {
+        heap: &heap,
+        pos: 0,
+        out: PAGE_ZIP_START,
+    };
+    let mut complete = true;
+    'records: for &rec in &recs {
+        let header = rec - REC_N_NEW_EXTRA_BYTES;
+        // Records added since the page was compressed are only in the log
+        let ended = writer.fill(&mut page, header)?;
+        if writer.out == header {
+            writer.out = rec;
+            write_u16(&mut page, rec - 4, heap_status);
+            heap_status += 1 << REC_HEAP_NO_SHIFT;
+        }
+        if ended {
+            complete = false;
+            break;
+        }
+
+        match kind {
+            ZipKind::Secondary => {}
+            ZipKind::NodePointer => {
+                let offsets = index.page_offsets(&page, rec)?;
+                if writer.fill(&mut page, rec + offsets.data_size() - NODE_PTR_SIZE)? {
+                    complete = false;
+                    break 'records;
+                }
+                writer.out += NODE_PTR_SIZE;
+            }
+            ZipKind::Clustered(trx_id_col) => {
+                let offsets = index.page_offsets(&page, rec)?;
+                for (i, field) in offsets.fields.iter().enumerate() {
+                    let skip = if i == trx_id_col {
+                        field.start..field.start + TRX_ID_ROLL_PTR_LEN
+                    } else if field.external {
+                        if field.end - field.start < EXTERN_FIELD_REF_SIZE {
+                            return Err(anyhow!("External field shorter than its pointer"));
+                        }
+                        field.end - EXTERN_FIELD_REF_SIZE..field.end
+                    } else {
+                        continue;
+                    };
+                    writer.fill_all(&mut page, rec + skip.start)?;
+                    writer.out = rec + skip.end;
+                }
+                writer.fill_all(&mut page, rec + offsets.data_size())?;
+            }
+        }
+    }
+    // Free space left over from a longer record a short one was put in
+    if complete {
+        if heap_top < writer.out {
+            return Err(anyhow!("Heap top {} inside the records", heap_top));
+        }
+        writer.fill(&mut page, heap_top)?;
+        if writer.pos != heap.len() {
+            return Err(anyhow!("Compressed heap is longer than the heap top"));
+        }
+    }
+
+    let log_len = apply_log(
+        &mut page,
+        &zip[log_start..storage],
+        &recs,
+        &index,
+        &mut heap_status,
+    )?;
+    let log_end = log_start + log_len;
+
+    // Columns kept out of the stream, stored in heap number order
+    let free = (n_recs..n_dense)
+        .map(|i| (dir_slot(i) & DIR_SLOT_MASK) as usize)
+        .collect::<Vec<_>>();
+    let mut externs = storage;
+    for (i, &rec) in recs.iter().enumerate() {
+        let slot = zip_dir_start - uncompressed * (i + 1);
+        let stored = &zip[slot..slot + uncompressed];
+        match kind {
+            ZipKind::Secondary => {}
+            ZipKind::NodePointer => {
+                let end = rec + index.page_offsets(&page, rec)?.data_size();
+                page[end - NODE_PTR_SIZE..end].copy_from_slice(stored);
+            }
+            ZipKind::Clustered(trx_id_col) => {
+                let offsets = index.page_offsets(&page, rec)?;
+                let trx_id = rec + offsets.fields[trx_id_col].start;
+                page[trx_id..trx_id + TRX_ID_ROLL_PTR_LEN].copy_from_slice(stored);
+                // Deleted records have their BLOB pointers cleared
+                let exists = !free.contains(&rec);
+                for field in offsets.fields.iter().filter(|field| field.external) {
+                    let at = rec + field.end - EXTERN_FIELD_REF_SIZE;
+                    if exists {
+                        externs = externs
+                            .checked_sub(EXTERN_FIELD_REF_SIZE)
+                            .filter(|&externs| externs >= log_end)
+                            .ok_or_else(|| anyhow!("BLOB pointers overlap the log"))?;
+                        page[at..at + EXTERN_FIELD_REF_SIZE]
+                            .copy_from_slice(&zip[externs..externs + EXTERN_FIELD_REF_SIZE]);
+                    } else {
+                        page[at..at + EXTERN_FIELD_REF_SIZE].fill(0);
+                    }
+                }
+            }
+        }
+    }
+
+    // Record headers, from the dense directory in list order then the free
+    // list
+    let mut info_bits = if !leaf && header.prev == FIL_NULL {
+        REC_INFO_MIN_REC_FLAG
+    } else {
+        0
+    };
+    let mut n_owned = 1;
+    let mut prev = INFIMUM;
+    for i in 0..n_recs {
+        let slot = dir_slot(i);
+        if slot & DIR_SLOT_DEL != 0 {
+            info_bits |= REC_INFO_DELETED_FLAG;
+        }
+        if slot & DIR_SLOT_OWNED != 0 {
+            info_bits |= n_owned;
+            n_owned = 1;
+        } else {
+            n_owned += 1;
+        }
+        let rec = (slot & DIR_SLOT_MASK) as usize;
+        set_next(&mut page, prev, rec);
+        page[rec - 5] = info_bits;
+        info_bits = 0;
+        prev = rec;
+    }
+    set_next(&mut page, prev, SUPREMUM);
+    page[SUPREMUM - 5] = n_owned;
+    for (i, &rec) in free.iter().enumerate() {
+        page[rec - 5] = 0;
+        set_next(&mut page, rec, free.get(i + 1).copied().unwrap_or(0));
+    }
+
+    let page = PageWriter::try_from_bytes_with_size(&page, size)?.finalize(ChecksumKind::Crc32);
+    OwnedPage::try_from_bytes_with_size(&page, size)
+}
+
+#[cfg(test)]
+mod test {
+
+    use anyhow::Result;
+
+    use crate::innodb::page::{
+        index::{record::RecordType, IndexPage},
+        Page, PageSize, FIL_PAGE_SIZE,
+    };
+
+    use crate::innodb::test_data;
+
+    use super::{decompress, inflate, ZipField, ZipIndex, PAGE_DATA};
+
+    /// float_sample page 4, compressed with KEY_BLOCK_SIZE=8
+    fn compressed_page() -> Vec<u8> {
+        test_data("compressed_8k.page")
+    }
+
+    fn float_sample_page() -> Vec<u8> {
+        test_data("float_sample.ibd")[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE].to_vec()
+    }
+
+    fn user_records(page: Page) -> Vec<(usize, Vec<u8>)> {
+        let index = IndexPage::try_from_page(page).unwrap();
+        index
+            .records()
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+            .into_iter()
+            .filter(|record| record.header.record_type == RecordType::Conventional)
+            .map(|record| (record.offset, record.buf[record.offset..][..20].to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_decompress() {
+        let zip = compressed_page();
+        let page = Page::from_bytes_with_size(&zip, PageSize::Size8K).unwrap();
+        let logical = page.decompress(PageSize::Size16K).unwrap();
+        assert_eq!(logical.raw_data(), &float_sample_page()[..]);
+        assert!(logical.page().checksum_matches().is_some());
+
+        let records = user_records(logical.page());
+        assert_eq!(
+            records,
+            vec![
+                (127, format!("{:<20}", "test1").into_bytes()),
+                (179, format!("{:<20}", "test2").into_bytes())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decompress_page_size() {
+        let zip = compressed_page();
+        let logical = decompress(&zip, PageSize::Size8K).unwrap();
+        assert_eq!(logical.raw_data().len(), 8192);
+        assert!(logical.page().checksum_matches().is_some());
+        assert_eq!(
+            user_records(logical.page()),
+            user_records(decompress(&zip, PageSize::Size16K).unwrap().page())
+        );
+
+        // The compressed page can't be larger than the logical one
+        assert!(decompress(&zip, PageSize::Size4K).is_err());
+        assert!(decompress(&zip, PageSize::Size32K).is_err());
+    }
+
+    #[test]
+    fn test_decompress_modification_log() {
+        let mut zip = compressed_page();
+        let (_, _, stream_len) = inflate(&zip[PAGE_DATA..], PageSize::Size16K).unwrap();
+        // Heap number 2 rewritten in place: its null bitmap and length, then
+        // the data without DB_TRX_ID and DB_ROLL_PTR, followed by the end marker
+        let mut entry = vec![(2 - 1) << 1, 0x00, 0x14];
+        entry.extend(format!("{:<20}", "TEST1").bytes());
+        entry.extend([0u8; 12]);
+        let log = PAGE_DATA + stream_len;
+        zip[log..log + entry.len()].copy_from_slice(&entry);
+
+        let logical = decompress(&zip, PageSize::Size16K).unwrap();
+        let original = float_sample_page();
+        let data = logical.raw_data();
+        assert_eq!(&data[127..147], format!("{:<20}", "TEST1").as_bytes());
+        assert_eq!(data[147..160], original[147..160]);
+        assert_eq!(data[160..172], [0u8; 12]);
+        assert_eq!(
+            data[172..FIL_PAGE_SIZE - 8],
+            original[172..FIL_PAGE_SIZE - 8]
+        );
+
+        // Entries for records that aren't on the page
+        zip[log..8192 - 4 - 26].fill(0x7f);
+        assert!(decompress(&zip, PageSize::Size16K).is_err());
+    }
+
+    #[test]
+    fn test_decode_fields() {
+        // (VARCHAR NOT NULL, DB_TRX_ID, DB_ROLL_PTR, FLOAT NULL, DOUBLE NULL)
+        let index = ZipIndex::decode(&[0x01, 0x1b, 0x08, 0x10, 0x01], true).unwrap();
+        assert_eq!(index.trx_id_col, Some(1));
+        assert_eq!(index.n_nullable, 2);
+        assert_eq!(
+            index.fields[1],
+            ZipField {
+                fixed_len: 13,
+                big: false,
+                nullable: false
+            }
+        );
+
+        // Node pointers of a nullable BLOB prefix and a 200 byte NOT NULL
+        // column, in an index with 3 nullable columns
+        let index = ZipIndex::decode(&[0x7e, 0x81, 0x91, 0x03], false).unwrap();
+        assert_eq!(index.trx_id_col, None);
+        assert_eq!(index.n_nullable, 3);
+        assert_eq!(
+            index.fields,
+            vec![
+                ZipField {
+                    fixed_len: 0,
+                    big: true,
+                    nullable: true
+                },
+                ZipField {
+                    fixed_len: 200,
+                    big: false,
+                    nullable: false
+                }
+            ]
+        );
+
+        // DB_TRX_ID past the last field, and fewer nullable than described
+        assert!(ZipIndex::decode(&[0x01, 0x1b, 0x02], true).is_err());
+        assert!(ZipIndex::decode(&[0x00, 0x00, 0x01], false).is_err());
+    }
+
+    #[test]
+    fn test_decompress_wrong_page() {
+        let data = test_data("float_sample.ibd");
+        assert!(decompress(&data[..8192], PageSize::Size16K).is_err());
+        // Not a compressed page size
+        assert!(decompress(&compressed_page()[..8000], PageSize::Size16K).is_err());
+
+        let mut zip = compressed_page();
+        zip[PAGE_DATA + 10] ^= 0xff;
+        assert!(decompress(&zip, PageSize::Size16K).is_err());
+    }
+}
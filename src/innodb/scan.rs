@@ -0,0 +1,327 @@
+use std::{
+    io::{ErrorKind, Read},
+    ops::ControlFlow,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use tracing::warn;
+
+use super::{
+    buffer_manager::BufferManager,
+    page::{
+        index::{
+            record::{Record, RecordType},
+            IndexPage,
+        },
+        Page, PageSize, PageType,
+    },
+    table::{field::FieldValue, row::Row, TableDefinition},
+};
+
+/// Counters handed to the progress callback after every page
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub pages: usize,
+    pub bytes: u64,
+    pub records: usize,
+}
+
+/// Reads pages from `reader` until it runs out, a short trailing page is
+/// ignored. `visit` returns how many records it emitted for the page and
+/// `progress` is called after each page with the totals so far.
+pub fn scan_pages<R, V, P>(
+    mut reader: R,
+    page_size: PageSize,
+    mut visit: V,
+    mut progress: P,
+) -> Result<ScanProgress>
+where
+    R: Read,
+    V: FnMut(&Page) -> Result<usize>,
+    P: FnMut(ScanProgress),
+{
+    let mut stats = ScanProgress::default();
+    let mut buffer = vec![0u8; page_size.bytes()].into_boxed_slice();
+    loop {
+        match reader.read_exact(&mut buffer) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let page = Page::from_bytes_with_size(&buffer, page_size)?;
+        stats.records += visit(&page)?;
+        stats.pages += 1;
+        stats.bytes += buffer.len() as u64;
+        progress(stats);
+    }
+    Ok(stats)
+}
+
+/// What `dump_index` found on an index page
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndexDump {
+    /// User records handed to the callback
+    pub rows: usize,
+    /// User records that didn't parse against the table
+    pub failed: usize,
+    pub node_pointers: usize,
+    /// Whether the record list broke off before the supremum
+    pub corrupt: bool,
+}
+
+/// Parses a user record against `table`, loading extern fields through
+/// `buffer_mgr`
+pub fn parse_row<'a>(
+    record: &Record<'a>,
+    table: &Arc<TableDefinition>,
+    buffer_mgr: &dyn BufferManager,
+) -> Result<(Row<'a>, Vec<FieldValue>)> {
+    let row = Row::try_from_record_and_table(record, table)?;
+    let values = row.parse_values(buffer_mgr)?;
+    if values.len() != table.field_count() {
+        return Err(anyhow!(
+            "Parsed {} values, table has {} columns",
+            values.len(),
+            table.field_count()
+        ));
+    }
+    Ok((row, values))
+}
+
+/// Parses the user records of `index` against `table` and hands each row
+/// to `callback`, which can stop the walk after that row. Records that
+/// don't parse are skipped with a warning.
+pub fn dump_index<F>(
+    index: &IndexPage,
+    table: &Arc<TableDefinition>,
+    buffer_mgr: &dyn BufferManager,
+    mut callback: F,
+) -> IndexDump
+where
+    F: FnMut(&Row, &[FieldValue]) -> ControlFlow<()>,
+{
+    let mut dump = IndexDump::default();
+    for record in index.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                warn!(
+                    "Page {} is corrupt, stopped walking records: {:?}",
+                    index.page.header.offset, e
+                );
+                dump.corrupt = true;
+                break;
+            }
+        };
+        match record.header.record_type {
+            RecordType::Conventional => {}
+            RecordType::NodePointer => {
+                dump.node_pointers += 1;
+                continue;
+            }
+            _ => continue,
+        }
+        match parse_row(&record, table, buffer_mgr) {
+            Ok((row, values)) => {
+                dump.rows += 1;
+                if callback(&row, &values).is_break() {
+                    break;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to parse record at {} on page {}: {:?}",
+                    record.offset, index.page.header.offset, e
+                );
+                dump.failed += 1;
+            }
+        }
+    }
+    dump
+}
+
+/// Parses the user records of every leaf page of `index_id`, or of every
+/// index when `None`, and hands them to `emit`. Pages with a bad checksum
+/// and records that don't parse against `table` are skipped with a warning.
+pub fn dump_rows<R, E, P>(
+    reader: R,
+    page_size: PageSize,
+    table: &Arc<TableDefinition>,
+    index_id: Option<u64>,
+    buffer_mgr: &dyn BufferManager,
+    mut emit: E,
+    progress: P,
+) -> Result<ScanProgress>
+where
+    R: Read,
+    E: FnMut(&Row, &[FieldValue]),
+    P: FnMut(ScanProgress),
+{
+    let visit = |page: &Page| -> Result<usize> {
+        if page.header.page_type != PageType::Index || page.checksum_matches().is_none() {
+            return Ok(0);
+        }
+        let index = IndexPage::try_from_page_ref(page)?;
+        if index.index_header.page_level != 0
+            || index_id.is_some_and(|id| id != index.index_header.index_id)
+        {
+            return Ok(0);
+        }
+        let dump = dump_index(&index, table, buffer_mgr, |row, values| {
+            emit(row, values);
+            ControlFlow::Continue(())
+        });
+        Ok(dump.rows)
+    };
+    scan_pages(reader, page_size, visit, progress)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::read_to_string, io::Cursor, ops::ControlFlow, sync::Arc};
+
+    use crate::innodb::{
+        buffer_manager::DummyBufferMangaer,
+        page::{index::IndexPage, Page, PageSize, FIL_PAGE_SIZE},
+        table::{field::FieldValue, TableDefinition},
+        test_data, test_data_path,
+    };
+
+    use super::{dump_index, dump_rows, scan_pages, IndexDump, ScanProgress};
+
+    /// float_sample with columns of the same width that can be parsed
+    fn float_sample_table() -> Arc<TableDefinition> {
+        let sql = read_to_string(test_data_path("double_test_table.sql"))
+            .unwrap()
+            .replace("`single_f` float", "`single_f` int")
+            .replace("`double_f` double", "`double_f` bigint");
+        Arc::new(TableDefinition::try_from_sql_statement(&sql).unwrap())
+    }
+
+    #[test]
+    fn test_scan_progress() {
+        // Twice the tablespace and half a page that's never reported
+        let mut data = test_data("float_sample.ibd").repeat(2);
+        data.extend_from_slice(&[0u8; FIL_PAGE_SIZE / 2]);
+
+        let mut reports = Vec::new();
+        let total = dump_rows(
+            Cursor::new(&data),
+            PageSize::default(),
+            &float_sample_table(),
+            None,
+            &DummyBufferMangaer,
+            |_, _| {},
+            |progress| reports.push(progress),
+        )
+        .unwrap();
+
+        assert_eq!(reports.len(), 14);
+        assert_eq!(reports.last(), Some(&total));
+        assert_eq!(
+            total,
+            ScanProgress {
+                pages: 14,
+                bytes: 14 * FIL_PAGE_SIZE as u64,
+                records: 4
+            }
+        );
+        for pair in reports.windows(2) {
+            assert_eq!(pair[1].pages, pair[0].pages + 1);
+            assert!(pair[1].bytes > pair[0].bytes);
+            assert!(pair[1].records >= pair[0].records);
+        }
+        // The index page is page 4
+        assert_eq!(reports[3].records, 0);
+        assert_eq!(reports[4].records, 2);
+    }
+
+    #[test]
+    fn test_dump_rows() {
+        let mut keys = Vec::new();
+        let total = dump_rows(
+            Cursor::new(test_data("float_sample.ibd")),
+            PageSize::default(),
+            &float_sample_table(),
+            Some(960),
+            &DummyBufferMangaer,
+            |_, values| keys.push(values[0].clone()),
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(total.records, 2);
+        assert_eq!(
+            keys,
+            vec![
+                FieldValue::String("test1".into()),
+                FieldValue::String("test2".into())
+            ]
+        );
+
+        // Another index, nothing is emitted
+        let total = dump_rows(
+            Cursor::new(test_data("float_sample.ibd")),
+            PageSize::default(),
+            &float_sample_table(),
+            Some(961),
+            &DummyBufferMangaer,
+            |_, _| panic!("Row of another index"),
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(total.records, 0);
+        assert_eq!(total.pages, 7);
+    }
+
+    #[test]
+    fn test_dump_index() {
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        let index = IndexPage::try_from_page(page).unwrap();
+        let table = float_sample_table();
+
+        let mut rows = Vec::new();
+        let dump = dump_index(&index, &table, &DummyBufferMangaer, |row, values| {
+            assert!(!row.record.header.info_flags.deleted);
+            rows.push(values.to_vec());
+            ControlFlow::Continue(())
+        });
+        assert_eq!(
+            dump,
+            IndexDump {
+                rows: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0], FieldValue::String("test1".into()));
+        assert_eq!(rows[1][0], FieldValue::String("test2".into()));
+        assert!(rows
+            .iter()
+            .all(|values| values.len() == table.field_count()));
+
+        // Stopping after the first row
+        let mut keys = Vec::new();
+        let dump = dump_index(&index, &table, &DummyBufferMangaer, |_, values| {
+            keys.push(values[0].clone());
+            ControlFlow::Break(())
+        });
+        assert_eq!(dump.rows, 1);
+        assert_eq!(keys, vec![FieldValue::String("test1".into())]);
+    }
+
+    #[test]
+    fn test_scan_pages_visit_error() {
+        let result = scan_pages(
+            Cursor::new(test_data("float_sample.ibd")),
+            PageSize::default(),
+            |page| match page.header.offset {
+                2 => Err(anyhow::anyhow!("stop")),
+                _ => Ok(0),
+            },
+            |progress| assert!(progress.pages <= 2),
+        );
+        assert!(result.is_err());
+    }
+}
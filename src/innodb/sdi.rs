@@ -0,0 +1,345 @@
+use std::{collections::HashSet, io::Read};
+
+use anyhow::{anyhow, Result};
+use flate2::read::ZlibDecoder;
+use num_enum::TryFromPrimitive;
+
+use crate::innodb::{
+    buffer_manager::BufferManager,
+    encryption::{encryption_info_offset, ENCRYPTION_INFO_MAX_SIZE},
+    file_list::FIL_NULL,
+    page::{
+        fsp::FspHeaderPage,
+        index::{
+            record::{FieldShape, Record},
+            IndexHeader, IndexPage,
+        },
+        lob::read_blob_chain,
+        Page, PageType,
+    },
+    table::blob_header::{ExternReference, EXTERN_REFERENCE_SIZE},
+    InnoDBError,
+};
+
+const SDI_VERSION: u32 = 1;
+
+/// zlib can't shrink data more than about 1032 times, a longer uncompressed
+/// length is corrupt
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+const fn fixed(len: usize) -> FieldShape {
+    FieldShape {
+        nullable: false,
+        fixed_len: Some(len),
+        max_len: len,
+    }
+}
+
+/// Type and id, then the child page number
+const NODE_POINTER_SHAPES: [FieldShape; 3] = [fixed(4), fixed(8), fixed(4)];
+/// Type, id, DB_TRX_ID, DB_ROLL_PTR, uncompressed and compressed length and
+/// the compressed data
+const LEAF_SHAPES: [FieldShape; 7] = [
+    fixed(4),
+    fixed(8),
+    fixed(6),
+    fixed(7),
+    fixed(4),
+    fixed(4),
+    FieldShape {
+        nullable: false,
+        fixed_len: None,
+        max_len: u32::MAX as usize,
+    },
+];
+
+/// What a SDI record describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum SdiType {
+    Table = 1,
+    Tablespace = 2,
+}
+
+/// Root page of the SDI index from page 0, it follows the encryption info.
+/// `None` when the tablespace has no SDI, as before MySQL 8.0.
+pub fn sdi_root(page: &Page) -> Result<Option<u32>> {
+    let fsp = FspHeaderPage::try_from_page(page)?;
+    if !fsp.has_sdi() {
+        return Ok(None);
+    }
+    let offset = encryption_info_offset(page.size()) + ENCRYPTION_INFO_MAX_SIZE;
+    let buf = page
+        .raw_data
+        .get(offset..offset + 8)
+        .ok_or(InnoDBError::InvalidLength)?;
+    let version = u32::from_be_bytes(buf[0..4].try_into().unwrap());
+    if version != SDI_VERSION {
+        return Err(anyhow!("Unknown SDI version {}", version));
+    }
+    Ok(Some(u32::from_be_bytes(buf[4..8].try_into().unwrap())))
+}
+
+/// A page of the SDI index, laid out like any index page
+#[derive(Debug)]
+pub struct SdiPage<'a> {
+    pub index: IndexPage<'a>,
+}
+
+impl<'a> SdiPage<'a> {
+    pub fn try_from_page(page: Page<'a>) -> Result<Self> {
+        if page.header.page_type != PageType::SDI {
+            return Err(anyhow!(InnoDBError::InvalidPageType {
+                expected: PageType::SDI,
+                has: page.header.page_type
+            }));
+        }
+
+        Ok(SdiPage {
+            index: IndexPage {
+                index_header: IndexHeader::from_bytes(page.body())?,
+                page,
+            },
+        })
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.index.index_header.page_level == 0
+    }
+
+    /// Child page the first node pointer of a non-leaf page points at
+    pub fn first_child(&self) -> Result<u32> {
+        let record = self.index.records().next().ok_or_else(|| {
+            anyhow!(
+                "SDI node pointer page {} has no records",
+                self.index.page.header.offset
+            )
+        })??;
+        let fields = record.raw_fields(&NODE_POINTER_SHAPES)?;
+        Ok(u32::from_be_bytes(fields[2].data.try_into().unwrap()))
+    }
+}
+
+/// A record of the SDI index, the JSON is compressed with zlib
+#[derive(Debug, Clone)]
+pub struct SdiRecord<'a> {
+    pub sdi_type: SdiType,
+    pub id: u64,
+    pub uncompressed_len: u32,
+    pub compressed_len: u32,
+    pub deleted: bool,
+    data: &'a [u8],
+    external: bool,
+}
+
+impl<'a> SdiRecord<'a> {
+    pub fn try_from_record(record: &Record<'a>) -> Result<Self> {
+        let fields = record.raw_fields(&LEAF_SHAPES)?;
+        let word = |idx: usize| u32::from_be_bytes(fields[idx].data.try_into().unwrap());
+        let sdi_type = word(0);
+        Ok(SdiRecord {
+            sdi_type: SdiType::try_from(sdi_type)
+                .map_err(|_| anyhow!("Unknown SDI type {}", sdi_type))?,
+            id: u64::from_be_bytes(fields[1].data.try_into().unwrap()),
+            uncompressed_len: word(4),
+            compressed_len: word(5),
+            deleted: record.header.info_flags.deleted,
+            data: fields[6].data,
+            external: fields[6].external,
+        })
+    }
+
+    /// The zlib stream, read from the BLOB pages when stored externally
+    pub fn compressed_data(&self, buffer_mgr: &dyn BufferManager) -> Result<Vec<u8>> {
+        let data = if self.external {
+            let split = self
+                .data
+                .len()
+                .checked_sub(EXTERN_REFERENCE_SIZE)
+                .ok_or(InnoDBError::InvalidLength)?;
+            let extern_ref = ExternReference::from_bytes(&self.data[split..])?;
+            [
+                &self.data[..split],
+                &read_blob_chain(&extern_ref, buffer_mgr)?,
+            ]
+            .concat()
+        } else {
+            self.data.to_vec()
+        };
+        if data.len() != self.compressed_len as usize {
+            return Err(anyhow!(
+                "SDI {:?} {} has {} compressed bytes, expected {}",
+                self.sdi_type,
+                self.id,
+                data.len(),
+                self.compressed_len
+            ));
+        }
+        Ok(data)
+    }
+
+    /// Inflated JSON
+    pub fn json(&self, buffer_mgr: &dyn BufferManager) -> Result<String> {
+        let compressed = self.compressed_data(buffer_mgr)?;
+        let uncompressed_len = self.uncompressed_len as u64;
+        if uncompressed_len > compressed.len() as u64 * MAX_DEFLATE_RATIO {
+            return Err(anyhow!(
+                "SDI {:?} {} can't inflate {} bytes to {}",
+                self.sdi_type,
+                self.id,
+                compressed.len(),
+                self.uncompressed_len
+            ));
+        }
+        let mut json = Vec::with_capacity(self.uncompressed_len as usize);
+        // One byte more than expected is enough to tell the length is wrong
+        ZlibDecoder::new(compressed.as_slice())
+            .take(uncompressed_len + 1)
+            .read_to_end(&mut json)?;
+        if json.len() != self.uncompressed_len as usize {
+            return Err(anyhow!(
+                "SDI {:?} {} inflated to {} bytes, expected {}",
+                self.sdi_type,
+                self.id,
+                json.len(),
+                self.uncompressed_len
+            ));
+        }
+        Ok(String::from_utf8(json)?)
+    }
+}
+
+/// A SDI object of a tablespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdiEntry {
+    pub sdi_type: SdiType,
+    pub id: u64,
+    pub json: String,
+}
+
+/// Every SDI object of the tablespace, in key order. Walks the SDI index
+/// from the root recorded on page 0, delete marked records are skipped.
+pub fn read_sdi(space_id: u32, buffer_mgr: &dyn BufferManager) -> Result<Vec<SdiEntry>> {
+    let root = {
+        let guard = buffer_mgr.pin(space_id, 0)?;
+        sdi_root(&guard.page())?.ok_or_else(|| anyhow!("Space {} has no SDI", space_id))?
+    };
+
+    // Down the leftmost node pointers, the level drops by one every step
+    let mut offset = root;
+    let mut expected_level = None;
+    loop {
+        let guard = buffer_mgr.pin(space_id, offset)?;
+        let sdi_page = SdiPage::try_from_page(guard.page())?;
+        let level = sdi_page.index.index_header.page_level;
+        if expected_level.is_some_and(|expected| expected != level) {
+            return Err(anyhow!(
+                "SDI page {} is on level {}, expected {:?}",
+                offset,
+                level,
+                expected_level
+            ));
+        }
+        if sdi_page.is_leaf() {
+            break;
+        }
+        offset = sdi_page.first_child()?;
+        expected_level = Some(level - 1);
+    }
+
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    while offset != FIL_NULL {
+        if !visited.insert(offset) {
+            return Err(anyhow!("SDI leaf level loops back to page {}", offset));
+        }
+        let guard = buffer_mgr.pin(space_id, offset)?;
+        let sdi_page = SdiPage::try_from_page(guard.page())?;
+        if !sdi_page.is_leaf() {
+            return Err(anyhow!("SDI page {} is not a leaf", offset));
+        }
+        for record in sdi_page.index.records() {
+            let record = SdiRecord::try_from_record(&record?)?;
+            if record.deleted {
+                continue;
+            }
+            entries.push(SdiEntry {
+                sdi_type: record.sdi_type,
+                id: record.id,
+                json: record.json(buffer_mgr)?,
+            });
+        }
+        offset = guard.header.next;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        buffer_manager::memory::MemoryBufferManager,
+        page::{Page, FIL_PAGE_SIZE},
+        test_data,
+    };
+
+    use super::{read_sdi, sdi_root, SdiPage, SdiRecord, SdiType};
+
+    #[test]
+    fn test_sdi_root() {
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[..FIL_PAGE_SIZE]).unwrap();
+        assert_eq!(sdi_root(&page).unwrap(), Some(3));
+
+        let index = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        assert!(sdi_root(&index).is_err());
+        assert!(SdiPage::try_from_page(index).is_err());
+    }
+
+    #[test]
+    fn test_read_sdi() {
+        let data = test_data("float_sample.ibd");
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_space(351, &data).unwrap();
+
+        let entries = read_sdi(351, &buffer_mgr).unwrap();
+        let types = entries
+            .iter()
+            .map(|entry| entry.sdi_type)
+            .collect::<Vec<_>>();
+        assert_eq!(types, [SdiType::Table, SdiType::Tablespace]);
+        let table = &entries[0].json;
+        assert!(table.contains("\"dd_object_type\":\"Table\""), "{}", table);
+        assert!(
+            table.contains("\"dd_object\":{\"name\":\"float_sample\""),
+            "{}",
+            table
+        );
+        assert!(entries[1]
+            .json
+            .contains("\"dd_object_type\":\"Tablespace\""));
+
+        // A record with a broken zlib stream
+        let page = Page::from_bytes(&data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE]).unwrap();
+        let sdi_page = SdiPage::try_from_page(page).unwrap();
+        let record = sdi_page.index.records().next().unwrap().unwrap();
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+        buf[record.offset + 40] ^= 0xff;
+        let page = Page::from_bytes(&buf).unwrap();
+        let sdi_page = SdiPage::try_from_page(page).unwrap();
+        let record = sdi_page.index.records().next().unwrap().unwrap();
+        let record = SdiRecord::try_from_record(&record).unwrap();
+        assert!(record.json(&buffer_mgr).is_err());
+
+        // Uncompressed lengths the stream can't hold, or doesn't
+        let page = Page::from_bytes(&data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE]).unwrap();
+        let sdi_page = SdiPage::try_from_page(page).unwrap();
+        let record = sdi_page.index.records().next().unwrap().unwrap();
+        let mut record = SdiRecord::try_from_record(&record).unwrap();
+        assert!(record.json(&buffer_mgr).is_ok());
+        record.uncompressed_len = u32::MAX;
+        assert!(record.json(&buffer_mgr).is_err());
+        record.uncompressed_len = record.compressed_len;
+        assert!(record.json(&buffer_mgr).is_err());
+    }
+}
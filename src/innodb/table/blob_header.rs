@@ -1,5 +1,8 @@
 use anyhow::Result;
 
+/// Length of the reference at the end of the stored part of an extern column
+pub const EXTERN_REFERENCE_SIZE: usize = 20;
+
 #[derive(Debug, Clone)]
 pub struct ExternReference {
     pub space_id: u32,
@@ -13,7 +16,7 @@ pub struct ExternReference {
 /// B-Tree Extern Reference
 impl ExternReference {
     pub fn from_bytes(bytes: &[u8]) -> Result<ExternReference> {
-        if bytes.len() < 20 {
+        if bytes.len() < EXTERN_REFERENCE_SIZE {
             anyhow::bail!("Insufficient bytes to construct BlobHeader");
         }
 
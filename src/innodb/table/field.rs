@@ -1,6 +1,11 @@
+use std::{cmp::Ordering, fmt::Display};
+
 use crate::innodb::charset::InnoDBCharset;
+use anyhow::{anyhow, Result};
 use chrono::DateTime;
-use tracing::{info, trace};
+use tracing::trace;
+
+use super::json_binary;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FieldType {
@@ -10,6 +15,10 @@ pub enum FieldType {
     Int(bool),       // 4
     Int6(bool),      // 6
     BigInt(bool),    // 8
+    /// DB_ROW_ID, the 6 byte clustered key InnoDB generates for tables
+    /// without a primary key or a usable unique key. The ids come from a
+    /// single counter so they grow in insertion order.
+    RowId,
 
     Float,
     Double,
@@ -22,14 +31,14 @@ pub enum FieldType {
     Date,
     DateTime,
     Timestamp,
+
+    /// Stored in MySQL's binary JSON format, see [`super::json_binary`]
+    Json,
 }
 impl FieldType {
     // Returns how many bytes does the "length" metadata takes up
     pub fn is_variable(&self) -> bool {
-        match self {
-            FieldType::Text(_, _) => true,
-            _ => false,
-        }
+        matches!(self, FieldType::Text(_, _) | FieldType::Json)
     }
 
     pub fn max_len(&self) -> u64 {
@@ -40,6 +49,7 @@ impl FieldType {
             FieldType::Int(_) => 4,
             FieldType::Int6(_) => 6,
             FieldType::BigInt(_) => 8,
+            FieldType::RowId => 6,
 
             FieldType::Float => 4,
             FieldType::Double => 8,
@@ -52,8 +62,54 @@ impl FieldType {
             FieldType::Date => 3,
             FieldType::DateTime => 8,
             FieldType::Timestamp => 4,
+
+            FieldType::Json => u32::MAX as u64,
         }
     }
+
+    /// Value of a literal from a `DEFAULT` clause, with the quotes already
+    /// removed from strings
+    pub fn parse_literal(&self, literal: &str) -> Result<FieldValue> {
+        let number_error = |e| anyhow!("Invalid {:?} literal {}: {}", self, literal, e);
+        Ok(match self {
+            FieldType::TinyInt(true)
+            | FieldType::SmallInt(true)
+            | FieldType::MediumInt(true)
+            | FieldType::Int(true)
+            | FieldType::Int6(true)
+            | FieldType::BigInt(true) => {
+                FieldValue::SignedInt(literal.trim().parse().map_err(number_error)?)
+            }
+            FieldType::TinyInt(false)
+            | FieldType::SmallInt(false)
+            | FieldType::MediumInt(false)
+            | FieldType::Int(false)
+            | FieldType::Int6(false)
+            | FieldType::BigInt(false)
+            | FieldType::RowId => {
+                FieldValue::UnsignedInt(literal.trim().parse().map_err(number_error)?)
+            }
+            FieldType::Float => FieldValue::Float(
+                literal
+                    .trim()
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid float literal {}: {}", literal, e))?,
+            ),
+            FieldType::Double => FieldValue::Double(
+                literal
+                    .trim()
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid double literal {}: {}", literal, e))?,
+            ),
+            FieldType::Enum(_)
+            | FieldType::Text(_, _)
+            | FieldType::Char(_, _)
+            | FieldType::Date
+            | FieldType::DateTime
+            | FieldType::Timestamp
+            | FieldType::Json => FieldValue::String(literal.to_owned()),
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -63,16 +119,145 @@ pub enum FieldValue {
     Float(f32),
     Double(f64),
     String(String),
-    PartialString { partial: String, total_len: usize },
+    PartialString {
+        partial: String,
+        total_len: usize,
+    },
+    /// Raw bytes of a value that can't be decoded, shown in hex
+    Bytes(Vec<u8>),
     Null,
     Skipped,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl FieldValue {
+    /// Orders two key values the way the index does, strings compare as binary
+    pub fn key_cmp(&self, other: &FieldValue) -> Result<Ordering> {
+        let ordering = match (self, other) {
+            (FieldValue::Null, FieldValue::Null) => Some(Ordering::Equal),
+            (FieldValue::Null, _) => Some(Ordering::Less),
+            (_, FieldValue::Null) => Some(Ordering::Greater),
+            (FieldValue::SignedInt(a), FieldValue::SignedInt(b)) => Some(a.cmp(b)),
+            (FieldValue::UnsignedInt(a), FieldValue::UnsignedInt(b)) => Some(a.cmp(b)),
+            (FieldValue::SignedInt(a), FieldValue::UnsignedInt(b)) => {
+                Some((*a as i128).cmp(&(*b as i128)))
+            }
+            (FieldValue::UnsignedInt(a), FieldValue::SignedInt(b)) => {
+                Some((*a as i128).cmp(&(*b as i128)))
+            }
+            (FieldValue::Float(a), FieldValue::Float(b)) => a.partial_cmp(b),
+            (FieldValue::Double(a), FieldValue::Double(b)) => a.partial_cmp(b),
+            (FieldValue::String(a), FieldValue::String(b)) => Some(a.as_bytes().cmp(b.as_bytes())),
+            (FieldValue::Bytes(a), FieldValue::Bytes(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        ordering.ok_or_else(|| anyhow!("Can't compare {:?} with {:?}", self, other))
+    }
+
+    /// Renders the value like [`Display`], with NULL written as `null`
+    pub fn display_with_null<'a>(&'a self, null: &'a str) -> FieldDisplay<'a> {
+        FieldDisplay {
+            value: self,
+            null,
+            width: 0,
+        }
+    }
+
+    /// Integer value, unsigned values only when they fit
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FieldValue::SignedInt(v) => Some(*v),
+            FieldValue::UnsignedInt(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Integer value, signed values only when they aren't negative
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            FieldValue::UnsignedInt(v) => Some(*v),
+            FieldValue::SignedInt(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Complete string value, a [`FieldValue::PartialString`] is not one
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Bytes of a complete string value, binary columns are parsed to strings
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            FieldValue::Bytes(bytes) => Some(bytes),
+            _ => self.as_str().map(str::as_bytes),
+        }
+    }
+
+    /// SQL NULL, a [`FieldValue::Skipped`] value is unknown rather than null
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldValue::Null)
+    }
+}
+
+/// Compares `key` with `bound` over the columns both have, so a shorter
+/// bound matches every key starting with it
+pub fn key_prefix_cmp(key: &[FieldValue], bound: &[FieldValue]) -> Result<Ordering> {
+    for (a, b) in key.iter().zip(bound.iter()) {
+        match a.key_cmp(b)? {
+            Ordering::Equal => {}
+            ordering => return Ok(ordering),
+        }
+    }
+    Ok(Ordering::Equal)
+}
+
+/// Plain text rendering of a [`FieldValue`], see [`FieldValue::display_with_null`]
+pub struct FieldDisplay<'a> {
+    value: &'a FieldValue,
+    null: &'a str,
+    /// Integers are padded with zeros up to this many digits
+    width: usize,
+}
+
+impl Display for FieldDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            FieldValue::SignedInt(v) => write!(f, "{:0width$}", v, width = self.width),
+            FieldValue::UnsignedInt(v) => write!(f, "{:0width$}", v, width = self.width),
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Double(v) => write!(f, "{}", v),
+            FieldValue::String(s) => f.write_str(s),
+            FieldValue::PartialString { partial, .. } => write!(f, "{}...", partial),
+            FieldValue::Bytes(bytes) => {
+                f.write_str("0x")?;
+                bytes.iter().try_for_each(|byte| write!(f, "{:02X}", byte))
+            }
+            FieldValue::Null | FieldValue::Skipped => f.write_str(self.null),
+        }
+    }
+}
+
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.display_with_null("").fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Field {
     pub name: String,
     pub field_type: FieldType,
     pub nullable: bool,
+    /// Display width of an integer column, the 5 of `INT(5)`
+    pub display_width: Option<u32>,
+    /// ZEROFILL integers are shown padded with zeros to the display width
+    pub zerofill: bool,
+    /// Value of the `DEFAULT` clause. Rows written before the column was
+    /// added with ALGORITHM=INSTANT don't store it and read as this value.
+    pub default: Option<FieldValue>,
 }
 
 impl Field {
@@ -81,7 +266,27 @@ impl Field {
             name: name.to_owned(),
             field_type: t,
             nullable,
+            display_width: None,
+            zerofill: false,
+            default: None,
+        }
+    }
+
+    /// Renders a value of this field, ZEROFILL integers are padded like the
+    /// mysql client does, to the declared width or the type's default one
+    pub fn display_value<'a>(&'a self, value: &'a FieldValue, null: &'a str) -> FieldDisplay<'a> {
+        let mut display = value.display_with_null(null);
+        if self.zerofill {
+            let default_width = match self.field_type {
+                FieldType::TinyInt(_) => 3,
+                FieldType::SmallInt(_) => 5,
+                FieldType::MediumInt(_) => 8,
+                FieldType::Int(_) => 10,
+                _ => 20,
+            };
+            display.width = self.display_width.unwrap_or(default_width) as usize;
         }
+        display
     }
 
     fn parse_uint(&self, buf: &[u8], len: usize) -> u64 {
@@ -117,7 +322,7 @@ impl Field {
         }
     }
 
-    pub fn parse(&self, buf: &[u8], length_opt: Option<u64>) -> (FieldValue, usize) {
+    pub fn parse(&self, buf: &[u8], length_opt: Option<u64>) -> Result<(FieldValue, usize)> {
         let (val, len) = match self.field_type {
             FieldType::TinyInt(signed) => (self.parse_int_field(buf, 1, signed), 1),
             FieldType::SmallInt(signed) => (self.parse_int_field(buf, 2, signed), 2),
@@ -125,32 +330,45 @@ impl Field {
             FieldType::Int(signed) => (self.parse_int_field(buf, 4, signed), 4),
             FieldType::Int6(signed) => (self.parse_int_field(buf, 6, signed), 6),
             FieldType::BigInt(signed) => (self.parse_int_field(buf, 8, signed), 8),
+            FieldType::RowId => (FieldValue::UnsignedInt(self.parse_uint(buf, 6)), 6),
             FieldType::Char(len, _) => (
                 FieldValue::String(
-                    String::from_utf8(buf[0..len].into())
-                        .expect("Failed parsing UTF-8")
+                    String::from_utf8(buf[0..len].into())?
                         .trim_end()
                         .to_string(),
                 ),
                 len,
             ),
-            FieldType::Text(max_len, _) => match length_opt {
+            FieldType::Text(_, _) => match length_opt {
                 None => (FieldValue::Null, 0),
                 Some(length) => {
-                    assert!(
-                        length <= self.field_type.max_len(),
-                        "Length larger than expected max? {} > {} in field {:?}",
-                        length,
-                        max_len,
-                        self
-                    );
-                    let str = String::from_utf8(buf[..length as usize].into())
-                        .expect("Failed parsing UTF-8")
+                    if length > self.field_type.max_len() || length as usize > buf.len() {
+                        return Err(anyhow!(
+                            "Length larger than expected max? {} > {} in field {:?}",
+                            length,
+                            self.field_type.max_len(),
+                            self
+                        ));
+                    }
+                    let str = String::from_utf8(buf[..length as usize].into())?
                         .trim_end()
                         .to_string();
                     (FieldValue::String(str), length as usize)
                 }
             },
+            FieldType::Json => match length_opt {
+                None => (FieldValue::Null, 0),
+                Some(length) => {
+                    let buf = buf.get(..length as usize).ok_or_else(|| {
+                        anyhow!("JSON value of {} bytes in field {:?}", length, self)
+                    })?;
+                    let value = match json_binary::decode(buf)? {
+                        Some(json) => FieldValue::String(json),
+                        None => FieldValue::Bytes(buf.to_vec()),
+                    };
+                    (value, length as usize)
+                }
+            },
             FieldType::Date => {
                 let date_num = self.parse_signed_int(buf, 3);
                 let day = date_num & 0x1F;
@@ -202,17 +420,16 @@ impl Field {
                 if num == 0 {
                     (FieldValue::String("".to_owned()), len)
                 } else {
-                    let variant_index = num - 1;
-                    assert!(
-                        (variant_index as usize) < values.len(),
-                        "Enum Value is larger than expected? {} vs {}",
-                        variant_index,
-                        values.len()
-                    );
-                    (
-                        FieldValue::String(values[variant_index as usize].clone()),
-                        len,
-                    )
+                    // Labels are returned exactly as declared, whatever their charset
+                    let label = values.get(num as usize - 1).ok_or_else(|| {
+                        anyhow!(
+                            "Enum value {} is out of range, {} has {} variants",
+                            num,
+                            self.name,
+                            values.len()
+                        )
+                    })?;
+                    (FieldValue::String(label.clone()), len)
                 }
             }
             #[allow(unreachable_patterns)]
@@ -222,13 +439,17 @@ impl Field {
         };
         trace!("Parsing field {} -> {:?}", self.name, val);
 
-        (val, len)
+        Ok((val, len))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Field, FieldType};
+    use crate::innodb::charset::InnoDBCharset;
+
+    use std::cmp::Ordering;
+
+    use super::{key_prefix_cmp, Field, FieldType, FieldValue};
 
     #[test]
     fn test_field_parse_medium_int() {
@@ -237,6 +458,9 @@ mod test {
             name: Default::default(),
             field_type: FieldType::MediumInt(true),
             nullable: false,
+            display_width: None,
+            zerofill: false,
+            default: None,
         };
         let result = field.parse_int_field(&buf, 3, true);
         match result {
@@ -252,6 +476,9 @@ mod test {
             name: Default::default(),
             field_type: FieldType::TinyInt(true),
             nullable: false,
+            display_width: None,
+            zerofill: false,
+            default: None,
         };
         let result = field.parse_int_field(&buf, 1, true);
         match result {
@@ -259,4 +486,152 @@ mod test {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn test_field_parse_text_oversized_length() {
+        let buf = [b'a'; 32];
+        let field = Field::new("text", FieldType::Text(5, InnoDBCharset::Ascii), false);
+        assert!(field.parse(&buf, Some(5)).is_ok());
+        assert!(field.parse(&buf, Some(6)).is_err());
+    }
+
+    #[test]
+    fn test_field_parse_json() {
+        let field = Field::new("doc", FieldType::Json, true);
+        // {"a": 1} followed by the next column
+        let buf = [
+            0x00, 0x01, 0x00, 0x0C, 0x00, 0x0B, 0x00, 0x01, 0x00, 0x05, 0x01, 0x00, b'a', 0xFF,
+        ];
+        assert_eq!(
+            field.parse(&buf, Some(13)).unwrap(),
+            (FieldValue::String(r#"{"a":1}"#.to_owned()), 13)
+        );
+        assert_eq!(field.parse(&buf, None).unwrap(), (FieldValue::Null, 0));
+        assert!(field.parse(&buf, Some(15)).is_err());
+
+        // An opaque DECIMAL is kept as it is
+        let opaque = [0x0F, 0xF6, 0x02, 0x01, 0x00];
+        let (value, len) = field.parse(&opaque, Some(5)).unwrap();
+        assert_eq!(value, FieldValue::Bytes(opaque.to_vec()));
+        assert_eq!(len, 5);
+        assert_eq!(value.to_string(), "0x0FF6020100");
+    }
+
+    #[test]
+    fn test_field_value_key_cmp() {
+        let cmp = |a: FieldValue, b: FieldValue| a.key_cmp(&b).unwrap();
+        assert_eq!(
+            cmp(FieldValue::SignedInt(-1), FieldValue::SignedInt(1)),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp(FieldValue::SignedInt(-1), FieldValue::UnsignedInt(u64::MAX)),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp(
+                FieldValue::String("b".to_owned()),
+                FieldValue::String("ab".to_owned())
+            ),
+            Ordering::Greater
+        );
+        assert_eq!(
+            cmp(FieldValue::Null, FieldValue::SignedInt(i64::MIN)),
+            Ordering::Less
+        );
+        assert!(FieldValue::String("1".to_owned())
+            .key_cmp(&FieldValue::SignedInt(1))
+            .is_err());
+
+        let key = [FieldValue::SignedInt(1), FieldValue::String("b".to_owned())];
+        let prefix = |bound: &[FieldValue]| key_prefix_cmp(&key, bound).unwrap();
+        assert_eq!(prefix(&[FieldValue::SignedInt(1)]), Ordering::Equal);
+        assert_eq!(prefix(&[FieldValue::SignedInt(2)]), Ordering::Less);
+        assert_eq!(
+            prefix(&[FieldValue::SignedInt(1), FieldValue::String("a".to_owned())]),
+            Ordering::Greater
+        );
+        assert_eq!(prefix(&[]), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_field_value_int_accessors() {
+        assert_eq!(FieldValue::SignedInt(-5).as_i64(), Some(-5));
+        assert_eq!(FieldValue::SignedInt(-5).as_u64(), None);
+        assert_eq!(FieldValue::SignedInt(5).as_u64(), Some(5));
+        assert_eq!(FieldValue::UnsignedInt(7).as_u64(), Some(7));
+        assert_eq!(FieldValue::UnsignedInt(7).as_i64(), Some(7));
+        assert_eq!(FieldValue::UnsignedInt(u64::MAX).as_i64(), None);
+        assert_eq!(FieldValue::Double(1.0).as_i64(), None);
+        assert_eq!(FieldValue::String("1".into()).as_u64(), None);
+        assert_eq!(FieldValue::Null.as_i64(), None);
+    }
+
+    #[test]
+    fn test_field_value_str_accessors() {
+        let value = FieldValue::String("abc".into());
+        assert_eq!(value.as_str(), Some("abc"));
+        assert_eq!(value.as_bytes(), Some(&b"abc"[..]));
+        assert_eq!(value.as_i64(), None);
+
+        let partial = FieldValue::PartialString {
+            partial: "ab".into(),
+            total_len: 3,
+        };
+        assert_eq!(partial.as_str(), None);
+        assert_eq!(partial.as_bytes(), None);
+        assert_eq!(FieldValue::SignedInt(1).as_str(), None);
+        assert_eq!(FieldValue::Null.as_bytes(), None);
+    }
+
+    #[test]
+    fn test_field_value_is_null() {
+        assert!(FieldValue::Null.is_null());
+        assert!(!FieldValue::Skipped.is_null());
+        assert!(!FieldValue::SignedInt(0).is_null());
+        assert!(!FieldValue::String(String::new()).is_null());
+    }
+
+    #[test]
+    fn test_field_parse_enum_multibyte() {
+        let values = vec!["café".to_owned(), "日本語".to_owned(), "🦀".to_owned()];
+        let field = Field::new("e", FieldType::Enum(values.clone()), false);
+
+        assert_eq!(
+            field.parse(&[0], None).unwrap(),
+            (FieldValue::String(String::new()), 1)
+        );
+        for (idx, label) in values.iter().enumerate() {
+            let (value, len) = field.parse(&[idx as u8 + 1], None).unwrap();
+            assert_eq!(value, FieldValue::String(label.clone()));
+            assert_eq!(len, 1);
+        }
+        assert!(field.parse(&[4], None).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(FieldValue::SignedInt(-42).to_string(), "-42");
+        assert_eq!(
+            FieldValue::UnsignedInt(u64::MAX).to_string(),
+            "18446744073709551615"
+        );
+        assert_eq!(FieldValue::Float(1.5).to_string(), "1.5");
+        assert_eq!(FieldValue::Double(-0.25).to_string(), "-0.25");
+        assert_eq!(FieldValue::String(String::from("a\tb")).to_string(), "a\tb");
+        let partial = FieldValue::PartialString {
+            partial: String::from("abc"),
+            total_len: 100,
+        };
+        assert_eq!(partial.to_string(), "abc...");
+        assert_eq!(FieldValue::Null.to_string(), "");
+        assert_eq!(FieldValue::Skipped.to_string(), "");
+        assert_eq!(FieldValue::Null.display_with_null("\\N").to_string(), "\\N");
+        assert_eq!(
+            FieldValue::SignedInt(1)
+                .display_with_null("\\N")
+                .to_string(),
+            "1"
+        );
+    }
 }
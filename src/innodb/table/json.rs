@@ -0,0 +1,329 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use struson::{
+    reader::{JsonReader, JsonStreamReader, ValueType},
+    writer::{JsonStreamWriter, JsonWriter, WriterSettings},
+};
+
+use crate::innodb::charset::InnoDBCharset;
+
+use super::{
+    field::{Field, FieldType, FieldValue},
+    TableDefinition,
+};
+
+impl TableDefinition {
+    /// Pretty printed JSON that [`TableDefinition::from_json`] reads back
+    pub fn to_json(&self) -> Result<String> {
+        let table = TableJson {
+            name: self.name.clone(),
+            cluster_columns: self.cluster_columns.iter().map(FieldJson::from).collect(),
+            data_columns: self.data_columns.iter().map(FieldJson::from).collect(),
+        };
+        let mut buf = Vec::new();
+        let settings = WriterSettings {
+            pretty_print: true,
+            ..Default::default()
+        };
+        let mut writer = JsonStreamWriter::new_custom(&mut buf, settings);
+        writer.serialize_value(&table)?;
+        writer.finish_document()?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<TableDefinition> {
+        let mut reader = JsonStreamReader::new(json.as_bytes());
+        // Derived structs would also take their fields from an array
+        if reader.peek()? != ValueType::Object {
+            return Err(anyhow!("A table definition is a JSON object"));
+        }
+        let table: TableJson = reader.deserialize_next()?;
+        reader.consume_trailing_whitespace()?;
+        let fields = |fields: Vec<FieldJson>| -> Result<Vec<Field>> {
+            fields.into_iter().map(Field::try_from).collect()
+        };
+        Ok(TableDefinition {
+            name: table.name,
+            cluster_columns: fields(table.cluster_columns)?,
+            data_columns: fields(table.data_columns)?,
+        })
+    }
+}
+
+/// The JSON form of a [`TableDefinition`], unknown keys are ignored
+#[derive(Serialize, Deserialize)]
+struct TableJson {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    cluster_columns: Vec<FieldJson>,
+    #[serde(default)]
+    data_columns: Vec<FieldJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FieldJson {
+    name: String,
+    #[serde(default)]
+    nullable: bool,
+    #[serde(flatten)]
+    field_type: TypeJson,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    zerofill: bool,
+    /// Written as the SQL literal and read back with the column type,
+    /// `Some(None)` for DEFAULT NULL
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    default: Option<Option<String>>,
+}
+
+/// [`FieldType`] with the type name in `type` next to its parameters
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TypeJson {
+    TinyInt {
+        #[serde(default = "signed")]
+        signed: bool,
+    },
+    SmallInt {
+        #[serde(default = "signed")]
+        signed: bool,
+    },
+    MediumInt {
+        #[serde(default = "signed")]
+        signed: bool,
+    },
+    Int {
+        #[serde(default = "signed")]
+        signed: bool,
+    },
+    Int6 {
+        #[serde(default = "signed")]
+        signed: bool,
+    },
+    BigInt {
+        #[serde(default = "signed")]
+        signed: bool,
+    },
+    #[serde(rename = "row_id")]
+    RowId,
+    Float,
+    Double,
+    Enum {
+        values: Vec<String>,
+    },
+    Text {
+        length: usize,
+        #[serde(with = "charset")]
+        charset: InnoDBCharset,
+    },
+    Char {
+        length: usize,
+        #[serde(with = "charset")]
+        charset: InnoDBCharset,
+    },
+    Date,
+    DateTime,
+    Timestamp,
+    Json,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn signed() -> bool {
+    true
+}
+
+/// Tells a `null` apart from a missing key
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Charsets by their MySQL name
+mod charset {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::innodb::charset::InnoDBCharset;
+
+    pub fn serialize<S: Serializer>(
+        charset: &InnoDBCharset,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(charset.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<InnoDBCharset, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        InnoDBCharset::with_name(&name).map_err(D::Error::custom)
+    }
+}
+
+impl From<&Field> for FieldJson {
+    fn from(field: &Field) -> Self {
+        let field_type = match &field.field_type {
+            FieldType::TinyInt(signed) => TypeJson::TinyInt { signed: *signed },
+            FieldType::SmallInt(signed) => TypeJson::SmallInt { signed: *signed },
+            FieldType::MediumInt(signed) => TypeJson::MediumInt { signed: *signed },
+            FieldType::Int(signed) => TypeJson::Int { signed: *signed },
+            FieldType::Int6(signed) => TypeJson::Int6 { signed: *signed },
+            FieldType::BigInt(signed) => TypeJson::BigInt { signed: *signed },
+            FieldType::RowId => TypeJson::RowId,
+            FieldType::Float => TypeJson::Float,
+            FieldType::Double => TypeJson::Double,
+            FieldType::Enum(values) => TypeJson::Enum {
+                values: values.clone(),
+            },
+            FieldType::Text(length, charset) => TypeJson::Text {
+                length: *length,
+                charset: *charset,
+            },
+            FieldType::Char(length, charset) => TypeJson::Char {
+                length: *length,
+                charset: *charset,
+            },
+            FieldType::Date => TypeJson::Date,
+            FieldType::DateTime => TypeJson::DateTime,
+            FieldType::Timestamp => TypeJson::Timestamp,
+            FieldType::Json => TypeJson::Json,
+        };
+        FieldJson {
+            name: field.name.clone(),
+            nullable: field.nullable,
+            field_type,
+            display_width: field.display_width,
+            zerofill: field.zerofill,
+            default: field.default.as_ref().map(|default| match default {
+                FieldValue::Null => None,
+                default => Some(default.to_string()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<FieldJson> for Field {
+    type Error = anyhow::Error;
+
+    fn try_from(json: FieldJson) -> Result<Self> {
+        let field_type = match json.field_type {
+            TypeJson::TinyInt { signed } => FieldType::TinyInt(signed),
+            TypeJson::SmallInt { signed } => FieldType::SmallInt(signed),
+            TypeJson::MediumInt { signed } => FieldType::MediumInt(signed),
+            TypeJson::Int { signed } => FieldType::Int(signed),
+            TypeJson::Int6 { signed } => FieldType::Int6(signed),
+            TypeJson::BigInt { signed } => FieldType::BigInt(signed),
+            TypeJson::RowId => FieldType::RowId,
+            TypeJson::Float => FieldType::Float,
+            TypeJson::Double => FieldType::Double,
+            TypeJson::Enum { values } => FieldType::Enum(values),
+            TypeJson::Text { length, charset } => FieldType::Text(length, charset),
+            TypeJson::Char { length, charset } => FieldType::Char(length, charset),
+            TypeJson::Date => FieldType::Date,
+            TypeJson::DateTime => FieldType::DateTime,
+            TypeJson::Timestamp => FieldType::Timestamp,
+            TypeJson::Json => FieldType::Json,
+        };
+        let default = match json.default {
+            Some(Some(literal)) => Some(
+                field_type
+                    .parse_literal(&literal)
+                    .map_err(|e| anyhow!("Column {}: {}", json.name, e))?,
+            ),
+            Some(None) => Some(FieldValue::Null),
+            None => None,
+        };
+        let mut field = Field::new(&json.name, field_type, json.nullable);
+        field.display_width = json.display_width;
+        field.zerofill = json.zerofill;
+        field.default = default;
+        Ok(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::read_to_string;
+
+    use crate::innodb::{
+        charset::InnoDBCharset,
+        table::{
+            field::{Field, FieldType, FieldValue},
+            TableDefinition,
+        },
+        test_data_path,
+    };
+
+    #[test]
+    fn test_json_round_trip() {
+        let sql = read_to_string(test_data_path("pre_ucenter_members.sql")).unwrap();
+        let mut table = TableDefinition::try_from_sql_statement(&sql).unwrap();
+        table.data_columns.extend([
+            Field::new(
+                "state",
+                FieldType::Enum(vec!["new".into(), "it's \"done\"".into()]),
+                true,
+            ),
+            Field::new("note", FieldType::Text(255, InnoDBCharset::Utf8mb4), true),
+            Field::new("code", FieldType::Char(4, InnoDBCharset::Latin1), false),
+            Field {
+                display_width: Some(5),
+                zerofill: true,
+                ..Field::new("small", FieldType::SmallInt(false), false)
+            },
+            Field::new("at", FieldType::DateTime, false),
+            Field {
+                default: Some(FieldValue::SignedInt(-3)),
+                ..Field::new("added", FieldType::Int(true), false)
+            },
+            Field {
+                default: Some(FieldValue::Null),
+                ..Field::new("added_null", FieldType::Double, true)
+            },
+        ]);
+
+        let json = table.to_json().unwrap();
+        assert!(json.contains("\"charset\": \"utf8mb4\""), "{json}");
+        assert_eq!(TableDefinition::from_json(&json).unwrap(), table);
+    }
+
+    #[test]
+    fn test_from_json() {
+        let json = r#"{
+            "name": "t",
+            "comment": "ignored",
+            "cluster_columns": [{"name": "id", "type": "int", "signed": false}],
+            "data_columns": [
+                {"name": "name", "type": "text", "length": 10, "charset": "latin1", "nullable": true}
+            ]
+        }"#;
+        let table = TableDefinition::from_json(json).unwrap();
+        assert_eq!(table.name, "t");
+        assert_eq!(
+            table.cluster_columns,
+            vec![Field::new("id", FieldType::Int(false), false)]
+        );
+        assert_eq!(
+            table.data_columns,
+            vec![Field::new(
+                "name",
+                FieldType::Text(10, InnoDBCharset::Latin1),
+                true
+            )]
+        );
+
+        let missing_length =
+            r#"{"data_columns": [{"name": "c", "type": "char", "charset": "ascii"}]}"#;
+        assert!(TableDefinition::from_json(missing_length).is_err());
+        let unknown_type = r#"{"data_columns": [{"name": "c", "type": "blob"}]}"#;
+        assert!(TableDefinition::from_json(unknown_type).is_err());
+        assert!(TableDefinition::from_json("[]").is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Binary format MySQL stores JSON columns in, see json_binary.h in the
+//! server sources. Every value is a type byte followed by its data, all
+//! numbers are little endian.
+
+use anyhow::{anyhow, Result};
+use struson::writer::{JsonStreamWriter, JsonWriter};
+
+const JSONB_TYPE_SMALL_OBJECT: u8 = 0x0;
+const JSONB_TYPE_LARGE_OBJECT: u8 = 0x1;
+const JSONB_TYPE_SMALL_ARRAY: u8 = 0x2;
+const JSONB_TYPE_LARGE_ARRAY: u8 = 0x3;
+const JSONB_TYPE_LITERAL: u8 = 0x4;
+const JSONB_TYPE_INT16: u8 = 0x5;
+const JSONB_TYPE_UINT16: u8 = 0x6;
+const JSONB_TYPE_INT32: u8 = 0x7;
+const JSONB_TYPE_UINT32: u8 = 0x8;
+const JSONB_TYPE_INT64: u8 = 0x9;
+const JSONB_TYPE_UINT64: u8 = 0xA;
+const JSONB_TYPE_DOUBLE: u8 = 0xB;
+const JSONB_TYPE_STRING: u8 = 0xC;
+/// A MySQL value without a JSON counterpart, like DECIMAL or DATETIME
+const JSONB_TYPE_OPAQUE: u8 = 0xF;
+
+/// MySQL refuses documents nested deeper than this, JSON_DOCUMENT_MAX_DEPTH
+const JSON_DOCUMENT_MAX_DEPTH: usize = 100;
+
+const JSONB_NULL_LITERAL: u8 = 0x0;
+const JSONB_TRUE_LITERAL: u8 = 0x1;
+const JSONB_FALSE_LITERAL: u8 = 0x2;
+
+/// Decodes a JSON column value to compact JSON text. `None` when the value
+/// holds a type that has no JSON text form, the opaque values MySQL uses for
+/// DECIMAL and the temporal types.
+pub fn decode(buf: &[u8]) -> Result<Option<String>> {
+    // MySQL reads an empty value as the null literal
+    let Some((value_type, data)) = buf.split_first() else {
+        return Ok(Some("null".to_owned()));
+    };
+    let mut json = Vec::new();
+    let mut writer = JsonStreamWriter::new(&mut json);
+    if !write_value(&mut writer, *value_type, data, 0)? {
+        return Ok(None);
+    }
+    writer.finish_document()?;
+    Ok(Some(String::from_utf8(json)?))
+}
+
+fn read_bytes<const N: usize>(data: &[u8], at: usize) -> Result<[u8; N]> {
+    data.get(at..at + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("JSON value truncated at {}", at))
+}
+
+/// Offsets and counts take 2 bytes in small containers and 4 in large ones
+fn read_offset(data: &[u8], at: usize, large: bool) -> Result<usize> {
+    Ok(if large {
+        u32::from_le_bytes(read_bytes(data, at)?) as usize
+    } else {
+        u16::from_le_bytes(read_bytes(data, at)?) as usize
+    })
+}
+
+/// String lengths use 7 bits of each byte, lowest first, while the high bit
+/// is set another byte follows. Returns the length and the bytes it used.
+fn read_varint(data: &[u8]) -> Result<(usize, usize)> {
+    let mut len = 0usize;
+    for (idx, byte) in data.iter().take(5).enumerate() {
+        len |= ((byte & 0x7F) as usize) << (7 * idx);
+        if byte & 0x80 == 0 {
+            return Ok((len, idx + 1));
+        }
+    }
+    Err(anyhow!("Invalid JSON string length"))
+}
+
+/// Small scalars are stored in the value entry of their container instead
+/// of at an offset
+fn is_inlined(value_type: u8, large: bool) -> bool {
+    match value_type {
+        JSONB_TYPE_LITERAL | JSONB_TYPE_INT16 | JSONB_TYPE_UINT16 => true,
+        JSONB_TYPE_INT32 | JSONB_TYPE_UINT32 => large,
+        _ => false,
+    }
+}
+
+/// Writes the value of `value_type` starting at `data`, inside `depth`
+/// containers. Returns false when it can't be written as JSON text.
+fn write_value<W: JsonWriter>(
+    writer: &mut W,
+    value_type: u8,
+    data: &[u8],
+    depth: usize,
+) -> Result<bool> {
+    match value_type {
+        JSONB_TYPE_SMALL_OBJECT | JSONB_TYPE_LARGE_OBJECT => {
+            let large = value_type == JSONB_TYPE_LARGE_OBJECT;
+            return write_container(writer, data, large, true, depth + 1);
+        }
+        JSONB_TYPE_SMALL_ARRAY | JSONB_TYPE_LARGE_ARRAY => {
+            let large = value_type == JSONB_TYPE_LARGE_ARRAY;
+            return write_container(writer, data, large, false, depth + 1);
+        }
+        JSONB_TYPE_LITERAL => match read_bytes::<1>(data, 0)?[0] {
+            JSONB_NULL_LITERAL => writer.null_value()?,
+            JSONB_TRUE_LITERAL => writer.bool_value(true)?,
+            JSONB_FALSE_LITERAL => writer.bool_value(false)?,
+            other => return Err(anyhow!("Unknown JSON literal {:#x}", other)),
+        },
+        JSONB_TYPE_INT16 => writer.number_value(i16::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_UINT16 => writer.number_value(u16::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_INT32 => writer.number_value(i32::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_UINT32 => writer.number_value(u32::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_INT64 => writer.number_value(i64::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_UINT64 => writer.number_value(u64::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_DOUBLE => writer.fp_number_value(f64::from_le_bytes(read_bytes(data, 0)?))?,
+        JSONB_TYPE_STRING => {
+            let (len, used) = read_varint(data)?;
+            let string = data
+                .get(used..used + len)
+                .ok_or_else(|| anyhow!("JSON string of {} bytes truncated", len))?;
+            writer.string_value(std::str::from_utf8(string)?)?;
+        }
+        JSONB_TYPE_OPAQUE => return Ok(false),
+        other => return Err(anyhow!("Unknown JSON value type {:#x}", other)),
+    }
+    Ok(true)
+}
+
+/// Objects and arrays start with the element count and their size in bytes,
+/// objects then have a (key offset, key length) entry per key. A (type,
+/// offset) entry per value follows, offsets are from the start of the count
+/// and point past the entries, so a container can't contain itself.
+fn write_container<W: JsonWriter>(
+    writer: &mut W,
+    data: &[u8],
+    large: bool,
+    object: bool,
+    depth: usize,
+) -> Result<bool> {
+    if depth > JSON_DOCUMENT_MAX_DEPTH {
+        return Err(anyhow!(
+            "JSON value nested deeper than {}",
+            JSON_DOCUMENT_MAX_DEPTH
+        ));
+    }
+    let offset_size = if large { 4 } else { 2 };
+    let count = read_offset(data, 0, large)?;
+    let size = read_offset(data, offset_size, large)?;
+    let data = data
+        .get(..size)
+        .ok_or_else(|| anyhow!("JSON container of {} bytes truncated", size))?;
+    let key_entries = 2 * offset_size;
+    let key_entry_size = offset_size + 2;
+    let value_entries = key_entries + if object { count * key_entry_size } else { 0 };
+    let value_entry_size = 1 + offset_size;
+    let entries_end = value_entries + count * value_entry_size;
+    let past_entries = |offset: usize| {
+        if offset < entries_end {
+            Err(anyhow!(
+                "JSON offset {} points into the entries ending at {}",
+                offset,
+                entries_end
+            ))
+        } else {
+            Ok(offset)
+        }
+    };
+
+    if object {
+        writer.begin_object()?;
+    } else {
+        writer.begin_array()?;
+    }
+    for idx in 0..count {
+        if object {
+            let entry = key_entries + idx * key_entry_size;
+            let key_offset = past_entries(read_offset(data, entry, large)?)?;
+            let key_len = u16::from_le_bytes(read_bytes(data, entry + offset_size)?) as usize;
+            let key = data
+                .get(key_offset..key_offset + key_len)
+                .ok_or_else(|| anyhow!("JSON key at {} truncated", key_offset))?;
+            writer.name(std::str::from_utf8(key)?)?;
+        }
+        let entry = value_entries + idx * value_entry_size;
+        let value_type = read_bytes::<1>(data, entry)?[0];
+        let value = if is_inlined(value_type, large) {
+            &data[entry + 1..]
+        } else {
+            let offset = past_entries(read_offset(data, entry + 1, large)?)?;
+            data.get(offset..)
+                .ok_or_else(|| anyhow!("JSON value at {} truncated", offset))?
+        };
+        if !write_value(writer, value_type, value, depth)? {
+            return Ok(false);
+        }
+    }
+    if object {
+        writer.end_object()?;
+    } else {
+        writer.end_array()?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode;
+
+    #[test]
+    fn test_decode_object() {
+        // {"a": 1}
+        let buf = [
+            0x00, // small object
+            0x01, 0x00, // one element
+            0x0C, 0x00, // of 12 bytes
+            0x0B, 0x00, 0x01, 0x00, // key at 11, 1 byte long
+            0x05, 0x01, 0x00, // int16 1, inlined
+            b'a',
+        ];
+        assert_eq!(decode(&buf).unwrap().unwrap(), r#"{"a":1}"#);
+        assert!(decode(&buf[..8]).is_err());
+    }
+
+    #[test]
+    fn test_decode_values() {
+        // ["x", true, null, 70000, -1.5]
+        let mut buf = vec![0x02, 0x05, 0x00];
+        let size_at = buf.len();
+        buf.extend_from_slice(&[0x00, 0x00]);
+        buf.extend_from_slice(&[0x0C, 0x13, 0x00]);
+        buf.extend_from_slice(&[0x04, 0x01, 0x00]);
+        buf.extend_from_slice(&[0x04, 0x00, 0x00]);
+        buf.extend_from_slice(&[0x08, 0x15, 0x00]);
+        buf.extend_from_slice(&[0x0B, 0x19, 0x00]);
+        buf.extend_from_slice(&[0x01, b'x']);
+        buf.extend_from_slice(&70000u32.to_le_bytes());
+        buf.extend_from_slice(&(-1.5f64).to_le_bytes());
+        let size = (buf.len() - 1) as u16;
+        buf[size_at..size_at + 2].copy_from_slice(&size.to_le_bytes());
+        assert_eq!(
+            decode(&buf).unwrap().unwrap(),
+            r#"["x",true,null,70000,-1.5]"#
+        );
+
+        // A DECIMAL inside an array
+        let opaque = [0x02, 0x01, 0x00, 0x09, 0x00, 0x0F, 0x07, 0x00, 0xF6, 0x00];
+        assert_eq!(decode(&opaque).unwrap(), None);
+        assert_eq!(decode(&[]).unwrap().unwrap(), "null");
+        assert_eq!(
+            decode(&[0x0C, 0x03, b'a', b'"', b'b']).unwrap().unwrap(),
+            r#""a\"b""#
+        );
+        assert!(decode(&[0x0D]).is_err());
+    }
+
+    #[test]
+    fn test_decode_self_reference() {
+        // An array whose only element is the array itself
+        let buf = [0x02, 0x01, 0x00, 0x07, 0x00, 0x02, 0x00, 0x00];
+        assert!(decode(&buf).is_err());
+        // A key inside the entries
+        let buf = [
+            0x00, 0x01, 0x00, 0x0C, 0x00, 0x02, 0x00, 0x01, 0x00, 0x05, 0x01, 0x00, b'a',
+        ];
+        assert!(decode(&buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_depth() {
+        // Arrays holding an array, around an empty one
+        let nested = |depth: usize| {
+            let mut value = vec![0x00, 0x00, 0x04, 0x00];
+            for _ in 1..depth {
+                let size = (7 + value.len()) as u16;
+                let mut outer = vec![0x01, 0x00];
+                outer.extend_from_slice(&size.to_le_bytes());
+                outer.extend_from_slice(&[0x02, 0x07, 0x00]);
+                outer.extend(value);
+                value = outer;
+            }
+            [vec![0x02], value].concat()
+        };
+        let json = decode(&nested(100)).unwrap().unwrap();
+        assert_eq!(json, format!("{}{}", "[".repeat(100), "]".repeat(100)));
+        assert!(decode(&nested(101)).is_err());
+    }
+}
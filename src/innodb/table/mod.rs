@@ -1,19 +1,39 @@
 pub mod blob_header;
 pub mod field;
+mod json;
+pub mod json_binary;
 pub mod row;
 
+use std::collections::HashSet;
+
 use anyhow::{anyhow, Result};
-use field::{Field, FieldType};
+use field::{Field, FieldType, FieldValue};
 use sqlparser::{
-    ast::{CharacterLength, ColumnOption, DataType, Statement, TableConstraint},
+    ast::{
+        CharacterLength, ColumnOption, DataType, Expr, Statement, TableConstraint, UnaryOperator,
+        Value,
+    },
     dialect::MySqlDialect,
     parser::Parser,
+    tokenizer::{Token, Tokenizer},
 };
 use tracing::{debug, info};
 
 use crate::innodb::charset::InnoDBCharset;
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// Position of a field in the data part of a clustered index record,
+/// relative to the record origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: usize,
+    pub width: usize,
+    /// Variable length field, `width` is its maximum and the offset of every
+    /// later field depends on the row
+    pub variable: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
 pub struct TableDefinition {
     pub name: String,
     pub cluster_columns: Vec<Field>,
@@ -22,7 +42,10 @@ pub struct TableDefinition {
 
 impl TableDefinition {
     pub fn try_from_sql_statement(sql: &str) -> Result<TableDefinition> {
-        let mut parser = Parser::new(&MySqlDialect {}).try_with_sql(sql)?;
+        let dialect = MySqlDialect {};
+        let tokens = Tokenizer::new(&dialect, sql).tokenize()?;
+        let (tokens, zerofill_columns) = strip_zerofill(tokens);
+        let mut parser = Parser::new(&dialect).with_tokens(tokens);
         let stmt = parser.parse_statement()?;
         if let Statement::CreateTable(parsed_table) = stmt {
             let mut table_def = TableDefinition::default();
@@ -48,8 +71,10 @@ impl TableDefinition {
                         }
                         _ => None,
                     })
-                    .last()
+                    .next_back()
                     .unwrap_or(table_charset);
+                // ZEROFILL implies UNSIGNED
+                let zerofill = zerofill_columns.contains(&column.name.value);
                 let f_type: FieldType = match &column.data_type {
                     DataType::Char(len_opt) => {
                         let final_len = match len_opt {
@@ -82,34 +107,67 @@ impl TableDefinition {
                     DataType::UnsignedMediumInt(_) => FieldType::MediumInt(false),
                     DataType::UnsignedInt(_) => FieldType::Int(false),
                     DataType::UnsignedBigInt(_) => FieldType::BigInt(false),
-                    DataType::TinyInt(_) => FieldType::TinyInt(true),
-                    DataType::SmallInt(_) => FieldType::SmallInt(true),
-                    DataType::MediumInt(_) => FieldType::MediumInt(true),
-                    DataType::Int(_) => FieldType::Int(true),
-                    DataType::BigInt(_) => FieldType::BigInt(true),
+                    DataType::TinyInt(_) => FieldType::TinyInt(!zerofill),
+                    DataType::SmallInt(_) => FieldType::SmallInt(!zerofill),
+                    DataType::MediumInt(_) => FieldType::MediumInt(!zerofill),
+                    DataType::Int(_) => FieldType::Int(!zerofill),
+                    DataType::BigInt(_) => FieldType::BigInt(!zerofill),
                     DataType::Enum(values) => FieldType::Enum(values.clone()),
                     DataType::Date => FieldType::Date,
                     DataType::Datetime(_) => FieldType::DateTime,
                     DataType::Timestamp(_, _) => FieldType::Timestamp,
-                    DataType::Float(opt) => FieldType::Float,
+                    DataType::Float(_) => FieldType::Float,
                     DataType::Double => FieldType::Double,
-                    DataType::Custom(name, _) => match name.0[0].value.as_str() {
-                        "mediumtext" => FieldType::Text((1 << 24) - 1, charset),
-                        "longtext" => FieldType::Text((1 << 32) - 1, charset),
-                        _ => unimplemented!("Custom: {} unhandled", name.0[0].value),
-                    },
+                    DataType::Text => text_type(u16::MAX as u64, charset),
+                    DataType::JSON => FieldType::Json,
+                    DataType::Custom(name, _) => {
+                        match name.0[0].value.to_ascii_lowercase().as_str() {
+                            "tinytext" => text_type(u8::MAX as u64, charset),
+                            "mediumtext" => text_type((1 << 24) - 1, charset),
+                            "longtext" => text_type((1 << 32) - 1, charset),
+                            _ => unimplemented!("Custom: {} unhandled", name.0[0].value),
+                        }
+                    }
                     _ => unimplemented!("mapping of {:?}", column.data_type),
                 };
+                let display_width = match &column.data_type {
+                    DataType::TinyInt(width)
+                    | DataType::UnsignedTinyInt(width)
+                    | DataType::SmallInt(width)
+                    | DataType::UnsignedSmallInt(width)
+                    | DataType::MediumInt(width)
+                    | DataType::UnsignedMediumInt(width)
+                    | DataType::Int(width)
+                    | DataType::UnsignedInt(width)
+                    | DataType::BigInt(width)
+                    | DataType::UnsignedBigInt(width) => width.map(|width| width as u32),
+                    _ => None,
+                };
 
                 let nullable = !column
                     .options
                     .iter()
                     .any(|opt| opt.option == ColumnOption::NotNull);
 
+                let default = column
+                    .options
+                    .iter()
+                    .filter_map(|opt| match &opt.option {
+                        ColumnOption::Default(expr) => Some(expr),
+                        _ => None,
+                    })
+                    .next_back()
+                    .map(|expr| default_value(&f_type, expr))
+                    .transpose()?
+                    .flatten();
+
                 let field = Field {
                     name: column.name.value.clone(),
                     field_type: f_type,
                     nullable,
+                    display_width,
+                    zerofill,
+                    default,
                 };
 
                 parsed_fields.push(field);
@@ -174,11 +232,9 @@ impl TableDefinition {
 
             if cluster_index_columns.is_empty() {
                 info!("No PRIMARY KEY or suitable UNIQUE, making a pseudo column for clustering index");
-                table_def.cluster_columns.push(Field {
-                    name: "ROWID".into(),
-                    field_type: FieldType::Int6(false),
-                    nullable: false,
-                });
+                table_def
+                    .cluster_columns
+                    .push(Field::new("ROWID", FieldType::RowId, false));
             }
 
             for field in cluster_index_columns.iter() {
@@ -218,6 +274,50 @@ impl TableDefinition {
         self.cluster_columns.len() + self.data_columns.len()
     }
 
+    /// Columns stored in node pointer records, which only carry the cluster
+    /// key followed by the child page number
+    pub fn key_definition(&self) -> TableDefinition {
+        TableDefinition {
+            name: self.name.clone(),
+            cluster_columns: self.cluster_columns.clone(),
+            data_columns: Vec::new(),
+        }
+    }
+
+    /// Expected layout of a record where every field is non null and every
+    /// variable length field has its maximum length. The hidden DB_TRX_ID
+    /// and DB_ROLL_PTR columns follow the cluster columns.
+    pub fn record_layout(&self) -> Vec<FieldLayout> {
+        let mut layout = Vec::new();
+        let mut offset = 0;
+        let mut push = |name: &str, width: usize, variable: bool| {
+            layout.push(FieldLayout {
+                name: name.to_string(),
+                offset,
+                width,
+                variable,
+            });
+            offset += width;
+        };
+        for f in self.cluster_columns.iter() {
+            push(
+                &f.name,
+                f.field_type.max_len() as usize,
+                f.field_type.is_variable(),
+            );
+        }
+        push("DB_TRX_ID", 6, false);
+        push("DB_ROLL_PTR", 7, false);
+        for f in self.data_columns.iter() {
+            push(
+                &f.name,
+                f.field_type.max_len() as usize,
+                f.field_type.is_variable(),
+            );
+        }
+        layout
+    }
+
     pub fn get_field(&self, name: &str) -> Option<&Field> {
         self.cluster_columns
             .iter()
@@ -233,13 +333,80 @@ impl TableDefinition {
     }
 }
 
+/// TEXT types are limited in bytes whatever the charset, text field lengths
+/// are in characters. Rounds up so the byte length is never below the limit.
+fn text_type(max_bytes: u64, charset: InnoDBCharset) -> FieldType {
+    FieldType::Text(max_bytes.div_ceil(charset.max_len()) as usize, charset)
+}
+
+/// Takes the ZEROFILL keywords sqlparser doesn't know out of a CREATE TABLE,
+/// returning the names of the columns they were on
+/// Value of a `DEFAULT` expression, `None` for expressions evaluated on
+/// insert like `CURRENT_TIMESTAMP`
+fn default_value(field_type: &FieldType, expr: &Expr) -> Result<Option<FieldValue>> {
+    let literal = match expr {
+        Expr::Value(Value::Null) => return Ok(Some(FieldValue::Null)),
+        Expr::Value(Value::Number(number, _)) => number.clone(),
+        Expr::Value(Value::SingleQuotedString(s) | Value::DoubleQuotedString(s)) => s.clone(),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => match expr.as_ref() {
+            Expr::Value(Value::Number(number, _)) => format!("-{}", number),
+            _ => return Ok(None),
+        },
+        _ => {
+            debug!("Ignoring DEFAULT {}", expr);
+            return Ok(None);
+        }
+    };
+    field_type.parse_literal(&literal).map(Some)
+}
+
+fn strip_zerofill(tokens: Vec<Token>) -> (Vec<Token>, HashSet<String>) {
+    let mut columns = HashSet::new();
+    let mut stripped = Vec::with_capacity(tokens.len());
+    let mut depth = 0;
+    // Every column definition starts with its name
+    let mut column = None;
+    let mut expect_name = false;
+    for token in tokens {
+        match &token {
+            Token::LParen => {
+                depth += 1;
+                expect_name = depth == 1;
+            }
+            Token::RParen => depth -= 1,
+            Token::Comma if depth == 1 => expect_name = true,
+            Token::Word(word) if depth == 1 && expect_name => {
+                column = Some(word.value.clone());
+                expect_name = false;
+            }
+            Token::Word(word)
+                if depth == 1
+                    && word.quote_style.is_none()
+                    && word.value.eq_ignore_ascii_case("ZEROFILL") =>
+            {
+                columns.extend(column.clone());
+                continue;
+            }
+            _ => {}
+        }
+        stripped.push(token);
+    }
+    (stripped, columns)
+}
+
 #[cfg(test)]
 mod test {
     use std::{fs::read_to_string, path::PathBuf};
 
-    use crate::innodb::{charset::InnoDBCharset, table::field::FieldType};
+    use crate::innodb::{
+        charset::InnoDBCharset,
+        table::field::{FieldType, FieldValue},
+    };
 
-    use super::{field::Field, TableDefinition};
+    use super::{field::Field, FieldLayout, TableDefinition};
 
     #[test]
     fn parse_sql_to_table_def_1() {
@@ -265,6 +432,98 @@ mod test {
         assert!(!field1.nullable);
     }
 
+    #[test]
+    fn parse_sql_text_types() {
+        let sql = r#"CREATE TABLE `t` (
+            `id` int NOT NULL,
+            `tiny` tinytext,
+            `plain` TEXT,
+            `medium` mediumtext CHARACTER SET latin1,
+            `long` LONGTEXT,
+            `doc` json,
+            PRIMARY KEY (`id`)
+        ) DEFAULT CHARSET=utf8mb4;"#;
+        let def = TableDefinition::try_from_sql_statement(sql).unwrap();
+        let tiny = &def.get_field("tiny").unwrap().field_type;
+        assert_eq!(*tiny, FieldType::Text(64, InnoDBCharset::Utf8mb4));
+        assert_eq!(tiny.max_len(), 256);
+        let plain = &def.get_field("plain").unwrap().field_type;
+        assert_eq!(*plain, FieldType::Text(16384, InnoDBCharset::Utf8mb4));
+        let medium = &def.get_field("medium").unwrap().field_type;
+        assert_eq!(medium.max_len(), (1 << 24) - 1);
+        let long = &def.get_field("long").unwrap().field_type;
+        assert_eq!(long.max_len(), 1 << 32);
+        let doc = &def.get_field("doc").unwrap().field_type;
+        assert_eq!(*doc, FieldType::Json);
+        assert!(doc.is_variable());
+    }
+
+    #[test]
+    fn parse_sql_defaults() {
+        let sql = r#"CREATE TABLE `t` (
+            `id` int NOT NULL,
+            `count` int unsigned NOT NULL DEFAULT '3',
+            `delta` smallint DEFAULT -2,
+            `ratio` double DEFAULT 0.5,
+            `name` varchar(10) DEFAULT 'none',
+            `note` varchar(10) DEFAULT NULL,
+            `at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            `plain` int,
+            PRIMARY KEY (`id`)
+        );"#;
+        let def = TableDefinition::try_from_sql_statement(sql).unwrap();
+        let default = |name: &str| def.get_field(name).unwrap().default.clone();
+        assert_eq!(default("count"), Some(FieldValue::UnsignedInt(3)));
+        assert_eq!(default("delta"), Some(FieldValue::SignedInt(-2)));
+        assert_eq!(default("ratio"), Some(FieldValue::Double(0.5)));
+        assert_eq!(default("name"), Some(FieldValue::String("none".into())));
+        assert_eq!(default("note"), Some(FieldValue::Null));
+        assert_eq!(default("at"), None);
+        assert_eq!(default("plain"), None);
+
+        let invalid = "CREATE TABLE t (id int NOT NULL DEFAULT 'x', PRIMARY KEY (id))";
+        assert!(TableDefinition::try_from_sql_statement(invalid).is_err());
+    }
+
+    #[test]
+    fn parse_sql_zerofill() {
+        let sql = r#"CREATE TABLE `t` (
+            `id` INT(5) ZEROFILL NOT NULL,
+            `code` tinyint(3) unsigned zerofill,
+            `plain` bigint(20),
+            `zerofill` int,
+            PRIMARY KEY (`id`)
+        );"#;
+        let def = TableDefinition::try_from_sql_statement(sql).unwrap();
+        let id = def.get_field("id").unwrap();
+        assert_eq!(id.field_type, FieldType::Int(false));
+        assert_eq!(id.display_width, Some(5));
+        assert!(id.zerofill);
+        assert_eq!(
+            id.display_value(&FieldValue::UnsignedInt(42), "")
+                .to_string(),
+            "00042"
+        );
+        let code = def.get_field("code").unwrap();
+        assert_eq!(code.field_type, FieldType::TinyInt(false));
+        assert!(code.zerofill);
+
+        let plain = def.get_field("plain").unwrap();
+        assert_eq!(plain.field_type, FieldType::BigInt(true));
+        assert_eq!(plain.display_width, Some(20));
+        assert!(!plain.zerofill);
+        assert_eq!(
+            plain
+                .display_value(&FieldValue::SignedInt(42), "")
+                .to_string(),
+            "42"
+        );
+        // Only the keyword, not a column named after it
+        let zerofill = def.get_field("zerofill").unwrap();
+        assert_eq!(zerofill.field_type, FieldType::Int(true));
+        assert!(!zerofill.zerofill);
+    }
+
     #[test]
     fn prase_sql_complex_table() {
         let sql = read_to_string(
@@ -273,7 +532,7 @@ mod test {
                 .join("pre_ucenter_members.sql"),
         )
         .unwrap();
-        let reference = TableDefinition {
+        let mut reference = TableDefinition {
             name: String::from("pre_ucenter_members"),
             cluster_columns: vec![
                 // name, type, nullable, signed, pk
@@ -316,8 +575,63 @@ mod test {
                 Field::new("secques", FieldType::Text(8, InnoDBCharset::Utf8mb4), false),
             ],
         };
+        // Every data column defaults to '' or '0'
+        for field in reference.data_columns.iter_mut() {
+            field.default = Some(field.field_type.parse_literal("0").unwrap());
+            if let FieldType::Text(_, _) = field.field_type {
+                field.default = Some(FieldValue::String(String::new()));
+            }
+        }
 
         let parsed = TableDefinition::try_from_sql_statement(&sql).expect("Failed to parse SQL");
         assert_eq!(parsed, reference);
     }
+
+    #[test]
+    fn parse_sql_enum_multibyte() {
+        let sql = r#"CREATE TABLE `t` (
+            `id` int NOT NULL,
+            `kind` enum('café','日本語','it''s') CHARACTER SET utf8mb4 NOT NULL,
+            PRIMARY KEY (`id`)
+        ) DEFAULT CHARSET=utf8mb4;"#;
+
+        let def = TableDefinition::try_from_sql_statement(sql).unwrap();
+        assert_eq!(
+            def.get_field("kind").unwrap().field_type,
+            FieldType::Enum(vec![
+                String::from("café"),
+                String::from("日本語"),
+                String::from("it's")
+            ])
+        );
+    }
+
+    #[test]
+    fn record_layout() {
+        let sql = r#"CREATE TABLE `t` (
+            `id` int NOT NULL,
+            `code` char(4) NOT NULL,
+            `name` varchar(10),
+            `created` datetime,
+            PRIMARY KEY (`id`)
+        );"#;
+        let def = TableDefinition::try_from_sql_statement(sql).unwrap();
+        let layout = |name: &str, offset, width, variable| FieldLayout {
+            name: name.to_string(),
+            offset,
+            width,
+            variable,
+        };
+        assert_eq!(
+            def.record_layout(),
+            vec![
+                layout("id", 0, 4, false),
+                layout("DB_TRX_ID", 4, 6, false),
+                layout("DB_ROLL_PTR", 10, 7, false),
+                layout("code", 17, 4, false),
+                layout("name", 21, 10, true),
+                layout("created", 31, 8, false),
+            ]
+        );
+    }
 }
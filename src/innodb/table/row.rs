@@ -1,28 +1,33 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
-    ops::Deref,
+    io::Write,
     sync::Arc,
 };
 
 use crate::innodb::{
-    buffer_manager::{BufferManager},
+    buffer_manager::BufferManager,
     page::{
-        index::record::{Record, RECORD_HEADER_FIXED_LENGTH},
-        lob::{data_page::LobData, LobFirst, LobIndexEntry},
+        index::record::{Record, RecordType, RECORD_HEADER_FIXED_LENGTH},
+        lob::{data_page::LobData, LobFirst},
     },
-    table::blob_header::ExternReference,
+    table::blob_header::{ExternReference, EXTERN_REFERENCE_SIZE},
+    undo::RollPtr,
     InnoDBError,
 };
 
 use super::{
-    field::{Field, FieldValue},
+    field::{Field, FieldType, FieldValue},
     TableDefinition,
 };
 
 use anyhow::{anyhow, Result};
 use tracing::{trace, warn};
 
+/// Most of an extern column reserved before reading it, the length in the
+/// reference comes from the page and may be garbage
+const MAX_EXTERN_RESERVE: usize = 1 << 20;
+
 pub struct Row<'a> {
     td: Arc<TableDefinition>,
     // Field Index, Null or Not
@@ -31,6 +36,12 @@ pub struct Row<'a> {
 
     // Field Index, length
     field_len_map: HashMap<usize, u64>,
+    // Columns stored in the record, the ones after were added instantly
+    // later and take their default
+    stored_columns: usize,
+    // Hidden DB_TRX_ID and DB_ROLL_PTR, only on clustered leaf records
+    trx_id: Option<u64>,
+    roll_ptr: Option<u64>,
     pub record: Record<'a>,
 }
 
@@ -39,6 +50,8 @@ impl<'a> Debug for Row<'a> {
         f.debug_struct("Row")
             .field("null_map", &self.null_map)
             .field("field_len_map", &self.field_len_map)
+            .field("trx_id", &self.trx_id)
+            .field("roll_ptr", &self.roll_ptr)
             .field("record", &self.record)
             .finish()
     }
@@ -52,6 +65,32 @@ impl<'a> Row<'a> {
 
         let mut extern_fields: HashSet<usize> = HashSet::new();
 
+        let columns = td.cluster_columns.len() + td.data_columns.len();
+        let stored_columns =
+            if r.header.info_flags.instant && r.header.record_type == RecordType::Conventional {
+                // Field count of the index, DB_TRX_ID and DB_ROLL_PTR included
+                let first = *byte_stream.next().ok_or(InnoDBError::InvalidLength)? as usize;
+                let n_fields = if first & 0x80 != 0 {
+                    let low = *byte_stream.next().ok_or(InnoDBError::InvalidLength)? as usize;
+                    ((first & 0x7F) << 8) | low
+                } else {
+                    first
+                };
+                n_fields
+                    .checked_sub(2)
+                    .filter(|n| (td.cluster_columns.len()..=columns).contains(n))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Record at {} has {} fields, the table has {} columns",
+                            r.offset,
+                            n_fields,
+                            columns
+                        )
+                    })?
+            } else {
+                columns
+            };
+
         // Map of null bits: <Field Idx, null_bit>
         let mut null_field_map: HashMap<usize, usize> = HashMap::new();
         for (idx, field) in td
@@ -59,6 +98,7 @@ impl<'a> Row<'a> {
             .iter()
             .chain(td.data_columns.iter())
             .enumerate()
+            .take(stored_columns)
         {
             if field.nullable {
                 null_field_map.insert(idx, null_field_map.len());
@@ -92,6 +132,7 @@ impl<'a> Row<'a> {
             .iter()
             .chain(td.data_columns.iter())
             .enumerate()
+            .take(stored_columns)
         {
             if field.field_type.is_variable() {
                 // NULL Fields don't have length?
@@ -116,21 +157,87 @@ impl<'a> Row<'a> {
                         let tmp = (len << 8) | byte2 as u64;
                         len = tmp & 0x3FFF;
                         if tmp & 0x4000 != 0 {
+                            // The 14 bits only cover what's on the page, any
+                            // prefix and the reference, which holds the full
+                            // length of even a LONGTEXT
+                            if len < EXTERN_REFERENCE_SIZE as u64 {
+                                return Err(anyhow!(
+                                    "Extern field {} has {} bytes, too short for a reference",
+                                    field.name,
+                                    len
+                                ));
+                            }
                             extern_fields.insert(idx);
                         }
                     }
                 }
+                if !extern_fields.contains(&idx) && len > field.field_type.max_len() {
+                    return Err(anyhow!(
+                        "Field {} has length {} larger than max length {}",
+                        field.name,
+                        len,
+                        field.field_type.max_len()
+                    ));
+                }
                 length_map.insert(idx, len);
             }
         }
 
-        Ok(Row {
+        let mut row = Row {
             td: td.clone(),
             null_map,
             field_len_map: length_map,
+            stored_columns,
+            trx_id: None,
+            roll_ptr: None,
             record: r.clone(),
             extern_fields,
-        })
+        };
+
+        // Node pointers end with the child page number instead
+        if r.header.record_type == RecordType::Conventional {
+            let hidden = row.cluster_end()?;
+            let bytes = r.buf.get(hidden..hidden + 6 + 7).ok_or_else(|| {
+                anyhow!("Record at {} is too short for the hidden columns", r.offset)
+            })?;
+            let be_uint = |bytes: &[u8]| bytes.iter().fold(0u64, |n, b| (n << 8) | *b as u64);
+            row.trx_id = Some(be_uint(&bytes[..6]));
+            row.roll_ptr = Some(be_uint(&bytes[6..]));
+        }
+
+        Ok(row)
+    }
+
+    /// DB_TRX_ID of the transaction that last modified the row, `None` for
+    /// node pointer records
+    pub fn trx_id(&self) -> Option<u64> {
+        self.trx_id
+    }
+
+    /// DB_ROLL_PTR to the undo log record, `None` for node pointer records
+    pub fn roll_ptr(&self) -> Option<u64> {
+        self.roll_ptr
+    }
+
+    /// DB_TRX_ID and the decoded DB_ROLL_PTR, see [`RollPtr::decode`] for
+    /// `undo_spaces`. `None` for node pointer records.
+    pub fn hidden_columns(&self, undo_spaces: &[u32]) -> Option<(u64, RollPtr)> {
+        Some((self.trx_id?, RollPtr::decode(self.roll_ptr?, undo_spaces)))
+    }
+
+    /// DB_ROW_ID of a table clustered on the generated row id, which is the
+    /// whole cluster key there. `None` for tables with a primary key.
+    pub fn row_id(&self) -> Result<Option<u64>> {
+        if !matches!(
+            self.td.cluster_columns.as_slice(),
+            [Field {
+                field_type: FieldType::RowId,
+                ..
+            }]
+        ) {
+            return Ok(None);
+        }
+        Ok(self.cluster_key()?[0].as_u64())
     }
 
     fn load_extern(
@@ -138,71 +245,86 @@ impl<'a> Row<'a> {
         extern_header: &ExternReference,
         buffer_mgr: &dyn BufferManager,
     ) -> Result<Box<[u8]>> {
+        let capacity = extern_header.length.min(MAX_EXTERN_RESERVE as u64) as usize;
+        let mut output_buffer = Vec::<u8>::with_capacity(capacity);
+        self.load_extern_to_writer(extern_header, buffer_mgr, &mut output_buffer)?;
+        Ok(output_buffer.into())
+    }
+
+    /// Streams the LOB of an extern column into `w` one page at a time, for
+    /// values too large to hold in memory. Returns the bytes written, which
+    /// is the length in the reference.
+    pub fn load_extern_to_writer(
+        &self,
+        extern_header: &ExternReference,
+        buffer_mgr: &dyn BufferManager,
+        w: &mut dyn Write,
+    ) -> Result<u64> {
         let space_id = extern_header.space_id;
         let first_page_number = extern_header.page_number;
         let lob_first_page = buffer_mgr.pin(space_id, first_page_number)?;
         if lob_first_page.header.offset != extern_header.page_number {
             return Err(anyhow!(InnoDBError::InvalidPage));
         }
-        let lob_first = LobFirst::try_from_page(lob_first_page.deref())?;
-        let index_list = &lob_first.header.index_list_head;
+        let lob_first_page = lob_first_page.page();
+        let lob_first = LobFirst::try_from_page(&lob_first_page)?;
         trace!("LOB First: {:#?}", lob_first);
 
-        let mut node_location = index_list.first_node;
-        let mut page_offset = 0;
-
-        let mut output_buffer = Vec::<u8>::new();
-        let mut filled = 0usize;
-        output_buffer.resize(extern_header.length as usize, 0);
-
-        while !node_location.is_null() {
-            trace!("Inspecting Node at offset {}", node_location.offset);
-            assert_eq!(
-                index_list.first_node.page_number, lob_first.page.header.offset,
-                "assumption"
-            );
-            let buf = &lob_first.page.raw_data[node_location.offset as usize..];
-            let node = LobIndexEntry::try_from_bytes(buf)?;
+        let mut chunk = vec![0u8; lob_first_page.raw_data.len()];
+        let mut filled = 0u64;
+        for node in lob_first.index_entries(buffer_mgr) {
+            let node = node?;
             trace!("Index Node: {:#?}", node);
+            let remaining = std::cmp::min(extern_header.length - filled, chunk.len() as u64);
+            let chunk = &mut chunk[..remaining as usize];
 
-            let mut bytes_read = 0usize;
-            if node.page_number == first_page_number {
-                bytes_read = lob_first.read(page_offset, &mut output_buffer[filled..]);
+            let bytes_read = if node.page_number == first_page_number {
+                let bytes_read = lob_first.read(0, chunk);
                 trace!(
                     "Read {} bytes from first page, in total expecting {} bytes",
                     bytes_read,
-                    output_buffer.len()
+                    extern_header.length
                 );
+                bytes_read
             } else {
                 let page_guard = buffer_mgr.pin(space_id, node.page_number)?;
-                let data_page = LobData::try_from_page(&page_guard)?;
+                let page = page_guard.page();
+                let data_page = LobData::try_from_page(&page)?;
                 trace!("Data page: {:#?}", data_page);
-                bytes_read = data_page.read(page_offset, &mut output_buffer[filled..]);
+                let bytes_read = data_page.read(0, chunk);
                 trace!("Read {} bytes from data page", bytes_read);
-            }
-            filled += bytes_read;
-            page_offset = page_offset.saturating_sub(bytes_read);
-
-            node_location = node.file_list_node.next;
+                bytes_read
+            };
+            w.write_all(&chunk[..bytes_read])?;
+            filled += bytes_read as u64;
         }
 
-        if filled < output_buffer.len() {
-            warn!("huh {}, {}", filled, output_buffer.len());
-            return Err(anyhow!("Read incomplete"));
+        if filled < extern_header.length {
+            return Err(anyhow!(
+                "LOB chain shorter than expected, read {} of {} bytes",
+                filled,
+                extern_header.length
+            ));
         }
 
-        Ok(output_buffer.into())
+        Ok(filled)
     }
 
+    /// Loads an extern column, `prefix` is the part stored in the record by
+    /// the COMPACT and REDUNDANT formats, empty for DYNAMIC and COMPRESSED
     fn parse_extern_field(
         &self,
         f: &Field,
+        prefix: &[u8],
         extern_header: &ExternReference,
         buffer_mgr: &dyn BufferManager,
     ) -> FieldValue {
-        // Load a page
-        match self.load_extern(extern_header, buffer_mgr) {
-            Ok(buf) => f.parse(&buf, Some(extern_header.length)).0,
+        let len = prefix.len() as u64 + extern_header.length;
+        match self
+            .load_extern(extern_header, buffer_mgr)
+            .and_then(|buf| f.parse(&[prefix, &buf].concat(), Some(len)))
+        {
+            Ok((value, _)) => value,
             Err(err) => {
                 warn!(
                     "Failed to open extern {:?}, error: {:?}",
@@ -213,54 +335,668 @@ impl<'a> Row<'a> {
         }
     }
 
+    /// The 20 byte reference ends the stored part of an extern column
+    fn extern_reference(&self, idx: usize, buf: &[u8]) -> Result<ExternReference> {
+        let len = *self.field_len_map.get(&idx).unwrap() as usize;
+        if len < EXTERN_REFERENCE_SIZE {
+            return Err(anyhow!(
+                "Extern column should be at least 20 bytes long, got {}",
+                len
+            ));
+        }
+        let extern_header = ExternReference::from_bytes(&buf[len - EXTERN_REFERENCE_SIZE..len])?;
+        trace!("Extern Header: {:?}", &extern_header);
+        Ok(extern_header)
+    }
+
     fn parse_single_field(
         &self,
         f: &Field,
         buf: &[u8],
         idx: usize,
         buf_mgr: &dyn BufferManager,
-    ) -> (FieldValue, usize) {
+    ) -> Result<(FieldValue, usize)> {
         if self.extern_fields.contains(&idx) {
-            let len = *self.field_len_map.get(&idx).unwrap() as usize;
-            assert_eq!(len, 20, "Extern header should be 20 bytes long");
-            let extern_header =
-                ExternReference::from_bytes(&buf[0..len]).expect("Can't make blob header");
-            trace!("Extern Header: {:?}", &extern_header);
-            (
-                self.parse_extern_field(f, &extern_header, buf_mgr),
+            let extern_header = self.extern_reference(idx, buf)?;
+            let len = self.field_len_map[&idx] as usize;
+            let prefix = &buf[..len - EXTERN_REFERENCE_SIZE];
+            Ok((
+                self.parse_extern_field(f, prefix, &extern_header, buf_mgr),
                 len,
-            )
+            ))
         } else {
-            let (value, len) = f.parse(buf, self.field_len_map.get(&idx).cloned());
-            (value, len)
+            f.parse(buf, self.field_len_map.get(&idx).cloned())
         }
     }
 
-    /// Only call on primary index
-    pub fn parse_values(&self, buffer_mgr: &dyn BufferManager) -> Vec<FieldValue> {
-        let mut values = Vec::new();
+    /// Visits every column in on-disk order, `visit` gets the record bytes
+    /// starting at that column and returns how many of them the column uses.
+    fn walk_fields<F>(&self, mut visit: F) -> Result<usize>
+    where
+        F: FnMut(usize, &Field, &'a [u8]) -> Result<usize>,
+    {
         let mut current_offset = self.record.offset;
-        let num_pk = self.td.cluster_columns.len();
-        assert_ne!(num_pk, 0, "Table must have PK");
+        let cluster_count = self.td.cluster_columns.len();
+        if cluster_count == 0 {
+            return Err(anyhow!(InnoDBError::MissingPrimaryKey));
+        }
 
         for (idx, f) in self.td.cluster_columns.iter().enumerate() {
-            let (value, consumed) =
-                self.parse_single_field(f, &self.record.buf[current_offset..], idx, buffer_mgr);
-            current_offset += consumed;
-            values.push(value);
+            current_offset += visit(idx, f, &self.record.buf[current_offset..])?;
         }
         // Hidden Columns
         current_offset += 6 + 7;
 
-        let cluster_count = self.td.cluster_columns.len();
-        for (idx, f) in self.td.data_columns.iter().enumerate() {
-            let idx = idx + cluster_count;
-            let (value, consumed) =
-                self.parse_single_field(f, &self.record.buf[current_offset..], idx, buffer_mgr);
+        for (idx, f) in self
+            .td
+            .data_columns
+            .iter()
+            .enumerate()
+            .take(self.stored_columns - cluster_count)
+        {
+            current_offset += visit(idx + cluster_count, f, &self.record.buf[current_offset..])?;
+        }
+
+        Ok(current_offset)
+    }
+
+    /// Parses the cluster columns, returning their values and the offset
+    /// right after them
+    fn parse_cluster_columns(&self) -> Result<(Vec<FieldValue>, usize)> {
+        let mut current_offset = self.record.offset;
+        let mut values = Vec::new();
+        for (idx, f) in self.td.cluster_columns.iter().enumerate() {
+            if self.extern_fields.contains(&idx) {
+                return Err(anyhow!("Cluster column {} is stored externally", f.name));
+            }
+            let (value, consumed) = f.parse(
+                &self.record.buf[current_offset..],
+                self.field_len_map.get(&idx).cloned(),
+            )?;
+            values.push(value);
             current_offset += consumed;
+        }
+
+        Ok((values, current_offset))
+    }
+
+    fn cluster_end(&self) -> Result<usize> {
+        Ok(self.parse_cluster_columns()?.1)
+    }
+
+    /// Values of the cluster columns only, also valid for node pointer
+    /// records when the table has no data columns
+    pub fn cluster_key(&self) -> Result<Vec<FieldValue>> {
+        Ok(self.parse_cluster_columns()?.0)
+    }
+
+    /// Child page of a node pointer record, stored right after the key.
+    /// The row must be built with [`TableDefinition::key_definition`].
+    pub fn child_page_number(&self) -> Result<u32> {
+        if self.record.header.record_type != RecordType::NodePointer {
+            return Err(anyhow!(
+                "Record at {} is not a node pointer",
+                self.record.offset
+            ));
+        }
+        let end = self.cluster_end()?;
+        let bytes = self
+            .record
+            .buf
+            .get(end..end + 4)
+            .ok_or(InnoDBError::InvalidLength)?;
+        Ok(u32::from_be_bytes(bytes.try_into()?))
+    }
+
+    /// Bytes a column takes up in the record, without loading extern columns
+    fn field_size(&self, idx: usize, f: &Field, buf: &[u8]) -> Result<usize> {
+        if self.extern_fields.contains(&idx) {
+            Ok(self.field_len_map[&idx] as usize)
+        } else {
+            Ok(f.parse(buf, self.field_len_map.get(&idx).cloned())?.1)
+        }
+    }
+
+    /// Bytes of column `idx` stored in the record, without loading anything:
+    /// the local prefix of an extern column, which is empty unless the row
+    /// format is COMPACT or REDUNDANT, and the whole value otherwise. `None`
+    /// for NULL columns and columns that can't be located.
+    pub fn inline_field_bytes(&self, idx: usize) -> Option<&'a [u8]> {
+        if self.null_map.get(&idx) == Some(&true) {
+            return None;
+        }
+        let mut found = None;
+        self.walk_fields(|field_idx, f, buf| {
+            let size = self.field_size(field_idx, f, buf)?;
+            if field_idx == idx {
+                let inline = if self.extern_fields.contains(&idx) {
+                    size - EXTERN_REFERENCE_SIZE
+                } else {
+                    size
+                };
+                found = buf.get(..inline);
+            }
+            Ok(size)
+        })
+        .ok()?;
+        found
+    }
+
+    /// Offset right after the last column of the record
+    pub fn data_end(&self) -> Result<usize> {
+        self.walk_fields(|idx, f, buf| self.field_size(idx, f, buf))
+    }
+
+    /// Bytes taken by the columns, the exact counterpart of
+    /// [`Record::payload_len`]
+    pub fn payload_len(&self) -> Result<usize> {
+        Ok(self.data_end()? - self.record.offset)
+    }
+
+    /// Checks the columns end before the header of the next record when that
+    /// one is stored after this one. Running into it usually means the table
+    /// definition doesn't match the record.
+    pub fn check_span(&self) -> Result<()> {
+        let end = self.data_end()?;
+        if end > self.record.buf.len() {
+            return Err(anyhow!(
+                "Record at {} ends at {}, past the end of the page",
+                self.record.offset,
+                end
+            ));
+        }
+        if let Ok(next) = self.record.next_offset() {
+            if next > self.record.offset && end > next - RECORD_HEADER_FIXED_LENGTH {
+                return Err(anyhow!(
+                    "Record at {} ends at {}, inside the next record at {}",
+                    self.record.offset,
+                    end,
+                    next
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Row whose columns don't fit in the record, see [`Row::check_span`]
+    pub fn is_suspect(&self) -> bool {
+        self.check_span().is_err()
+    }
+
+    /// Only call on primary index. Rows failing [`Row::check_span`] are
+    /// rejected instead of returning values read from the next record.
+    pub fn parse_values(&self, buffer_mgr: &dyn BufferManager) -> Result<Vec<FieldValue>> {
+        self.check_span()?;
+        let mut values = Vec::new();
+        self.walk_fields(|idx, f, buf| {
+            let (value, consumed) = self.parse_single_field(f, buf, idx, buffer_mgr)?;
             values.push(value);
+            Ok(consumed)
+        })?;
+        // Columns added instantly after the row was written
+        let stored_data = self.stored_columns - self.td.cluster_columns.len();
+        values.extend(
+            self.td.data_columns[stored_data..]
+                .iter()
+                .map(|f| f.default.clone().unwrap_or(FieldValue::Null)),
+        );
+
+        Ok(values)
+    }
+
+    /// References of all externally stored columns, without loading them.
+    pub fn extern_references(&self) -> Result<Vec<ExternReference>> {
+        let mut references = Vec::new();
+        self.walk_fields(|idx, f, buf| {
+            if self.extern_fields.contains(&idx) {
+                references.push(self.extern_reference(idx, buf)?);
+            }
+            self.field_size(idx, f, buf)
+        })?;
+
+        Ok(references)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::read_to_string, sync::Arc};
+
+    use crate::innodb::{
+        buffer_manager::{memory::MemoryBufferManager, DummyBufferMangaer},
+        charset::InnoDBCharset,
+        page::{
+            index::{record::Record, IndexPage},
+            Page, FIL_PAGE_SIZE,
+        },
+        table::{
+            field::{Field, FieldType, FieldValue},
+            TableDefinition,
+        },
+        test_data, test_data_path,
+        testing::lob_pages,
+        undo::RollPtr,
+        InnoDBError,
+    };
+
+    use super::Row;
+
+    fn single_text_table() -> Arc<TableDefinition> {
+        Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![Field::new(
+                "id",
+                FieldType::Text(5, InnoDBCharset::Ascii),
+                false,
+            )],
+            data_columns: vec![],
+        })
+    }
+
+    // Length byte, 5 byte record header, then the payload and room for the
+    // hidden columns
+    fn record_bytes(len: u8) -> Vec<u8> {
+        let mut buf = vec![len, 0x00, 0x00, 0x10, 0x00, 0x00];
+        buf.extend_from_slice(b"0123456789");
+        buf.extend_from_slice(&[0u8; 13]);
+        buf
+    }
+
+    #[test]
+    fn test_row_length_within_max() {
+        let td = single_text_table();
+        let buf = record_bytes(5);
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        let values = row.parse_values(&DummyBufferMangaer).unwrap();
+        assert_eq!(values, vec![FieldValue::String(String::from("01234"))]);
+    }
+
+    #[test]
+    fn test_row_oversized_length_prefix() {
+        let td = single_text_table();
+        let buf = record_bytes(10);
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        assert!(Row::try_from_record_and_table(&record, &td).is_err());
+    }
+
+    #[test]
+    fn test_row_without_primary_key() {
+        let td = Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![],
+            data_columns: vec![Field::new(
+                "c",
+                FieldType::Text(5, InnoDBCharset::Ascii),
+                false,
+            )],
+        });
+        let buf = record_bytes(5);
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        let err = row.parse_values(&DummyBufferMangaer).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InnoDBError>(),
+            Some(&InnoDBError::MissingPrimaryKey)
+        );
+    }
+
+    #[test]
+    fn test_row_instant_defaults() {
+        let td = Arc::new(
+            TableDefinition::try_from_sql_statement(
+                "CREATE TABLE t (
+                    id INT NOT NULL,
+                    a INT NOT NULL,
+                    b INT NOT NULL DEFAULT -7,
+                    c VARCHAR(10) DEFAULT 'x',
+                    PRIMARY KEY (id)
+                )",
+            )
+            .unwrap(),
+        );
+        // Written before b and c were added: id, the hidden columns and a
+        let mut buf = vec![4, 0x80, 0x00, 0x10, 0x00, 0x00];
+        buf.extend_from_slice(&(1u32 ^ 0x8000_0000).to_be_bytes());
+        buf.extend_from_slice(&[0u8; 13]);
+        buf.extend_from_slice(&(2u32 ^ 0x8000_0000).to_be_bytes());
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        assert!(record.header.info_flags.instant);
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        assert_eq!(
+            row.parse_values(&DummyBufferMangaer).unwrap(),
+            vec![
+                FieldValue::SignedInt(1),
+                FieldValue::SignedInt(2),
+                FieldValue::SignedInt(-7),
+                FieldValue::String(String::from("x")),
+            ]
+        );
+        assert_eq!(row.payload_len().unwrap(), 4 + 13 + 4);
+        assert_eq!(row.inline_field_bytes(2), None);
+
+        // More fields than the table has columns
+        buf[0] = 7;
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        assert!(Row::try_from_record_and_table(&record, &td).is_err());
+    }
+
+    #[test]
+    fn test_hidden_columns() {
+        let td = single_text_table();
+        let mut buf = record_bytes(5);
+        buf[11..17].copy_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x02, 0x03]);
+        buf[17..24].copy_from_slice(&[0x81, 0x00, 0x00, 0x00, 0x90, 0x01, 0x20]);
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        let (trx_id, roll_ptr) = row.hidden_columns(&[]).unwrap();
+        assert_eq!(trx_id, 0x01_0203);
+        assert_eq!(
+            roll_ptr,
+            RollPtr {
+                is_insert: true,
+                rseg_id: 1,
+                space_id: 0,
+                page_number: 0x90,
+                offset: 0x120,
+            }
+        );
+        // Rollback segment 1 of two undo tablespaces is in the second
+        assert_eq!(row.hidden_columns(&[3, 4]).unwrap().1.space_id, 4);
+
+        // Node pointers have neither
+        buf[3] |= 0x01;
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        assert_eq!(row.hidden_columns(&[]), None);
+    }
+
+    const LOB_SPACE: u32 = 5;
+    const LOB_FIRST_PAGE: u32 = 7;
+
+    /// LOB pages holding `data` in chunks of `chunk` bytes
+    fn lob_chain(data: &[u8], chunk: usize) -> Vec<Vec<u8>> {
+        let chunks: Vec<&[u8]> = data.chunks(chunk).collect();
+        lob_pages(LOB_SPACE, LOB_FIRST_PAGE, &chunks)
+    }
+
+    #[test]
+    fn test_row_extern_from_memory() {
+        let td = Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![Field::new("id", FieldType::Int(false), false)],
+            data_columns: vec![Field::new(
+                "body",
+                FieldType::Text((1 << 24) - 1, InnoDBCharset::Ascii),
+                false,
+            )],
+        });
+        let text: String = (0..1000).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in lob_chain(text.as_bytes(), 300) {
+            buffer_mgr.add_page(&page).unwrap();
+        }
+
+        // Extern flagged 2 byte length, record header, id, hidden columns
+        let mut buf = vec![20, 0xC0, 0x00, 0x00, 0x10, 0x00, 0x00];
+        buf.extend_from_slice(&42u32.to_be_bytes());
+        buf.extend_from_slice(&[0u8; 13]);
+        buf.extend_from_slice(&LOB_SPACE.to_be_bytes());
+        buf.extend_from_slice(&LOB_FIRST_PAGE.to_be_bytes());
+        buf.extend_from_slice(&1u32.to_be_bytes());
+        buf.extend_from_slice(&(text.len() as u64).to_be_bytes());
+
+        let record = Record::try_from_offset(&buf, 7).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        let values = row.parse_values(&buffer_mgr).unwrap();
+        assert_eq!(
+            values,
+            vec![FieldValue::UnsignedInt(42), FieldValue::String(text)]
+        );
+        for page in LOB_FIRST_PAGE..LOB_FIRST_PAGE + 4 {
+            assert_eq!(buffer_mgr.pin_count(LOB_SPACE, page), 0);
+        }
+
+        // A corrupt length fails on the chain instead of reserving it
+        let mut corrupt = buf.clone();
+        let at = corrupt.len() - 8;
+        corrupt[at..].copy_from_slice(&(1u64 << 40).to_be_bytes());
+        let record = Record::try_from_offset(&corrupt, 7).unwrap();
+        let corrupt_row = Row::try_from_record_and_table(&record, &td).unwrap();
+        let values = corrupt_row.parse_values(&buffer_mgr).unwrap();
+        assert_eq!(values[1], FieldValue::Skipped);
+
+        // DYNAMIC keeps no prefix in the record
+        assert_eq!(row.inline_field_bytes(0), Some(&42u32.to_be_bytes()[..]));
+        assert_eq!(row.inline_field_bytes(1), Some(&[][..]));
+        assert_eq!(row.inline_field_bytes(2), None);
+    }
+
+    #[test]
+    fn test_row_extern_to_writer() {
+        let td = Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![Field::new("id", FieldType::Int(false), false)],
+            data_columns: vec![Field::new(
+                "body",
+                FieldType::Text((1 << 24) - 1, InnoDBCharset::Ascii),
+                false,
+            )],
+        });
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in lob_chain(&data, 15_000) {
+            buffer_mgr.add_page(&page).unwrap();
+        }
+
+        let mut buf = vec![20, 0xC0, 0x00, 0x00, 0x10, 0x00, 0x00];
+        buf.extend_from_slice(&42u32.to_be_bytes());
+        buf.extend_from_slice(&[0u8; 13]);
+        buf.extend_from_slice(&LOB_SPACE.to_be_bytes());
+        buf.extend_from_slice(&LOB_FIRST_PAGE.to_be_bytes());
+        buf.extend_from_slice(&1u32.to_be_bytes());
+        buf.extend_from_slice(&(data.len() as u64).to_be_bytes());
+
+        let record = Record::try_from_offset(&buf, 7).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        let references = row.extern_references().unwrap();
+        assert_eq!(references.len(), 1);
+
+        let mut sink = Vec::new();
+        let written = row
+            .load_extern_to_writer(&references[0], &buffer_mgr, &mut sink)
+            .unwrap();
+        assert_eq!(written, data.len() as u64);
+        assert!(sink == data);
+        for page in LOB_FIRST_PAGE..LOB_FIRST_PAGE + 10 {
+            assert_eq!(buffer_mgr.pin_count(LOB_SPACE, page), 0);
+        }
+
+        // A reference longer than the chain
+        let mut longer = references[0].clone();
+        longer.length += 1;
+        assert!(row
+            .load_extern_to_writer(&longer, &buffer_mgr, &mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_row_extern_prefix() {
+        let td = Arc::new(TableDefinition {
+            name: String::from("t"),
+            cluster_columns: vec![Field::new("id", FieldType::Int(false), false)],
+            data_columns: vec![
+                Field::new("body", FieldType::Text(65535, InnoDBCharset::Ascii), false),
+                Field::new("note", FieldType::Text(255, InnoDBCharset::Ascii), true),
+            ],
+        });
+        let text: String = (0..2000).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let (prefix, rest) = text.as_bytes().split_at(768);
+
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in lob_chain(rest, 500) {
+            buffer_mgr.add_page(&page).unwrap();
+        }
+
+        // COMPACT row: 768 byte prefix and the reference, 788 in an extern
+        // flagged 2 byte length, NULL note, record header
+        let mut buf = vec![0x14, 0xC3, 0x01, 0x00, 0x00, 0x10, 0x00, 0x00];
+        buf.extend_from_slice(&42u32.to_be_bytes());
+        buf.extend_from_slice(&[0u8; 13]);
+        buf.extend_from_slice(prefix);
+        buf.extend_from_slice(&LOB_SPACE.to_be_bytes());
+        buf.extend_from_slice(&LOB_FIRST_PAGE.to_be_bytes());
+        buf.extend_from_slice(&1u32.to_be_bytes());
+        buf.extend_from_slice(&(rest.len() as u64).to_be_bytes());
+
+        let record = Record::try_from_offset(&buf, 8).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        assert_eq!(row.inline_field_bytes(1), Some(prefix));
+        assert_eq!(row.inline_field_bytes(2), None);
+        assert_eq!(row.payload_len().unwrap(), 4 + 13 + 788);
+        assert_eq!(row.extern_references().unwrap()[0].length, 1232);
+
+        // The value is the prefix followed by the LOB, which needs its pages
+        let values = row.parse_values(&DummyBufferMangaer).unwrap();
+        assert_eq!(values[1], FieldValue::Skipped);
+        let values = row.parse_values(&buffer_mgr).unwrap();
+        assert_eq!(values[1], FieldValue::String(text));
+        assert_eq!(values[2], FieldValue::Null);
+    }
+
+    #[test]
+    fn test_row_extern_longtext() {
+        let td = Arc::new(
+            TableDefinition::try_from_sql_statement(
+                "CREATE TABLE t (id INT NOT NULL, body LONGTEXT NOT NULL, PRIMARY KEY (id))",
+            )
+            .unwrap(),
+        );
+        assert!(td.data_columns[0].field_type.max_len() > 0x3FFF);
+        let text: String = (0..100_000)
+            .map(|i| (b'a' + (i % 26) as u8) as char)
+            .collect();
+
+        let mut buffer_mgr = MemoryBufferManager::new();
+        for page in lob_chain(text.as_bytes(), 15_000) {
+            buffer_mgr.add_page(&page).unwrap();
+        }
+
+        // Only the reference is on the page, the length is in the reference
+        let mut buf = vec![20, 0xC0, 0x00, 0x00, 0x10, 0x00, 0x00];
+        buf.extend_from_slice(&(42u32 ^ 0x8000_0000).to_be_bytes());
+        buf.extend_from_slice(&[0u8; 13]);
+        buf.extend_from_slice(&LOB_SPACE.to_be_bytes());
+        buf.extend_from_slice(&LOB_FIRST_PAGE.to_be_bytes());
+        buf.extend_from_slice(&1u32.to_be_bytes());
+        buf.extend_from_slice(&(text.len() as u64).to_be_bytes());
+
+        let record = Record::try_from_offset(&buf, 7).unwrap();
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        assert_eq!(row.payload_len().unwrap(), 4 + 13 + 20);
+        assert_eq!(
+            row.extern_references().unwrap()[0].length,
+            text.len() as u64
+        );
+        let values = row.parse_values(&buffer_mgr).unwrap();
+        assert_eq!(values[1], FieldValue::String(text));
+
+        // Too short to hold a reference
+        buf[0] = 12;
+        let record = Record::try_from_offset(&buf, 7).unwrap();
+        assert!(Row::try_from_record_and_table(&record, &td).is_err());
+    }
+
+    #[test]
+    fn test_row_system_columns() {
+        let sql = read_to_string(test_data_path("double_test_table.sql")).unwrap();
+        let td = Arc::new(TableDefinition::try_from_sql_statement(&sql).unwrap());
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        let index = IndexPage::try_from_page(page).unwrap();
+
+        let rows: Vec<(Option<u64>, Option<u64>)> = index
+            .records()
+            .map(|record| {
+                let row = Row::try_from_record_and_table(&record.unwrap(), &td).unwrap();
+                (row.trx_id(), row.roll_ptr())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(0x3417), Some(0x81_0000_0088_0110)),
+                (Some(0x3418), Some(0x82_0000_008b_0110))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_row_id_cluster_key() {
+        let td = Arc::new(
+            TableDefinition::try_from_sql_statement("CREATE TABLE t (v INT NOT NULL)").unwrap(),
+        );
+        assert_eq!(td.cluster_columns[0].field_type, FieldType::RowId);
+
+        // Two records: header, DB_ROW_ID, DB_TRX_ID, DB_ROLL_PTR and v
+        let record_len = 5 + 6 + 6 + 7 + 4;
+        let mut buf = Vec::new();
+        for (row_id, v) in [(0x200u64, 7u32), (0x201, 8)] {
+            buf.extend_from_slice(&[0x00, 0x00, 0x10, 0x00, record_len as u8]);
+            buf.extend_from_slice(&row_id.to_be_bytes()[2..]);
+            buf.extend_from_slice(&[0u8; 13]);
+            buf.extend_from_slice(&(v ^ 0x8000_0000).to_be_bytes());
+        }
+
+        let mut row_ids = Vec::new();
+        for origin in [5, 5 + record_len] {
+            let record = Record::try_from_offset(&buf, origin).unwrap();
+            let row = Row::try_from_record_and_table(&record, &td).unwrap();
+            let values = row.parse_values(&DummyBufferMangaer).unwrap();
+            // The row id is a column of its own, not one of the hidden ones
+            assert_eq!(values[0], row.cluster_key().unwrap()[0]);
+            assert_eq!(values.len(), 2);
+            assert_eq!(row.trx_id(), Some(0));
+            row_ids.push(row.row_id().unwrap().unwrap());
         }
+        assert_eq!(row_ids, [0x200, 0x201]);
+
+        let with_pk = single_text_table();
+        let buf = record_bytes(5);
+        let record = Record::try_from_offset(&buf, 6).unwrap();
+        let row = Row::try_from_record_and_table(&record, &with_pk).unwrap();
+        assert_eq!(row.row_id().unwrap(), None);
+    }
+
+    #[test]
+    fn test_row_span_mismatch() {
+        let sql = read_to_string(test_data_path("double_test_table.sql")).unwrap();
+        // Same widths as FLOAT and DOUBLE, which can't be parsed yet
+        let table = |single: &str| {
+            let sql = sql
+                .replace("`single_f` float", &format!("`single_f` {}", single))
+                .replace("`double_f` double", "`double_f` bigint");
+            Arc::new(TableDefinition::try_from_sql_statement(&sql).unwrap())
+        };
+        let td = table("int");
+        // 8 bytes instead of 4 reads into the next record
+        let wrong = table("bigint");
+        let data = test_data("float_sample.ibd");
+        let page = Page::from_bytes(&data[4 * FIL_PAGE_SIZE..5 * FIL_PAGE_SIZE]).unwrap();
+        let index = IndexPage::try_from_page(page).unwrap();
+        let record = index.records().next().unwrap().unwrap();
+
+        let row = Row::try_from_record_and_table(&record, &td).unwrap();
+        assert_eq!(row.data_end().unwrap(), 127 + 20 + 13 + 4 + 8);
+        assert_eq!(row.payload_len().unwrap(), 20 + 13 + 4 + 8);
+        assert!(row.payload_len().unwrap() <= record.payload_len(&index.index_header).unwrap());
+        assert!(!row.is_suspect());
+        assert!(row.parse_values(&DummyBufferMangaer).is_ok());
 
-        values
+        let row = Row::try_from_record_and_table(&record, &wrong).unwrap();
+        assert!(row.is_suspect());
+        assert!(row.parse_values(&DummyBufferMangaer).is_err());
     }
 }
@@ -0,0 +1,180 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::innodb::{
+    page::{fsp::FspHeaderPage, index::IndexPage, OwnedPage, PageSize, PageType},
+    InnoDBError,
+};
+
+/// Root page of an index found in a tablespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexRoot {
+    pub index_id: u64,
+    pub page_number: u32,
+    /// Level of the root, one less than the height of the B+tree
+    pub level: u16,
+}
+
+/// A tablespace file, `.ibd` or a system tablespace, read a page at a time
+pub struct Tablespace<R = File> {
+    reader: RefCell<R>,
+    page_size: PageSize,
+    page_count: u32,
+    fsp_header: FspHeaderPage,
+}
+
+impl Tablespace<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_reader(File::open(path)?).with_context(|| format!("{}", path.display()))
+    }
+}
+
+impl<R: Read + Seek> Tablespace<R> {
+    /// Reads the page size from page 0, which must pass its checksum
+    pub fn from_reader(mut reader: R) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut head = vec![0u8; PageSize::Size4K.bytes()];
+        reader.read_exact(&mut head)?;
+        let fsp_header = FspHeaderPage::try_from_bytes(&head)?;
+        if fsp_header.flags.is_compressed() {
+            return Err(anyhow!(
+                "Space {} is compressed, compressed tablespaces are not supported",
+                fsp_header.space_id
+            ));
+        }
+        let page_size = fsp_header.logical_page_size()?;
+
+        let mut tablespace = Tablespace {
+            reader: RefCell::new(reader),
+            page_size,
+            page_count: (len / page_size.bytes() as u64) as u32,
+            fsp_header,
+        };
+        let first_page = tablespace.page(0)?;
+        if !first_page.page().validate().is_valid() {
+            return Err(anyhow!(InnoDBError::InvalidChecksum))
+                .context(format!("Page 0 of space {}", tablespace.space_id()));
+        }
+        // Again from the whole page, for the encryption info
+        tablespace.fsp_header = FspHeaderPage::try_from_page(&first_page.page())?;
+        Ok(tablespace)
+    }
+
+    pub fn space_id(&self) -> u32 {
+        self.fsp_header.space_id
+    }
+
+    pub fn page_size(&self) -> PageSize {
+        self.page_size
+    }
+
+    /// The FSP header of page 0
+    pub fn fsp_header(&self) -> &FspHeaderPage {
+        &self.fsp_header
+    }
+
+    /// Whole pages in the file, a short page at the end is left out
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// Reads page `n`, its checksum is not checked
+    pub fn page(&self, n: u32) -> Result<OwnedPage> {
+        if n >= self.page_count {
+            return Err(anyhow!(InnoDBError::PageNotFound));
+        }
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(n as u64 * self.page_size.bytes() as u64))?;
+        OwnedPage::from_reader_with_size(&mut *reader, self.page_size)
+    }
+
+    /// Every page in file order
+    pub fn pages(&self) -> impl Iterator<Item = Result<OwnedPage>> + '_ {
+        (0..self.page_count).map(|n| self.page(n))
+    }
+
+    /// Root pages of the indexes, found by scanning every page. Pages that
+    /// fail their checksum are skipped.
+    pub fn index_roots(&self) -> Result<Vec<IndexRoot>> {
+        let mut roots = Vec::new();
+        for page in self.pages() {
+            let page = page?;
+            if page.header.page_type != PageType::Index || !page.page().validate().is_valid() {
+                continue;
+            }
+            let index = IndexPage::try_from_page(page.page())?;
+            if index.is_root() {
+                roots.push(IndexRoot {
+                    index_id: index.index_header.index_id,
+                    page_number: page.header.offset,
+                    level: index.index_header.page_level,
+                });
+            }
+        }
+        Ok(roots)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::innodb::page::{PageSize, PageType, FIL_PAGE_SIZE};
+
+    use crate::innodb::{test_data, test_data_path};
+
+    use super::{IndexRoot, Tablespace};
+
+    #[test]
+    fn test_open_tablespace() {
+        let tablespace = Tablespace::open(test_data_path("float_sample.ibd")).unwrap();
+        assert_eq!(tablespace.space_id(), 351);
+        assert_eq!(tablespace.page_size(), PageSize::Size16K);
+        assert_eq!(tablespace.page_count(), 7);
+        assert!(tablespace.fsp_header().encryption.is_none());
+        assert_eq!(
+            tablespace.page(4).unwrap().header.page_type,
+            PageType::Index
+        );
+        assert!(tablespace.page(7).is_err());
+        let offsets = tablespace
+            .pages()
+            .map(|page| page.unwrap().header.offset)
+            .collect::<Vec<_>>();
+        // The last two pages were never written
+        assert_eq!(offsets, [0, 1, 2, 3, 4, 0, 0]);
+        assert_eq!(
+            tablespace.index_roots().unwrap(),
+            [IndexRoot {
+                index_id: 960,
+                page_number: 4,
+                level: 0
+            }]
+        );
+
+        let small = Tablespace::open(test_data_path("float_sample_8k.ibd")).unwrap();
+        assert_eq!(small.page_size(), PageSize::Size8K);
+        assert_eq!(small.index_roots().unwrap()[0].index_id, 960);
+
+        let encrypted = Tablespace::open(test_data_path("encrypted_sample.ibd")).unwrap();
+        assert!(encrypted.fsp_header().encryption.is_some());
+    }
+
+    #[test]
+    fn test_open_bad_tablespace() {
+        let mut data = test_data("float_sample.ibd");
+        // Starts with an index page
+        assert!(Tablespace::from_reader(Cursor::new(data[4 * FIL_PAGE_SIZE..].to_vec())).is_err());
+        data[200] ^= 0xFF;
+        let err = Tablespace::from_reader(Cursor::new(data)).err().unwrap();
+        assert!(err.to_string().contains("Page 0 of space 351"), "{err}");
+    }
+}
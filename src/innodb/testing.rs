@@ -0,0 +1,97 @@
+//! Fixtures shared by the library, binary and integration tests and the
+//! benches. Not part of the API.
+
+use std::path::PathBuf;
+
+use super::page::{crc32_checksum, PageType, FIL_PAGE_SIZE};
+
+/// Path of a file in test_data
+pub fn test_data_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test_data")
+        .join(name)
+}
+
+/// Contents of a file in test_data
+pub fn test_data(name: &str) -> Vec<u8> {
+    std::fs::read(test_data_path(name)).unwrap()
+}
+
+/// Zeroed 16K page with the FIL header fields tests look at filled in
+pub fn blank_page(space_id: u32, offset: u32, page_type: PageType) -> Vec<u8> {
+    let mut buf = vec![0u8; FIL_PAGE_SIZE];
+    buf[4..8].copy_from_slice(&offset.to_be_bytes());
+    buf[24..26].copy_from_slice(&(page_type as u16).to_be_bytes());
+    buf[34..38].copy_from_slice(&space_id.to_be_bytes());
+    buf
+}
+
+/// Stores the crc32 checksum of a modified page in its header and trailer
+pub fn set_crc32_checksum(page: &mut [u8]) {
+    let checksum = crc32_checksum(page).to_be_bytes();
+    let len = page.len();
+    page[0..4].copy_from_slice(&checksum);
+    page[len - 8..len - 4].copy_from_slice(&checksum);
+}
+
+/// Replaces the user records of the COMPACT index page `page` with
+/// `(header, data)` records, the header holds the bytes before the fixed 5
+/// byte header. Records are laid out back to back from the end of the
+/// supremum, the page directory is left alone.
+pub fn with_records(mut page: Vec<u8>, records: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let record_type = if page[64..66] == [0, 0] { 0 } else { 1 };
+    let mut cursor = 120;
+    let mut prev = 99;
+    for (i, (header, data)) in records.iter().enumerate() {
+        page[cursor..cursor + header.len()].copy_from_slice(header);
+        let rec = cursor + header.len() + 5;
+        page[rec - 4..rec - 2]
+            .copy_from_slice(&((((i + 2) as u16) << 3) | record_type).to_be_bytes());
+        page[rec..rec + data.len()].copy_from_slice(data);
+        page[prev - 2..prev].copy_from_slice(&((rec as i16) - (prev as i16)).to_be_bytes());
+        prev = rec;
+        cursor = rec + data.len();
+    }
+    page[prev - 2..prev].copy_from_slice(&(112 - (prev as i16)).to_be_bytes());
+    page[42..44].copy_from_slice(&(0x8000 | (records.len() as u16 + 2)).to_be_bytes());
+    page[54..56].copy_from_slice(&(records.len() as u16).to_be_bytes());
+    page
+}
+
+/// LOB first page at `first_page` holding the first chunk, followed by a
+/// data page per remaining chunk. Every index entry lives on the first page,
+/// in chunk order.
+pub fn lob_pages(space_id: u32, first_page: u32, chunks: &[&[u8]]) -> Vec<Vec<u8>> {
+    let entry_offset = |idx: usize| 38 + 58 + idx * 60;
+    let mut first = blank_page(space_id, first_page, PageType::LobFirst);
+    first[38 + 16..38 + 20].copy_from_slice(&(chunks[0].len() as u32).to_be_bytes());
+    first[38 + 26..38 + 30].copy_from_slice(&(chunks.len() as u32).to_be_bytes());
+    first[38 + 30..38 + 34].copy_from_slice(&first_page.to_be_bytes());
+    first[38 + 34..38 + 36].copy_from_slice(&(entry_offset(0) as u16).to_be_bytes());
+    let first_data = 38 + 58 + 600;
+    first[first_data..first_data + chunks[0].len()].copy_from_slice(chunks[0]);
+
+    let mut pages = Vec::new();
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let at = entry_offset(idx);
+        let page_number = first_page + idx as u32;
+        let (next_page, next_offset) = if idx + 1 < chunks.len() {
+            (first_page, entry_offset(idx + 1) as u16)
+        } else {
+            (u32::MAX, 0)
+        };
+        first[at + 6..at + 10].copy_from_slice(&next_page.to_be_bytes());
+        first[at + 10..at + 12].copy_from_slice(&next_offset.to_be_bytes());
+        first[at + 48..at + 52].copy_from_slice(&page_number.to_be_bytes());
+        first[at + 52..at + 54].copy_from_slice(&(chunk.len() as u16).to_be_bytes());
+
+        if idx > 0 {
+            let mut page = blank_page(space_id, page_number, PageType::LobData);
+            page[39..43].copy_from_slice(&(chunk.len() as u32).to_be_bytes());
+            page[49..49 + chunk.len()].copy_from_slice(chunk);
+            pages.push(page);
+        }
+    }
+    pages.insert(0, first);
+    pages
+}
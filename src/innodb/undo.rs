@@ -0,0 +1,366 @@
+use anyhow::{anyhow, Result};
+
+use crate::innodb::{
+    buffer_manager::BufferManager,
+    page::undo::{UndoColumn, UndoPage, UndoRecordType, REC_MAX_N_FIELDS},
+    table::{
+        field::{Field, FieldValue},
+        row::Row,
+        TableDefinition,
+    },
+};
+
+/// DB_TRX_ID and DB_ROLL_PTR, between the cluster and the data columns
+const N_SYSTEM_FIELDS: usize = 2;
+
+/// Decoded DB_ROLL_PTR, where the undo record of the last change to a row is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollPtr {
+    /// The row was inserted by the change, there's no previous version
+    pub is_insert: bool,
+    pub rseg_id: u8,
+    /// Tablespace of the rollback segment
+    pub space_id: u32,
+    pub page_number: u32,
+    pub offset: u16,
+}
+
+impl RollPtr {
+    /// `undo_spaces` are the ids of the undo tablespaces in the order the
+    /// server opened them, rollback segments are spread over them round
+    /// robin. Without undo tablespaces the segments are in the system
+    /// tablespace.
+    pub fn decode(roll_ptr: u64, undo_spaces: &[u32]) -> Self {
+        let rseg_id = ((roll_ptr >> 48) & 0x7f) as u8;
+        let space_id = if undo_spaces.is_empty() {
+            0
+        } else {
+            undo_spaces[rseg_id as usize % undo_spaces.len()]
+        };
+        RollPtr {
+            is_insert: roll_ptr & (1 << 55) != 0,
+            rseg_id,
+            space_id,
+            page_number: (roll_ptr >> 16) as u32,
+            offset: roll_ptr as u16,
+        }
+    }
+}
+
+/// A row as it was before a change
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowVersion {
+    /// Cluster columns then data columns, like [`Row::parse_values`]
+    pub values: Vec<FieldValue>,
+    /// Transaction that wrote this version
+    pub trx_id: u64,
+    /// Where the version before this one is
+    pub roll_ptr: u64,
+    pub deleted: bool,
+}
+
+/// Parses an old column value from an undo record
+fn parse_undo_column(field: &Field, column: &UndoColumn) -> Result<FieldValue> {
+    match column {
+        UndoColumn::Null => Ok(FieldValue::Null),
+        UndoColumn::Value(bytes) => {
+            if !field.field_type.is_variable() && (bytes.len() as u64) < field.field_type.max_len()
+            {
+                return Err(anyhow!(
+                    "Old value of {} has {} bytes, expected {}",
+                    field.name,
+                    bytes.len(),
+                    field.field_type.max_len()
+                ));
+            }
+            Ok(field.parse(bytes, Some(bytes.len() as u64))?.0)
+        }
+        UndoColumn::Extern(_) => Err(anyhow!(
+            "Old value of {} is stored externally, which isn't supported",
+            field.name
+        )),
+    }
+}
+
+/// Applies the undo record at `roll_ptr` to `values`, the current version
+/// of a row. `None` when the row has no older version.
+pub fn prev_version(
+    values: &[FieldValue],
+    roll_ptr: &RollPtr,
+    table_def: &TableDefinition,
+    buffer_mgr: &dyn BufferManager,
+) -> Result<Option<RowVersion>> {
+    if roll_ptr.is_insert {
+        return Ok(None);
+    }
+    if values.len() != table_def.field_count() {
+        return Err(anyhow!(
+            "Row has {} values, table has {} columns",
+            values.len(),
+            table_def.field_count()
+        ));
+    }
+    let guard = buffer_mgr.pin(roll_ptr.space_id, roll_ptr.page_number)?;
+    let undo_page = UndoPage::try_from_page(guard.page())?;
+    let record = undo_page.record_at(roll_ptr.offset as usize)?;
+    if record.record_type == UndoRecordType::Insert {
+        return Ok(None);
+    }
+
+    let n_unique = table_def.cluster_columns.len();
+    let (key, _) = record.key(n_unique)?;
+    for ((field, column), value) in table_def.cluster_columns.iter().zip(&key).zip(values) {
+        if !parse_undo_column(field, column)?.key_cmp(value)?.is_eq() {
+            return Err(anyhow!(
+                "Undo record at {:?} is for another row, the page was probably purged",
+                roll_ptr
+            ));
+        }
+    }
+
+    let fields = table_def
+        .cluster_columns
+        .iter()
+        .chain(table_def.data_columns.iter())
+        .collect::<Vec<_>>();
+    let mut prev = values.to_vec();
+    for update in record.update_vector(n_unique)? {
+        let field_no = update.field_no as usize;
+        let idx = if field_no < n_unique {
+            field_no
+        } else if field_no < n_unique + N_SYSTEM_FIELDS || update.field_no >= REC_MAX_N_FIELDS {
+            // System and virtual columns aren't part of the values
+            continue;
+        } else {
+            field_no - N_SYSTEM_FIELDS
+        };
+        let field = fields
+            .get(idx)
+            .ok_or_else(|| anyhow!("Undo record updates unknown field {}", field_no))?;
+        prev[idx] = parse_undo_column(field, &update.value)?;
+    }
+
+    Ok(Some(RowVersion {
+        values: prev,
+        trx_id: record.trx_id.unwrap_or_default(),
+        roll_ptr: record.roll_ptr.unwrap_or_default(),
+        deleted: record.was_deleted(),
+    }))
+}
+
+/// Values of `row` before the change that wrote it. Only changes that fit in
+/// the undo record are supported, old externally stored values aren't.
+pub fn fetch_prev_version(
+    row: &Row,
+    roll_ptr: &RollPtr,
+    table_def: &TableDefinition,
+    buffer_mgr: &dyn BufferManager,
+) -> Result<Vec<FieldValue>> {
+    let values = row.parse_values(buffer_mgr)?;
+    prev_version(&values, roll_ptr, table_def, buffer_mgr)?
+        .map(|version| version.values)
+        .ok_or_else(|| anyhow!("The row was inserted, it has no previous version"))
+}
+
+/// Older versions of a row, newest first, following the roll pointers until
+/// an insert. Stops after the first error.
+pub struct VersionChain<'a> {
+    values: Vec<FieldValue>,
+    trx_id: u64,
+    roll_ptr: Option<u64>,
+    undo_spaces: &'a [u32],
+    table_def: &'a TableDefinition,
+    buffer_mgr: &'a dyn BufferManager,
+}
+
+impl<'a> VersionChain<'a> {
+    pub fn new(
+        values: Vec<FieldValue>,
+        trx_id: u64,
+        roll_ptr: u64,
+        undo_spaces: &'a [u32],
+        table_def: &'a TableDefinition,
+        buffer_mgr: &'a dyn BufferManager,
+    ) -> Self {
+        VersionChain {
+            values,
+            trx_id,
+            roll_ptr: Some(roll_ptr),
+            undo_spaces,
+            table_def,
+            buffer_mgr,
+        }
+    }
+
+    fn step(&mut self, roll_ptr: u64) -> Result<Option<RowVersion>> {
+        let roll_ptr = RollPtr::decode(roll_ptr, self.undo_spaces);
+        let Some(version) = prev_version(&self.values, &roll_ptr, self.table_def, self.buffer_mgr)?
+        else {
+            return Ok(None);
+        };
+        // Versions get older down the chain, anything else is a reused page
+        if version.trx_id >= self.trx_id {
+            return Err(anyhow!(
+                "Version at {:?} is from transaction {}, not older than {}",
+                roll_ptr,
+                version.trx_id,
+                self.trx_id
+            ));
+        }
+        self.values.clone_from(&version.values);
+        self.trx_id = version.trx_id;
+        self.roll_ptr = Some(version.roll_ptr);
+        Ok(Some(version))
+    }
+}
+
+impl Iterator for VersionChain<'_> {
+    type Item = Result<RowVersion>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let roll_ptr = self.roll_ptr.take()?;
+        self.step(roll_ptr).transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::innodb::{
+        buffer_manager::memory::MemoryBufferManager,
+        page::{writer::PageWriter, ChecksumKind, Page, FIL_PAGE_SIZE},
+        table::{field::FieldValue, TableDefinition},
+        test_data,
+    };
+
+    use super::{prev_version, RollPtr, VersionChain};
+
+    const UNDO_SPACE: u32 = 4294967279;
+    const UNDO_PAGE: u32 = 9;
+    const ROLL_PTR: u64 = (1 << 48) | ((UNDO_PAGE as u64) << 16) | 56;
+
+    fn table() -> TableDefinition {
+        TableDefinition::try_from_sql_statement(
+            "CREATE TABLE t (id INT NOT NULL, name VARCHAR(10), n INT, PRIMARY KEY (id))",
+        )
+        .unwrap()
+    }
+
+    /// Undo page with an UPD_EXIST record at 56 for row id 1, the change
+    /// was made over a version by transaction 0x10 where name was "old" and
+    /// n NULL, the row was inserted before that
+    fn build_undo_page() -> Box<[u8]> {
+        let data = test_data("t_empty.ibd");
+        let mut buf = data[3 * FIL_PAGE_SIZE..4 * FIL_PAGE_SIZE].to_vec();
+        buf[4..8].copy_from_slice(&UNDO_PAGE.to_be_bytes());
+        buf[24..26].copy_from_slice(&2u16.to_be_bytes());
+        buf[34..38].copy_from_slice(&UNDO_SPACE.to_be_bytes());
+        let record: &[u8] = &[
+            0x00, 0x00, // next
+            12,   // UPD_EXIST
+            0x01, 0x84, 0x2a, // undo_no, table id
+            0x00, // info bits
+            0x00, 0x00, 0x00, 0x00, 0x10, // DB_TRX_ID
+            0xE0, 0x81, 0x00, 0x00, 0x00, 0x90, 0x01, 0x20, // DB_ROLL_PTR, an insert
+            4, 0x80, 0x00, 0x00, 0x01, // id
+            2,    // n_fields
+            3, 3, b'o', b'l', b'd', // name
+            4, 0xF0, 0xFF, 0xFF, 0xFF, 0xFF, // n, NULL
+        ];
+        let start = 56;
+        buf[start..start + record.len()].copy_from_slice(record);
+        buf[38..40].copy_from_slice(&2u16.to_be_bytes());
+        buf[40..42].copy_from_slice(&(start as u16).to_be_bytes());
+        buf[42..44].copy_from_slice(&((start + record.len()) as u16).to_be_bytes());
+        PageWriter::new(&Page::from_bytes(&buf).unwrap()).finalize(ChecksumKind::Crc32)
+    }
+
+    fn current() -> Vec<FieldValue> {
+        vec![
+            FieldValue::SignedInt(1),
+            FieldValue::String("new".into()),
+            FieldValue::SignedInt(7),
+        ]
+    }
+
+    #[test]
+    fn test_decode_roll_ptr() {
+        let roll_ptr = RollPtr::decode(0x81_0000_0088_0110, &[]);
+        assert!(roll_ptr.is_insert);
+        assert_eq!(roll_ptr.rseg_id, 1);
+        assert_eq!(roll_ptr.space_id, 0);
+        assert_eq!((roll_ptr.page_number, roll_ptr.offset), (0x88, 0x110));
+
+        let roll_ptr = RollPtr::decode(0x03_0000_0004_0038, &[10, 11]);
+        assert!(!roll_ptr.is_insert);
+        assert_eq!((roll_ptr.rseg_id, roll_ptr.space_id), (3, 11));
+    }
+
+    #[test]
+    fn test_prev_version() {
+        let table = table();
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_page(&build_undo_page()).unwrap();
+
+        let roll_ptr = RollPtr::decode(ROLL_PTR, &[UNDO_SPACE]);
+        let version = prev_version(&current(), &roll_ptr, &table, &buffer_mgr)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            version.values,
+            [
+                FieldValue::SignedInt(1),
+                FieldValue::String("old".into()),
+                FieldValue::Null
+            ]
+        );
+        assert_eq!(version.trx_id, 0x10);
+        assert_eq!(version.roll_ptr, 0x81_0000_0090_0120);
+        assert!(!version.deleted);
+
+        let insert = RollPtr::decode(version.roll_ptr, &[UNDO_SPACE]);
+        assert!(prev_version(&version.values, &insert, &table, &buffer_mgr)
+            .unwrap()
+            .is_none());
+
+        // Records of another row are rejected
+        let mut other = current();
+        other[0] = FieldValue::SignedInt(2);
+        assert!(prev_version(&other, &roll_ptr, &table, &buffer_mgr).is_err());
+        // Nothing at the record's page in the system tablespace
+        let roll_ptr = RollPtr::decode(ROLL_PTR, &[]);
+        assert!(prev_version(&current(), &roll_ptr, &table, &buffer_mgr).is_err());
+    }
+
+    #[test]
+    fn test_version_chain() {
+        let table = table();
+        let mut buffer_mgr = MemoryBufferManager::new();
+        buffer_mgr.add_page(&build_undo_page()).unwrap();
+
+        let versions = VersionChain::new(
+            current(),
+            0x20,
+            ROLL_PTR,
+            &[UNDO_SPACE],
+            &table,
+            &buffer_mgr,
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].trx_id, 0x10);
+
+        // An older version can't be from a newer transaction
+        let mut chain = VersionChain::new(
+            current(),
+            0x10,
+            ROLL_PTR,
+            &[UNDO_SPACE],
+            &table,
+            &buffer_mgr,
+        );
+        assert!(chain.next().unwrap().is_err());
+        assert!(chain.next().is_none());
+    }
+}
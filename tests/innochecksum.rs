@@ -0,0 +1,81 @@
+use std::process::Command;
+
+use innodb::innodb::test_data_path;
+
+/// page_explorer's --innochecksum lines for one page of a fixture
+fn page_report(fixture: &str, page: &str, args: &[&str]) -> String {
+    let fixture = test_data_path(fixture);
+    let output = Command::new(env!("CARGO_BIN_EXE_page_explorer"))
+        .arg("--no-color")
+        .arg(fixture)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.contains(page))
+        .collect()
+}
+
+#[test]
+fn test_page_explorer_innochecksum() {
+    // Page 4 of float_sample.ibd was written with crc32, the flag defaults
+    // to crc32 when no algorithm follows it
+    let report = page_report("float_sample.ibd", "Page 4 @ 0x10000:", &["--innochecksum"]);
+    assert!(
+        report.contains("crc32 calculated = 3256290427 "),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains(
+            "recorded checksum field1 = 3256290427 field2 = 3256290427; \
+             crc32 passes, library Some(Crc32)"
+        ),
+        "{}",
+        report
+    );
+
+    let report = page_report(
+        "float_sample.ibd",
+        "Page 4 @ 0x10000:",
+        &["--innochecksum", "strict_innodb"],
+    );
+    assert!(report.contains("strict_innodb fails"), "{}", report);
+
+    let report = page_report(
+        "float_sample.ibd",
+        "Page 4 @ 0x10000:",
+        &["--innochecksum=innodb"],
+    );
+    assert!(report.contains("innodb passes"), "{}", report);
+
+    // Page 3 of t_empty.ibd was written with the innodb algorithm
+    let report = page_report(
+        "t_empty.ibd",
+        "Page 3 @ 0xc000:",
+        &["--innochecksum=strict_innodb"],
+    );
+    assert!(
+        report.contains(
+            "innodb calculated = 1268509086, old style = 3983286166; \
+             recorded checksum field1 = 1268509086 field2 = 3983286166; \
+             strict_innodb passes, library Some(InnoDb)"
+        ),
+        "{}",
+        report
+    );
+    let report = page_report(
+        "t_empty.ibd",
+        "Page 3 @ 0xc000:",
+        &["--innochecksum=strict_crc32"],
+    );
+    assert!(report.contains("strict_crc32 fails"), "{}", report);
+
+    // Without the flag there's no report
+    assert_eq!(
+        page_report("float_sample.ibd", "Page 4 @ 0x10000:", &[]),
+        ""
+    );
+}
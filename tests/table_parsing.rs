@@ -1,13 +1,12 @@
 use std::{
-    fs::{read_to_string, File},
-    io::Read,
+    fs::read_to_string,
     path::PathBuf, sync::Arc,
 };
 
 use innodb::innodb::{
-    buffer_manager::DummyBufferMangaer, charset::InnoDBCharset, page::{index::{record::RecordType, IndexPage}, Page, PageType}, table::{
+    buffer_manager::DummyBufferMangaer, charset::InnoDBCharset, page::{index::{record::RecordType, IndexPage}, PageType}, table::{
         field::{Field, FieldType, FieldValue}, row::Row, TableDefinition
-    }
+    }, tablespace::Tablespace
 };
 
 #[test]
@@ -36,7 +35,7 @@ fn test_parsing_table_with_floats() {
     let parsed_table = Arc::new(TableDefinition::try_from_sql_statement(&sql).expect("Failed to parse SQL"));
     assert_eq!(parsed_table.as_ref(), &reference);
 
-    let mut table_content_file = File::open(
+    let tablespace = Tablespace::open(
         PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("test_data")
             .join("float_sample.ibd"),
@@ -51,32 +50,21 @@ fn test_parsing_table_with_floats() {
     let mut parsed_values = Vec::<Vec<FieldValue>>::new();
 
     let buf_mgr = DummyBufferMangaer;
-    let mut buffer = Vec::<u8>::new();
-    buffer.resize(16384, 0);
-
-    loop {
-        match table_content_file.read_exact(&mut buffer) {
-            Ok(_) => {
-                let page = Page::from_bytes(&buffer).unwrap();
-                if page.header.page_type == PageType::Index {
-                    let index = IndexPage::try_from_page(page).unwrap();
-                    assert_eq!(index.index_header.index_id, 960, "Wrong Index ID");
-                    let mut record = index.infimum().unwrap();
-                    while record.next().is_some() {
-
-                        if record.header.record_type == RecordType::Conventional {
-                            let row = Row::try_from_record_and_table(&record, &parsed_table).expect("Failed to parse row");
-                            let values = row.parse_values(&buf_mgr);
-                            assert_eq!(values.len(), parsed_table.field_count());
-                            parsed_values.push(values);
-                        }
 
-                        record = record.next().unwrap();
-                    }
-                    assert_eq!(record.header.record_type, RecordType::Supremum);
+    for page in tablespace.pages() {
+        let page = page.expect("Failed to read page");
+        if page.header.page_type == PageType::Index {
+            let index = IndexPage::try_from_page(page.page()).unwrap();
+            assert_eq!(index.index_header.index_id, 960, "Wrong Index ID");
+            for record in index.records() {
+                let record = record.expect("Failed to walk records");
+                if record.header.record_type == RecordType::Conventional {
+                    let row = Row::try_from_record_and_table(&record, &parsed_table).expect("Failed to parse row");
+                    let values = row.parse_values(&buf_mgr).expect("Failed to parse values");
+                    assert_eq!(values.len(), parsed_table.field_count());
+                    parsed_values.push(values);
                 }
             }
-            Err(_) => break,
         }
     }
 